@@ -1,5 +1,5 @@
 use std::{
-    fs::{create_dir_all, remove_file},
+    fs::{create_dir_all, read, remove_file, write},
     io,
     path::{Path, PathBuf},
 };
@@ -20,12 +20,19 @@ const ARTWORK_CACHE_FILENAME: &str = "playing.png";
 // track, then normalize it to a png format. There is only ever a single
 // artwork cached at a time.
 pub fn cache_track_artwork(track: &Track) -> Result<()> {
-    let Some(picture) = extract_artwork(track) else {
-        clear_artwork_cache()?;
-        return Ok(());
+    // Tracks without embedded artwork may have artwork that was fetched for their album.
+    let image_data = match extract_artwork(track) {
+        Some(picture) => picture.into_data(),
+        None => match track.album.as_deref().and_then(load_album_artwork) {
+            Some(data) => data,
+            None => {
+                clear_artwork_cache()?;
+                return Ok(());
+            }
+        },
     };
 
-    let image = load_from_memory(picture.data())?;
+    let image = load_from_memory(&image_data)?;
 
     image.save_with_format(artwork_cache_path()?, ImageFormat::Png)?;
 
@@ -43,9 +50,30 @@ pub fn clear_artwork_cache() -> Result<()> {
 }
 
 pub fn artwork_uri() -> Option<String> {
+    artwork_path().map(|path| compute_uri(&path))
+}
+
+// The cached artwork of the playing track, if it has any.
+pub fn artwork_path() -> Option<PathBuf> {
     let path = artwork_cache_path().ok()?;
 
-    path.is_file().then(|| compute_uri(&path))
+    path.is_file().then_some(path)
+}
+
+// Artwork fetched online for an album can be kept here instead of being embedded in the files.
+pub fn save_album_artwork(album: &str, image_data: &[u8]) -> Result<()> {
+    write(album_artwork_path(album)?, image_data)?;
+    Ok(())
+}
+
+pub fn load_album_artwork(album: &str) -> Option<Vec<u8>> {
+    read(album_artwork_path(album).ok()?).ok()
+}
+
+pub fn album_artwork_path(album: &str) -> Result<PathBuf> {
+    let directory = get_or_init_artwork_cache()?.join("albums");
+    create_dir_all(&directory)?;
+    Ok(directory.join(sanitize_filename::sanitize(album)))
 }
 
 fn artwork_cache_path() -> Result<PathBuf> {
@@ -58,7 +86,7 @@ fn compute_uri(path: &Path) -> String {
         .to_string()
 }
 
-fn get_or_init_artwork_cache() -> io::Result<PathBuf> {
+pub fn get_or_init_artwork_cache() -> io::Result<PathBuf> {
     let proj_dirs = ProjectDirs::from("", "", APP_NAME).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no project dirs"))?;
 
     let directory = proj_dirs.cache_dir().join("artwork");
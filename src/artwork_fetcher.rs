@@ -0,0 +1,136 @@
+use std::{
+    sync::mpsc::{Receiver, Sender, channel},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow, bail};
+use egui::Context;
+use fully_pub::fully_pub;
+use log::{info, warn};
+use m3u::Url;
+
+const MUSICBRAINZ_RELEASE_SEARCH_URL: &str = "https://musicbrainz.org/ws/2/release/";
+const COVER_ART_ARCHIVE_URL: &str = "https://coverartarchive.org/release";
+const RELEASES_TO_TRY: usize = 3; // Not every release of an album has artwork in the Cover Art Archive.
+const MIN_REQUEST_INTERVAL: Duration = Duration::from_millis(1100); // MusicBrainz allows one request per second.
+
+// Looks up album artwork online in a background thread, one album at a time.
+#[fully_pub]
+struct ArtworkFetcher {
+    request_sender: Sender<ArtworkFetchRequest>,
+    result_receiver: Receiver<ArtworkFetchResult>,
+}
+
+#[fully_pub]
+struct ArtworkFetchRequest {
+    album: String,
+    album_artist: Option<String>, // Not searched for, but handed back to tell albums with the same title apart.
+    artist: Option<String>,
+}
+
+#[fully_pub]
+struct ArtworkFetchResult {
+    album: String,
+    album_artist: Option<String>,
+    image: Result<Option<Vec<u8>>>, // Ok(None) means that no artwork was found.
+}
+
+pub fn setup_artwork_fetcher(ctx: Context) -> ArtworkFetcher {
+    let (request_sender, request_receiver) = channel::<ArtworkFetchRequest>();
+    let (result_sender, result_receiver) = channel();
+
+    thread::spawn(move || {
+        let mut last_request = None;
+
+        while let Ok(request) = request_receiver.recv() {
+            info!("Fetching artwork for album '{}'", request.album);
+
+            let image = fetch_album_artwork(&request, &mut last_request);
+            let result = ArtworkFetchResult {
+                album: request.album,
+                album_artist: request.album_artist,
+                image,
+            };
+
+            if result_sender.send(result).is_err() {
+                break; // The app is shutting down.
+            }
+
+            ctx.request_repaint();
+        }
+    });
+
+    ArtworkFetcher {
+        request_sender,
+        result_receiver,
+    }
+}
+
+fn fetch_album_artwork(request: &ArtworkFetchRequest, last_request: &mut Option<Instant>) -> Result<Option<Vec<u8>>> {
+    let release_ids = search_release_ids(request, last_request)?;
+
+    for release_id in release_ids {
+        let url = format!("{}/{}/front-500", COVER_ART_ARCHIVE_URL, release_id);
+        let response = rate_limited_get(&url, last_request)?;
+
+        match response.status {
+            200 => return Ok(Some(response.bytes)),
+            404 => continue, // This release has no front cover.
+            status => bail!("Cover Art Archive returned {} {}", status, response.status_text),
+        }
+    }
+
+    Ok(None)
+}
+
+fn search_release_ids(request: &ArtworkFetchRequest, last_request: &mut Option<Instant>) -> Result<Vec<String>> {
+    // Quotes are escaped so that titles are searched as phrases.
+    let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+
+    let mut query = format!("release:\"{}\"", escape(&request.album));
+    if let Some(artist) = &request.artist {
+        query.push_str(&format!(" AND artist:\"{}\"", escape(artist)));
+    }
+
+    let limit = RELEASES_TO_TRY.to_string();
+    let url = Url::parse_with_params(
+        MUSICBRAINZ_RELEASE_SEARCH_URL,
+        &[("query", query.as_str()), ("fmt", "json"), ("limit", limit.as_str())],
+    )?;
+
+    let response = rate_limited_get(url.as_str(), last_request)?;
+    if !response.ok {
+        bail!("MusicBrainz returned {} {}", response.status, response.status_text);
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(&response.bytes)?;
+    let releases = json["releases"].as_array().cloned().unwrap_or_default();
+
+    let release_ids = releases
+        .iter()
+        .filter_map(|release| release["id"].as_str())
+        .map(|id| id.to_owned())
+        .collect();
+
+    Ok(release_ids)
+}
+
+fn rate_limited_get(url: &str, last_request: &mut Option<Instant>) -> Result<ehttp::Response> {
+    if let Some(last) = *last_request {
+        let elapsed = last.elapsed();
+        if elapsed < MIN_REQUEST_INTERVAL {
+            thread::sleep(MIN_REQUEST_INTERVAL - elapsed);
+        }
+    }
+    *last_request = Some(Instant::now());
+
+    // MusicBrainz asks that clients identify themselves.
+    let user_agent = format!("GemPlayer/{} ( {} )", env!("CARGO_PKG_VERSION"), env!("CARGO_PKG_REPOSITORY"));
+    let request = ehttp::Request::get(url).with_header("User-Agent", user_agent);
+
+    ehttp::fetch_blocking(&request).map_err(|e| {
+        warn!("Request to {} failed: {}", url, e);
+        anyhow!("Request failed: {}", e)
+    })
+}
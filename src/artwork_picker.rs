@@ -0,0 +1,29 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::{Receiver, channel},
+    thread,
+};
+
+use fully_pub::fully_pub;
+use rfd::FileDialog;
+
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "gif", "bmp", "webp"];
+
+// An open artwork picker dialog, and the tracks that will receive the picked image.
+#[fully_pub]
+struct ArtworkPicker {
+    track_keys: Vec<PathBuf>,
+    receiver: Receiver<Option<PathBuf>>,
+}
+
+/// Spawns an image picker in a background thread. The selected image will eventually be sent to the returned picker's receiver.
+pub fn spawn_artwork_picker(track_keys: Vec<PathBuf>) -> ArtworkPicker {
+    let (sender, receiver) = channel();
+
+    thread::spawn(move || {
+        let selected_image = FileDialog::new().add_filter("Images", &IMAGE_EXTENSIONS).pick_file();
+        let _ = sender.send(selected_image);
+    });
+
+    ArtworkPicker { track_keys, receiver }
+}
@@ -0,0 +1,58 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use strum_macros::EnumIter;
+
+use crate::track::Track;
+
+pub const TOP_PLAYED_COUNT: usize = 25;
+pub const FAVORITE_MIN_RATING: u8 = 4;
+
+// How many times each track has been played, keyed by the track's path.
+pub type PlayCounts = HashMap<PathBuf, u32>;
+
+// Built-in playlists whose tracks are derived from the library. They aren't backed by a playlist file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum AutoPlaylist {
+    TopPlayed,
+    Favorites,
+}
+
+impl AutoPlaylist {
+    pub fn name(self) -> &'static str {
+        match self {
+            AutoPlaylist::TopPlayed => "Top 25 Most Played",
+            AutoPlaylist::Favorites => "Favorites",
+        }
+    }
+
+    pub fn empty_message(self) -> &'static str {
+        match self {
+            AutoPlaylist::TopPlayed => "Tracks show up here once they have been played.",
+            AutoPlaylist::Favorites => "Tracks rated 4 stars or higher show up here.",
+        }
+    }
+
+    pub fn tracks(self, library: &[Track], play_counts: &PlayCounts) -> Vec<Track> {
+        match self {
+            AutoPlaylist::TopPlayed => {
+                let mut played: Vec<(&Track, u32)> = library
+                    .iter()
+                    .filter_map(|t| play_counts.get(&t.path).map(|&count| (t, count)))
+                    .filter(|&(_, count)| count > 0)
+                    .collect();
+
+                played.sort_by(|a, b| b.1.cmp(&a.1)); // The sort is stable, so ties keep the library order.
+                played.into_iter().take(TOP_PLAYED_COUNT).map(|(t, _)| t.clone()).collect()
+            }
+            AutoPlaylist::Favorites => {
+                let mut favorites: Vec<Track> = library.iter().filter(|t| t.rating >= FAVORITE_MIN_RATING).cloned().collect();
+                favorites.sort_by(|a, b| b.rating.cmp(&a.rating));
+                favorites
+            }
+        }
+    }
+}
+
+pub fn record_play(play_counts: &mut PlayCounts, track: &Track) {
+    *play_counts.entry(track.path.clone()).or_default() += 1;
+}
@@ -0,0 +1,50 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use fully_pub::fully_pub;
+
+use crate::track::Track;
+
+pub const DEFAULT_BOOKMARK_THRESHOLD: f32 = 20.0;
+pub const MAX_BOOKMARK_THRESHOLD: f32 = 120.0;
+
+// Positions this close to either end aren't worth resuming from. Near the end, the track counts as finished.
+const MIN_BOOKMARK_POSITION: Duration = Duration::from_secs(10);
+const FINISHED_MARGIN: Duration = Duration::from_secs(30);
+
+// Playback positions of long tracks (audiobooks, mixes, podcasts), so that they continue where they were left off.
+#[fully_pub]
+struct Bookmarks {
+    threshold: f32,                        // In minutes. Only tracks at least this long are bookmarked. Zero disables bookmarks.
+    positions: HashMap<PathBuf, Duration>, // Keyed by the track's path.
+}
+
+impl Bookmarks {
+    pub fn new(threshold: f32, positions: HashMap<PathBuf, Duration>) -> Self {
+        Self { threshold, positions }
+    }
+}
+
+pub fn is_bookmarkable(bookmarks: &Bookmarks, track: &Track) -> bool {
+    bookmarks.threshold > 0.0 && track.duration.as_secs_f32() >= bookmarks.threshold * 60.0
+}
+
+pub fn update_bookmark(bookmarks: &mut Bookmarks, track: &Track, position: Duration) {
+    if !is_bookmarkable(bookmarks, track) {
+        return;
+    }
+
+    let finished = position + FINISHED_MARGIN >= track.duration;
+    if finished || position < MIN_BOOKMARK_POSITION {
+        bookmarks.positions.remove(&track.path);
+    } else {
+        bookmarks.positions.insert(track.path.clone(), position);
+    }
+}
+
+pub fn bookmark_position(bookmarks: &Bookmarks, track: &Track) -> Option<Duration> {
+    if !is_bookmarkable(bookmarks, track) {
+        return None;
+    }
+
+    bookmarks.positions.get(&track.path).copied()
+}
@@ -0,0 +1,567 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use fully_pub::fully_pub;
+use log::warn;
+
+use crate::track::Track;
+
+#[fully_pub]
+#[derive(Clone, Debug)]
+struct Chapter {
+    title: String,
+    start: Duration,
+}
+
+const MP4_EXTENSIONS: [&str; 3] = ["m4a", "m4b", "mp4"];
+const ID3_EXTENSIONS: [&str; 1] = ["mp3"];
+
+const ID3_HEADER_LENGTH: usize = 10;
+const ID3_FRAME_HEADER_LENGTH: usize = 10;
+
+// Returns the chapters sorted by start time, or nothing if the track has none.
+pub fn load_chapters(track: &Track) -> Vec<Chapter> {
+    // Chapters belong to the whole audio file, which doesn't line up with the tracks of a cue sheet.
+    if track.cue.is_some() {
+        return Vec::new();
+    }
+
+    let extension = track.path.extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+    let result = match extension.as_deref() {
+        Some(e) if MP4_EXTENSIONS.contains(&e) => read_mp4_chapters(&track.path),
+        Some(e) if ID3_EXTENSIONS.contains(&e) => read_id3_chapters(&track.path),
+        _ => return Vec::new(),
+    };
+
+    let mut chapters = match result {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("Failed to read chapters of '{}': {}", track.path.display(), e);
+            return Vec::new();
+        }
+    };
+
+    chapters.retain(|c| c.start < track.duration);
+    chapters.sort_by_key(|c| c.start);
+    chapters
+}
+
+// The chapter that the position is in, i.e. the last one that started at or before it.
+pub fn current_chapter_index(chapters: &[Chapter], position: Duration) -> Option<usize> {
+    chapters.iter().rposition(|c| c.start <= position)
+}
+
+pub fn next_chapter_start(chapters: &[Chapter], position: Duration) -> Option<Duration> {
+    chapters.iter().map(|c| c.start).find(|start| *start > position)
+}
+
+// Like previous track, going back past the rewind threshold restarts the current chapter instead.
+pub fn previous_chapter_start(chapters: &[Chapter], position: Duration, rewind_threshold: f32) -> Option<Duration> {
+    let current = current_chapter_index(chapters, position)?;
+    let into_chapter = position - chapters[current].start;
+
+    let restart = rewind_threshold > 0.0 && into_chapter.as_secs_f32() >= rewind_threshold;
+    if restart || current == 0 {
+        return Some(chapters[current].start);
+    }
+
+    Some(chapters[current - 1].start)
+}
+
+// Mp4 files store chapters either in a Nero "chpl" atom or in a QuickTime text track that the audio track references.
+// Audiobook tools usually write both, so the simpler Nero list is preferred.
+fn read_mp4_chapters(path: &Path) -> Result<Vec<Chapter>> {
+    let mut file = File::open(path)?;
+    let moov = read_top_level_atom(&mut file, b"moov")?.ok_or_else(|| anyhow!("No moov atom"))?;
+
+    if let Some(chpl) = find_atom(&moov, &[b"udta", b"chpl"]) {
+        return parse_nero_chapters(chpl);
+    }
+
+    read_quicktime_chapters(&mut file, &moov)
+}
+
+fn read_top_level_atom(file: &mut File, name: &[u8; 4]) -> Result<Option<Vec<u8>>> {
+    let file_length = file.metadata()?.len();
+    let mut position = 0;
+
+    // Lengths come from the file and may be corrupt, so they are checked against what is left of it before anything is
+    // allocated for them.
+    while let Some(remaining) = file_length.checked_sub(position).filter(|r| *r >= 8) {
+        file.seek(SeekFrom::Start(position))?;
+
+        let mut header = [0; 8];
+        file.read_exact(&mut header)?;
+
+        let mut header_length = 8;
+        let mut atom_length = u32::from_be_bytes(header[0..4].try_into()?) as u64;
+        if atom_length == 1 {
+            let mut extended = [0; 8];
+            file.read_exact(&mut extended)?;
+            atom_length = u64::from_be_bytes(extended);
+            header_length = 16;
+        } else if atom_length == 0 {
+            atom_length = remaining; // The atom extends to the end of the file.
+        }
+
+        if atom_length > remaining {
+            return Err(anyhow!("Atom at {} extends past the end of the file", position));
+        }
+        let body_length = atom_length
+            .checked_sub(header_length)
+            .ok_or_else(|| anyhow!("Malformed atom at {}", position))?;
+
+        if &header[4..8] == name {
+            let mut body = vec![0; body_length as usize];
+            file.read_exact(&mut body)?;
+            return Ok(Some(body));
+        }
+
+        position += atom_length;
+    }
+
+    Ok(None)
+}
+
+// Splits the body of an atom into its children, skipping anything malformed.
+fn child_atoms(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut children = Vec::new();
+    let mut position = 0;
+
+    while let (Some(length), Some(name)) = (read_u32(data, position), data.get(position + 4..position + 8)) {
+        let length = length as usize;
+        if length < 8 || position + length > data.len() {
+            break;
+        }
+
+        children.push((name, &data[position + 8..position + length]));
+        position += length;
+    }
+
+    children
+}
+
+fn find_atom<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    let (first, rest) = path.split_first()?;
+    let (_, body) = child_atoms(data).into_iter().find(|(name, _)| *name == *first)?;
+
+    if rest.is_empty() { Some(body) } else { find_atom(body, rest) }
+}
+
+fn parse_nero_chapters(chpl: &[u8]) -> Result<Vec<Chapter>> {
+    let truncated = || anyhow!("Truncated chpl atom");
+
+    let version = *chpl.first().ok_or_else(truncated)?;
+    let mut position = if version == 0 { 4 } else { 8 }; // Version and flags, then an unused field in version 1.
+
+    let count = *chpl.get(position).ok_or_else(truncated)?;
+    position += 1;
+
+    let mut chapters = Vec::new();
+    for _ in 0..count {
+        let start = read_u64(chpl, position).ok_or_else(truncated)?; // In units of 100 nanoseconds.
+        let title_length = *chpl.get(position + 8).ok_or_else(truncated)? as usize;
+        let title = chpl.get(position + 9..position + 9 + title_length).ok_or_else(truncated)?;
+        position += 9 + title_length;
+
+        chapters.push(Chapter {
+            title: String::from_utf8_lossy(title).into_owned(),
+            start: Duration::from_nanos(start.saturating_mul(100)),
+        });
+    }
+
+    Ok(chapters)
+}
+
+fn read_quicktime_chapters(file: &mut File, moov: &[u8]) -> Result<Vec<Chapter>> {
+    let tracks: Vec<&[u8]> = child_atoms(moov)
+        .into_iter()
+        .filter(|(name, _)| *name == b"trak")
+        .map(|(_, body)| body)
+        .collect();
+
+    let chapter_track_id = tracks
+        .iter()
+        .find_map(|trak| find_atom(trak, &[b"tref", b"chap"]).and_then(|chap| read_u32(chap, 0)));
+    let Some(chapter_track_id) = chapter_track_id else {
+        return Ok(Vec::new());
+    };
+
+    let chapter_track = tracks
+        .iter()
+        .find(|trak| find_atom(trak, &[b"tkhd"]).and_then(track_id) == Some(chapter_track_id))
+        .ok_or_else(|| anyhow!("Missing chapter track {}", chapter_track_id))?;
+
+    let malformed = || anyhow!("Malformed chapter track");
+
+    let mdhd = find_atom(chapter_track, &[b"mdia", b"mdhd"]).ok_or_else(malformed)?;
+    let timescale = timescale(mdhd).filter(|t| *t > 0).ok_or_else(malformed)?;
+
+    let file_length = file.metadata()?.len();
+
+    let stbl = find_atom(chapter_track, &[b"mdia", b"minf", b"stbl"]).ok_or_else(malformed)?;
+    let sizes = sample_sizes(find_atom(stbl, &[b"stsz"]).ok_or_else(malformed)?, file_length).ok_or_else(malformed)?;
+    let starts = sample_starts(find_atom(stbl, &[b"stts"]).ok_or_else(malformed)?, sizes.len()).ok_or_else(malformed)?;
+    let offsets = sample_offsets(stbl, &sizes).ok_or_else(malformed)?;
+
+    let mut chapters = Vec::new();
+    for ((start, size), offset) in starts.into_iter().zip(sizes).zip(offsets) {
+        if offset.checked_add(size as u64).is_none_or(|end| end > file_length) {
+            return Err(anyhow!("Chapter sample at {} extends past the end of the file", offset));
+        }
+
+        // Each sample is a text sample: a 16 bit length followed by the title.
+        let mut sample = vec![0; size as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut sample)?;
+
+        let title_length = sample.get(0..2).map_or(0, |l| u16::from_be_bytes([l[0], l[1]]) as usize);
+        let title = sample.get(2..2 + title_length).unwrap_or_default();
+
+        chapters.push(Chapter {
+            title: String::from_utf8_lossy(title).into_owned(),
+            start: Duration::from_secs_f64(start as f64 / timescale as f64),
+        });
+    }
+
+    Ok(chapters)
+}
+
+// The id in a tkhd atom comes after the creation and modification times, which are 64 bit in version 1.
+fn track_id(tkhd: &[u8]) -> Option<u32> {
+    let offset = if *tkhd.first()? == 1 { 20 } else { 12 };
+    read_u32(tkhd, offset)
+}
+
+fn timescale(mdhd: &[u8]) -> Option<u32> {
+    let offset = if *mdhd.first()? == 1 { 20 } else { 12 };
+    read_u32(mdhd, offset)
+}
+
+// Whether a table of count entries fits in the atom after its header, so that a corrupt count is caught before
+// anything is allocated for it.
+fn table_fits(atom: &[u8], header_length: usize, count: usize, entry_length: usize) -> bool {
+    let remaining = atom.len().checked_sub(header_length);
+    let table_length = count.checked_mul(entry_length);
+
+    matches!((remaining, table_length), (Some(remaining), Some(length)) if length <= remaining)
+}
+
+// The stts atom holds runs of samples with the same duration. Runs can claim any number of samples, so only as many
+// starts as there are samples are made.
+fn sample_starts(stts: &[u8], sample_count: usize) -> Option<Vec<u64>> {
+    let count = read_u32(stts, 4)? as usize;
+    if !table_fits(stts, 8, count, 8) {
+        return None;
+    }
+
+    let mut starts = Vec::with_capacity(sample_count);
+    let mut time = 0u64;
+    for i in 0..count {
+        let run_length = read_u32(stts, 8 + i * 8)?;
+        let duration = read_u32(stts, 12 + i * 8)? as u64;
+
+        for _ in 0..run_length {
+            if starts.len() == sample_count {
+                return Some(starts);
+            }
+
+            starts.push(time);
+            time = time.checked_add(duration)?;
+        }
+    }
+
+    Some(starts)
+}
+
+// The samples have to be in the file, so a uniform size can't add up to more than the file's length.
+fn sample_sizes(stsz: &[u8], file_length: u64) -> Option<Vec<u32>> {
+    let uniform_size = read_u32(stsz, 4)?;
+    let count = read_u32(stsz, 8)? as usize;
+
+    if uniform_size != 0 {
+        let total = (uniform_size as u64).checked_mul(count as u64)?;
+        return (total <= file_length).then(|| vec![uniform_size; count]);
+    }
+
+    if !table_fits(stsz, 12, count, 4) {
+        return None;
+    }
+
+    (0..count).map(|i| read_u32(stsz, 12 + i * 4)).collect()
+}
+
+// Samples are stored in chunks. The stsc atom says how many samples each chunk holds, and stco (or co64 for large
+// files) says where each chunk starts.
+fn sample_offsets(stbl: &[u8], sizes: &[u32]) -> Option<Vec<u64>> {
+    let chunk_offsets: Vec<u64> = if let Some(stco) = find_atom(stbl, &[b"stco"]) {
+        let count = read_u32(stco, 4)? as usize;
+        if !table_fits(stco, 8, count, 4) {
+            return None;
+        }
+
+        (0..count)
+            .map(|i| read_u32(stco, 8 + i * 4).map(u64::from))
+            .collect::<Option<_>>()?
+    } else {
+        let co64 = find_atom(stbl, &[b"co64"])?;
+        let count = read_u32(co64, 4)? as usize;
+        if !table_fits(co64, 8, count, 8) {
+            return None;
+        }
+
+        (0..count).map(|i| read_u64(co64, 8 + i * 8)).collect::<Option<_>>()?
+    };
+
+    let stsc = find_atom(stbl, &[b"stsc"])?;
+    let run_count = read_u32(stsc, 4)? as usize;
+    if !table_fits(stsc, 8, run_count, 12) {
+        return None;
+    }
+
+    let runs = (0..run_count)
+        .map(|i| Some((read_u32(stsc, 8 + i * 12)?, read_u32(stsc, 12 + i * 12)?)))
+        .collect::<Option<Vec<(u32, u32)>>>()?;
+
+    let mut offsets = Vec::new();
+    let mut sizes = sizes.iter();
+    for (index, chunk_offset) in chunk_offsets.into_iter().enumerate() {
+        // Chunks are numbered from 1, and each run applies until the next one starts.
+        let chunk_number = index as u32 + 1;
+        let samples_per_chunk = runs.iter().rev().find(|(first_chunk, _)| *first_chunk <= chunk_number)?.1;
+
+        let mut offset = chunk_offset;
+        for _ in 0..samples_per_chunk {
+            let Some(size) = sizes.next() else {
+                return Some(offsets);
+            };
+
+            offsets.push(offset);
+            offset = offset.checked_add(*size as u64)?;
+        }
+    }
+
+    Some(offsets)
+}
+
+// Podcasts and audiobooks in mp3 files list their chapters in CHAP frames of the ID3v2 tag. A CTOC frame marked as
+// the top level one says which of them make up the table of contents; without one, every CHAP frame is a chapter.
+fn read_id3_chapters(path: &Path) -> Result<Vec<Chapter>> {
+    let mut file = File::open(path)?;
+
+    let mut header = [0; ID3_HEADER_LENGTH];
+    file.read_exact(&mut header)?;
+    if &header[0..3] != b"ID3" {
+        return Ok(Vec::new());
+    }
+
+    let version = header[3];
+    let flags = header[5];
+    if version != 3 && version != 4 {
+        return Ok(Vec::new()); // ID3v2.2 has no chapter frames.
+    }
+
+    let tag_length = synchsafe(&header[6..10]).ok_or_else(|| anyhow!("Malformed ID3 header"))? as usize;
+    let remaining = file.metadata()?.len().saturating_sub(ID3_HEADER_LENGTH as u64);
+    if tag_length as u64 > remaining {
+        return Err(anyhow!("ID3 tag extends past the end of the file"));
+    }
+
+    let mut tag = vec![0; tag_length];
+    file.read_exact(&mut tag)?;
+
+    // In version 3, unsynchronisation applies to the whole tag. In version 4, it is set on each frame instead.
+    if version == 3 && flags & 0x80 != 0 {
+        tag = remove_unsynchronisation(&tag);
+    }
+
+    let mut position = 0;
+    if flags & 0x40 != 0 {
+        // The extended header's length excludes its own length field in version 3, but not in version 4.
+        position = match version {
+            3 => read_u32(&tag, 0).ok_or_else(|| anyhow!("Malformed ID3 extended header"))? as usize + 4,
+            _ => synchsafe(&tag).ok_or_else(|| anyhow!("Malformed ID3 extended header"))? as usize,
+        };
+    }
+
+    let frames = id3_frames(tag.get(position..).unwrap_or_default(), version);
+
+    let mut chapters = Vec::new();
+    let mut table_of_contents = None;
+    for (id, body) in &frames {
+        match id {
+            b"CHAP" => chapters.extend(parse_chap_frame(body, version)),
+            b"CTOC" => {
+                if let Some(children) = parse_top_level_ctoc_frame(body) {
+                    table_of_contents = Some(children);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(children) = table_of_contents {
+        chapters.retain(|(element_id, _, _)| children.contains(element_id));
+    }
+
+    let chapters = chapters
+        .into_iter()
+        .enumerate()
+        .map(|(i, (_, title, start))| Chapter {
+            title: title.unwrap_or_else(|| format!("Chapter {}", i + 1)),
+            start,
+        })
+        .collect();
+
+    Ok(chapters)
+}
+
+// The id and body of each frame, stopping at the padding or anything malformed.
+fn id3_frames(data: &[u8], version: u8) -> Vec<([u8; 4], Vec<u8>)> {
+    let mut frames = Vec::new();
+    let mut position = 0;
+
+    while let Some(header) = data.get(position..position + ID3_FRAME_HEADER_LENGTH) {
+        let Ok(id) = <[u8; 4]>::try_from(&header[0..4]) else {
+            break;
+        };
+        if !id.iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit()) {
+            break; // Padding.
+        }
+
+        let length = match version {
+            3 => read_u32(header, 4),
+            _ => synchsafe(&header[4..8]),
+        };
+        let Some(length) = length.map(|l| l as usize) else {
+            break;
+        };
+
+        let start = position + ID3_FRAME_HEADER_LENGTH;
+        let Some(body) = data.get(start..start + length) else {
+            break;
+        };
+        position = start + length;
+
+        // Version 4 frames may be unsynchronised on their own, and may start with their length before unsynchronisation.
+        let format_flags = header[9];
+        let mut body = body.to_vec();
+        if version == 4 && format_flags & 0x02 != 0 {
+            body = remove_unsynchronisation(&body);
+        }
+        if version == 4 && format_flags & 0x01 != 0 {
+            body = body.get(4..).unwrap_or_default().to_vec();
+        }
+
+        frames.push((id, body));
+    }
+
+    frames
+}
+
+// A chapter's element id, title, and start. The title is in an embedded TIT2 frame, if any.
+fn parse_chap_frame(body: &[u8], version: u8) -> Option<(Vec<u8>, Option<String>, Duration)> {
+    let id_end = body.iter().position(|b| *b == 0)?;
+    let element_id = body[..id_end].to_vec();
+
+    let start = read_u32(body, id_end + 1)?; // In milliseconds. The end time and byte offsets follow.
+    let subframes = id3_frames(body.get(id_end + 17..).unwrap_or_default(), version);
+
+    let title = subframes
+        .iter()
+        .find(|(id, _)| id == b"TIT2")
+        .and_then(|(_, text)| decode_id3_text(text))
+        .filter(|title| !title.is_empty());
+
+    Some((element_id, title, Duration::from_millis(start as u64)))
+}
+
+// The element ids of the chapters in the table of contents, if it is the top level one.
+fn parse_top_level_ctoc_frame(body: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let id_end = body.iter().position(|b| *b == 0)?;
+    let flags = *body.get(id_end + 1)?;
+    let count = *body.get(id_end + 2)?;
+
+    if flags & 0x02 == 0 {
+        return None;
+    }
+
+    let mut children = Vec::new();
+    let mut rest = body.get(id_end + 3..)?;
+    for _ in 0..count {
+        let end = rest.iter().position(|b| *b == 0).unwrap_or(rest.len());
+        children.push(rest[..end].to_vec());
+        rest = rest.get(end + 1..).unwrap_or_default();
+    }
+
+    Some(children)
+}
+
+// Text frames start with their encoding: ISO-8859-1, UTF-16 with a byte order mark, UTF-16BE, or UTF-8.
+fn decode_id3_text(text: &[u8]) -> Option<String> {
+    let (encoding, text) = text.split_first()?;
+
+    let decode_utf16 = |bytes: &[u8], big_endian: bool| {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| {
+                if big_endian {
+                    u16::from_be_bytes([pair[0], pair[1]])
+                } else {
+                    u16::from_le_bytes([pair[0], pair[1]])
+                }
+            })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    let decoded = match encoding {
+        0 => text.iter().map(|b| *b as char).collect(),
+        1 => match text {
+            [0xFF, 0xFE, rest @ ..] => decode_utf16(rest, false),
+            [0xFE, 0xFF, rest @ ..] => decode_utf16(rest, true),
+            _ => decode_utf16(text, true),
+        },
+        2 => decode_utf16(text, true),
+        3 => String::from_utf8_lossy(text).into_owned(),
+        _ => return None,
+    };
+
+    Some(decoded.trim_end_matches('\0').to_owned())
+}
+
+// Sizes in ID3 headers use 7 bits per byte, so that they never look like the start of an mpeg frame.
+fn synchsafe(bytes: &[u8]) -> Option<u32> {
+    let bytes = bytes.get(0..4)?;
+    Some(bytes.iter().fold(0, |size, b| (size << 7) | (*b & 0x7F) as u32))
+}
+
+// Unsynchronisation inserts a zero after every 0xFF byte.
+fn remove_unsynchronisation(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut previous = 0;
+
+    for &b in data {
+        if !(previous == 0xFF && b == 0) {
+            result.push(b);
+        }
+        previous = b;
+    }
+
+    result
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    Some(u64::from_be_bytes(bytes.try_into().ok()?))
+}
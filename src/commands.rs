@@ -1,21 +1,38 @@
-use std::{path::PathBuf, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use egui::{Context, OpenUrl, ViewportCommand};
 use log::{error, info, warn};
 use strum_macros::{Display, EnumString};
 
 use crate::{
-    GemPlayer, maybe_play_next, maybe_play_previous,
+    GemPlayer,
+    artwork_fetcher::ArtworkFetchRequest,
+    artwork_picker::spawn_artwork_picker,
+    chapters::{next_chapter_start, previous_chapter_start},
+    file_opener::spawn_file_opener,
+    library_watcher::LibraryWatcherCommand,
+    maybe_play_next, maybe_play_previous,
     os_media_controls::{OSMediaControlsState, update_metadata, update_playback},
     player::{
-        enqueue, enqueue_next, get_position, mute_or_unmute, pause, play, replace_queue, seek, set_volume, stop, toggle, toggle_repeat,
+        cycle_repeat, enqueue, enqueue_next, get_position, mute_or_unmute, pause, play, replace_queue, seek, set_volume, toggle,
         toggle_shuffle,
     },
-    playlist::{PlaylistRetrieval, add_to_playlist, remove_from_playlist},
-    track::{Track, TrackRetrieval, open_file_location},
-    ui::root::format_duration_to_mmss,
+    playlist::{ExportOptions, PlaylistRetrieval, add_to_playlist, remove_from_playlist, remove_missing_entries},
+    playlist_entry_locator::spawn_playlist_entry_locator,
+    playlist_exporter::spawn_playlist_exporter,
+    track::{Track, TrackRetrieval, now_playing_string, open_file_location, write_rating},
+    ui::{
+        albums_view::AlbumKey,
+        root::{View, format_duration_to_mmss, invert_track_selection, select_all_tracks},
+    },
 };
 
+#[cfg(target_os = "linux")]
+use crate::os_media_controls::update_volume;
+
 // The general rule for what justifies a command is whether or not it mutates state
 // and if it used in multiple places.
 #[derive(PartialEq, Debug, Clone, EnumString, Display)]
@@ -28,12 +45,14 @@ pub enum GemCommand {
     NextTrack,
     PreviousTrack,
 
-    ToggleRepeat,
+    CycleRepeat,
     ToggleShuffle,
 
     SeekTo(Duration),
     SeekForward(Duration),
     SeekBackward(Duration),
+    NextChapter,
+    PreviousChapter,
 
     SetVolume(f32),
     ToggleMute,
@@ -42,13 +61,26 @@ pub enum GemCommand {
         track_keys: Vec<PathBuf>,
         start_at: Option<PathBuf>,
     },
+    ShuffleLibrary,
+    OpenFiles(Vec<PathBuf>), // Files and folders from outside the app, e.g. "Open with…". Played even if not in the library.
     AddTracksToPlaylist {
         playlist_key: PathBuf,
         track_keys: Vec<PathBuf>,
     },
     RemoveTracksFromPlaylist {
         playlist_key: PathBuf,
-        track_keys: Vec<PathBuf>
+        track_keys: Vec<PathBuf>,
+    },
+    LocateMissingPlaylistEntry {
+        playlist_key: PathBuf,
+        missing_path: PathBuf,
+    },
+    RemoveMissingPlaylistEntries {
+        playlist_key: PathBuf,
+    },
+    ExportPlaylist {
+        playlist_key: PathBuf,
+        options: ExportOptions,
     },
     EnqueueTracks {
         track_keys: Vec<PathBuf>,
@@ -56,7 +88,29 @@ pub enum GemCommand {
     EnqueueTracksNext {
         track_keys: Vec<PathBuf>,
     },
+    SetRating {
+        track_keys: Vec<PathBuf>,
+        rating: u8,
+    },
+    PickArtwork {
+        track_keys: Vec<PathBuf>,
+    },
+    FetchAlbumArtwork {
+        album: AlbumKey,
+        artist: Option<String>,
+    },
+    TrashTracks {
+        track_keys: Vec<PathBuf>,
+    },
+    RemoveReferencedTracks {
+        track_keys: Vec<PathBuf>,
+    },
     OpenTrackLocation(PathBuf),
+    CopyNowPlaying,
+    LocatePlayingTrack,
+    SelectAllTracks, // In the table of the current view.
+    InvertTrackSelection,
+    ToggleAmbientMode,
 
     OpenUri(String),
     ReportIssue,
@@ -94,7 +148,7 @@ pub fn execute(ctx: &Context, gem: &mut GemPlayer, command: GemCommand) {
             }
         }
         GemCommand::Stop => {
-            stop(&mut gem.player);
+            fade_out_and_stop(&mut gem.player);
 
             if let OSMediaControlsState::Initialized(osmc) = &mut gem.os_media_controls {
                 if let Err(e) = update_metadata(&mut osmc.controls, &gem.player) {
@@ -119,7 +173,7 @@ pub fn execute(ctx: &Context, gem: &mut GemPlayer, command: GemCommand) {
 
             info!("Seeking to {}", format_duration_to_mmss(position));
         }
-        GemCommand::ToggleRepeat => toggle_repeat(&mut gem.player),
+        GemCommand::CycleRepeat => cycle_repeat(&mut gem.player),
         GemCommand::ToggleShuffle => toggle_shuffle(&mut gem.player),
         GemCommand::SeekForward(offset) => {
             if let Some(position) = get_position(&gem.player) {
@@ -137,7 +191,7 @@ pub fn execute(ctx: &Context, gem: &mut GemPlayer, command: GemCommand) {
         }
         GemCommand::SeekBackward(offset) => {
             if let Some(position) = get_position(&gem.player) {
-                let new_position = position - offset;
+                let new_position = position.saturating_sub(offset); // Remote controls can seek back past the start.
                 if let Err(e) = seek(&mut gem.player, new_position) {
                     error!("{}", e);
                 } else if let OSMediaControlsState::Initialized(osmc) = &mut gem.os_media_controls
@@ -149,13 +203,39 @@ pub fn execute(ctx: &Context, gem: &mut GemPlayer, command: GemCommand) {
                 error!("Unable to retrieve position");
             }
         }
+        GemCommand::NextChapter => {
+            let position = get_position(&gem.player).unwrap_or_default();
+            if let Some(start) = next_chapter_start(&gem.chapters, position) {
+                execute(ctx, gem, GemCommand::SeekTo(start));
+            }
+        }
+        GemCommand::PreviousChapter => {
+            let position = get_position(&gem.player).unwrap_or_default();
+            if let Some(start) = previous_chapter_start(&gem.chapters, position, gem.player.rewind_threshold) {
+                execute(ctx, gem, GemCommand::SeekTo(start));
+            }
+        }
         GemCommand::SetVolume(volume) => {
             if let Err(e) = set_volume(&mut gem.player, volume) {
                 error!("{}", e);
             }
+
+            #[cfg(target_os = "linux")]
+            if let OSMediaControlsState::Initialized(osmc) = &mut gem.os_media_controls
+                && let Err(e) = update_volume(&mut osmc.controls, &gem.player)
+            {
+                error!("{}", e);
+            }
         }
         GemCommand::ToggleMute => {
             mute_or_unmute(&mut gem.player);
+
+            #[cfg(target_os = "linux")]
+            if let OSMediaControlsState::Initialized(osmc) = &mut gem.os_media_controls
+                && let Err(e) = update_volume(&mut osmc.controls, &gem.player)
+            {
+                error!("{}", e);
+            }
         }
         GemCommand::PlayTrackList { track_keys, start_at } => {
             let tracks: Vec<Track> = track_keys
@@ -172,6 +252,25 @@ pub fn execute(ctx: &Context, gem: &mut GemPlayer, command: GemCommand) {
 
             maybe_play_next(ctx, gem);
         }
+        GemCommand::OpenFiles(paths) => {
+            // Replaces files that are still being opened, since the latest files opened are the ones to play.
+            gem.file_opener = Some(spawn_file_opener(paths, &gem.library, ctx.clone()));
+        }
+        GemCommand::ShuffleLibrary => {
+            if gem.library.is_empty() {
+                gem.ui.toasts.info("The library is empty.");
+                return;
+            }
+
+            info!("Shuffling the library.");
+
+            // Start from the library order so that turning shuffle off restores it.
+            gem.player.shuffle = None;
+            replace_queue(&mut gem.player, &gem.library, 0);
+            toggle_shuffle(&mut gem.player);
+
+            maybe_play_next(ctx, gem);
+        }
         GemCommand::AddTracksToPlaylist { playlist_key, track_keys } => {
             if track_keys.is_empty() {
                 warn!("No track(s) were provided for adding to playlist.");
@@ -181,16 +280,34 @@ pub fn execute(ctx: &Context, gem: &mut GemPlayer, command: GemCommand) {
             let playlist = gem.playlists.get_by_path_mut(&playlist_key);
 
             let mut added_count = 0;
+            let mut repeated_count = 0;
+            let mut last_error = None;
             for track_key in &track_keys {
                 let track = gem.library.get_by_path(track_key);
+                let is_repeat = playlist.tracks.contains(track);
 
                 if let Err(e) = add_to_playlist(playlist, track.clone()) {
-                    error!("Failed to add track to playlist: {}", e);
+                    error!("Failed to add track to playlist: {:#}", e);
+                    last_error = Some(e);
                 } else {
                     added_count += 1;
+                    if is_repeat {
+                        repeated_count += 1;
+                    }
                 }
             }
 
+            if let Some(e) = last_error {
+                gem.ui.toasts.error(format!("Failed to add track(s) to playlist: {:#}", e));
+            }
+
+            // Only playlists that allow duplicates get here with repeats, so this is a heads up rather than an error.
+            if repeated_count > 0 {
+                let message = format!("{} track(s) were already in playlist '{}'.", repeated_count, playlist.name);
+                warn!("{}", message);
+                gem.ui.toasts.warning(message);
+            }
+
             gem.ui.playlists.cache_dirty = true;
 
             if added_count > 0 {
@@ -203,21 +320,27 @@ pub fn execute(ctx: &Context, gem: &mut GemPlayer, command: GemCommand) {
         }
         GemCommand::RemoveTracksFromPlaylist { playlist_key, track_keys } => {
             let playlist = gem.playlists.get_by_path_mut(&playlist_key);
-            
+
             if gem.ui.playlists.selected_tracks.is_empty() {
                 error!("No track(s) were provided for removing track from playlist.");
                 return;
             };
 
             let mut added_count = 0;
+            let mut last_error = None;
             for track_key in &track_keys {
                 if let Err(e) = remove_from_playlist(playlist, track_key) {
-                    error!("Failed to remove track from playlist: {}", e);
+                    error!("Failed to remove track from playlist: {:#}", e);
+                    last_error = Some(e);
                 } else {
                     added_count += 1;
                 }
             }
 
+            if let Some(e) = last_error {
+                gem.ui.toasts.error(format!("Failed to remove track(s) from playlist: {:#}", e));
+            }
+
             gem.ui.playlists.cache_dirty = true;
 
             if added_count > 0 {
@@ -228,6 +351,41 @@ pub fn execute(ctx: &Context, gem: &mut GemPlayer, command: GemCommand) {
                 gem.ui.toasts.error("No tracks were removed.");
             }
         }
+        GemCommand::LocateMissingPlaylistEntry {
+            playlist_key,
+            missing_path,
+        } => {
+            if gem.playlist_entry_locator.is_some() {
+                warn!("A playlist entry locator is already open.");
+                return;
+            }
+
+            gem.playlist_entry_locator = Some(spawn_playlist_entry_locator(playlist_key, missing_path));
+        }
+        GemCommand::RemoveMissingPlaylistEntries { playlist_key } => {
+            let playlist = gem.playlists.get_by_path_mut(&playlist_key);
+
+            match remove_missing_entries(playlist) {
+                Ok(removed_count) => {
+                    let message = format!("Removed {} missing entries from playlist '{}'.", removed_count, playlist.name);
+                    info!("{}", message);
+                    gem.ui.toasts.success(message);
+                }
+                Err(e) => {
+                    error!("{:#}", e);
+                    gem.ui.toasts.error(format!("Failed to remove missing entries: {:#}", e));
+                }
+            }
+        }
+        GemCommand::ExportPlaylist { playlist_key, options } => {
+            if gem.playlist_exporter.is_some() {
+                warn!("A playlist exporter is already open.");
+                return;
+            }
+
+            let playlist = gem.playlists.get_by_path(&playlist_key);
+            gem.playlist_exporter = Some(spawn_playlist_exporter(playlist_key.clone(), &playlist.name, options));
+        }
         GemCommand::EnqueueTracks { track_keys } => {
             if track_keys.is_empty() {
                 warn!("No track(s) were provided for enqueue.");
@@ -239,13 +397,111 @@ pub fn execute(ctx: &Context, gem: &mut GemPlayer, command: GemCommand) {
                 enqueue(&mut gem.player, track.clone());
             }
         }
+        GemCommand::SetRating { track_keys, rating } => {
+            let mut last_error = None;
+
+            for track_key in &track_keys {
+                let Some(track) = gem.library.iter().find(|t| &t.path == track_key) else {
+                    warn!("Track {} is not in the library.", track_key.display());
+                    continue;
+                };
+
+                if let Err(e) = write_rating(track, rating, &gem.rating_email) {
+                    error!("{}", e);
+                    last_error = Some(e);
+                    continue;
+                }
+
+                set_rating(gem, track_key, rating);
+            }
+
+            if let Some(e) = last_error {
+                gem.ui.toasts.error(format!("{}", e));
+            }
+
+            gem.ui.library.cache_dirty = true;
+            gem.ui.albums.cache_dirty = true;
+            gem.ui.playlists.cache_dirty = true;
+        }
+        GemCommand::PickArtwork { track_keys } => {
+            if gem.artwork_picker.is_some() {
+                warn!("An artwork picker is already open.");
+                return;
+            }
+
+            gem.artwork_picker = Some(spawn_artwork_picker(track_keys));
+        }
+        GemCommand::FetchAlbumArtwork { album, artist } => {
+            let request = ArtworkFetchRequest {
+                album: album.0.clone(),
+                album_artist: album.1.clone(),
+                artist,
+            };
+
+            if gem.artwork_fetcher.request_sender.send(request).is_err() {
+                error!("The artwork fetcher has stopped.");
+                gem.ui.toasts.error("Failed to fetch artwork.");
+                return;
+            }
+
+            gem.ui.albums.fetching.insert(album);
+        }
+        GemCommand::TrashTracks { track_keys } => {
+            let mut trashed = Vec::new();
+            for track_key in &track_keys {
+                if let Err(e) = trash::delete(track_key) {
+                    error!("Failed to move '{}' to the trash: {}", track_key.display(), e);
+                    gem.ui
+                        .toasts
+                        .error(format!("Failed to move '{}' to the trash.", track_key.display()));
+                    continue;
+                }
+
+                info!("Moved '{}' to the trash.", track_key.display());
+                trashed.push(track_key.clone());
+            }
+
+            if trashed.is_empty() {
+                return;
+            }
+
+            // The watcher would notice the deletions eventually, but the library should update immediately.
+            gem.library.retain(|t| !trashed.contains(&t.path));
+            if gem.referenced_files.iter().any(|f| trashed.contains(f)) {
+                gem.referenced_files.retain(|f| !trashed.contains(f));
+                send_referenced_files(gem);
+            }
+
+            gem.ui.library.cache_dirty = true;
+            gem.ui.albums.cache_dirty = true;
+            gem.ui.toasts.success(format!("Moved {} track(s) to the trash.", trashed.len()));
+        }
+        GemCommand::RemoveReferencedTracks { track_keys } => {
+            let before = gem.referenced_files.len();
+            gem.referenced_files.retain(|f| !track_keys.contains(f));
+            let removed_count = before - gem.referenced_files.len();
+
+            if removed_count == 0 {
+                warn!("None of the tracks to remove were referenced files.");
+                return;
+            }
+
+            gem.library.retain(|t| !track_keys.contains(&t.path));
+            send_referenced_files(gem);
+
+            gem.ui.library.cache_dirty = true;
+            gem.ui.albums.cache_dirty = true;
+            gem.ui
+                .toasts
+                .success(format!("Removed {} track(s) from the library.", removed_count));
+        }
         GemCommand::OpenTrackLocation(track_key) => {
             let track = gem.library.get_by_path(&track_key);
 
             if let Err(e) = open_file_location(track) {
                 error!("Failed to open track location: {}", e);
             } else {
-                info!("Opening track location: {}", track.path.display());
+                info!("Opening track location: {}", track.audio_path().display());
             }
         }
         GemCommand::EnqueueTracksNext { track_keys } => {
@@ -259,6 +515,33 @@ pub fn execute(ctx: &Context, gem: &mut GemPlayer, command: GemCommand) {
                 enqueue_next(&mut gem.player, track.clone());
             }
         }
+        GemCommand::CopyNowPlaying => {
+            let Some(track) = &gem.player.playing else {
+                gem.ui.toasts.info("Nothing is playing.");
+                return;
+            };
+
+            let text = now_playing_string(track);
+            info!("Copied to clipboard: {}", text);
+            ctx.copy_text(text);
+            gem.ui.toasts.success("Copied now playing to clipboard.");
+        }
+        GemCommand::LocatePlayingTrack => {
+            if gem.player.playing.is_none() {
+                gem.ui.toasts.info("Nothing is playing.");
+                return;
+            }
+
+            // The queue and history don't contain the playing track, so we locate it in the library instead.
+            let playlist_is_selected =
+                gem.ui.playlists.selected_playlist_key.is_some() || gem.ui.playlists.selected_auto_playlist.is_some();
+            let playlist_is_open = gem.ui.current_view == View::Playlists && playlist_is_selected;
+            if gem.ui.current_view != View::Library && !playlist_is_open {
+                gem.ui.current_view = View::Library;
+            }
+
+            gem.ui.locate_playing.scroll_pending = true;
+        }
         GemCommand::OpenUri(uri) => {
             warn!("OpenUri is not supported: {uri}");
         }
@@ -266,7 +549,51 @@ pub fn execute(ctx: &Context, gem: &mut GemPlayer, command: GemCommand) {
             let url = format!("{}/issues", env!("CARGO_PKG_REPOSITORY"));
             ctx.open_url(OpenUrl { url, new_tab: true });
         }
+        GemCommand::SelectAllTracks => match gem.ui.current_view {
+            View::Library => select_all_tracks(&mut gem.ui.library.selected_tracks, &gem.ui.library.cached_library),
+            View::Playlists => {
+                let playlists = &mut gem.ui.playlists;
+                select_all_tracks(&mut playlists.selected_tracks, &playlists.cached_playlist_tracks);
+            }
+            _ => {}
+        },
+        GemCommand::InvertTrackSelection => match gem.ui.current_view {
+            View::Library => invert_track_selection(&mut gem.ui.library.selected_tracks, &gem.ui.library.cached_library),
+            View::Playlists => {
+                let playlists = &mut gem.ui.playlists;
+                invert_track_selection(&mut playlists.selected_tracks, &playlists.cached_playlist_tracks);
+            }
+            _ => {}
+        },
+        GemCommand::ToggleAmbientMode => {
+            gem.ui.ambient.enabled = !gem.ui.ambient.enabled;
+            ctx.send_viewport_cmd(ViewportCommand::Fullscreen(gem.ui.ambient.enabled));
+        }
         GemCommand::RaiseWindow => ctx.send_viewport_cmd(ViewportCommand::Focus),
         GemCommand::Quit => ctx.send_viewport_cmd(ViewportCommand::Close),
     }
 }
+
+fn send_referenced_files(gem: &mut GemPlayer) {
+    let command = LibraryWatcherCommand::SetReferencedFiles(gem.referenced_files.clone());
+    if let Err(e) = gem.library_watcher.command_sender.send(command) {
+        error!("Failed to update referenced files: {e}");
+    }
+}
+
+// Tracks are cloned into the playlists and the player, so every copy needs the new rating.
+fn set_rating(gem: &mut GemPlayer, track_key: &Path, rating: u8) {
+    let playlist_tracks = gem.playlists.iter_mut().flat_map(|p| p.tracks.iter_mut());
+    let player_tracks = gem
+        .player
+        .queue
+        .iter_mut()
+        .chain(gem.player.history.iter_mut())
+        .chain(gem.player.playing.as_mut());
+
+    for track in gem.library.iter_mut().chain(playlist_tracks).chain(player_tracks) {
+        if track.path == track_key {
+            track.rating = rating;
+        }
+    }
+}
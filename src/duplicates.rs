@@ -0,0 +1,120 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{metadata, read},
+    hash::{DefaultHasher, Hash, Hasher},
+    path::PathBuf,
+};
+
+use fully_pub::fully_pub;
+use rayon::prelude::*;
+
+use crate::track::Track;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateReason {
+    SameFile, // The audio files have identical contents.
+    SameTags, // The title, artist, and duration match.
+}
+
+impl DuplicateReason {
+    pub fn label(self) -> &'static str {
+        match self {
+            DuplicateReason::SameFile => "Identical files",
+            DuplicateReason::SameTags => "Same title, artist, and length",
+        }
+    }
+}
+
+#[fully_pub]
+struct DuplicateGroup {
+    reason: DuplicateReason,
+    tracks: Vec<Track>,
+}
+
+// Finds tracks that are likely duplicates of each other. This reads files, so it should not be called on the ui thread.
+pub fn find_duplicates(tracks: &[Track]) -> Vec<DuplicateGroup> {
+    let mut groups = find_identical_files(tracks);
+
+    // Tracks that are already grouped as identical files would just show up again here.
+    let already_grouped: HashSet<Vec<PathBuf>> = groups.iter().map(|g| sorted_paths(&g.tracks)).collect();
+
+    for group in find_matching_tags(tracks) {
+        if !already_grouped.contains(&sorted_paths(&group.tracks)) {
+            groups.push(group);
+        }
+    }
+
+    groups
+}
+
+fn find_identical_files(tracks: &[Track]) -> Vec<DuplicateGroup> {
+    // Virtual cue tracks share their audio file, so they aren't compared by contents.
+    let files: Vec<&Track> = tracks.iter().filter(|t| t.cue.is_none()).collect();
+
+    // Only files of the same size can be identical, which avoids hashing most of the library.
+    let mut tracks_by_size: HashMap<u64, Vec<&Track>> = HashMap::new();
+    for track in files {
+        if let Ok(file_metadata) = metadata(&track.path) {
+            tracks_by_size.entry(file_metadata.len()).or_default().push(track);
+        }
+    }
+
+    let candidates: Vec<&Track> = tracks_by_size.into_values().filter(|t| t.len() > 1).flatten().collect();
+
+    let hashed: Vec<(u64, &Track)> = candidates
+        .par_iter()
+        .filter_map(|track| {
+            let contents = read(&track.path).ok()?;
+            let mut hasher = DefaultHasher::new();
+            contents.hash(&mut hasher);
+            Some((hasher.finish(), *track))
+        })
+        .collect();
+
+    let mut tracks_by_hash: HashMap<u64, Vec<Track>> = HashMap::new();
+    for (hash, track) in hashed {
+        tracks_by_hash.entry(hash).or_default().push(track.clone());
+    }
+
+    into_groups(tracks_by_hash, DuplicateReason::SameFile)
+}
+
+fn find_matching_tags(tracks: &[Track]) -> Vec<DuplicateGroup> {
+    let mut tracks_by_tags: HashMap<(String, String, u64), Vec<Track>> = HashMap::new();
+
+    for track in tracks {
+        if track.title.is_none() || track.artist.is_none() {
+            continue;
+        }
+
+        let fields = &track.search_fields;
+        let key = (
+            fields.title.trim().to_owned(),
+            fields.artist.trim().to_owned(),
+            track.duration.as_secs(),
+        );
+        tracks_by_tags.entry(key).or_default().push(track.clone());
+    }
+
+    into_groups(tracks_by_tags, DuplicateReason::SameTags)
+}
+
+fn into_groups<K>(tracks_by_key: HashMap<K, Vec<Track>>, reason: DuplicateReason) -> Vec<DuplicateGroup> {
+    let mut groups: Vec<DuplicateGroup> = tracks_by_key
+        .into_values()
+        .filter(|tracks| tracks.len() > 1)
+        .map(|mut tracks| {
+            tracks.sort_by(|a, b| a.path.cmp(&b.path));
+            DuplicateGroup { reason, tracks }
+        })
+        .collect();
+
+    groups.sort_by(|a, b| a.tracks[0].title.cmp(&b.tracks[0].title));
+    groups
+}
+
+fn sorted_paths(tracks: &[Track]) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = tracks.iter().map(|t| t.path.clone()).collect();
+    paths.sort();
+    paths
+}
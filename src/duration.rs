@@ -0,0 +1,350 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use lofty::file::FileType;
+use rodio::{Decoder, Source};
+
+use crate::track::Track;
+
+const MPEG_VERSION_1: u8 = 3;
+const MPEG_VERSION_2_5: u8 = 0;
+const LAYER_1: u8 = 3;
+const LAYER_2: u8 = 2;
+
+// In kbps, by bitrate index. Index 0 is free format and 15 is invalid, neither of which can be counted.
+const MPEG1_LAYER1_BITRATES: [u32; 15] = [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448];
+const MPEG1_LAYER2_BITRATES: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384];
+const MPEG1_LAYER3_BITRATES: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+const MPEG2_LAYER1_BITRATES: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256];
+const MPEG2_LAYER23_BITRATES: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+const MPEG1_SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+const FRAME_LOOKAHEAD: usize = 4096; // More than the longest frame and the header of the next one.
+const READ_LENGTH: usize = 16 * 1024;
+
+// The durations that lofty reads from headers can be far off, e.g. for VBR mp3s without a Xing header, whose duration
+// is estimated from the first frame's bitrate, and for ADTS streams, which have no header at all. Such tracks can be
+// measured instead.
+pub fn needs_measured_duration(track: &Track) -> bool {
+    let unreliable_format = matches!(track.codec, FileType::Mpeg | FileType::Aac);
+    !track.duration_measured && track.cue.is_none() && (unreliable_format || track.duration.is_zero())
+}
+
+// Mpeg files are measured by counting their frames, which is much faster than decoding. Anything else is decoded.
+pub fn measure_duration(track: &Track) -> Result<Duration> {
+    let path = track.audio_path();
+
+    if track.codec == FileType::Mpeg {
+        let file = File::open(path).with_context(|| format!("Failed to open audio file at {:?}", path))?;
+        let file_length = file.metadata()?.len();
+        return count_mpeg_frames(BufReader::new(file), file_length).with_context(|| format!("Failed to count the frames of {:?}", path));
+    }
+
+    decode_duration(path)
+}
+
+fn decode_duration(path: &Path) -> Result<Duration> {
+    let file = File::open(path).with_context(|| format!("Failed to open audio file at {:?}", path))?;
+    let decoder = Decoder::try_from(file).with_context(|| format!("Failed to decode audio file {:?}", path))?;
+
+    let samples_per_second = decoder.sample_rate().get() as f64 * decoder.channels().get() as f64;
+    let samples = decoder.count();
+
+    Ok(Duration::from_secs_f64(samples as f64 / samples_per_second))
+}
+
+// Walks the frames from the first to the last, skipping tags and junk between frames. The file is streamed, so only a
+// few frames are held in memory at a time.
+fn count_mpeg_frames(reader: impl Read, file_length: u64) -> Result<Duration> {
+    let mut window = ReadWindow::new(reader);
+
+    let mut position = id3v2_size(window.get(0, 10)?) as u64;
+    let mut seconds = 0.0;
+    let mut frames = 0;
+    let mut in_sync = false;
+
+    while position + 4 <= file_length {
+        let data = window.get(position, FRAME_LOOKAHEAD)?;
+        if data.len() < 4 {
+            break; // The file is shorter than its length said.
+        }
+
+        if data.starts_with(b"TAG") && file_length - position == 128 {
+            break; // An ID3v1 tag ends the file.
+        }
+
+        let Some(frame) = parse_frame_header(data) else {
+            in_sync = false;
+            position += 1;
+            continue;
+        };
+
+        // Frame syncs also show up inside of audio data and artwork, so after junk a frame only counts if another one
+        // follows it.
+        let next = frame.length;
+        if !in_sync && next + 4 <= data.len() && parse_frame_header(&data[next..]).is_none() {
+            position += 1;
+            continue;
+        }
+
+        // The Xing or Info header of VBR files is stored in a frame of silence that decoders skip.
+        let is_info_frame = frames == 0 && {
+            let frame_data = &data[..next.min(data.len())];
+            frame_data.windows(4).take(64).any(|w| w == b"Xing" || w == b"Info")
+        };
+
+        if !is_info_frame {
+            seconds += frame.samples as f64 / frame.sample_rate as f64;
+        }
+
+        frames += 1;
+        in_sync = true;
+        position += next as u64;
+    }
+
+    if frames == 0 {
+        bail!("No mpeg frames found");
+    }
+
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+// The bytes of a file around a position that only moves forward, read as they are needed.
+struct ReadWindow<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    start: u64, // The position in the file of the first byte of the buffer.
+    at_end: bool,
+}
+
+impl<R: Read> ReadWindow<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            start: 0,
+            at_end: false,
+        }
+    }
+
+    // Up to `length` bytes from the position, fewer at the end of the file. Positions before an earlier one are gone.
+    fn get(&mut self, position: u64, length: usize) -> io::Result<&[u8]> {
+        let end = self.start + self.buffer.len() as u64;
+        if position >= end {
+            // Skipped bytes, e.g. of a large ID3v2 tag, are read past without being kept.
+            io::copy(&mut (&mut self.reader).take(position - end), &mut io::sink())?;
+            self.buffer.clear();
+            self.start = position;
+        }
+
+        let offset = (position - self.start) as usize;
+        if self.buffer.len() - offset < length && !self.at_end {
+            self.buffer.drain(..offset);
+            self.start = position;
+
+            while self.buffer.len() < length && !self.at_end {
+                let filled = self.buffer.len();
+                self.buffer.resize(filled + READ_LENGTH, 0);
+                let read = self.reader.read(&mut self.buffer[filled..])?;
+                self.buffer.truncate(filled + read);
+                self.at_end = read == 0;
+            }
+        }
+
+        let available = &self.buffer[(position - self.start) as usize..];
+        Ok(&available[..length.min(available.len())])
+    }
+}
+
+struct FrameHeader {
+    length: usize, // In bytes, including the header.
+    samples: u32,
+    sample_rate: u32,
+}
+
+fn parse_frame_header(data: &[u8]) -> Option<FrameHeader> {
+    let header = data.get(..4)?;
+    if header[0] != 0xff || header[1] & 0xe0 != 0xe0 {
+        return None;
+    }
+
+    let version = (header[1] >> 3) & 0x03; // 0 is MPEG 2.5, 1 is reserved, 2 is MPEG 2, and 3 is MPEG 1.
+    let layer = (header[1] >> 1) & 0x03; // 0 is reserved, 1 is layer III, 2 is layer II, and 3 is layer I.
+    let bitrate_index = (header[2] >> 4) as usize;
+    let sample_rate_index = ((header[2] >> 2) & 0x03) as usize;
+    let padding = ((header[2] >> 1) & 0x01) as usize;
+
+    if version == 1 || layer == 0 || bitrate_index == 0 || bitrate_index == 15 || sample_rate_index == 3 {
+        return None;
+    }
+
+    let is_mpeg1 = version == MPEG_VERSION_1;
+    let bitrates = match (is_mpeg1, layer) {
+        (true, LAYER_1) => &MPEG1_LAYER1_BITRATES,
+        (true, LAYER_2) => &MPEG1_LAYER2_BITRATES,
+        (true, _) => &MPEG1_LAYER3_BITRATES,
+        (false, LAYER_1) => &MPEG2_LAYER1_BITRATES,
+        (false, _) => &MPEG2_LAYER23_BITRATES,
+    };
+    let bitrate = bitrates[bitrate_index] * 1000;
+
+    // MPEG 2 halves the sample rates of MPEG 1, and MPEG 2.5 halves them again.
+    let sample_rate = match version {
+        MPEG_VERSION_1 => MPEG1_SAMPLE_RATES[sample_rate_index],
+        MPEG_VERSION_2_5 => MPEG1_SAMPLE_RATES[sample_rate_index] / 4,
+        _ => MPEG1_SAMPLE_RATES[sample_rate_index] / 2,
+    };
+
+    // Layer I frames are counted in slots of 4 bytes. Layer III frames of MPEG 2 and 2.5 hold half as many samples.
+    let (samples, length) = if layer == LAYER_1 {
+        (384, ((12 * bitrate / sample_rate) as usize + padding) * 4)
+    } else if layer == LAYER_2 || is_mpeg1 {
+        (1152, (144 * bitrate / sample_rate) as usize + padding)
+    } else {
+        (576, (72 * bitrate / sample_rate) as usize + padding)
+    };
+
+    Some(FrameHeader {
+        length,
+        samples,
+        sample_rate,
+    })
+}
+
+// The size of the ID3v2 tag at the start of the file, if there is one, which can hold frame syncs in its artwork.
+fn id3v2_size(data: &[u8]) -> usize {
+    if data.len() < 10 || !data.starts_with(b"ID3") {
+        return 0;
+    }
+
+    let size = data[6..10].iter().fold(0usize, |size, byte| (size << 7) | (*byte & 0x7f) as usize);
+    let footer = if data[5] & 0x10 != 0 { 10 } else { 0 };
+
+    10 + size + footer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: [u8; 4] = [0xFF, 0xFB, 0x90, 0x00]; // MPEG 1 layer III, 128 kbps, 44.1 kHz, no padding.
+    const FRAME_LENGTH: usize = 417;
+    const FRAME_SECONDS: f64 = 1152.0 / 44100.0;
+
+    fn frame() -> Vec<u8> {
+        let mut frame = vec![0; FRAME_LENGTH];
+        frame[..4].copy_from_slice(&HEADER);
+        frame
+    }
+
+    fn frames(count: usize) -> Vec<u8> {
+        (0..count).flat_map(|_| frame()).collect()
+    }
+
+    fn count(data: &[u8]) -> f64 {
+        count_mpeg_frames(data, data.len() as u64)
+            .expect("The frames must be counted")
+            .as_secs_f64()
+    }
+
+    fn assert_frames(seconds: f64, frames: usize) {
+        let expected = frames as f64 * FRAME_SECONDS;
+        assert!((seconds - expected).abs() < 1e-6, "{} seconds, expected {}", seconds, expected);
+    }
+
+    #[test]
+    fn parses_mpeg1_layer3_header() {
+        let header = parse_frame_header(&HEADER).expect("The header must be valid");
+        assert_eq!(header.length, FRAME_LENGTH);
+        assert_eq!(header.samples, 1152);
+        assert_eq!(header.sample_rate, 44100);
+    }
+
+    #[test]
+    fn padding_adds_a_byte() {
+        let header = parse_frame_header(&[0xFF, 0xFB, 0x92, 0x00]).expect("The header must be valid");
+        assert_eq!(header.length, FRAME_LENGTH + 1);
+    }
+
+    #[test]
+    fn parses_mpeg2_layer3_header() {
+        let header = parse_frame_header(&[0xFF, 0xF3, 0x90, 0x00]).expect("The header must be valid"); // 80 kbps, 22.05 kHz.
+        assert_eq!(header.length, 261);
+        assert_eq!(header.samples, 576);
+        assert_eq!(header.sample_rate, 22050);
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        assert!(parse_frame_header(&[0xFF, 0xFB, 0xF0, 0x00]).is_none()); // Bitrate index 15.
+        assert!(parse_frame_header(&[0xFF, 0xFB, 0x00, 0x00]).is_none()); // Free format.
+        assert!(parse_frame_header(&[0xFF, 0xFB, 0x9C, 0x00]).is_none()); // Sample rate index 3.
+        assert!(parse_frame_header(&[0xFF, 0xEB, 0x90, 0x00]).is_none()); // Reserved version.
+        assert!(parse_frame_header(&[0xFF, 0xF9, 0x90, 0x00]).is_none()); // Reserved layer.
+        assert!(parse_frame_header(&[0xFF, 0xFB, 0x90]).is_none()); // Truncated.
+    }
+
+    #[test]
+    fn counts_constant_bitrate_frames() {
+        assert_frames(count(&frames(20)), 20);
+    }
+
+    #[test]
+    fn skips_xing_and_info_frames() {
+        for tag in [b"Xing", b"Info"] {
+            let mut info_frame = frame();
+            info_frame[36..40].copy_from_slice(tag);
+
+            let data = [info_frame, frames(10)].concat();
+            assert_frames(count(&data), 10);
+        }
+    }
+
+    #[test]
+    fn skips_id3v2_tag_with_frame_syncs() {
+        // The tag's body holds a fake frame header, e.g. in its artwork.
+        let mut body = vec![0; 300];
+        body[100..104].copy_from_slice(&HEADER);
+        let header = [b'I', b'D', b'3', 3, 0, 0, 0, 0, 0x02, 0x2C]; // 300 as a synchsafe integer.
+
+        let data = [header.to_vec(), body, frames(5)].concat();
+        assert_frames(count(&data), 5);
+    }
+
+    #[test]
+    fn stops_at_id3v1_tag() {
+        let mut tag = vec![0; 128];
+        tag[..3].copy_from_slice(b"TAG");
+        tag[50..54].copy_from_slice(&HEADER);
+
+        let data = [frames(5), tag].concat();
+        assert_frames(count(&data), 5);
+    }
+
+    #[test]
+    fn skips_junk_between_frames() {
+        let mut junk = vec![0x55; 37];
+        junk[10..14].copy_from_slice(&HEADER); // A frame sync that isn't followed by another frame.
+
+        let data = [frames(5), junk, frames(5)].concat();
+        assert_frames(count(&data), 10);
+    }
+
+    #[test]
+    fn streams_files_longer_than_the_read_window() {
+        let frame_count = 3 * READ_LENGTH / FRAME_LENGTH;
+        assert_frames(count(&frames(frame_count)), frame_count);
+    }
+
+    #[test]
+    fn fails_without_frames() {
+        let data = vec![0; 1000];
+        assert!(count_mpeg_frames(&data[..], data.len() as u64).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    collections::HashMap,
+    mem::take,
+    path::PathBuf,
+    sync::mpsc::{Receiver, channel},
+    thread,
+    time::{Duration, Instant},
+};
+
+use fully_pub::fully_pub;
+use log::{error, info, warn};
+
+use crate::{
+    GemPlayer,
+    duration::{measure_duration, needs_measured_duration},
+    library_watcher::LibraryWatcherCommand,
+    track::Track,
+};
+
+pub const APPLY_INTERVAL: Duration = Duration::from_secs(5); // Applying durations rebuilds the views, so they are applied in batches.
+
+pub enum DurationScanMessage {
+    Measured { path: PathBuf, duration: Duration },
+    Failed { path: PathBuf, header_duration: Duration },
+}
+
+// Measures the durations of tracks whose headers may be wrong in the background, one track at a time. Dropping the
+// scanner stops it after the current track.
+#[fully_pub]
+struct DurationScanner {
+    receiver: Receiver<DurationScanMessage>,
+    progress: (usize, usize),             // Tracks measured so far and the total.
+    measured: HashMap<PathBuf, Duration>, // Measured but not yet applied.
+    last_applied: Instant,
+}
+
+fn spawn_duration_scanner(tracks: Vec<Track>) -> DurationScanner {
+    let (sender, receiver) = channel();
+    let total = tracks.len();
+
+    thread::spawn(move || {
+        for track in tracks {
+            let message = match measure_duration(&track) {
+                Ok(duration) => DurationScanMessage::Measured {
+                    path: track.path,
+                    duration,
+                },
+                Err(e) => {
+                    warn!("Failed to measure the duration of '{}': {:#}", track.path.display(), e);
+                    DurationScanMessage::Failed {
+                        path: track.path,
+                        header_duration: track.duration,
+                    }
+                }
+            };
+
+            if sender.send(message).is_err() {
+                return; // The scanner was dropped.
+            }
+        }
+    });
+
+    DurationScanner {
+        receiver,
+        progress: (0, total),
+        measured: HashMap::new(),
+        last_applied: Instant::now(),
+    }
+}
+
+// Measures the library tracks that may have a wrong duration, replacing any scan that is already running.
+pub fn start_duration_scan(gem: &mut GemPlayer) {
+    stop_duration_scan(gem);
+
+    let tracks: Vec<Track> = gem.library.iter().filter(|t| needs_measured_duration(t)).cloned().collect();
+    if tracks.is_empty() {
+        return;
+    }
+
+    info!("Measuring the duration of {} track(s).", tracks.len());
+    gem.duration_scanner = Some(spawn_duration_scanner(tracks));
+}
+
+// Durations that were already measured are kept.
+pub fn stop_duration_scan(gem: &mut GemPlayer) {
+    apply_measured_durations(gem);
+    gem.duration_scanner = None;
+}
+
+// Corrects the durations everywhere the tracks are held, and hands them to the library watcher so that rescans and the
+// library cache keep them.
+pub fn apply_measured_durations(gem: &mut GemPlayer) {
+    let Some(scanner) = &mut gem.duration_scanner else {
+        return;
+    };
+
+    scanner.last_applied = Instant::now();
+    if scanner.measured.is_empty() {
+        return;
+    }
+    let measured = take(&mut scanner.measured);
+
+    let player = &mut gem.player;
+    let tracks = gem
+        .library
+        .iter_mut()
+        .chain(gem.playlists.iter_mut().flat_map(|p| p.tracks.iter_mut()))
+        .chain(player.queue.iter_mut())
+        .chain(player.history.iter_mut())
+        .chain(player.shuffle.iter_mut().flatten())
+        .chain(player.playing.iter_mut());
+
+    for track in tracks {
+        if let Some(duration) = measured.get(&track.path) {
+            track.duration = *duration;
+            track.duration_measured = true;
+        }
+    }
+
+    gem.ui.library.cache_dirty = true;
+    gem.ui.albums.cache_dirty = true;
+    gem.ui.playlists.cache_dirty = true;
+
+    let command = LibraryWatcherCommand::SetMeasuredDurations(measured);
+    if let Err(e) = gem.library_watcher.command_sender.send(command) {
+        error!("Failed to send measured durations to the library watcher: {}", e);
+    }
+}
@@ -0,0 +1,257 @@
+use fully_pub::fully_pub;
+use rodio::{ChannelCount, Sample, SampleRate, Source, source::SeekError};
+use std::{
+    f32::consts::PI,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+pub const BAND_COUNT: usize = 10;
+pub const BAND_FREQUENCIES: [f32; BAND_COUNT] = [31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0];
+pub const MAX_GAIN_DB: f32 = 12.0;
+const BAND_Q: f32 = 1.41; // Roughly one octave wide, so neighbouring bands overlap smoothly.
+
+#[fully_pub]
+struct Equalizer {
+    enabled: bool,
+    gains: [f32; BAND_COUNT], // In decibels.
+    shared_gains: Arc<SharedGains>,
+}
+
+// The gains read by the audio thread. Stored as f32 bits so they can be updated without locking.
+pub struct SharedGains {
+    gains: [AtomicU32; BAND_COUNT],
+    version: AtomicU64, // Incremented on every change so that sources know to recompute their filters.
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum EqualizerPreset {
+    Flat,
+    Rock,
+    BassBoost,
+}
+
+impl EqualizerPreset {
+    pub fn label(self) -> &'static str {
+        match self {
+            EqualizerPreset::Flat => "Flat",
+            EqualizerPreset::Rock => "Rock",
+            EqualizerPreset::BassBoost => "Bass Boost",
+        }
+    }
+
+    pub fn gains(self) -> [f32; BAND_COUNT] {
+        match self {
+            EqualizerPreset::Flat => [0.0; BAND_COUNT],
+            EqualizerPreset::Rock => [5.0, 4.0, 3.0, 1.0, -1.0, -1.0, 1.0, 3.0, 4.0, 5.0],
+            EqualizerPreset::BassBoost => [7.0, 6.0, 5.0, 3.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+impl Equalizer {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            gains: [0.0; BAND_COUNT],
+            shared_gains: Arc::new(SharedGains {
+                gains: Default::default(),
+                version: AtomicU64::new(0),
+            }),
+        }
+    }
+}
+
+impl Default for Equalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Publishes the current settings to the audio thread. Call this after changing `enabled` or `gains`.
+pub fn apply(equalizer: &Equalizer) {
+    for (shared, gain) in equalizer.shared_gains.gains.iter().zip(equalizer.gains) {
+        let gain = if equalizer.enabled {
+            gain.clamp(-MAX_GAIN_DB, MAX_GAIN_DB)
+        } else {
+            0.0
+        };
+        shared.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    equalizer.shared_gains.version.fetch_add(1, Ordering::Release);
+}
+
+// The preset that matches the current gains, if any.
+pub fn matching_preset(gains: &[f32; BAND_COUNT]) -> Option<EqualizerPreset> {
+    EqualizerPreset::iter().find(|preset| preset.gains() == *gains)
+}
+
+// A peaking filter from the Audio EQ Cookbook (https://www.w3.org/TR/audio-eq-cookbook/).
+#[derive(Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Biquad {
+    fn peaking(frequency: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let a = 10.0_f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let alpha = w0.sin() / (2.0 * BAND_Q);
+        let cos_w0 = w0.cos();
+
+        let a0 = 1.0 + alpha / a;
+        Self {
+            b0: (1.0 + alpha * a) / a0,
+            b1: (-2.0 * cos_w0) / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: (-2.0 * cos_w0) / a0,
+            a2: (1.0 - alpha / a) / a0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct BiquadState {
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl BiquadState {
+    #[inline]
+    fn process(&mut self, filter: &Biquad, x: f32) -> f32 {
+        let y = filter.b0 * x + filter.b1 * self.x1 + filter.b2 * self.x2 - filter.a1 * self.y1 - filter.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+pub struct EqualizerSource<I> {
+    input: I,
+    shared_gains: Arc<SharedGains>,
+    version: u64,
+    filters: Vec<Biquad>, // Only the bands with a non-zero gain. Empty means the audio passes through untouched.
+    states: Vec<Vec<BiquadState>>, // One set of filter states per channel.
+    channel: usize,       // The channel of the next sample, since samples are interleaved.
+}
+
+impl<I> EqualizerSource<I>
+where
+    I: Source,
+{
+    pub fn new(input: I, shared_gains: Arc<SharedGains>) -> Self {
+        let mut source = Self {
+            input,
+            shared_gains,
+            version: 0,
+            filters: Vec::new(),
+            states: Vec::new(),
+            channel: 0,
+        };
+        source.update_filters();
+        source
+    }
+
+    fn update_filters(&mut self) {
+        self.version = self.shared_gains.version.load(Ordering::Acquire);
+
+        let sample_rate = self.input.sample_rate().get() as f32;
+        let nyquist = sample_rate / 2.0;
+
+        self.filters = BAND_FREQUENCIES
+            .iter()
+            .zip(&self.shared_gains.gains)
+            .map(|(&frequency, gain)| (frequency, f32::from_bits(gain.load(Ordering::Relaxed))))
+            .filter(|&(frequency, gain)| gain != 0.0 && frequency < nyquist)
+            .map(|(frequency, gain)| Biquad::peaking(frequency, gain, sample_rate))
+            .collect();
+
+        let channels = self.input.channels().get() as usize;
+        self.states = vec![vec![BiquadState::default(); self.filters.len()]; channels];
+    }
+}
+
+impl<I> Iterator for EqualizerSource<I>
+where
+    I: Source,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.channel == 0 && self.shared_gains.version.load(Ordering::Relaxed) != self.version {
+            self.update_filters();
+        }
+
+        let sample = self.input.next()?;
+
+        let channel = self.channel;
+        self.channel = (self.channel + 1) % self.states.len();
+
+        if self.filters.is_empty() {
+            return Some(sample);
+        }
+
+        let mut output: Sample = sample;
+        for (filter, state) in self.filters.iter().zip(&mut self.states[channel]) {
+            output = state.process(filter, output);
+        }
+
+        Some(output)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> Source for EqualizerSource<I>
+where
+    I: Source,
+{
+    #[inline]
+    fn current_span_len(&self) -> Option<usize> {
+        self.input.current_span_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> ChannelCount {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> SampleRate {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+
+        // The filter history belongs to the old position.
+        self.channel = 0;
+        self.update_filters();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::mpsc::{Receiver, channel},
+    thread,
+};
+
+use anyhow::Error;
+use egui::Context;
+use fully_pub::fully_pub;
+use log::error;
+use walkdir::WalkDir;
+
+use crate::{
+    GemPlayer,
+    track::{Track, is_audio_file, load_track},
+};
+
+pub enum OpenedFile {
+    InLibrary(PathBuf), // Not read, since the library already has its track.
+    Loaded(Track),
+    Unsupported(PathBuf),
+    Failed(PathBuf, Error),
+}
+
+// Files and folders opened from outside the app, e.g. with "Open with…". Folders are walked and the files read in the
+// background, so that opening a large folder doesn't stall the ui. Opened files that aren't in the library are loaded
+// as temporary tracks. They are played, but not added to the library.
+#[fully_pub]
+struct FileOpener {
+    receiver: Receiver<Vec<OpenedFile>>,
+}
+
+pub fn spawn_file_opener(paths: Vec<PathBuf>, library: &[Track], ctx: Context) -> FileOpener {
+    let (sender, receiver) = channel();
+    let library_paths: HashSet<PathBuf> = library.iter().map(|t| t.path.clone()).collect();
+
+    thread::spawn(move || {
+        let opened = open_files(&paths, &library_paths);
+        if sender.send(opened).is_ok() {
+            ctx.request_repaint();
+        }
+    });
+
+    FileOpener { receiver }
+}
+
+// Folders are replaced by the audio files inside of them, in path order.
+pub fn open_files(paths: &[PathBuf], library_paths: &HashSet<PathBuf>) -> Vec<OpenedFile> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut folder_files: Vec<PathBuf> = WalkDir::new(path)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .map(|e| e.into_path())
+                .filter(|p| is_audio_file(p))
+                .collect();
+            folder_files.sort();
+
+            files.extend(folder_files);
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    files
+        .into_iter()
+        .map(|file| {
+            if library_paths.contains(&file) {
+                OpenedFile::InLibrary(file)
+            } else if !is_audio_file(&file) {
+                OpenedFile::Unsupported(file)
+            } else {
+                match load_track(&file) {
+                    Ok(track) => OpenedFile::Loaded(track),
+                    Err(e) => OpenedFile::Failed(file, e),
+                }
+            }
+        })
+        .collect()
+}
+
+// The tracks of the opened files. The files that couldn't be opened are reported.
+pub fn opened_tracks(gem: &mut GemPlayer, opened: Vec<OpenedFile>) -> Vec<Track> {
+    let mut tracks = Vec::new();
+
+    for file in opened {
+        match file {
+            OpenedFile::InLibrary(path) => {
+                // The library may have been reloaded without the track since the file was opened.
+                if let Some(track) = gem.library.iter().find(|t| t.path == path) {
+                    tracks.push(track.clone());
+                }
+            }
+            OpenedFile::Loaded(track) => tracks.push(track),
+            OpenedFile::Unsupported(path) => {
+                let file_name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                gem.ui.toasts.error(format!("'{}' is not a supported audio file.", file_name));
+            }
+            OpenedFile::Failed(path, e) => {
+                error!("Failed to open '{}': {:#}", path.display(), e);
+                let file_name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                gem.ui.toasts.error(format!("Failed to open '{}'.", file_name));
+            }
+        }
+    }
+
+    tracks
+}
+
+// Opens the files right away. Only for files that are known to be audio files, since folders are walked on the caller's
+// thread.
+pub fn load_opened_tracks(gem: &mut GemPlayer, paths: &[PathBuf]) -> Vec<Track> {
+    let library_paths: HashSet<PathBuf> = gem.library.iter().map(|t| t.path.clone()).collect();
+    let opened = open_files(paths, &library_paths);
+    opened_tracks(gem, opened)
+}
@@ -0,0 +1,149 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+
+const STREAMINFO: u8 = 0;
+const VORBIS_COMMENT: u8 = 4;
+const CUESHEET: u8 = 5;
+
+const CUESHEET_COMMENT: &str = "CUESHEET";
+const LEAD_OUT_TRACKS: [u8; 2] = [170, 255]; // The lead-out track number of CD and non-CD cue sheets.
+
+// A cue sheet stored inside of a FLAC file, as rippers write for live sets and whole-disc images.
+pub enum EmbeddedCueSheet {
+    Text(String),                 // A regular cue sheet in a CUESHEET Vorbis comment, which includes titles and performers.
+    Tracks(Vec<(u32, Duration)>), // The number and start of each track in the CUESHEET metadata block.
+}
+
+// The text form is preferred since the metadata block only knows where the tracks start.
+pub fn read_embedded_cue_sheet(path: &Path) -> Result<Option<EmbeddedCueSheet>> {
+    let mut file = BufReader::new(File::open(path)?);
+
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err(anyhow!("Not a FLAC file"));
+    }
+
+    let mut sample_rate = None;
+    let mut cue_sheet_block = None;
+
+    loop {
+        let mut header = [0; 4];
+        file.read_exact(&mut header)?;
+
+        let is_last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7f;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+
+        match block_type {
+            STREAMINFO | VORBIS_COMMENT | CUESHEET => {
+                let mut block = vec![0; length];
+                file.read_exact(&mut block)?;
+
+                match block_type {
+                    STREAMINFO => sample_rate = stream_sample_rate(&block),
+                    VORBIS_COMMENT => {
+                        if let Some(text) = find_comment(&block, CUESHEET_COMMENT) {
+                            return Ok(Some(EmbeddedCueSheet::Text(text)));
+                        }
+                    }
+                    _ => cue_sheet_block = Some(block),
+                }
+            }
+            _ => {
+                file.seek(SeekFrom::Current(length as i64))?; // Pictures and padding can be large, so they are skipped.
+            }
+        }
+
+        if is_last {
+            break;
+        }
+    }
+
+    let (Some(block), Some(sample_rate)) = (cue_sheet_block, sample_rate) else {
+        return Ok(None);
+    };
+
+    let tracks = parse_cue_sheet_block(&block, sample_rate).ok_or_else(|| anyhow!("Malformed CUESHEET block"))?;
+    Ok((!tracks.is_empty()).then_some(EmbeddedCueSheet::Tracks(tracks)))
+}
+
+// The sample rate is 20 bits, after the block and frame sizes.
+fn stream_sample_rate(block: &[u8]) -> Option<u32> {
+    let bytes = block.get(10..13)?;
+    let rate = ((bytes[0] as u32) << 12) | ((bytes[1] as u32) << 4) | ((bytes[2] as u32) >> 4);
+
+    (rate > 0).then_some(rate)
+}
+
+// Vorbis comments are little endian, unlike the rest of the FLAC metadata. Field names are case insensitive.
+fn find_comment(block: &[u8], name: &str) -> Option<String> {
+    let vendor_length = read_u32_le(block, 0)? as usize;
+    let mut position = 4 + vendor_length;
+
+    let count = read_u32_le(block, position)?;
+    position += 4;
+
+    for _ in 0..count {
+        let length = read_u32_le(block, position)? as usize;
+        let comment = block.get(position + 4..position + 4 + length)?;
+        position += 4 + length;
+
+        let comment = String::from_utf8_lossy(comment);
+        if let Some((field, value)) = comment.split_once('=')
+            && field.eq_ignore_ascii_case(name)
+        {
+            return Some(value.to_owned());
+        }
+    }
+
+    None
+}
+
+// Offsets in the block are in samples. A track starts at its index 1, or its first index if it has none.
+fn parse_cue_sheet_block(block: &[u8], sample_rate: u32) -> Option<Vec<(u32, Duration)>> {
+    // The catalog number (128 bytes), lead-in samples (8 bytes), and flags with reserved bytes (259 bytes) come first.
+    let track_count = *block.get(395)?;
+    let mut position = 396;
+
+    let mut tracks = Vec::new();
+    for _ in 0..track_count {
+        let offset = read_u64(block, position)?;
+        let number = *block.get(position + 8)?;
+        let index_count = *block.get(position + 35)? as usize; // After the ISRC (12 bytes) and flags (14 bytes).
+        position += 36;
+
+        let mut indexes = Vec::with_capacity(index_count);
+        for _ in 0..index_count {
+            let index_offset = read_u64(block, position)?;
+            let index_number = *block.get(position + 8)?;
+            indexes.push((index_number, index_offset));
+            position += 12;
+        }
+
+        if LEAD_OUT_TRACKS.contains(&number) {
+            continue;
+        }
+
+        let start_index = indexes.iter().find(|(n, _)| *n == 1).or(indexes.first());
+        let start_samples = offset + start_index.map_or(0, |(_, o)| *o);
+
+        tracks.push((number as u32, Duration::from_secs_f64(start_samples as f64 / sample_rate as f64)));
+    }
+
+    Some(tracks)
+}
+
+fn read_u32_le(data: &[u8], position: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(position..position + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], position: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(position..position + 8)?.try_into().ok()?))
+}
@@ -0,0 +1,58 @@
+use egui::Context;
+use fully_pub::fully_pub;
+use log::info;
+
+use crate::{GemPlayer, commands::GemCommand};
+
+#[fully_pub]
+struct FocusPause {
+    pause_on_focus_loss: bool,
+    resume_on_focus_gain: bool,
+
+    auto_paused: bool, // Set when we paused playback ourselves, so that we only ever resume what we paused.
+    was_focused: bool,
+}
+
+impl FocusPause {
+    pub fn new(pause_on_focus_loss: bool, resume_on_focus_gain: bool) -> Self {
+        Self {
+            pause_on_focus_loss,
+            resume_on_focus_gain,
+            auto_paused: false,
+            was_focused: true,
+        }
+    }
+}
+
+// Pauses playback when the window loses focus and optionally resumes it when focus returns.
+pub fn poll_window_focus(ctx: &Context, gem: &mut GemPlayer) {
+    let is_paused = gem.player.backend.as_ref().is_none_or(|b| b.is_paused());
+    let is_playing = gem.player.playing.is_some() && !is_paused;
+
+    // If playback was resumed some other way (e.g. media keys) while we were unfocused, don't fight it.
+    if gem.focus_pause.auto_paused && is_playing {
+        gem.focus_pause.auto_paused = false;
+    }
+
+    let is_focused = ctx.input(|i| i.viewport().focused).unwrap_or(true);
+    if is_focused == gem.focus_pause.was_focused {
+        return;
+    }
+
+    gem.focus_pause.was_focused = is_focused;
+
+    if !is_focused && gem.focus_pause.pause_on_focus_loss && is_playing {
+        info!("Window lost focus. Pausing playback.");
+        gem.focus_pause.auto_paused = true;
+        gem.commands.push(GemCommand::Pause);
+    }
+
+    if is_focused && gem.focus_pause.auto_paused {
+        gem.focus_pause.auto_paused = false;
+
+        if gem.focus_pause.resume_on_focus_gain {
+            info!("Window regained focus. Resuming playback.");
+            gem.commands.push(GemCommand::Play);
+        }
+    }
+}
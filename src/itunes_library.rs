@@ -0,0 +1,232 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+use fully_pub::fully_pub;
+use roxmltree::{Document, Node};
+
+use crate::track::{MAX_RATING, Track};
+
+// The library that iTunes (and Apple Music) exports as "Library.xml". It is a property list of tracks and playlists.
+#[fully_pub]
+struct ItunesLibrary {
+    tracks: HashMap<u64, ItunesTrack>, // Keyed by the track id that playlists refer to.
+    playlists: Vec<ItunesPlaylist>,
+}
+
+#[fully_pub]
+struct ItunesTrack {
+    name: Option<String>,
+    artist: Option<String>,
+    location: Option<PathBuf>, // None for tracks that aren't local files, e.g. from streaming.
+    rating: u8,                // 0 to MAX_RATING stars, like the library's ratings.
+    play_count: u32,
+}
+
+#[fully_pub]
+struct ItunesPlaylist {
+    name: String,
+    track_ids: Vec<u64>,
+}
+
+impl ItunesTrack {
+    // How the track is listed when it can't be matched.
+    pub fn description(&self) -> String {
+        let name = self.name.as_deref().unwrap_or("Unknown Title");
+        match (&self.artist, &self.location) {
+            (Some(artist), _) => format!("{} — {}", name, artist),
+            (None, Some(location)) => format!("{} ({})", name, location.display()),
+            (None, None) => name.to_owned(),
+        }
+    }
+}
+
+pub fn parse_itunes_library(path: &Path) -> Result<ItunesLibrary> {
+    let contents = read_to_string(path).with_context(|| format!("Failed to read '{}'", path.display()))?;
+    let document = Document::parse(&contents).with_context(|| format!("Failed to parse '{}'", path.display()))?;
+
+    let root = document
+        .root_element()
+        .children()
+        .find(|n| n.has_tag_name("dict"))
+        .ok_or_else(|| anyhow!("'{}' is not an iTunes library", path.display()))?;
+    let root = dict_entries(root);
+
+    let tracks_dict = root
+        .get("Tracks")
+        .ok_or_else(|| anyhow!("'{}' is not an iTunes library", path.display()))?;
+
+    let mut tracks = HashMap::new();
+    for track_node in dict_entries(*tracks_dict).into_values() {
+        let entries = dict_entries(track_node);
+
+        let Some(id) = entries.get("Track ID").and_then(|n| integer(*n)) else {
+            continue;
+        };
+
+        // A computed rating is inherited from the album rather than given to the track.
+        let rating_is_computed = entries.get("Rating Computed").is_some_and(|n| n.has_tag_name("true"));
+        let rating = match entries.get("Rating").and_then(|n| integer(*n)) {
+            Some(rating) if !rating_is_computed => ((rating.min(100) + 10) / 20) as u8, // Ratings are out of 100.
+            _ => 0,
+        };
+
+        let track = ItunesTrack {
+            name: entries.get("Name").and_then(|n| n.text()).map(|s| s.to_owned()),
+            artist: entries.get("Artist").and_then(|n| n.text()).map(|s| s.to_owned()),
+            location: entries.get("Location").and_then(|n| n.text()).and_then(location_to_path),
+            rating: rating.min(MAX_RATING),
+            play_count: entries.get("Play Count").and_then(|n| integer(*n)).unwrap_or(0) as u32,
+        };
+        tracks.insert(id, track);
+    }
+
+    let mut playlists = Vec::new();
+    if let Some(playlists_array) = root.get("Playlists") {
+        for playlist_node in playlists_array.children().filter(|n| n.has_tag_name("dict")) {
+            let entries = dict_entries(playlist_node);
+
+            // Skip the whole library, the built-in playlists (Music, Podcasts, …), folders, and smart playlists.
+            let is_special = ["Master", "Distinguished Kind", "Folder", "Smart Info"]
+                .iter()
+                .any(|key| entries.get(key).is_some_and(|n| !n.has_tag_name("false")));
+            if is_special {
+                continue;
+            }
+
+            let Some(name) = entries.get("Name").and_then(|n| n.text()) else {
+                continue;
+            };
+
+            let track_ids = entries
+                .get("Playlist Items")
+                .map(|items| {
+                    items
+                        .children()
+                        .filter(|n| n.has_tag_name("dict"))
+                        .filter_map(|item| dict_entries(item).get("Track ID").and_then(|n| integer(*n)))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            playlists.push(ItunesPlaylist {
+                name: name.to_owned(),
+                track_ids,
+            });
+        }
+    }
+
+    Ok(ItunesLibrary { tracks, playlists })
+}
+
+// Finds the library file of each iTunes track. The library may have moved since it was exported (e.g. to another
+// computer), so tracks are also matched by the end of their path, as long as the match is unambiguous.
+pub fn match_tracks(itunes: &ItunesLibrary, library: &[Track]) -> HashMap<u64, PathBuf> {
+    const MAX_SUFFIX_LENGTH: usize = 3; // Usually artist / album / file name.
+
+    let library_paths: HashSet<&Path> = library.iter().map(|t| t.path.as_path()).collect();
+
+    let mut by_suffix: HashMap<String, Vec<&Path>> = HashMap::new();
+    for track in library {
+        for length in 1..=MAX_SUFFIX_LENGTH {
+            if let Some(suffix) = path_suffix(&track.path, length) {
+                by_suffix.entry(suffix).or_default().push(&track.path);
+            }
+        }
+    }
+
+    let mut matches = HashMap::new();
+    for (id, itunes_track) in &itunes.tracks {
+        let Some(location) = &itunes_track.location else {
+            continue;
+        };
+
+        if library_paths.contains(location.as_path()) {
+            matches.insert(*id, location.clone());
+            continue;
+        }
+
+        let found = (1..=MAX_SUFFIX_LENGTH)
+            .rev()
+            .filter_map(|length| path_suffix(location, length))
+            .find_map(|suffix| match by_suffix.get(&suffix).map(|paths| paths.as_slice()) {
+                Some([path]) => Some(path.to_path_buf()),
+                _ => None,
+            });
+
+        if let Some(path) = found {
+            matches.insert(*id, path);
+        }
+    }
+
+    matches
+}
+
+// The last components of the path, compared case insensitively since file systems differ.
+fn path_suffix(path: &Path, length: usize) -> Option<String> {
+    let components: Vec<String> = path
+        .components()
+        .rev()
+        .take(length)
+        .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+        .collect();
+
+    if components.len() < length {
+        return None;
+    }
+
+    Some(components.into_iter().rev().collect::<Vec<_>>().join("/"))
+}
+
+// Pairs up the <key> elements of a <dict> with the value element that follows each one.
+fn dict_entries<'a, 'input>(dict: Node<'a, 'input>) -> HashMap<&'a str, Node<'a, 'input>> {
+    let mut entries = HashMap::new();
+    let mut elements = dict.children().filter(|n| n.is_element());
+
+    while let (Some(key), Some(value)) = (elements.next(), elements.next()) {
+        if let Some(key) = key.text() {
+            entries.insert(key, value);
+        }
+    }
+
+    entries
+}
+
+fn integer(node: Node) -> Option<u64> {
+    node.text()?.trim().parse().ok()
+}
+
+// Locations are file urls like "file://localhost/Users/name/Music/Artist/Track.mp3".
+fn location_to_path(location: &str) -> Option<PathBuf> {
+    let path = location.strip_prefix("file://")?;
+    let path = path.strip_prefix("localhost").unwrap_or(path);
+    let path = percent_decode(path)?;
+
+    // Windows paths look like "/C:/Users/…".
+    let is_windows_path = path.as_bytes().get(2) == Some(&b':');
+    let path = if is_windows_path { &path[1..] } else { &path[..] };
+
+    Some(PathBuf::from(path))
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
@@ -0,0 +1,44 @@
+//! The playback and library core of Gem Player, without any of the UI.
+//!
+//! Everything here can be used headlessly, e.g. to embed the player in another application.
+//!
+//! ```no_run
+//! use gem_player::{
+//!     player::{Player, build_audio_backend_from_device, enqueue, play_next},
+//!     track::load_from_file,
+//! };
+//! use rodio::cpal::{default_host, traits::HostTrait};
+//! use std::path::Path;
+//!
+//! let device = default_host().default_output_device().expect("An output device must be available");
+//! let backend = build_audio_backend_from_device(device, false, None).expect("The audio backend must start");
+//! let mut player = Player::new(Some(backend));
+//!
+//! let track = load_from_file(Path::new("assets/clair_de_lune.mp3")).expect("The track must load");
+//! enqueue(&mut player, track);
+//!
+//! play_next(&mut player).expect("The track must play");
+//! ```
+//!
+//! A library directory can be loaded (and kept up to date) with [`library_watcher::setup_library_watcher`].
+
+pub mod auto_playlist;
+pub mod bookmarks;
+pub mod chapters;
+pub mod duplicates;
+pub mod duration;
+pub mod equalizer;
+pub mod flac_metadata;
+pub mod itunes_library;
+pub mod library_cache;
+pub mod library_export;
+pub mod library_watcher;
+pub mod loudness;
+pub mod lyrics;
+pub mod player;
+pub mod playlist;
+pub mod resampler;
+pub mod scan_exclusions;
+pub mod search_query;
+pub mod track;
+pub mod visualizer;
@@ -0,0 +1,253 @@
+use std::{
+    collections::HashMap,
+    fs::{File, create_dir_all, read, rename},
+    io::{BufWriter, Write},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use lofty::file::FileType;
+use serde_json::{Value, json};
+
+use crate::track::{CueRange, LibrarySnapshot, ReplayGain, SearchFields, Track, file_type_name, with_search_fields};
+
+const CACHE_VERSION: u64 = 1; // Caches written with another version are ignored and rebuilt by the next scan.
+
+// The codecs that can be told apart by file_type_name(). Tracks of any other codec aren't cached.
+const FILE_TYPES: [FileType; 12] = [
+    FileType::Aac,
+    FileType::Aiff,
+    FileType::Ape,
+    FileType::Flac,
+    FileType::Mpeg,
+    FileType::Mp4,
+    FileType::Mpc,
+    FileType::Opus,
+    FileType::Vorbis,
+    FileType::Speex,
+    FileType::Wav,
+    FileType::WavPack,
+];
+
+// The library as of the last scan, kept on disk so that the next session can show it right away while the directory
+// is scanned again in the background. Tracks that are part of the snapshot are stored with the modification time
+// their file had when it was read, so that the scan only reads the files that changed since.
+pub fn save_library_cache(cache_path: &Path, snapshot: &LibrarySnapshot, tracks: &[Track]) -> Result<()> {
+    let tracks: Vec<Value> = tracks
+        .iter()
+        .filter_map(|track| {
+            let modified = snapshot.entries.get(&track.path).map(|(modified, _)| *modified);
+            track_to_json(track, modified)
+        })
+        .collect();
+
+    let cache = json!({
+        "version": CACHE_VERSION,
+        "directory": snapshot.directory.to_str(),
+        "tracks": tracks,
+    });
+
+    write_cache(cache_path, &cache)
+}
+
+// Stores durations that were measured since the last scan, so that the next session doesn't measure them again.
+pub fn update_cached_durations(cache_path: &Path, durations: &HashMap<PathBuf, Duration>) -> Result<()> {
+    if !cache_path.is_file() {
+        return Ok(()); // The durations are saved with the first scan instead.
+    }
+
+    let data = read(cache_path).with_context(|| format!("Failed to read '{}'", cache_path.display()))?;
+    let mut cache: Value = serde_json::from_slice(&data)?;
+
+    let Some(tracks) = cache["tracks"].as_array_mut() else {
+        return Ok(());
+    };
+
+    for track in tracks {
+        let Some(duration) = track["path"].as_str().and_then(|path| durations.get(Path::new(path))) else {
+            continue;
+        };
+
+        track["duration"] = duration_to_json(*duration);
+        track["duration_measured"] = Value::Bool(true);
+    }
+
+    write_cache(cache_path, &cache)
+}
+
+// Written next to the cache and then moved over it, so that quitting midway doesn't leave a truncated cache.
+fn write_cache(cache_path: &Path, cache: &Value) -> Result<()> {
+    if let Some(parent) = cache_path.parent() {
+        create_dir_all(parent)?;
+    }
+
+    let temporary_path = cache_path.with_extension("tmp");
+    let file = File::create(&temporary_path).with_context(|| format!("Failed to create '{}'", temporary_path.display()))?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer(&mut writer, cache)?;
+    writer.flush()?;
+
+    rename(&temporary_path, cache_path)?;
+    Ok(())
+}
+
+// None if there is no cache, or it was made for another directory or by another version.
+pub fn load_library_cache(cache_path: &Path, directory: &Path) -> Result<Option<(Vec<Track>, LibrarySnapshot)>> {
+    if !cache_path.is_file() {
+        return Ok(None);
+    }
+
+    let data = read(cache_path).with_context(|| format!("Failed to read '{}'", cache_path.display()))?;
+    let cache: Value = serde_json::from_slice(&data)?;
+
+    let version_matches = cache["version"].as_u64() == Some(CACHE_VERSION);
+    let directory_matches = cache["directory"].as_str().is_some_and(|d| Path::new(d) == directory);
+    if !version_matches || !directory_matches {
+        return Ok(None);
+    }
+
+    let mut tracks = Vec::new();
+    let mut snapshot = LibrarySnapshot {
+        directory: directory.to_path_buf(),
+        entries: HashMap::new(),
+    };
+
+    for value in cache["tracks"].as_array().into_iter().flatten() {
+        let Some((track, modified)) = track_from_json(value) else {
+            continue; // Skipped tracks are read again by the scan.
+        };
+
+        if let Some(modified) = modified {
+            snapshot.entries.insert(track.path.clone(), (modified, track.clone()));
+        }
+        tracks.push(track);
+    }
+
+    Ok(Some((tracks, snapshot)))
+}
+
+fn track_to_json(track: &Track, modified: Option<SystemTime>) -> Option<Value> {
+    let codec = FILE_TYPES.iter().find(|ft| **ft == track.codec).map(|ft| file_type_name(*ft))?;
+
+    let cue = match &track.cue {
+        Some(cue) => json!({
+            "audio_path": cue.audio_path.to_str()?,
+            "start": duration_to_json(cue.start),
+            "end": cue.end.map(duration_to_json),
+        }),
+        None => Value::Null,
+    };
+
+    Some(json!({
+        "title": track.title,
+        "artist": track.artist,
+        "artists": track.artists,
+        "album": track.album,
+        "album_artist": track.album_artist,
+        "composer": track.composer,
+        "genre": track.genre,
+        "genres": track.genres,
+        "track_number": track.track_number,
+        "disc_number": track.disc_number,
+        "year": track.year,
+        "rating": track.rating,
+        "duration": duration_to_json(track.duration),
+        "duration_measured": track.duration_measured,
+        "path": track.path.to_str()?, // Paths that aren't valid UTF-8 can't be stored as JSON text.
+        "sample_rate": track.sample_rate.map(|rate| rate.get()),
+        "bitrate": track.bitrate,
+        "channels": track.channels,
+        "codec": codec,
+        "date_added": time_to_json(track.date_added),
+        "date_modified": time_to_json(track.date_modified),
+        "cue": cue,
+        "replay_gain": [
+            track.replay_gain.track_gain,
+            track.replay_gain.track_peak,
+            track.replay_gain.album_gain,
+            track.replay_gain.album_peak,
+        ],
+        "modified": modified.map(time_to_json),
+    }))
+}
+
+fn track_from_json(value: &Value) -> Option<(Track, Option<SystemTime>)> {
+    let string = |key: &str| value[key].as_str().map(String::from);
+    let strings = |key: &str| -> Vec<String> {
+        let values = value[key].as_array().into_iter().flatten();
+        values.filter_map(|v| v.as_str().map(String::from)).collect()
+    };
+    let number = |key: &str| value[key].as_u64();
+
+    let codec = value["codec"].as_str()?;
+    let codec = *FILE_TYPES.iter().find(|ft| file_type_name(**ft) == codec)?;
+
+    let cue = match &value["cue"] {
+        Value::Null => None,
+        cue => Some(CueRange {
+            audio_path: PathBuf::from(cue["audio_path"].as_str()?),
+            start: duration_from_json(&cue["start"])?,
+            end: duration_from_json(&cue["end"]),
+        }),
+    };
+
+    let gains = &value["replay_gain"];
+    let gain = |i: usize| gains[i].as_f64().map(|g| g as f32);
+    let replay_gain = ReplayGain {
+        track_gain: gain(0),
+        track_peak: gain(1),
+        album_gain: gain(2),
+        album_peak: gain(3),
+    };
+
+    let track = with_search_fields(Track {
+        title: string("title"),
+        artist: string("artist"),
+        artists: strings("artists"),
+        album: string("album"),
+        album_artist: string("album_artist"),
+        composer: string("composer"),
+        genre: string("genre"),
+        genres: strings("genres"),
+        track_number: number("track_number").map(|n| n as u32),
+        disc_number: number("disc_number").map(|n| n as u32),
+        year: number("year").map(|n| n as u16),
+        rating: number("rating").unwrap_or(0) as u8,
+        duration: duration_from_json(&value["duration"])?,
+        duration_measured: value["duration_measured"].as_bool().unwrap_or(false),
+        path: PathBuf::from(value["path"].as_str()?),
+        sample_rate: number("sample_rate").and_then(|rate| NonZeroU32::new(rate as u32)),
+        bitrate: number("bitrate").map(|n| n as u32),
+        channels: number("channels").map(|n| n as u8),
+        codec,
+        date_added: time_from_json(&value["date_added"])?,
+        date_modified: time_from_json(&value["date_modified"])?,
+        cue,
+        replay_gain,
+        search_fields: SearchFields::default(), // Made again rather than stored, so that the cache stays small.
+    });
+
+    Some((track, time_from_json(&value["modified"])))
+}
+
+// Times are stored exactly, as seconds and nanoseconds, since the snapshot compares modification times for equality.
+pub fn time_to_json(time: SystemTime) -> Value {
+    duration_to_json(time.duration_since(UNIX_EPOCH).unwrap_or_default())
+}
+
+// None for times that the platform can't represent, rather than panicking on a corrupted cache.
+pub fn time_from_json(value: &Value) -> Option<SystemTime> {
+    UNIX_EPOCH.checked_add(duration_from_json(value)?)
+}
+
+fn duration_to_json(duration: Duration) -> Value {
+    json!([duration.as_secs(), duration.subsec_nanos()])
+}
+
+// The nanoseconds are checked since Duration::new() carries them over into the seconds, which can overflow.
+fn duration_from_json(value: &Value) -> Option<Duration> {
+    let nanos = u32::try_from(value[1].as_u64()?).ok().filter(|n| *n < 1_000_000_000)?;
+    Some(Duration::new(value[0].as_u64()?, nanos))
+}
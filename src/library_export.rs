@@ -0,0 +1,115 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde_json::json;
+use strum_macros::EnumIter;
+
+use crate::{auto_playlist::PlayCounts, track::Track};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum LibraryExportFormat {
+    Csv,
+    Json,
+}
+
+impl LibraryExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            LibraryExportFormat::Csv => "csv",
+            LibraryExportFormat::Json => "json",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LibraryExportFormat::Csv => "CSV",
+            LibraryExportFormat::Json => "JSON",
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<LibraryExportFormat> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "csv" => Some(LibraryExportFormat::Csv),
+            "json" => Some(LibraryExportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+const CSV_HEADER: &str = "path,title,artist,album,duration,rating,play_count";
+
+// Writes a row per track for backups and analysis tools. Durations are in seconds. The progress callback gets the number
+// of tracks written so far and the total.
+pub fn export_library(
+    tracks: &[Track],
+    play_counts: &PlayCounts,
+    path: &Path,
+    format: LibraryExportFormat,
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create '{}'", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    match format {
+        LibraryExportFormat::Csv => writeln!(writer, "{}", CSV_HEADER)?,
+        LibraryExportFormat::Json => writeln!(writer, "[")?,
+    }
+
+    for (i, track) in tracks.iter().enumerate() {
+        let play_count = play_counts.get(&track.path).copied().unwrap_or(0);
+
+        match format {
+            LibraryExportFormat::Csv => {
+                let fields = [
+                    track.path.to_string_lossy().into_owned(),
+                    track.title.clone().unwrap_or_default(),
+                    track.artist.clone().unwrap_or_default(),
+                    track.album.clone().unwrap_or_default(),
+                    format!("{:.3}", track.duration.as_secs_f64()),
+                    track.rating.to_string(),
+                    play_count.to_string(),
+                ];
+                let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+                writeln!(writer, "{}", row.join(","))?;
+            }
+            LibraryExportFormat::Json => {
+                let object = json!({
+                    "path": track.path.to_string_lossy(),
+                    "title": track.title,
+                    "artist": track.artist,
+                    "album": track.album,
+                    "duration": track.duration.as_secs_f64(),
+                    "rating": track.rating,
+                    "play_count": play_count,
+                });
+
+                let separator = if i + 1 < tracks.len() { "," } else { "" };
+                writeln!(writer, "  {}{}", object, separator)?;
+            }
+        }
+
+        on_progress(i + 1, tracks.len());
+    }
+
+    if format == LibraryExportFormat::Json {
+        writeln!(writer, "]")?;
+    }
+
+    writer.flush().with_context(|| format!("Failed to write '{}'", path.display()))?;
+
+    Ok(())
+}
+
+// Fields with separators, quotes, or line breaks are quoted, with quotes doubled.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
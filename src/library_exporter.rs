@@ -0,0 +1,71 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::{Receiver, channel},
+    thread,
+};
+
+use anyhow::Result;
+use egui::Context;
+use fully_pub::fully_pub;
+use log::info;
+use rfd::FileDialog;
+use strum::IntoEnumIterator;
+
+use crate::{
+    auto_playlist::PlayCounts,
+    library_export::{LibraryExportFormat, export_library},
+    track::Track,
+};
+
+const PROGRESS_INTERVAL: usize = 250; // Tracks written between progress updates, so that big libraries don't flood the channel.
+
+pub enum LibraryExportMessage {
+    Progress { done: usize, total: usize },
+    Finished(Result<PathBuf>),
+    Cancelled,
+}
+
+// A library export that is running in the background, from choosing the file to writing it.
+#[fully_pub]
+struct LibraryExporter {
+    receiver: Receiver<LibraryExportMessage>,
+    progress: Option<(usize, usize)>, // Tracks written so far and the total.
+}
+
+/// Spawns a save dialog in a background thread and then writes the library to the chosen file on the same thread.
+pub fn spawn_library_exporter(ctx: &Context, tracks: Vec<Track>, play_counts: PlayCounts) -> LibraryExporter {
+    let (sender, receiver) = channel();
+    let ctx = ctx.clone();
+
+    thread::spawn(move || {
+        let mut dialog = FileDialog::new().set_file_name(format!("library.{}", LibraryExportFormat::Csv.extension()));
+        for format in LibraryExportFormat::iter() {
+            dialog = dialog.add_filter(format.label(), &[format.extension()]);
+        }
+
+        let Some(mut path) = dialog.save_file() else {
+            let _ = sender.send(LibraryExportMessage::Cancelled);
+            return;
+        };
+
+        // Without a known extension there is no format to go by, so csv is used.
+        let format = LibraryExportFormat::from_path(&path).unwrap_or_else(|| {
+            path.set_extension(LibraryExportFormat::Csv.extension());
+            LibraryExportFormat::Csv
+        });
+
+        info!("Exporting {} track(s) to {}", tracks.len(), path.display());
+
+        let result = export_library(&tracks, &play_counts, &path, format, |done, total| {
+            if done % PROGRESS_INTERVAL == 0 || done == total {
+                let _ = sender.send(LibraryExportMessage::Progress { done, total });
+                ctx.request_repaint();
+            }
+        });
+
+        let _ = sender.send(LibraryExportMessage::Finished(result.map(|()| path)));
+        ctx.request_repaint();
+    });
+
+    LibraryExporter { receiver, progress: None }
+}
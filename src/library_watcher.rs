@@ -1,8 +1,9 @@
 use std::{
-    path::PathBuf,
-    sync::mpsc::{Receiver, Sender, channel},
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, RecvTimeoutError, Sender, channel},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::{Context, Result};
@@ -12,25 +13,57 @@ use notify::RecursiveMode;
 use notify_debouncer_mini::{DebounceEventResult, new_debouncer};
 
 use crate::{
+    library_cache::{load_library_cache, save_library_cache, update_cached_durations},
     playlist::{Playlist, load_playlists_from_directory},
-    track::{Track, load_tracks_from_directory},
+    scan_exclusions::is_excluded,
+    track::{LibrarySnapshot, Track, load_tracks_from_directory, load_tracks_from_files},
 };
 
+const PROGRESS_INTERVAL: usize = 50; // Files between progress updates, so that the channel isn't flooded.
+
+// File changes are coalesced into one reload, which waits until the changes have stopped for a moment, e.g. once a
+// batch of files has been copied into the library. Long copies still show up every so often, but reloads never follow
+// each other too closely since every reload walks the whole directory.
+const QUIET_PERIOD: Duration = Duration::from_secs(2);
+const MAX_RELOAD_DELAY: Duration = Duration::from_secs(30);
+const MIN_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
 #[fully_pub]
 struct LibraryWatcher {
     command_sender: Sender<LibraryWatcherCommand>,
-    update_receiver: Receiver<Option<LibraryAndPlaylists>>,
+    update_receiver: Receiver<LibraryUpdate>,
+}
+
+pub enum LibraryUpdate {
+    Cached(Vec<Track>),     // The library from the last session, sent before the first scan of its directory.
+    Progress(LoadProgress), // Sent while loading. Loading ends with one of the other updates.
+    Loaded(LibraryAndPlaylists),
+    Failed,
+}
+
+#[fully_pub]
+#[derive(Clone)]
+struct LoadProgress {
+    done: usize,
+    total: usize,    // Zero while the files are still being listed.
+    folder: PathBuf, // The folder of the most recently loaded file.
+    started: Instant,
 }
 
 pub enum LibraryWatcherCommand {
     Load,
+    FilesChanged(Vec<PathBuf>), // Sent by the debouncer. The library is only reloaded if a change isn't excluded.
     SetPath(PathBuf),
+    SetReferencedFiles(Vec<PathBuf>), // Files outside of the library directory that are part of the library.
+    SetExclusions(Vec<String>),       // See scan_exclusions.
+    SetMeasuredDurations(HashMap<PathBuf, Duration>), // Kept for rescans of unchanged files and saved to the cache. See duration.
     Shutdown,
 }
 
 pub type LibraryAndPlaylists = (Vec<Track>, Vec<Playlist>);
 
-pub fn setup_library_watcher() -> Result<LibraryWatcher> {
+// If a cache path is given, the library is saved there after every scan. See library_cache.
+pub fn setup_library_watcher(cache_path: Option<PathBuf>) -> Result<LibraryWatcher> {
     let (command_sender, command_receiver) = channel();
     let (update_sender, update_receiver) = channel();
 
@@ -40,10 +73,8 @@ pub fn setup_library_watcher() -> Result<LibraryWatcher> {
     let mut debouncer = new_debouncer(Duration::from_millis(500), move |res: DebounceEventResult| match res {
         Err(e) => error!("watch error: {:?}", e),
         Ok(events) => {
-            for e in events {
-                info!("Event for {:?}", e.path);
-            }
-            let _ = debouncer_command_sender.send(LibraryWatcherCommand::Load);
+            let paths = events.into_iter().map(|e| e.path).collect();
+            let _ = debouncer_command_sender.send(LibraryWatcherCommand::FilesChanged(paths));
         }
     })
     .context("failed to create filesystem debouncer")?;
@@ -52,23 +83,87 @@ pub fn setup_library_watcher() -> Result<LibraryWatcher> {
 
     thread::spawn(move || {
         let mut watcher_directory: Option<PathBuf> = None;
+        let mut snapshot: Option<LibrarySnapshot> = None; // Used to only re-read changed files on rescans. Seeded from the library cache.
+        let mut referenced_files: Vec<PathBuf> = Vec::new();
+        let mut exclusions: Vec<String> = Vec::new();
+        let mut pending_changes: Option<(Instant, Instant)> = None; // When the first and the latest unloaded changes happened.
+        let mut last_reload: Option<Instant> = None; // When the last reload finished.
+
+        loop {
+            let command = match pending_changes {
+                Some((first, latest)) => {
+                    let deadline = reload_deadline(first, latest, last_reload);
+                    match command_receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(command) => command,
+                        Err(RecvTimeoutError::Timeout) => LibraryWatcherCommand::Load,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                None => match command_receiver.recv() {
+                    Ok(command) => command,
+                    Err(_) => break,
+                },
+            };
 
-        while let Ok(command) = command_receiver.recv() {
             match command {
                 LibraryWatcherCommand::Load => {
+                    pending_changes = None; // Every load picks up all changes so far.
                     let Some(path) = &watcher_directory else {
                         warn!("Load command received with no watcher_directory set");
-                        let _ = update_sender.send(None);
+                        let _ = update_sender.send(LibraryUpdate::Failed);
                         continue;
                     };
 
                     if !path.is_dir() {
                         error!("Cannot load library: invalid path {:?}", path);
-                        let _ = update_sender.send(None);
+                        let _ = update_sender.send(LibraryUpdate::Failed);
                         continue;
                     }
 
-                    let library = load_tracks_from_directory(path);
+                    // The cached library can be shown and played from while the directory is scanned for changes.
+                    if snapshot.is_none()
+                        && let Some(cache_path) = &cache_path
+                    {
+                        match load_library_cache(cache_path, path) {
+                            Ok(Some((tracks, cached_snapshot))) => {
+                                info!("Loaded {} cached tracks for {:?}.", tracks.len(), path);
+                                let _ = update_sender.send(LibraryUpdate::Cached(tracks));
+                                snapshot = Some(cached_snapshot);
+                            }
+                            Ok(None) => {}
+                            Err(e) => warn!("Failed to load the library cache: {}", e),
+                        }
+                    }
+
+                    let started = Instant::now();
+                    let send_progress = |done, total, folder: PathBuf| {
+                        let progress = LoadProgress {
+                            done,
+                            total,
+                            folder,
+                            started,
+                        };
+                        let _ = update_sender.send(LibraryUpdate::Progress(progress));
+                    };
+
+                    send_progress(0, 0, path.clone());
+
+                    let progress = |done: usize, total: usize, file: &Path| {
+                        if done % PROGRESS_INTERVAL == 0 || done == total {
+                            send_progress(done, total, file.parent().unwrap_or(path).to_path_buf());
+                        }
+                    };
+                    let (mut library, new_snapshot) = load_tracks_from_directory(path, &exclusions, snapshot.as_ref(), progress);
+
+                    library.extend(load_tracks_from_files(&referenced_files));
+
+                    if let Some(cache_path) = &cache_path
+                        && let Err(e) = save_library_cache(cache_path, &new_snapshot, &library)
+                    {
+                        warn!("Failed to save the library cache: {}", e);
+                    }
+                    snapshot = Some(new_snapshot);
+
                     let playlists = load_playlists_from_directory(path);
 
                     info!(
@@ -78,12 +173,33 @@ pub fn setup_library_watcher() -> Result<LibraryWatcher> {
                         playlists.len()
                     );
 
-                    let _ = update_sender.send(Some((library, playlists)));
+                    let _ = update_sender.send(LibraryUpdate::Loaded((library, playlists)));
+                    last_reload = Some(Instant::now());
+                }
+                LibraryWatcherCommand::FilesChanged(paths) => {
+                    let Some(directory) = &watcher_directory else {
+                        continue;
+                    };
+
+                    let relevant_paths: Vec<&PathBuf> = paths.iter().filter(|p| !is_excluded(p, directory, &exclusions)).collect();
+                    if relevant_paths.is_empty() {
+                        continue; // Everything that changed is excluded from the library.
+                    }
+
+                    for path in relevant_paths {
+                        info!("Event for {:?}", path);
+                    }
+
+                    let now = Instant::now();
+                    pending_changes = Some(match pending_changes {
+                        Some((first, _)) => (first, now),
+                        None => (now, now),
+                    });
                 }
                 LibraryWatcherCommand::SetPath(new_directory) => {
                     if !new_directory.is_dir() {
                         warn!("Invalid library path: {:?}", new_directory);
-                        let _ = update_sender.send(None);
+                        let _ = update_sender.send(LibraryUpdate::Failed);
                         continue;
                     }
 
@@ -91,19 +207,62 @@ pub fn setup_library_watcher() -> Result<LibraryWatcher> {
                         && let Err(e) = debouncer.watcher().unwatch(old)
                     {
                         error!("Failed to unwatch old folder {:?}: {:?}", old, e);
-                        let _ = update_sender.send(None);
+                        let _ = update_sender.send(LibraryUpdate::Failed);
                         continue;
                     }
 
                     if let Err(e) = debouncer.watcher().watch(&new_directory, RecursiveMode::Recursive) {
                         error!("Failed to watch new folder {:?}: {:?}", new_directory, e);
-                        let _ = update_sender.send(None);
+                        let _ = update_sender.send(LibraryUpdate::Failed);
                         continue;
                     }
 
                     watcher_directory = Some(new_directory);
+                    snapshot = None; // A new directory requires a full scan.
                     let _ = watcher_command_sender.send(LibraryWatcherCommand::Load);
                 }
+                LibraryWatcherCommand::SetReferencedFiles(new_files) => {
+                    for old in &referenced_files {
+                        if let Err(e) = debouncer.watcher().unwatch(old) {
+                            warn!("Failed to unwatch referenced file {:?}: {:?}", old, e);
+                        }
+                    }
+
+                    for file in &new_files {
+                        if let Err(e) = debouncer.watcher().watch(file, RecursiveMode::NonRecursive) {
+                            warn!("Failed to watch referenced file {:?}: {:?}", file, e);
+                        }
+                    }
+
+                    referenced_files = new_files;
+
+                    if watcher_directory.is_some() {
+                        let _ = watcher_command_sender.send(LibraryWatcherCommand::Load);
+                    }
+                }
+                LibraryWatcherCommand::SetExclusions(new_exclusions) => {
+                    exclusions = new_exclusions;
+
+                    if watcher_directory.is_some() {
+                        let _ = watcher_command_sender.send(LibraryWatcherCommand::Load);
+                    }
+                }
+                LibraryWatcherCommand::SetMeasuredDurations(durations) => {
+                    if let Some(snapshot) = &mut snapshot {
+                        for (path, duration) in &durations {
+                            if let Some((_, track)) = snapshot.entries.get_mut(path) {
+                                track.duration = *duration;
+                                track.duration_measured = true;
+                            }
+                        }
+                    }
+
+                    if let Some(cache_path) = &cache_path
+                        && let Err(e) = update_cached_durations(cache_path, &durations)
+                    {
+                        warn!("Failed to save measured durations to the library cache: {}", e);
+                    }
+                }
                 LibraryWatcherCommand::Shutdown => {
                     info!("Received shutdown message. Shutting down the library watcher.");
                     return;
@@ -114,5 +273,18 @@ pub fn setup_library_watcher() -> Result<LibraryWatcher> {
         info!("Command channel closed. Shutting down watcher.");
     });
 
-    Ok(LibraryWatcher { command_sender, update_receiver })
+    Ok(LibraryWatcher {
+        command_sender,
+        update_receiver,
+    })
+}
+
+// Waits for the changes to go quiet, but no longer than the maximum delay, and no sooner than the minimum interval.
+fn reload_deadline(first_change: Instant, latest_change: Instant, last_reload: Option<Instant>) -> Instant {
+    let deadline = (latest_change + QUIET_PERIOD).min(first_change + MAX_RELOAD_DELAY);
+
+    match last_reload {
+        Some(last_reload) => deadline.max(last_reload + MIN_RELOAD_INTERVAL),
+        None => deadline,
+    }
 }
@@ -0,0 +1,293 @@
+use std::{
+    collections::{HashMap, HashSet},
+    f64::consts::PI,
+    fs::File,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result, bail};
+use fully_pub::fully_pub;
+use rodio::{Decoder, Source};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    library_cache::{time_from_json, time_to_json},
+    player::RangeSource,
+    track::Track,
+};
+
+// Measured loudness by track path. Measuring decodes the whole track, so the results are kept between sessions, along
+// with the tracks that couldn't be measured so that they aren't decoded again on every reload. Both are stored with
+// the modification time the track had then, and only count while the track is unchanged.
+#[fully_pub]
+#[derive(Default)]
+struct LoudnessCache {
+    measured: HashMap<PathBuf, (SystemTime, TrackLoudness)>,
+    failed: HashMap<PathBuf, SystemTime>,
+}
+
+pub const DEFAULT_TARGET_LOUDNESS: f32 = -18.0; // In LUFS. The reference level of ReplayGain 2.0.
+pub const MIN_TARGET_LOUDNESS: f32 = -24.0;
+pub const MAX_TARGET_LOUDNESS: f32 = -10.0;
+
+const BLOCK_SEGMENTS: usize = 4; // Gating blocks are 400 ms long and overlap by 75%, so they are made of four 100 ms segments.
+const ABSOLUTE_GATE: f64 = -70.0; // In LUFS.
+const RELATIVE_GATE: f64 = -10.0; // In LU, relative to the loudness of the blocks above the absolute gate.
+
+#[fully_pub]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TrackLoudness {
+    integrated: f32, // In LUFS.
+    peak: f32,       // The largest sample magnitude, where 1.0 is full scale.
+}
+
+impl LoudnessCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, track: &Track) -> Option<&TrackLoudness> {
+        let (modified, loudness) = self.measured.get(&track.path)?;
+        (*modified == track.date_modified).then_some(loudness)
+    }
+
+    // Whether the track has neither been measured nor failed to be since it last changed.
+    pub fn needs_measuring(&self, track: &Track) -> bool {
+        let failed = self.failed.get(&track.path) == Some(&track.date_modified);
+        self.get(track).is_none() && !failed
+    }
+
+    pub fn insert(&mut self, path: PathBuf, modified: SystemTime, loudness: TrackLoudness) {
+        self.failed.remove(&path);
+        self.measured.insert(path, (modified, loudness));
+    }
+
+    pub fn insert_failure(&mut self, path: PathBuf, modified: SystemTime) {
+        self.measured.remove(&path);
+        self.failed.insert(path, modified);
+    }
+
+    // Forgets the tracks that are no longer in the library, so that the saved cache doesn't keep growing.
+    pub fn retain_tracks(&mut self, tracks: &[Track]) {
+        let paths: HashSet<&Path> = tracks.iter().map(|t| t.path.as_path()).collect();
+        self.measured.retain(|path, _| paths.contains(path.as_path()));
+        self.failed.retain(|path, _| paths.contains(path.as_path()));
+    }
+
+    // Times are stored the same way as in the library cache, since they are compared for equality.
+    pub fn to_json(&self) -> Value {
+        let measured: Map<String, Value> = self
+            .measured
+            .iter()
+            .filter_map(|(path, (modified, loudness))| {
+                let value = json!({ "modified": time_to_json(*modified), "integrated": loudness.integrated, "peak": loudness.peak });
+                Some((path.to_str()?.to_owned(), value))
+            })
+            .collect();
+
+        let failed: Map<String, Value> = self
+            .failed
+            .iter()
+            .filter_map(|(path, modified)| Some((path.to_str()?.to_owned(), time_to_json(*modified))))
+            .collect();
+
+        json!({ "measured": measured, "failed": failed })
+    }
+
+    // Entries that can't be read are left out, and are measured again.
+    pub fn from_json(value: &Value) -> Self {
+        let measured = value["measured"].as_object().into_iter().flatten().filter_map(|(path, entry)| {
+            let loudness = TrackLoudness {
+                integrated: entry["integrated"].as_f64()? as f32,
+                peak: entry["peak"].as_f64()? as f32,
+            };
+            Some((PathBuf::from(path), (time_from_json(&entry["modified"])?, loudness)))
+        });
+
+        let failed = value["failed"].as_object().into_iter().flatten();
+        let failed = failed.filter_map(|(path, modified)| Some((PathBuf::from(path), time_from_json(modified)?)));
+
+        Self {
+            measured: measured.collect(),
+            failed: failed.collect(),
+        }
+    }
+}
+
+// Measures the integrated loudness of the track as described in EBU R128 / ITU-R BS.1770.
+pub fn measure_loudness(track: &Track) -> Result<TrackLoudness> {
+    let audio_path = track.audio_path();
+    let file = File::open(audio_path).with_context(|| format!("Failed to open audio file at {:?}", audio_path))?;
+    let decoder = Decoder::try_from(file).with_context(|| format!("Failed to decode audio file {:?}", audio_path))?;
+
+    let (start, end) = match &track.cue {
+        Some(cue) => (cue.start, cue.end),
+        None => (Duration::ZERO, None),
+    };
+    let source = RangeSource::new(decoder, start, end).with_context(|| format!("Failed to seek audio file {:?}", audio_path))?;
+
+    let mut meter = LoudnessMeter::new(source.sample_rate().get() as f64, source.channels().get() as usize);
+    for sample in source {
+        meter.push(sample);
+    }
+
+    meter.finish()
+}
+
+struct LoudnessMeter {
+    channel_weights: Vec<f64>,
+    filters: Vec<[Biquad; 2]>, // The K-weighting filters of each channel.
+    channel: usize,            // The channel of the next sample, since samples are interleaved.
+
+    segment_frames: usize, // Frames in 100 ms.
+    frames_in_segment: usize,
+    segment_sums: Vec<f64>, // The sum of the squared, filtered samples of each channel in the current segment.
+    segment_powers: Vec<f64>,
+
+    peak: f32,
+}
+
+impl LoudnessMeter {
+    fn new(sample_rate: f64, channels: usize) -> Self {
+        // The low frequency channel of 5.1 audio is left out, and the surround channels count for more.
+        let channel_weights = (0..channels)
+            .map(|channel| match (channels, channel) {
+                (6, 3) => 0.0,
+                (6, 4 | 5) => 1.41,
+                _ => 1.0,
+            })
+            .collect();
+
+        Self {
+            channel_weights,
+            filters: vec![k_weighting(sample_rate); channels],
+            channel: 0,
+            segment_frames: (sample_rate / 10.0).round().max(1.0) as usize,
+            frames_in_segment: 0,
+            segment_sums: vec![0.0; channels],
+            segment_powers: Vec::new(),
+            peak: 0.0,
+        }
+    }
+
+    fn push(&mut self, sample: f32) {
+        self.peak = self.peak.max(sample.abs());
+
+        let [shelf, high_pass] = &mut self.filters[self.channel];
+        let filtered = high_pass.process(shelf.process(sample as f64));
+        self.segment_sums[self.channel] += filtered * filtered;
+
+        self.channel += 1;
+        if self.channel < self.channel_weights.len() {
+            return;
+        }
+        self.channel = 0;
+
+        self.frames_in_segment += 1;
+        if self.frames_in_segment < self.segment_frames {
+            return;
+        }
+
+        let power: f64 = self
+            .segment_sums
+            .iter()
+            .zip(&self.channel_weights)
+            .map(|(sum, weight)| weight * sum / self.segment_frames as f64)
+            .sum();
+        self.segment_powers.push(power);
+
+        self.segment_sums.fill(0.0);
+        self.frames_in_segment = 0;
+    }
+
+    fn finish(self) -> Result<TrackLoudness> {
+        let block_powers: Vec<f64> = self
+            .segment_powers
+            .windows(BLOCK_SEGMENTS)
+            .map(|segments| segments.iter().sum::<f64>() / BLOCK_SEGMENTS as f64)
+            .collect();
+
+        let above_absolute_gate: Vec<f64> = block_powers.into_iter().filter(|&p| loudness(p) > ABSOLUTE_GATE).collect();
+        if above_absolute_gate.is_empty() {
+            bail!("The track is too short or too quiet to measure");
+        }
+
+        let relative_gate = loudness(mean(&above_absolute_gate)) + RELATIVE_GATE;
+        let gated: Vec<f64> = above_absolute_gate.into_iter().filter(|&p| loudness(p) > relative_gate).collect();
+
+        Ok(TrackLoudness {
+            integrated: loudness(mean(&gated)) as f32,
+            peak: self.peak,
+        })
+    }
+}
+
+fn loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len().max(1) as f64
+}
+
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2], // a1 and a2. a0 is normalized to 1.
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1] - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+
+        y
+    }
+}
+
+// The high shelf and high-pass filters from BS.1770, derived for any sample rate (the standard only lists 48 kHz).
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    let shelf = {
+        let frequency = 1681.974450955533;
+        let gain_db = 3.999843853973347;
+        let q = 0.7071752369554196;
+
+        let k = (PI * frequency / sample_rate).tan();
+        let vh = 10.0_f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    };
+
+    let high_pass = {
+        let frequency = 38.13547087602444;
+        let q = 0.5003270373238773;
+
+        let k = (PI * frequency / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    };
+
+    [shelf, high_pass]
+}
@@ -0,0 +1,88 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::{Receiver, channel},
+    thread,
+    time::SystemTime,
+};
+
+use fully_pub::fully_pub;
+use log::{info, warn};
+
+use crate::{
+    GemPlayer,
+    loudness::{TrackLoudness, measure_loudness},
+    track::Track,
+};
+
+// Each with the modification time of the track that was measured.
+pub enum LoudnessScanMessage {
+    Measured {
+        path: PathBuf,
+        modified: SystemTime,
+        loudness: TrackLoudness,
+    },
+    Failed {
+        path: PathBuf,
+        modified: SystemTime,
+    },
+}
+
+// Measures the loudness of tracks in the background. Tracks are measured one at a time so that playback isn't starved
+// of CPU. Dropping the scanner stops it after the current track.
+#[fully_pub]
+struct LoudnessScanner {
+    receiver: Receiver<LoudnessScanMessage>,
+    progress: (usize, usize), // Tracks measured so far and the total.
+}
+
+fn spawn_loudness_scanner(tracks: Vec<Track>) -> LoudnessScanner {
+    let (sender, receiver) = channel();
+    let total = tracks.len();
+
+    thread::spawn(move || {
+        for track in tracks {
+            let message = match measure_loudness(&track) {
+                Ok(loudness) => LoudnessScanMessage::Measured {
+                    path: track.path,
+                    modified: track.date_modified,
+                    loudness,
+                },
+                Err(e) => {
+                    warn!("Failed to measure the loudness of '{}': {:#}", track.path.display(), e);
+                    LoudnessScanMessage::Failed {
+                        path: track.path,
+                        modified: track.date_modified,
+                    }
+                }
+            };
+
+            if sender.send(message).is_err() {
+                return; // The scanner was dropped.
+            }
+        }
+    });
+
+    LoudnessScanner {
+        receiver,
+        progress: (0, total),
+    }
+}
+
+// Measures the library tracks that haven't been measured (or failed to be) since they last changed, replacing any scan
+// that is already running.
+pub fn start_loudness_scan(gem: &mut GemPlayer) {
+    let tracks: Vec<Track> = gem
+        .library
+        .iter()
+        .filter(|t| gem.player.loudness.needs_measuring(t))
+        .cloned()
+        .collect();
+
+    if tracks.is_empty() {
+        gem.loudness_scanner = None;
+        return;
+    }
+
+    info!("Measuring the loudness of {} track(s).", tracks.len());
+    gem.loudness_scanner = Some(spawn_loudness_scanner(tracks));
+}
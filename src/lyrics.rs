@@ -0,0 +1,108 @@
+use std::{fs::read_to_string, path::Path, time::Duration};
+
+use lofty::{file::TaggedFileExt, read_from_path, tag::ItemKey};
+
+use crate::track::Track;
+
+pub enum Lyrics {
+    Synced(Vec<LyricLine>), // Sorted by time.
+    Plain(String),
+}
+
+pub struct LyricLine {
+    pub time: Duration,
+    pub text: String,
+}
+
+// Sidecar .lrc files take priority over embedded lyrics since they are usually synced.
+pub fn load_lyrics(track: &Track) -> Option<Lyrics> {
+    // A sidecar file belongs to the whole audio file, which doesn't line up with the tracks of a cue sheet.
+    if track.cue.is_none() {
+        let lrc_path = track.path.with_extension("lrc");
+        if let Ok(text) = read_to_string(&lrc_path) {
+            return parse_lyrics(&text);
+        }
+    }
+
+    let text = read_embedded_lyrics(track.audio_path())?;
+    parse_lyrics(&text)
+}
+
+fn read_embedded_lyrics(path: &Path) -> Option<String> {
+    let tagged_file = read_from_path(path).ok()?;
+    let tag = tagged_file.primary_tag().or_else(|| tagged_file.first_tag())?;
+    tag.get_string(ItemKey::Lyrics).map(|l| l.to_owned())
+}
+
+// Embedded lyrics may also be in the lrc format, so both sources are parsed the same way.
+pub fn parse_lyrics(text: &str) -> Option<Lyrics> {
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    let lines = parse_lrc(text);
+    if lines.is_empty() {
+        return Some(Lyrics::Plain(text.trim().to_owned()));
+    }
+
+    Some(Lyrics::Synced(lines))
+}
+
+// Parses lines like "[01:23.45]Some words". A line may have several timestamps, e.g. for a repeated chorus.
+fn parse_lrc(text: &str) -> Vec<LyricLine> {
+    let mut offset_ms: i64 = 0;
+    let mut lines = Vec::new();
+
+    for raw_line in text.lines() {
+        let mut rest = raw_line.trim();
+        let mut times = Vec::new();
+
+        while let Some(tag_end) = rest.strip_prefix('[').and_then(|r| r.find(']')) {
+            let tag = &rest[1..=tag_end];
+            rest = &rest[tag_end + 2..];
+
+            if let Some(value) = tag.strip_prefix("offset:") {
+                offset_ms = value.trim().parse().unwrap_or(0);
+            } else if let Some(time) = parse_timestamp(tag) {
+                times.push(time);
+            }
+        }
+
+        for time in times {
+            lines.push((time, rest.trim().to_owned()));
+        }
+    }
+
+    // A positive offset makes the lyrics appear sooner.
+    let mut lines: Vec<LyricLine> = lines
+        .into_iter()
+        .map(|(time, text)| {
+            let millis = (time.as_millis() as i64 - offset_ms).max(0);
+            LyricLine {
+                time: Duration::from_millis(millis as u64),
+                text,
+            }
+        })
+        .collect();
+
+    lines.sort_by_key(|line| line.time);
+    lines
+}
+
+// Timestamps look like "mm:ss", "mm:ss.xx", or "mm:ss.xxx".
+fn parse_timestamp(tag: &str) -> Option<Duration> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes: u64 = minutes.trim().parse().ok()?;
+    let seconds: f64 = seconds.trim().parse().ok()?;
+
+    if !seconds.is_finite() || seconds < 0.0 {
+        return None;
+    }
+
+    Some(Duration::from_secs(minutes * 60) + Duration::from_secs_f64(seconds))
+}
+
+// The index of the line being sung at the given position, if any have started.
+pub fn current_line_index(lines: &[LyricLine], position: Duration) -> Option<usize> {
+    lines.partition_point(|line| line.time <= position).checked_sub(1)
+}
@@ -1,75 +1,200 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // Hides console for Windows release
 
-#[cfg(not(any(target_os = "macos", target_os = "windows")))]
-compile_error!("Gem Player only supports macOS and Windows.");
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+compile_error!("Gem Player only supports macOS, Windows, and Linux.");
 
 use crate::{
     artwork_cache::{artwork_uri, cache_track_artwork, clear_artwork_cache},
+    artwork_fetcher::{ArtworkFetcher, setup_artwork_fetcher},
+    artwork_picker::ArtworkPicker,
     commands::{GemCommand, execute},
+    duration_scanner::{
+        APPLY_INTERVAL, DurationScanMessage, DurationScanner, apply_measured_durations, start_duration_scan, stop_duration_scan,
+    },
+    file_opener::{FileOpener, opened_tracks},
+    focus_pause::{FocusPause, poll_window_focus},
+    library_exporter::{LibraryExportMessage, LibraryExporter},
     library_watcher::LibraryWatcher,
+    loudness_scanner::{LoudnessScanMessage, LoudnessScanner, start_loudness_scan},
     nosleep_manager::NoSleepManager,
     os_media_controls::{OSMediaControlsState, poll_media_events, setup_os_media_controls, update_metadata, update_playback},
-    player::{get_position, stop},
-    track::is_audio_file,
+    player::{get_position, play, seek, stop},
+    playlist_entry_locator::PlaylistEntryLocator,
+    playlist_exporter::PlaylistExporter,
+    remote_control::{
+        DEFAULT_REMOTE_PORT, RemoteControl, generate_remote_token, poll_remote_control, restart_remote_control, stop_remote_control,
+    },
+    single_instance::{InstanceClaim, InstanceListener, PrimaryInstance, claim_instance, listen_for_instances},
+    track::{changed_tracks, is_audio_file},
+    track_notification::show_track_notification,
     ui::{
+        albums_view::{AlbumKey, AlbumsViewState, FetchedArtwork},
+        ambient_view::AmbientState,
+        duplicates_window::DuplicatesState,
+        itunes_import_window::ItunesImportState,
         library_view::LibraryViewState,
         playlist_view::PlaylistsViewState,
-        root::{UIState, View, gem_player_ui},
-        widgets::marquee::Marquee,
+        queue_view::QueueViewState,
+        root::{LocatePlayingState, MAX_ZOOM, MIN_ZOOM, UIState, View, format_duration_to_mmss, gem_player_ui},
+        track_columns::{ColumnLayout, LIBRARY_COLUMNS, PLAYLIST_COLUMNS, QUEUE_COLUMNS, TrackColumn, parse_saved_columns},
+        type_to_jump::TypeToJumpState,
+        widgets::{
+            level_meter::LevelMeter,
+            marquee::Marquee,
+            spectrogram::{self, ColorMap, DEFAULT_CEILING_DB, DEFAULT_FLOOR_DB, Spectrogram},
+        },
     },
-    visualizer::VisualizerState,
 };
+use auto_playlist::{PlayCounts, record_play};
+use bookmarks::{Bookmarks, DEFAULT_BOOKMARK_THRESHOLD, MAX_BOOKMARK_THRESHOLD, bookmark_position, update_bookmark};
+use chapters::{Chapter, load_chapters};
 use dark_light::Mode;
+use directories::ProjectDirs;
 use eframe::{App, CreationContext, Frame, NativeOptions, Storage, icon_data, run_native, wgpu::rwh::HasWindowHandle};
-use egui::{Color32, Context, FontData, FontDefinitions, FontFamily, Rgba, Shadow, ThemePreference, Ui, Vec2, ViewportBuilder, Visuals};
+use egui::{
+    Color32, Context, FontData, FontDefinitions, FontFamily, Key, KeyboardShortcut, Modifiers, Rgba, Shadow, ThemePreference, Ui, Vec2,
+    ViewportBuilder, Visuals, load::Bytes,
+};
 use egui_notify::Toasts;
+use equalizer::{BAND_COUNT, MAX_GAIN_DB};
 use font_kit::{family_name::FamilyName, handle::Handle, properties::Properties, source::SystemSource};
 use fully_pub::fully_pub;
-use library_watcher::{LibraryWatcherCommand, setup_library_watcher};
+use gem_player::{
+    auto_playlist, bookmarks, chapters, duplicates, duration, equalizer, itunes_library, library_export, library_watcher, loudness, lyrics,
+    player, playlist, search_query, track, visualizer,
+};
+use library_watcher::{LibraryUpdate, LibraryWatcherCommand, LoadProgress, setup_library_watcher};
 use log::{debug, error, info, warn};
+use loudness::{DEFAULT_TARGET_LOUDNESS, LoudnessCache, MAX_TARGET_LOUDNESS, MIN_TARGET_LOUDNESS};
+use lyrics::{Lyrics, load_lyrics};
 use mimalloc::MiMalloc;
-use player::{Player, build_audio_backend_from_device, play_next, play_previous};
-use playlist::Playlist;
+use player::{
+    DEFAULT_PAUSE_FADE, DEFAULT_REWIND_THRESHOLD, DEFAULT_SEEK_STEP, MAX_CROSSFADE, MAX_PAUSE_FADE, MAX_REWIND_THRESHOLD, OutputSettings,
+    Player, ReplayGainMode, ResamplerQuality, SEEK_STEPS, ShuffleMode, build_audio_backend_from_device, crossfade_is_due, device_name,
+    play_next, play_previous, replace_queue, resume_track, update_crossfade, update_pause_fade,
+};
+use playlist::{ExportOptions, Playlist, PlaylistFormat, export, relink_missing_entry};
 use rodio::cpal::{default_host, traits::HostTrait};
+use search_query::SearchQuery;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    env,
     fs::{copy, read},
     mem::take,
-    path::PathBuf,
+    path::{self, Path, PathBuf},
     sync::{
         Arc,
+        atomic::{AtomicBool, Ordering},
         mpsc::{Receiver, TryRecvError},
     },
     thread,
     time::Duration,
 };
-use track::{SortBy, SortOrder, Track};
-use visualizer::{CENTER_FREQUENCIES, setup_visualizer_pipeline};
+use thumbnail_cache::ThumbnailCache;
+use track::{DEFAULT_RATING_EMAIL, SortBy, SortOrder, Track, embed_artwork, load_track, set_rating_email};
+use visualizer::{
+    DEFAULT_ATTACK, DEFAULT_DECAY, SMOOTHING_RANGE, VisualizerStyle, send_visualizer_command, set_meter_enabled, set_visualizer_enabled,
+    set_visualizer_style,
+};
 
 #[cfg(target_os = "macos")]
 use {crate::platform::macos_menu::MenuBar, std::str::FromStr};
 
+#[cfg(feature = "global-media-keys")]
+use crate::media_keys::{MediaKeys, poll_media_keys, setup_media_keys};
+
 mod artwork_cache;
+mod artwork_fetcher;
+mod artwork_picker;
 mod commands;
+mod duration_scanner;
+mod file_opener;
+mod focus_pause;
+mod library_exporter;
 mod library_folder_picker;
-mod library_watcher;
+mod loudness_scanner;
+#[cfg(feature = "global-media-keys")]
+mod media_keys;
 mod nosleep_manager;
 mod os_media_controls;
 mod platform;
-mod player;
-mod playlist;
-mod track;
+mod playlist_entry_locator;
+mod playlist_exporter;
+mod remote_control;
+mod single_instance;
+mod thumbnail_cache;
+mod track_notification;
 mod ui;
-mod visualizer;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
 const APP_NAME: &str = "Gem Player";
+const LIBRARY_CACHE_FILENAME: &str = "library.json"; // In the cache directory. See library_cache.
 
 pub const LIBRARY_DIRECTORY_STORAGE_KEY: &str = "library_directory";
+pub const REFERENCED_FILES_STORAGE_KEY: &str = "referenced_files";
+pub const REFERENCE_DROPPED_FILES_STORAGE_KEY: &str = "reference_dropped_files";
+pub const SCAN_EXCLUSIONS_STORAGE_KEY: &str = "scan_exclusions";
+pub const RATING_EMAIL_STORAGE_KEY: &str = "rating_email";
 pub const THEME_STORAGE_KEY: &str = "theme";
+pub const REDUCE_ANIMATIONS_STORAGE_KEY: &str = "reduce_animations";
+pub const ZOOM_STORAGE_KEY: &str = "zoom";
+pub const LARGE_ROWS_STORAGE_KEY: &str = "large_rows";
+pub const SHOW_LYRICS_STORAGE_KEY: &str = "show_lyrics";
+pub const SHOW_TRACK_NUMBER_COLUMN_STORAGE_KEY: &str = "show_track_number_column";
+pub const SHOW_GENRE_COLUMN_STORAGE_KEY: &str = "show_genre_column";
+pub const SHOW_YEAR_COLUMN_STORAGE_KEY: &str = "show_year_column";
+pub const LIBRARY_COLUMNS_STORAGE_KEY: &str = "library_columns";
+pub const PLAYLIST_COLUMNS_STORAGE_KEY: &str = "playlist_columns";
+pub const QUEUE_COLUMNS_STORAGE_KEY: &str = "queue_columns";
+pub const SHOW_TITLE_BAR_INFO_STORAGE_KEY: &str = "show_title_bar_info";
 pub const VOLUME_STORAGE_KEY: &str = "volume";
+pub const REWIND_THRESHOLD_STORAGE_KEY: &str = "rewind_threshold";
+pub const SEEK_STEP_STORAGE_KEY: &str = "seek_step";
+pub const CROSSFADE_STORAGE_KEY: &str = "crossfade";
+pub const PAUSE_FADE_STORAGE_KEY: &str = "pause_fade";
+pub const LOW_LATENCY_STORAGE_KEY: &str = "low_latency";
+pub const OUTPUT_SETTINGS_STORAGE_KEY: &str = "output_settings";
+pub const SHUFFLE_MODE_STORAGE_KEY: &str = "shuffle_mode";
+pub const REPLAY_GAIN_MODE_STORAGE_KEY: &str = "replay_gain_mode";
+pub const LOUDNESS_STORAGE_KEY: &str = "loudness";
+pub const TARGET_LOUDNESS_STORAGE_KEY: &str = "target_loudness";
+pub const VISUALIZER_STYLE_STORAGE_KEY: &str = "visualizer_style";
+pub const VISUALIZER_ATTACK_STORAGE_KEY: &str = "visualizer_attack";
+pub const VISUALIZER_DECAY_STORAGE_KEY: &str = "visualizer_decay";
+pub const VISUALIZER_PEAK_HOLD_STORAGE_KEY: &str = "visualizer_peak_hold";
+pub const LEVEL_METER_STORAGE_KEY: &str = "level_meter";
+pub const VISUALIZER_ENABLED_STORAGE_KEY: &str = "visualizer_enabled";
+pub const SPECTROGRAM_FLOOR_STORAGE_KEY: &str = "spectrogram_floor_db";
+pub const SPECTROGRAM_CEILING_STORAGE_KEY: &str = "spectrogram_ceiling_db";
+pub const SPECTROGRAM_COLOR_MAP_STORAGE_KEY: &str = "spectrogram_color_map";
+pub const RESUME_ON_STARTUP_STORAGE_KEY: &str = "resume_on_startup";
+pub const RESUME_TRACK_STORAGE_KEY: &str = "resume_track";
+pub const RESUME_POSITION_STORAGE_KEY: &str = "resume_position";
+pub const QUEUE_STORAGE_KEY: &str = "queue";
+pub const HISTORY_STORAGE_KEY: &str = "history";
+pub const PLAY_COUNTS_STORAGE_KEY: &str = "play_counts";
+pub const BOOKMARK_THRESHOLD_STORAGE_KEY: &str = "bookmark_threshold";
+pub const BOOKMARKS_STORAGE_KEY: &str = "bookmarks";
+pub const EQUALIZER_ENABLED_STORAGE_KEY: &str = "equalizer_enabled";
+pub const EQUALIZER_GAINS_STORAGE_KEY: &str = "equalizer_gains";
+pub const PAUSE_ON_FOCUS_LOSS_STORAGE_KEY: &str = "pause_on_focus_loss";
+pub const RESUME_ON_FOCUS_GAIN_STORAGE_KEY: &str = "resume_on_focus_gain";
+pub const TRACK_NOTIFICATIONS_STORAGE_KEY: &str = "track_notifications";
+pub const ACCURATE_DURATIONS_STORAGE_KEY: &str = "accurate_durations";
+pub const REMOTE_CONTROL_ENABLED_STORAGE_KEY: &str = "remote_control_enabled";
+pub const REMOTE_CONTROL_PORT_STORAGE_KEY: &str = "remote_control_port";
+pub const REMOTE_CONTROL_ALLOW_NETWORK_STORAGE_KEY: &str = "remote_control_allow_network";
+pub const REMOTE_CONTROL_TOKEN_STORAGE_KEY: &str = "remote_control_token";
+
+pub const COPY_NOW_PLAYING_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::C);
+pub const SHUFFLE_LIBRARY_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::S);
+pub const LOCATE_PLAYING_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::L);
+pub const SELECT_ALL_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::A);
+pub const AMBIENT_MODE_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::F11);
+pub const SEEK_BACKWARD_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::SHIFT, Key::ArrowLeft);
+pub const SEEK_FORWARD_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::SHIFT, Key::ArrowRight);
 
 #[fully_pub]
 struct GemPlayer {
@@ -79,25 +204,82 @@ struct GemPlayer {
     playlists: Vec<Playlist>,
 
     library_directory: Option<PathBuf>,
+    referenced_files: Vec<PathBuf>, // Tracks that are part of the library but live outside of the library directory.
+    reference_dropped_files: bool,  // Whether dropped files are referenced in place instead of being copied into the library directory.
+    scan_exclusions: Vec<String>,   // Patterns for folders and files in the library directory that are left out of the library.
+    rating_email: String,           // The player that ratings in ID3 tags are saved for. See write_rating().
     folder_picker_receiver: Option<Receiver<Option<PathBuf>>>, // None -> No folder picker dialog. Some -> Folder picker dialog open.
+    artwork_picker: Option<ArtworkPicker>, // Some while the user is picking an image to embed as artwork.
+    artwork_fetcher: ArtworkFetcher,
+    playlist_entry_locator: Option<PlaylistEntryLocator>, // Some while the user is locating the file of a missing playlist entry.
+    library_exporter: Option<LibraryExporter>,            // Some while the library is being exported.
+    playlist_exporter: Option<PlaylistExporter>,          // Some while the user is choosing where to export a playlist.
+    loudness_scanner: Option<LoudnessScanner>,            // Some while tracks are being measured for loudness normalization.
+    duration_scanner: Option<DurationScanner>,            // Some while tracks are being measured for accurate durations.
     library_watcher: LibraryWatcher,
+    library_progress: Option<LoadProgress>, // Some while the library watcher is loading the library.
+    instance_listener: Option<InstanceListener>, // None if the instance file couldn't be written.
+    file_opener: Option<FileOpener>,        // Some while files opened from outside the app are being read.
 
     commands: Vec<GemCommand>,
 
     player: Player,
 
     nosleep_manager: NoSleepManager,
+    idle: Arc<AtomicBool>, // Whether the window is unfocused or animations are reduced. Shared with the logic wakeup thread.
+
+    focus_pause: FocusPause,
+    remote_control: RemoteControl,
+    resume_on_startup: bool,         // Whether to load the track that was playing when the app was last closed.
+    saved_queue: Option<SavedQueue>, // The queue from the last session. Restored once the library has loaded.
+    track_notifications: bool,       // Whether to show a desktop notification when the next track starts while unfocused.
+    accurate_durations: bool,        // Whether to measure the durations of tracks whose headers may be wrong. See duration.
+    lyrics: Option<Lyrics>,          // The lyrics of the playing track.
+    chapters: Vec<Chapter>,          // The chapters of the playing track, e.g. an audiobook.
+    play_counts: PlayCounts,
+    bookmarks: Bookmarks,
 
     os_media_controls: OSMediaControlsState,
 
+    #[cfg(feature = "global-media-keys")]
+    media_keys: Option<MediaKeys>, // None if the media keys could not be registered.
+
     #[cfg(target_os = "macos")]
     menubar: platform::macos_menu::MenuBar,
 }
 
+// Track paths rather than tracks, since tracks may have changed or disappeared between sessions.
+#[fully_pub]
+struct SavedQueue {
+    queue: Vec<PathBuf>,
+    history: Vec<PathBuf>,
+}
+
 fn main() -> eframe::Result {
     env_logger::init(); // Log to stderr (if run with `RUST_LOG=debug`).
     info!("Starting up Gem Player.");
 
+    // Files and folders to play, e.g. from the OS file associations. Options like macOS's -psn_ are skipped.
+    // Paths are made absolute since they may be handed to an instance with a different working directory.
+    let open_paths: Vec<PathBuf> = env::args_os()
+        .skip(1)
+        .filter(|arg| !arg.to_string_lossy().starts_with('-'))
+        .filter_map(|arg| path::absolute(arg).ok())
+        .collect();
+
+    // If Gem Player is already running, it takes over (e.g. for "Open with…") instead of us opening a second window.
+    let primary_instance = match claim_instance(&open_paths) {
+        Ok(InstanceClaim::Primary(primary)) => Some(primary),
+        Ok(InstanceClaim::HandedOff) => {
+            info!("Gem Player is already running. Handed off to the running instance.");
+            return Ok(());
+        }
+        Err(e) => {
+            warn!("Failed to check for a running instance: {}", e);
+            None
+        }
+    };
+
     let icon_data = icon_data::from_png_bytes(include_bytes!("../assets/icon.png")).expect("The icon data must be valid");
 
     let options = NativeOptions {
@@ -110,16 +292,17 @@ fn main() -> eframe::Result {
             .with_icon(icon_data),
         ..Default::default()
     };
-    run_native(APP_NAME, options, Box::new(|cc| Ok(Box::new(init_gem_player(cc)))))
+    run_native(
+        APP_NAME,
+        options,
+        Box::new(|cc| Ok(Box::new(init_gem_player(cc, primary_instance, open_paths)))),
+    )
 }
 
-pub fn init_gem_player(cc: &CreationContext<'_>) -> GemPlayer {
+pub fn init_gem_player(cc: &CreationContext<'_>, primary_instance: Option<PrimaryInstance>, open_paths: Vec<PathBuf>) -> GemPlayer {
     egui_extras::install_image_loaders(&cc.egui_ctx);
     egui_material_icons::initialize(&cc.egui_ctx);
 
-    cc.egui_ctx.options_mut(|w| {
-        w.zoom_with_keyboard = false;
-    });
     // This is a temporary solution until false positives with virtualized egui_extras::Table is fixed. It only occurs in debug mode.
     // github issue: https://github.com/emilk/egui/issues/8092
     #[cfg(debug_assertions)]
@@ -142,42 +325,428 @@ pub fn init_gem_player(cc: &CreationContext<'_>) -> GemPlayer {
     load_system_fonts(&mut fonts);
     cc.egui_ctx.set_fonts(fonts);
 
-    let mut backend = None;
-
-    let host = default_host();
-    if let Some(device) = host.default_output_device() {
-        let backend_result = build_audio_backend_from_device(device);
-        match backend_result {
-            Ok(b) => backend = Some(b),
-            Err(e) => error!("Failed to start audio device: {}", e),
-        }
-    }
-
-    let (visualizer_command_sender, bands_receiver) = setup_visualizer_pipeline();
-
     let mut library_directory = None;
+    let mut referenced_files = Vec::new();
+    let mut reference_dropped_files = false;
+    let mut scan_exclusions = Vec::new();
+    let mut rating_email = DEFAULT_RATING_EMAIL.to_owned();
     let mut theme_preference = ThemePreference::System;
+    let mut reduce_animations = false;
+    let mut zoom = 1.0;
+    let mut large_rows = false;
+    let mut show_title_bar_info = false;
+    let mut show_lyrics = false;
+    let mut show_track_number_column = false;
+    let mut show_genre_column = false;
+    let mut show_year_column = false;
+    let mut library_columns = ColumnLayout::new(&LIBRARY_COLUMNS);
+    let mut playlist_columns = ColumnLayout::new(&PLAYLIST_COLUMNS);
+    let mut queue_columns = ColumnLayout::new(&QUEUE_COLUMNS);
     let mut initial_volume = 0.6; // If this is the first run, we want a reasonable default.
+    let mut rewind_threshold = DEFAULT_REWIND_THRESHOLD;
+    let mut seek_step = DEFAULT_SEEK_STEP;
+    let mut crossfade = 0.0;
+    let mut pause_fade = DEFAULT_PAUSE_FADE;
+    let mut low_latency = false;
+    let mut output_settings = HashMap::new();
+    let mut shuffle_mode = ShuffleMode::Random;
+    let mut replay_gain_mode = ReplayGainMode::Off;
+    let mut loudness = LoudnessCache::new();
+    let mut target_loudness = DEFAULT_TARGET_LOUDNESS;
+    let mut visualizer_style = VisualizerStyle::Bars;
+    let mut visualizer_attack = DEFAULT_ATTACK;
+    let mut visualizer_decay = DEFAULT_DECAY;
+    let mut visualizer_peak_hold = true;
+    let mut level_meter_enabled = false;
+    let mut visualizer_enabled = true;
+    let mut spectrogram_floor_db = DEFAULT_FLOOR_DB;
+    let mut spectrogram_ceiling_db = DEFAULT_CEILING_DB;
+    let mut spectrogram_color_map = ColorMap::Fire;
+    let mut resume_on_startup = true;
+    let mut resume_track_path: Option<PathBuf> = None;
+    let mut resume_position = 0.0;
+    let mut saved_queue = None;
+    let mut play_counts = PlayCounts::new();
+    let mut bookmark_threshold = DEFAULT_BOOKMARK_THRESHOLD;
+    let mut bookmark_positions = HashMap::new();
+    let mut equalizer_enabled = false;
+    let mut equalizer_gains = [0.0; BAND_COUNT];
+    let mut pause_on_focus_loss = false;
+    let mut resume_on_focus_gain = false;
+    let mut track_notifications = false;
+    let mut accurate_durations = false;
+    let mut remote_control_enabled = false;
+    let mut remote_control_port = DEFAULT_REMOTE_PORT;
+    let mut remote_control_allow_network = false;
+    let mut remote_control_token = None;
 
     if let Some(storage) = cc.storage {
         if let Some(library_directory_string) = storage.get_string(LIBRARY_DIRECTORY_STORAGE_KEY) {
             library_directory = Some(PathBuf::from(library_directory_string));
         }
 
+        if let Some(files_string) = storage.get_string(REFERENCED_FILES_STORAGE_KEY)
+            && let Ok(files) = serde_json::from_str::<Vec<PathBuf>>(&files_string)
+        {
+            referenced_files = files;
+        }
+
+        if let Some(reference_string) = storage.get_string(REFERENCE_DROPPED_FILES_STORAGE_KEY)
+            && let Ok(reference) = serde_json::from_str(&reference_string)
+        {
+            reference_dropped_files = reference;
+        }
+
+        if let Some(exclusions_string) = storage.get_string(SCAN_EXCLUSIONS_STORAGE_KEY)
+            && let Ok(exclusions) = serde_json::from_str::<Vec<String>>(&exclusions_string)
+        {
+            scan_exclusions = exclusions;
+        }
+
+        if let Some(email_string) = storage.get_string(RATING_EMAIL_STORAGE_KEY)
+            && let Ok(email) = serde_json::from_str(&email_string)
+        {
+            rating_email = email;
+        }
+
         if let Some(theme_string) = storage.get_string(THEME_STORAGE_KEY)
             && let Ok(theme) = serde_json::from_str(&theme_string)
         {
             theme_preference = theme;
         }
 
+        if let Some(reduce_string) = storage.get_string(REDUCE_ANIMATIONS_STORAGE_KEY)
+            && let Ok(reduce) = serde_json::from_str(&reduce_string)
+        {
+            reduce_animations = reduce;
+        }
+
+        if let Some(zoom_string) = storage.get_string(ZOOM_STORAGE_KEY)
+            && let Ok(z) = serde_json::from_str::<f32>(&zoom_string)
+        {
+            zoom = z.clamp(MIN_ZOOM, MAX_ZOOM);
+        }
+
+        if let Some(large_rows_string) = storage.get_string(LARGE_ROWS_STORAGE_KEY)
+            && let Ok(large) = serde_json::from_str(&large_rows_string)
+        {
+            large_rows = large;
+        }
+
+        if let Some(show_string) = storage.get_string(SHOW_TITLE_BAR_INFO_STORAGE_KEY)
+            && let Ok(show) = serde_json::from_str(&show_string)
+        {
+            show_title_bar_info = show;
+        }
+
+        if let Some(show_string) = storage.get_string(SHOW_LYRICS_STORAGE_KEY)
+            && let Ok(show) = serde_json::from_str(&show_string)
+        {
+            show_lyrics = show;
+        }
+
+        if let Some(show_string) = storage.get_string(SHOW_TRACK_NUMBER_COLUMN_STORAGE_KEY)
+            && let Ok(show) = serde_json::from_str(&show_string)
+        {
+            show_track_number_column = show;
+        }
+
+        if let Some(show_string) = storage.get_string(SHOW_GENRE_COLUMN_STORAGE_KEY)
+            && let Ok(show) = serde_json::from_str(&show_string)
+        {
+            show_genre_column = show;
+        }
+
+        if let Some(show_string) = storage.get_string(SHOW_YEAR_COLUMN_STORAGE_KEY)
+            && let Ok(show) = serde_json::from_str(&show_string)
+        {
+            show_year_column = show;
+        }
+
+        if let Some(columns_string) = storage.get_string(LIBRARY_COLUMNS_STORAGE_KEY)
+            && let Some(saved) = parse_saved_columns(&columns_string)
+        {
+            library_columns = ColumnLayout::from_saved(&saved, &LIBRARY_COLUMNS);
+        } else {
+            // These columns used to be shown with toggles in the settings.
+            let legacy_columns = [
+                (TrackColumn::TrackNumber, show_track_number_column),
+                (TrackColumn::Genre, show_genre_column),
+                (TrackColumn::Year, show_year_column),
+            ];
+            for (column, show) in legacy_columns {
+                if show {
+                    library_columns.hidden.remove(&column);
+                }
+            }
+        }
+
+        if let Some(columns_string) = storage.get_string(PLAYLIST_COLUMNS_STORAGE_KEY)
+            && let Some(saved) = parse_saved_columns(&columns_string)
+        {
+            playlist_columns = ColumnLayout::from_saved(&saved, &PLAYLIST_COLUMNS);
+        }
+
+        if let Some(columns_string) = storage.get_string(QUEUE_COLUMNS_STORAGE_KEY)
+            && let Some(saved) = parse_saved_columns(&columns_string)
+        {
+            queue_columns = ColumnLayout::from_saved(&saved, &QUEUE_COLUMNS);
+        }
+
         if let Some(volume_string) = storage.get_string(VOLUME_STORAGE_KEY)
             && let Ok(volume) = serde_json::from_str::<f32>(&volume_string)
         {
             initial_volume = volume.clamp(0.0, 1.0);
         }
+
+        if let Some(threshold_string) = storage.get_string(REWIND_THRESHOLD_STORAGE_KEY)
+            && let Ok(threshold) = serde_json::from_str::<f32>(&threshold_string)
+        {
+            rewind_threshold = threshold.clamp(0.0, MAX_REWIND_THRESHOLD);
+        }
+
+        if let Some(seek_step_string) = storage.get_string(SEEK_STEP_STORAGE_KEY)
+            && let Ok(seconds) = serde_json::from_str::<u64>(&seek_step_string)
+            && SEEK_STEPS.contains(&seconds)
+        {
+            seek_step = seconds;
+        }
+
+        if let Some(crossfade_string) = storage.get_string(CROSSFADE_STORAGE_KEY)
+            && let Ok(seconds) = serde_json::from_str::<f32>(&crossfade_string)
+        {
+            crossfade = seconds.clamp(0.0, MAX_CROSSFADE);
+        }
+
+        if let Some(pause_fade_string) = storage.get_string(PAUSE_FADE_STORAGE_KEY)
+            && let Ok(seconds) = serde_json::from_str::<f32>(&pause_fade_string)
+        {
+            pause_fade = seconds.clamp(0.0, MAX_PAUSE_FADE);
+        }
+
+        if let Some(low_latency_string) = storage.get_string(LOW_LATENCY_STORAGE_KEY)
+            && let Ok(enabled) = serde_json::from_str::<bool>(&low_latency_string)
+        {
+            low_latency = enabled;
+        }
+
+        if let Some(settings_string) = storage.get_string(OUTPUT_SETTINGS_STORAGE_KEY)
+            && let Ok(saved) = serde_json::from_str::<HashMap<String, (Option<u32>, ResamplerQuality)>>(&settings_string)
+        {
+            for (device_name, (sample_rate, resampler)) in saved {
+                output_settings.insert(device_name, OutputSettings { sample_rate, resampler });
+            }
+        }
+
+        if let Some(mode_string) = storage.get_string(SHUFFLE_MODE_STORAGE_KEY)
+            && let Ok(mode) = serde_json::from_str::<ShuffleMode>(&mode_string)
+        {
+            shuffle_mode = mode;
+        }
+
+        if let Some(mode_string) = storage.get_string(REPLAY_GAIN_MODE_STORAGE_KEY)
+            && let Ok(mode) = serde_json::from_str::<ReplayGainMode>(&mode_string)
+        {
+            replay_gain_mode = mode;
+        }
+
+        if let Some(loudness_string) = storage.get_string(LOUDNESS_STORAGE_KEY)
+            && let Ok(saved) = serde_json::from_str::<serde_json::Value>(&loudness_string)
+        {
+            loudness = LoudnessCache::from_json(&saved);
+        }
+
+        if let Some(target_string) = storage.get_string(TARGET_LOUDNESS_STORAGE_KEY)
+            && let Ok(target) = serde_json::from_str::<f32>(&target_string)
+        {
+            target_loudness = target.clamp(MIN_TARGET_LOUDNESS, MAX_TARGET_LOUDNESS);
+        }
+
+        if let Some(style_string) = storage.get_string(VISUALIZER_STYLE_STORAGE_KEY)
+            && let Ok(style) = serde_json::from_str::<VisualizerStyle>(&style_string)
+        {
+            visualizer_style = style;
+        }
+
+        let (min_smoothing, max_smoothing) = SMOOTHING_RANGE;
+
+        if let Some(attack_string) = storage.get_string(VISUALIZER_ATTACK_STORAGE_KEY)
+            && let Ok(attack) = serde_json::from_str::<f32>(&attack_string)
+        {
+            visualizer_attack = attack.clamp(min_smoothing, max_smoothing);
+        }
+
+        if let Some(decay_string) = storage.get_string(VISUALIZER_DECAY_STORAGE_KEY)
+            && let Ok(decay) = serde_json::from_str::<f32>(&decay_string)
+        {
+            visualizer_decay = decay.clamp(min_smoothing, max_smoothing);
+        }
+
+        if let Some(peak_hold_string) = storage.get_string(VISUALIZER_PEAK_HOLD_STORAGE_KEY)
+            && let Ok(peak_hold) = serde_json::from_str(&peak_hold_string)
+        {
+            visualizer_peak_hold = peak_hold;
+        }
+
+        if let Some(level_meter_string) = storage.get_string(LEVEL_METER_STORAGE_KEY)
+            && let Ok(enabled) = serde_json::from_str(&level_meter_string)
+        {
+            level_meter_enabled = enabled;
+        }
+
+        if let Some(visualizer_enabled_string) = storage.get_string(VISUALIZER_ENABLED_STORAGE_KEY)
+            && let Ok(enabled) = serde_json::from_str(&visualizer_enabled_string)
+        {
+            visualizer_enabled = enabled;
+        }
+
+        if let Some(floor_string) = storage.get_string(SPECTROGRAM_FLOOR_STORAGE_KEY)
+            && let Ok(floor) = serde_json::from_str::<f32>(&floor_string)
+        {
+            spectrogram_floor_db = floor.clamp(spectrogram::MIN_DB, spectrogram::MAX_DB);
+        }
+
+        if let Some(ceiling_string) = storage.get_string(SPECTROGRAM_CEILING_STORAGE_KEY)
+            && let Ok(ceiling) = serde_json::from_str::<f32>(&ceiling_string)
+        {
+            spectrogram_ceiling_db = ceiling.clamp(spectrogram::MIN_DB, spectrogram::MAX_DB);
+        }
+
+        if let Some(color_map_string) = storage.get_string(SPECTROGRAM_COLOR_MAP_STORAGE_KEY)
+            && let Ok(color_map) = serde_json::from_str::<ColorMap>(&color_map_string)
+        {
+            spectrogram_color_map = color_map;
+        }
+
+        if let Some(resume_string) = storage.get_string(RESUME_ON_STARTUP_STORAGE_KEY)
+            && let Ok(resume) = serde_json::from_str(&resume_string)
+        {
+            resume_on_startup = resume;
+        }
+
+        if let Some(track_string) = storage.get_string(RESUME_TRACK_STORAGE_KEY)
+            && let Ok(track) = serde_json::from_str(&track_string)
+        {
+            resume_track_path = track;
+        }
+
+        if let Some(position_string) = storage.get_string(RESUME_POSITION_STORAGE_KEY)
+            && let Ok(position) = serde_json::from_str::<f32>(&position_string)
+        {
+            resume_position = position.max(0.0);
+        }
+
+        let queue = storage
+            .get_string(QUEUE_STORAGE_KEY)
+            .and_then(|s| serde_json::from_str::<Vec<PathBuf>>(&s).ok())
+            .unwrap_or_default();
+        let history = storage
+            .get_string(HISTORY_STORAGE_KEY)
+            .and_then(|s| serde_json::from_str::<Vec<PathBuf>>(&s).ok())
+            .unwrap_or_default();
+        if !queue.is_empty() || !history.is_empty() {
+            saved_queue = Some(SavedQueue { queue, history });
+        }
+
+        if let Some(counts_string) = storage.get_string(PLAY_COUNTS_STORAGE_KEY)
+            && let Ok(counts) = serde_json::from_str(&counts_string)
+        {
+            play_counts = counts;
+        }
+
+        if let Some(threshold_string) = storage.get_string(BOOKMARK_THRESHOLD_STORAGE_KEY)
+            && let Ok(threshold) = serde_json::from_str::<f32>(&threshold_string)
+        {
+            bookmark_threshold = threshold.clamp(0.0, MAX_BOOKMARK_THRESHOLD);
+        }
+
+        if let Some(bookmarks_string) = storage.get_string(BOOKMARKS_STORAGE_KEY)
+            && let Ok(positions) = serde_json::from_str(&bookmarks_string)
+        {
+            bookmark_positions = positions;
+        }
+
+        if let Some(enabled_string) = storage.get_string(EQUALIZER_ENABLED_STORAGE_KEY)
+            && let Ok(enabled) = serde_json::from_str(&enabled_string)
+        {
+            equalizer_enabled = enabled;
+        }
+
+        if let Some(gains_string) = storage.get_string(EQUALIZER_GAINS_STORAGE_KEY)
+            && let Ok(gains) = serde_json::from_str::<[f32; BAND_COUNT]>(&gains_string)
+        {
+            equalizer_gains = gains.map(|g| g.clamp(-MAX_GAIN_DB, MAX_GAIN_DB));
+        }
+
+        if let Some(pause_string) = storage.get_string(PAUSE_ON_FOCUS_LOSS_STORAGE_KEY)
+            && let Ok(pause) = serde_json::from_str(&pause_string)
+        {
+            pause_on_focus_loss = pause;
+        }
+
+        if let Some(resume_string) = storage.get_string(RESUME_ON_FOCUS_GAIN_STORAGE_KEY)
+            && let Ok(resume) = serde_json::from_str(&resume_string)
+        {
+            resume_on_focus_gain = resume;
+        }
+
+        if let Some(notifications_string) = storage.get_string(TRACK_NOTIFICATIONS_STORAGE_KEY)
+            && let Ok(notifications) = serde_json::from_str(&notifications_string)
+        {
+            track_notifications = notifications;
+        }
+
+        if let Some(accurate_string) = storage.get_string(ACCURATE_DURATIONS_STORAGE_KEY)
+            && let Ok(accurate) = serde_json::from_str(&accurate_string)
+        {
+            accurate_durations = accurate;
+        }
+
+        if let Some(enabled_string) = storage.get_string(REMOTE_CONTROL_ENABLED_STORAGE_KEY)
+            && let Ok(enabled) = serde_json::from_str(&enabled_string)
+        {
+            remote_control_enabled = enabled;
+        }
+
+        if let Some(port_string) = storage.get_string(REMOTE_CONTROL_PORT_STORAGE_KEY)
+            && let Ok(port) = serde_json::from_str(&port_string)
+        {
+            remote_control_port = port;
+        }
+
+        if let Some(allow_string) = storage.get_string(REMOTE_CONTROL_ALLOW_NETWORK_STORAGE_KEY)
+            && let Ok(allow) = serde_json::from_str(&allow_string)
+        {
+            remote_control_allow_network = allow;
+        }
+
+        if let Some(token_string) = storage.get_string(REMOTE_CONTROL_TOKEN_STORAGE_KEY)
+            && let Ok(token) = serde_json::from_str(&token_string)
+        {
+            remote_control_token = Some(token);
+        }
+    }
+
+    set_rating_email(&rating_email); // Before the library is loaded, which reads the ratings.
+
+    let library_cache_path = ProjectDirs::from("", "", APP_NAME).map(|dirs| dirs.cache_dir().join(LIBRARY_CACHE_FILENAME));
+    let library_watcher = setup_library_watcher(library_cache_path).expect("Failed to initialize library watcher.");
+    if !referenced_files.is_empty() {
+        let command = LibraryWatcherCommand::SetReferencedFiles(referenced_files.clone());
+
+        if let Err(e) = library_watcher.command_sender.send(command) {
+            error!("Failed to start watching referenced files: {e}");
+        }
+    }
+
+    if !scan_exclusions.is_empty() {
+        let command = LibraryWatcherCommand::SetExclusions(scan_exclusions.clone());
+
+        if let Err(e) = library_watcher.command_sender.send(command) {
+            error!("Failed to set scan exclusions: {e}");
+        }
     }
 
-    let library_watcher = setup_library_watcher().expect("Failed to initialize library watcher.");
     if let Some(directory) = &library_directory {
         let command = LibraryWatcherCommand::SetPath(directory.clone());
 
@@ -187,8 +756,55 @@ pub fn init_gem_player(cc: &CreationContext<'_>) -> GemPlayer {
         }
     }
 
+    let mut backend = None;
+
+    let host = default_host();
+    if let Some(device) = host.default_output_device() {
+        let sample_rate = output_settings.get(&device_name(&device)).and_then(|s| s.sample_rate);
+        let backend_result = build_audio_backend_from_device(device, low_latency, sample_rate);
+        match backend_result {
+            Ok(b) => backend = Some(b),
+            Err(e) => error!("Failed to start audio device: {}", e),
+        }
+    }
+
     if let Some(b) = &backend {
-        b.player.set_volume(initial_volume);
+        b.set_volume(initial_volume);
+    }
+
+    let mut player = Player::new(backend);
+    player.rewind_threshold = rewind_threshold;
+    player.seek_step = seek_step;
+    player.crossfade = crossfade;
+    player.pause_fade = pause_fade;
+    player.low_latency = low_latency;
+    player.output_settings = output_settings;
+    player.shuffle_mode = shuffle_mode;
+    player.replay_gain_mode = replay_gain_mode;
+    player.loudness = loudness;
+    player.target_loudness = target_loudness;
+    set_visualizer_style(&mut player.visualizer, visualizer_style);
+    player.visualizer.attack = visualizer_attack;
+    player.visualizer.decay = visualizer_decay;
+    player.visualizer.peak_hold = visualizer_peak_hold;
+    set_meter_enabled(&mut player.visualizer, level_meter_enabled);
+    set_visualizer_enabled(&mut player.visualizer, visualizer_enabled);
+    player.equalizer.enabled = equalizer_enabled;
+    player.equalizer.gains = equalizer_gains;
+    equalizer::apply(&player.equalizer);
+
+    let mut resumed = false;
+    if resume_on_startup && let Some(path) = resume_track_path {
+        let position = Duration::from_secs_f32(resume_position);
+        let result = load_track(&path).and_then(|track| resume_track(&mut player, track, position));
+
+        match result {
+            Ok(()) => {
+                info!("Resumed '{}' at {:?}.", path.display(), position);
+                resumed = true;
+            }
+            Err(e) => warn!("Failed to resume the last played track: {}", e),
+        }
     }
 
     #[cfg(target_os = "macos")]
@@ -198,27 +814,94 @@ pub fn init_gem_player(cc: &CreationContext<'_>) -> GemPlayer {
         (menu, receiver)
     };
 
-    start_logic_wakeup_thread(&cc.egui_ctx);
+    #[cfg(feature = "global-media-keys")]
+    let media_keys = match setup_media_keys() {
+        Ok(keys) => Some(keys),
+        Err(e) => {
+            error!("Failed to set up global media keys: {}", e);
+            None
+        }
+    };
+
+    let idle = start_logic_wakeup_thread(&cc.egui_ctx);
 
-    GemPlayer {
+    let mut gem = GemPlayer {
         ui: UIState {
             current_view: View::Library,
             theme_preference,
+            reduce_animations,
+            zoom,
+            applied_zoom: None,
+            large_rows,
+            show_title_bar_info,
+            show_lyrics,
+            lyrics_line: None,
             search: String::new(),
             library: LibraryViewState {
                 selected_tracks: Vec::new(),
                 cached_library: Vec::new(),
                 cache_dirty: true,
+                search_dirty: false,
+                cached_query: SearchQuery::default(),
+                scroll_anchor: None,
+                restore_scroll: false,
                 sort_by: SortBy::Title,
                 sort_order: SortOrder::Ascending,
+                columns: library_columns,
+                min_rating: 0,
+            },
+            albums: AlbumsViewState {
+                expanded_album: None,
+                cached_albums: Vec::new(),
+                cache_dirty: true,
+                artwork: ThumbnailCache::default(),
+                fetching: HashSet::new(),
+                fetched_artwork: Vec::new(),
             },
             playlists: PlaylistsViewState {
                 selected_playlist_key: None,
+                selected_auto_playlist: None,
                 selected_tracks: Vec::new(),
                 cached_playlist_tracks: Vec::new(),
                 cache_dirty: true,
+                scroll_anchor: None,
+                restore_scroll: false,
+                sort_by: None,
+                sort_order: SortOrder::Ascending,
                 rename_buffer: None,
                 delete_modal_open: false,
+                export_modal_open: false,
+                export_options: ExportOptions::default(),
+                columns: playlist_columns,
+            },
+            queue: QueueViewState {
+                selected_indices: Vec::new(),
+                target_position: 1,
+                columns: queue_columns,
+            },
+            locate_playing: LocatePlayingState {
+                scroll_pending: false,
+                flash_started: None,
+            },
+            ambient: AmbientState {
+                enabled: false,
+                last_activity: 0.0,
+            },
+            type_to_jump: TypeToJumpState {
+                prefix: String::new(),
+                last_typed: 0.0,
+            },
+            duplicates: DuplicatesState {
+                window_open: false,
+                scan_receiver: None,
+                groups: Vec::new(),
+            },
+            itunes_import: ItunesImportState {
+                window_open: false,
+                receiver: None,
+                progress: None,
+                summary: None,
+                error: None,
             },
             toasts: Toasts::default().with_anchor(egui_notify::Anchor::BottomRight).with_shadow(Shadow {
                 offset: [0, 0],
@@ -227,44 +910,78 @@ pub fn init_gem_player(cc: &CreationContext<'_>) -> GemPlayer {
                 color: Color32::BLACK,
             }),
             marquee: Marquee::new(),
+            spectrogram: Spectrogram::new(spectrogram_floor_db, spectrogram_ceiling_db, spectrogram_color_map),
+            level_meter: LevelMeter::new(),
             volume_popup_is_open: false,
+            exclusion_buffer: String::new(),
         },
 
         library: Vec::new(),
         playlists: Vec::new(),
 
         library_directory,
+        referenced_files,
+        reference_dropped_files,
+        scan_exclusions,
+        rating_email,
         folder_picker_receiver: None,
+        artwork_picker: None,
+        artwork_fetcher: setup_artwork_fetcher(cc.egui_ctx.clone()),
+        playlist_entry_locator: None,
+        library_exporter: None,
+        playlist_exporter: None,
+        loudness_scanner: None,
+        duration_scanner: None,
         library_watcher,
+        library_progress: None,
+        instance_listener: primary_instance.map(|primary| listen_for_instances(primary, cc.egui_ctx.clone())),
+        file_opener: None,
 
         commands: Vec::new(),
 
-        player: Player {
-            history: Vec::new(),
-            playing: None,
-            queue: Vec::new(),
-
-            repeat: false,
-            shuffle: None,
-            muted: false,
-            volume_before_mute: None,
-            paused_before_scrubbing: None,
-
-            backend,
-            visualizer: VisualizerState {
-                command_sender: visualizer_command_sender,
-                bands_receiver,
-                display_bands: vec![0.0; CENTER_FREQUENCIES.len()],
-            },
-        },
+        player,
 
         nosleep_manager: NoSleepManager::new(),
+        idle,
+
+        focus_pause: FocusPause::new(pause_on_focus_loss, resume_on_focus_gain),
+        remote_control: RemoteControl::new(
+            remote_control_enabled,
+            remote_control_port,
+            remote_control_allow_network,
+            remote_control_token.unwrap_or_else(generate_remote_token),
+        ),
+        resume_on_startup,
+        saved_queue,
+        track_notifications,
+        accurate_durations,
+        lyrics: None,
+        chapters: Vec::new(),
+        play_counts,
+        bookmarks: Bookmarks::new(bookmark_threshold, bookmark_positions),
 
         os_media_controls: OSMediaControlsState::Pending,
 
+        #[cfg(feature = "global-media-keys")]
+        media_keys,
+
         #[cfg(target_os = "macos")]
         menubar: MenuBar { menu, menu_receiver },
+    };
+
+    if resumed {
+        on_track_change(&cc.egui_ctx, &mut gem);
+    }
+
+    if !open_paths.is_empty() {
+        gem.commands.push(GemCommand::OpenFiles(open_paths));
+    }
+
+    if gem.remote_control.enabled {
+        restart_remote_control(&cc.egui_ctx, &mut gem.remote_control);
     }
+
+    gem
 }
 
 impl App for GemPlayer {
@@ -272,75 +989,278 @@ impl App for GemPlayer {
         Rgba::TRANSPARENT.to_array() // Make sure we don't paint anything behind the rounded corners
     }
 
-    // This is set because egui was persisting the state of the library table scroll position across runs.
-    fn persist_egui_memory(&self) -> bool {
-        false
-    }
+    // This is set because egui was persisting the state of the library table scroll position across runs.
+    fn persist_egui_memory(&self) -> bool {
+        false
+    }
+
+    fn save(&mut self, storage: &mut dyn Storage) {
+        if let Some(library_directory) = &self.library_directory {
+            storage.set_string(LIBRARY_DIRECTORY_STORAGE_KEY, library_directory.to_string_lossy().to_string());
+        }
+
+        let referenced_files_json_string = serde_json::to_string(&self.referenced_files).unwrap();
+        storage.set_string(REFERENCED_FILES_STORAGE_KEY, referenced_files_json_string);
+
+        let reference_json_string = serde_json::to_string(&self.reference_dropped_files).unwrap();
+        storage.set_string(REFERENCE_DROPPED_FILES_STORAGE_KEY, reference_json_string);
+
+        let scan_exclusions_json_string = serde_json::to_string(&self.scan_exclusions).unwrap();
+        storage.set_string(SCAN_EXCLUSIONS_STORAGE_KEY, scan_exclusions_json_string);
+
+        let rating_email_json_string = serde_json::to_string(&self.rating_email).unwrap();
+        storage.set_string(RATING_EMAIL_STORAGE_KEY, rating_email_json_string);
+
+        let theme_json_string = serde_json::to_string(&self.ui.theme_preference).unwrap();
+        storage.set_string(THEME_STORAGE_KEY, theme_json_string);
+
+        let reduce_animations_json_string = serde_json::to_string(&self.ui.reduce_animations).unwrap();
+        storage.set_string(REDUCE_ANIMATIONS_STORAGE_KEY, reduce_animations_json_string);
+
+        let zoom_json_string = serde_json::to_string(&self.ui.zoom).unwrap();
+        storage.set_string(ZOOM_STORAGE_KEY, zoom_json_string);
+
+        let large_rows_json_string = serde_json::to_string(&self.ui.large_rows).unwrap();
+        storage.set_string(LARGE_ROWS_STORAGE_KEY, large_rows_json_string);
+
+        let show_title_bar_info_json_string = serde_json::to_string(&self.ui.show_title_bar_info).unwrap();
+        storage.set_string(SHOW_TITLE_BAR_INFO_STORAGE_KEY, show_title_bar_info_json_string);
+
+        let show_lyrics_json_string = serde_json::to_string(&self.ui.show_lyrics).unwrap();
+        storage.set_string(SHOW_LYRICS_STORAGE_KEY, show_lyrics_json_string);
+
+        let library_columns_json_string = serde_json::to_string(&self.ui.library.columns.to_saved()).unwrap();
+        storage.set_string(LIBRARY_COLUMNS_STORAGE_KEY, library_columns_json_string);
+
+        let playlist_columns_json_string = serde_json::to_string(&self.ui.playlists.columns.to_saved()).unwrap();
+        storage.set_string(PLAYLIST_COLUMNS_STORAGE_KEY, playlist_columns_json_string);
+
+        let queue_columns_json_string = serde_json::to_string(&self.ui.queue.columns.to_saved()).unwrap();
+        storage.set_string(QUEUE_COLUMNS_STORAGE_KEY, queue_columns_json_string);
+
+        if let Some(backend) = &self.player.backend {
+            let volume_json_string = serde_json::to_string(&backend.volume()).unwrap();
+            storage.set_string(VOLUME_STORAGE_KEY, volume_json_string);
+        }
+
+        let rewind_threshold_json_string = serde_json::to_string(&self.player.rewind_threshold).unwrap();
+        storage.set_string(REWIND_THRESHOLD_STORAGE_KEY, rewind_threshold_json_string);
+
+        let seek_step_json_string = serde_json::to_string(&self.player.seek_step).unwrap();
+        storage.set_string(SEEK_STEP_STORAGE_KEY, seek_step_json_string);
+
+        let crossfade_json_string = serde_json::to_string(&self.player.crossfade).unwrap();
+        storage.set_string(CROSSFADE_STORAGE_KEY, crossfade_json_string);
+
+        let pause_fade_json_string = serde_json::to_string(&self.player.pause_fade).unwrap();
+        storage.set_string(PAUSE_FADE_STORAGE_KEY, pause_fade_json_string);
+
+        let low_latency_json_string = serde_json::to_string(&self.player.low_latency).unwrap();
+        storage.set_string(LOW_LATENCY_STORAGE_KEY, low_latency_json_string);
+
+        let saved_output_settings: HashMap<&String, (Option<u32>, ResamplerQuality)> = self
+            .player
+            .output_settings
+            .iter()
+            .map(|(device_name, settings)| (device_name, (settings.sample_rate, settings.resampler)))
+            .collect();
+        let output_settings_json_string = serde_json::to_string(&saved_output_settings).unwrap();
+        storage.set_string(OUTPUT_SETTINGS_STORAGE_KEY, output_settings_json_string);
+
+        let shuffle_mode_json_string = serde_json::to_string(&self.player.shuffle_mode).unwrap();
+        storage.set_string(SHUFFLE_MODE_STORAGE_KEY, shuffle_mode_json_string);
+
+        let replay_gain_mode_json_string = serde_json::to_string(&self.player.replay_gain_mode).unwrap();
+        storage.set_string(REPLAY_GAIN_MODE_STORAGE_KEY, replay_gain_mode_json_string);
+
+        let loudness_json_string = self.player.loudness.to_json().to_string();
+        storage.set_string(LOUDNESS_STORAGE_KEY, loudness_json_string);
+
+        let target_loudness_json_string = serde_json::to_string(&self.player.target_loudness).unwrap();
+        storage.set_string(TARGET_LOUDNESS_STORAGE_KEY, target_loudness_json_string);
+
+        let visualizer_style_json_string = serde_json::to_string(&self.player.visualizer.style).unwrap();
+        storage.set_string(VISUALIZER_STYLE_STORAGE_KEY, visualizer_style_json_string);
+
+        let visualizer_attack_json_string = serde_json::to_string(&self.player.visualizer.attack).unwrap();
+        storage.set_string(VISUALIZER_ATTACK_STORAGE_KEY, visualizer_attack_json_string);
+
+        let visualizer_decay_json_string = serde_json::to_string(&self.player.visualizer.decay).unwrap();
+        storage.set_string(VISUALIZER_DECAY_STORAGE_KEY, visualizer_decay_json_string);
+
+        let visualizer_peak_hold_json_string = serde_json::to_string(&self.player.visualizer.peak_hold).unwrap();
+        storage.set_string(VISUALIZER_PEAK_HOLD_STORAGE_KEY, visualizer_peak_hold_json_string);
+
+        let level_meter_json_string = serde_json::to_string(&self.player.visualizer.meter_enabled).unwrap();
+        storage.set_string(LEVEL_METER_STORAGE_KEY, level_meter_json_string);
+
+        let visualizer_enabled_json_string = serde_json::to_string(&self.player.visualizer.pipeline.is_some()).unwrap();
+        storage.set_string(VISUALIZER_ENABLED_STORAGE_KEY, visualizer_enabled_json_string);
+
+        let spectrogram_floor_json_string = serde_json::to_string(&self.ui.spectrogram.floor_db).unwrap();
+        storage.set_string(SPECTROGRAM_FLOOR_STORAGE_KEY, spectrogram_floor_json_string);
+
+        let spectrogram_ceiling_json_string = serde_json::to_string(&self.ui.spectrogram.ceiling_db).unwrap();
+        storage.set_string(SPECTROGRAM_CEILING_STORAGE_KEY, spectrogram_ceiling_json_string);
+
+        let spectrogram_color_map_json_string = serde_json::to_string(&self.ui.spectrogram.color_map).unwrap();
+        storage.set_string(SPECTROGRAM_COLOR_MAP_STORAGE_KEY, spectrogram_color_map_json_string);
+
+        let resume_on_startup_json_string = serde_json::to_string(&self.resume_on_startup).unwrap();
+        storage.set_string(RESUME_ON_STARTUP_STORAGE_KEY, resume_on_startup_json_string);
+
+        let resume_track_path = self.player.playing.as_ref().map(|t| &t.path);
+        let resume_track_json_string = serde_json::to_string(&resume_track_path).unwrap();
+        storage.set_string(RESUME_TRACK_STORAGE_KEY, resume_track_json_string);
+
+        let resume_position = get_position(&self.player).unwrap_or_default().as_secs_f32();
+        let resume_position_json_string = serde_json::to_string(&resume_position).unwrap();
+        storage.set_string(RESUME_POSITION_STORAGE_KEY, resume_position_json_string);
+
+        // If the library never loaded, the saved queue was not restored, so we keep it for next time.
+        let (queue, history): (Vec<&PathBuf>, Vec<&PathBuf>) = match &self.saved_queue {
+            Some(saved) => (saved.queue.iter().collect(), saved.history.iter().collect()),
+            None => (
+                self.player.queue.iter().map(|t| &t.path).collect(),
+                self.player.history.iter().map(|t| &t.path).collect(),
+            ),
+        };
+
+        let queue_json_string = serde_json::to_string(&queue).unwrap();
+        storage.set_string(QUEUE_STORAGE_KEY, queue_json_string);
+
+        let history_json_string = serde_json::to_string(&history).unwrap();
+        storage.set_string(HISTORY_STORAGE_KEY, history_json_string);
+
+        let play_counts_json_string = serde_json::to_string(&self.play_counts).unwrap();
+        storage.set_string(PLAY_COUNTS_STORAGE_KEY, play_counts_json_string);
+
+        let bookmark_threshold_json_string = serde_json::to_string(&self.bookmarks.threshold).unwrap();
+        storage.set_string(BOOKMARK_THRESHOLD_STORAGE_KEY, bookmark_threshold_json_string);
+
+        let bookmarks_json_string = serde_json::to_string(&self.bookmarks.positions).unwrap();
+        storage.set_string(BOOKMARKS_STORAGE_KEY, bookmarks_json_string);
+
+        let equalizer_enabled_json_string = serde_json::to_string(&self.player.equalizer.enabled).unwrap();
+        storage.set_string(EQUALIZER_ENABLED_STORAGE_KEY, equalizer_enabled_json_string);
+
+        let equalizer_gains_json_string = serde_json::to_string(&self.player.equalizer.gains).unwrap();
+        storage.set_string(EQUALIZER_GAINS_STORAGE_KEY, equalizer_gains_json_string);
+
+        let pause_json_string = serde_json::to_string(&self.focus_pause.pause_on_focus_loss).unwrap();
+        storage.set_string(PAUSE_ON_FOCUS_LOSS_STORAGE_KEY, pause_json_string);
+
+        let resume_json_string = serde_json::to_string(&self.focus_pause.resume_on_focus_gain).unwrap();
+        storage.set_string(RESUME_ON_FOCUS_GAIN_STORAGE_KEY, resume_json_string);
+
+        let track_notifications_json_string = serde_json::to_string(&self.track_notifications).unwrap();
+        storage.set_string(TRACK_NOTIFICATIONS_STORAGE_KEY, track_notifications_json_string);
+
+        let accurate_durations_json_string = serde_json::to_string(&self.accurate_durations).unwrap();
+        storage.set_string(ACCURATE_DURATIONS_STORAGE_KEY, accurate_durations_json_string);
+
+        let remote_control_enabled_json_string = serde_json::to_string(&self.remote_control.enabled).unwrap();
+        storage.set_string(REMOTE_CONTROL_ENABLED_STORAGE_KEY, remote_control_enabled_json_string);
 
-    fn save(&mut self, storage: &mut dyn Storage) {
-        if let Some(library_directory) = &self.library_directory {
-            storage.set_string(LIBRARY_DIRECTORY_STORAGE_KEY, library_directory.to_string_lossy().to_string());
-        }
+        let remote_control_port_json_string = serde_json::to_string(&self.remote_control.port).unwrap();
+        storage.set_string(REMOTE_CONTROL_PORT_STORAGE_KEY, remote_control_port_json_string);
 
-        let theme_json_string = serde_json::to_string(&self.ui.theme_preference).unwrap();
-        storage.set_string(THEME_STORAGE_KEY, theme_json_string);
+        let remote_control_allow_network_json_string = serde_json::to_string(&self.remote_control.allow_network).unwrap();
+        storage.set_string(REMOTE_CONTROL_ALLOW_NETWORK_STORAGE_KEY, remote_control_allow_network_json_string);
 
-        if let Some(backend) = &self.player.backend {
-            let volume_json_string = serde_json::to_string(&backend.player.volume()).unwrap();
-            storage.set_string(VOLUME_STORAGE_KEY, volume_json_string);
-        }
+        let remote_control_token_json_string = serde_json::to_string(&self.remote_control.token).unwrap();
+        storage.set_string(REMOTE_CONTROL_TOKEN_STORAGE_KEY, remote_control_token_json_string);
     }
 
     fn logic(&mut self, ctx: &Context, frame: &mut Frame) {
         poll_file_drops(ctx, self);
         poll_library_folder_picker(self);
+        poll_artwork_picker(ctx, self);
+        poll_artwork_fetcher(self);
+        poll_playlist_entry_locator(self);
+        poll_playlist_exporter(self);
+        poll_library_exporter(self);
+        poll_loudness_scanner(self);
+        poll_duration_scanner(self);
         poll_library_watcher(self);
+        poll_instance_hand_offs(self);
+        poll_file_opener(ctx, self);
+        poll_remote_control(self);
         poll_media_events(self);
 
+        #[cfg(feature = "global-media-keys")]
+        poll_media_keys(self);
+
         #[cfg(target_os = "macos")]
         poll_macos_menu_events(self);
 
         maybe_initialize_os_media_controls(self, frame);
+        update_playing_bookmark(self);
         check_for_next_track(ctx, self);
+        poll_window_focus(ctx, self);
+        poll_idle(ctx, self);
 
+        poll_keyboard_shortcuts(ctx, self);
         poll_commands(ctx, self);
     }
 
     fn ui(&mut self, ui: &mut Ui, _frame: &mut Frame) {
         apply_theme(ui, self.ui.theme_preference);
+        apply_zoom(ui, &mut self.ui.zoom, &mut self.ui.applied_zoom);
 
         gem_player_ui(ui, self);
 
         self.ui.toasts.show(ui);
 
-        // Set a minimum refresh rate for the app to keep the ui elements updated.
-        ui.request_repaint_after(Duration::from_millis(33)); // ~30 fps
+        // Set a minimum refresh rate for the app to keep the ui elements updated. Only the visualizer needs it to be smooth,
+        // and not while the window is in the background or animations are reduced, to save battery.
+        let is_idle = self.idle.load(Ordering::Relaxed);
+        let refresh_interval = if self.player.visualizer.pipeline.is_some() && !is_idle {
+            Duration::from_millis(33) // ~30 fps
+        } else {
+            Duration::from_millis(250)
+        };
+        ui.request_repaint_after(refresh_interval);
     }
 
     fn on_exit(&mut self) {
         stop(&mut self.player);
 
-        let _ = self.player.visualizer.command_sender.send(visualizer::VisualizerCommand::Shutdown);
+        send_visualizer_command(&self.player.visualizer, visualizer::VisualizerCommand::Shutdown);
 
         let _ = self.library_watcher.command_sender.send(LibraryWatcherCommand::Shutdown);
 
+        stop_remote_control(&mut self.remote_control);
+
         self.nosleep_manager.disable();
     }
 }
 
 // Continuously wake the egui event loop so `logic()` continues running even
 // while the window is minimized or hidden. This keeps media playback,
-// OS media controls, and background polling responsive.
-pub fn start_logic_wakeup_thread(ctx: &Context) {
+// OS media controls, and background polling responsive. The wakeups are less
+// frequent while the returned flag is set, to save battery.
+pub fn start_logic_wakeup_thread(ctx: &Context) -> Arc<AtomicBool> {
     let ctx = ctx.clone();
+    let idle = Arc::new(AtomicBool::new(false));
+    let thread_idle = Arc::clone(&idle);
 
     thread::spawn(move || {
         loop {
             ctx.request_repaint();
 
-            thread::sleep(Duration::from_millis(100));
+            let interval = if thread_idle.load(Ordering::Relaxed) { 250 } else { 100 };
+            thread::sleep(Duration::from_millis(interval));
         }
     });
+
+    idle
+}
+
+// Checked in logic() rather than ui(), since the ui isn't drawn while the window is minimized.
+fn poll_idle(ctx: &Context, gem: &mut GemPlayer) {
+    let is_focused = ctx.input(|i| i.viewport().focused).unwrap_or(true);
+    gem.idle.store(!is_focused || gem.ui.reduce_animations, Ordering::Relaxed);
 }
 
 fn poll_commands(ctx: &Context, gem: &mut GemPlayer) {
@@ -351,12 +1271,65 @@ fn poll_commands(ctx: &Context, gem: &mut GemPlayer) {
     }
 }
 
+fn poll_keyboard_shortcuts(ctx: &Context, gem: &mut GemPlayer) {
+    if ctx.input_mut(|i| i.consume_shortcut(&COPY_NOW_PLAYING_SHORTCUT)) {
+        gem.commands.push(GemCommand::CopyNowPlaying);
+    }
+
+    if ctx.input_mut(|i| i.consume_shortcut(&SHUFFLE_LIBRARY_SHORTCUT)) {
+        gem.commands.push(GemCommand::ShuffleLibrary);
+    }
+
+    if ctx.input_mut(|i| i.consume_shortcut(&LOCATE_PLAYING_SHORTCUT)) {
+        gem.commands.push(GemCommand::LocatePlayingTrack);
+    }
+
+    // Text fields have their own select all.
+    let text_has_focus = ctx.memory(|m| m.focused().is_some());
+    if !text_has_focus && ctx.input_mut(|i| i.consume_shortcut(&SELECT_ALL_SHORTCUT)) {
+        gem.commands.push(GemCommand::SelectAllTracks);
+    }
+
+    // Shift and the arrow keys also extend the selection in text fields.
+    let seek_step = Duration::from_secs(gem.player.seek_step);
+    if gem.player.playing.is_some() && !text_has_focus {
+        if ctx.input_mut(|i| i.consume_shortcut(&SEEK_BACKWARD_SHORTCUT)) {
+            gem.commands.push(GemCommand::SeekBackward(seek_step));
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&SEEK_FORWARD_SHORTCUT)) {
+            gem.commands.push(GemCommand::SeekForward(seek_step));
+        }
+    }
+
+    let escape_pressed = || ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape));
+    if ctx.input_mut(|i| i.consume_shortcut(&AMBIENT_MODE_SHORTCUT)) || (gem.ui.ambient.enabled && escape_pressed()) {
+        gem.commands.push(GemCommand::ToggleAmbientMode);
+    }
+}
+
 fn maybe_initialize_os_media_controls(gem: &mut GemPlayer, frame: &mut Frame) {
     if matches!(gem.os_media_controls, OSMediaControlsState::Pending)
         && let Ok(handle) = frame.window_handle()
     {
         gem.os_media_controls = match setup_os_media_controls(handle) {
-            Ok(mc) => OSMediaControlsState::Initialized(mc),
+            Ok(mut mc) => {
+                // A track may have been resumed before the controls were ready.
+                if let Err(e) = update_metadata(&mut mc.controls, &gem.player) {
+                    error!("Failed to set OS media metadata: {e}");
+                }
+
+                if let Err(e) = update_playback(&mut mc.controls, &gem.player) {
+                    error!("Failed to set OS media playback state: {e}");
+                }
+
+                #[cfg(target_os = "linux")]
+                if let Err(e) = os_media_controls::update_volume(&mut mc.controls, &gem.player) {
+                    error!("Failed to set OS media volume: {e}");
+                }
+
+                OSMediaControlsState::Initialized(mc)
+            }
             Err(e) => {
                 error!("Failed to initialize media controls: {:?}", e);
                 OSMediaControlsState::Failed
@@ -381,13 +1354,25 @@ fn poll_library_watcher(gem: &mut GemPlayer) {
     let mut latest = None;
 
     for update in gem.library_watcher.update_receiver.try_iter() {
-        latest = Some(update);
+        match update {
+            LibraryUpdate::Progress(progress) => gem.library_progress = Some(progress),
+            update => {
+                gem.library_progress = None;
+                latest = Some(update);
+            }
+        }
     }
 
     if let Some(update) = latest {
         match update {
-            Some((new_library, new_playlists)) => on_library_reloaded(gem, new_library, new_playlists),
-            None => {
+            LibraryUpdate::Progress(_) => {}
+            LibraryUpdate::Cached(cached_library) => {
+                // The playlists are loaded along with the scan, so they are left as they are until then.
+                let playlists = take(&mut gem.playlists);
+                on_library_reloaded(gem, cached_library, playlists);
+            }
+            LibraryUpdate::Loaded((new_library, new_playlists)) => on_library_reloaded(gem, new_library, new_playlists),
+            LibraryUpdate::Failed => {
                 let message = "Failed to load library folder.";
                 error!("{}", message);
                 gem.ui.toasts.error(message);
@@ -431,6 +1416,344 @@ fn poll_library_folder_picker(gem: &mut GemPlayer) {
     }
 }
 
+fn poll_artwork_picker(ctx: &Context, gem: &mut GemPlayer) {
+    let Some(picker) = &gem.artwork_picker else {
+        return;
+    };
+
+    match picker.receiver.try_recv() {
+        Ok(maybe_image) => {
+            let track_keys = gem.artwork_picker.take().map(|p| p.track_keys).unwrap_or_default();
+
+            let Some(image_path) = maybe_image else {
+                info!("No image selected");
+                return;
+            };
+
+            info!("Selected artwork: {:?}", image_path);
+            set_artwork(ctx, gem, &track_keys, &image_path);
+        }
+        Err(TryRecvError::Empty) => {} // The picker is still open.
+        Err(TryRecvError::Disconnected) => {
+            error!("Artwork picker channel disconnected unexpectedly.");
+            gem.artwork_picker = None;
+        }
+    }
+}
+
+fn poll_artwork_fetcher(gem: &mut GemPlayer) {
+    while let Ok(result) = gem.artwork_fetcher.result_receiver.try_recv() {
+        let album = (result.album.clone(), result.album_artist);
+        gem.ui.albums.fetching.remove(&album);
+
+        match result.image {
+            Ok(Some(image_data)) => gem.ui.albums.fetched_artwork.push(FetchedArtwork {
+                album,
+                image: Bytes::from(image_data),
+            }),
+            Ok(None) => {
+                gem.ui.toasts.info(format!("No artwork was found for '{}'.", result.album));
+            }
+            Err(e) => {
+                error!("Failed to fetch artwork for '{}': {:#}", result.album, e);
+                gem.ui.toasts.error(format!("Failed to fetch artwork for '{}'.", result.album));
+            }
+        }
+    }
+}
+
+fn poll_instance_hand_offs(gem: &mut GemPlayer) {
+    let Some(listener) = &gem.instance_listener else {
+        return;
+    };
+
+    while let Ok(paths) = listener.receiver.try_recv() {
+        debug!("Another instance was launched with {:?}", paths);
+
+        if !paths.is_empty() {
+            gem.commands.push(GemCommand::OpenFiles(paths));
+        }
+
+        gem.commands.push(GemCommand::RaiseWindow);
+    }
+}
+
+fn poll_file_opener(ctx: &Context, gem: &mut GemPlayer) {
+    let Some(opener) = &gem.file_opener else {
+        return;
+    };
+
+    match opener.receiver.try_recv() {
+        Ok(opened) => {
+            gem.file_opener = None;
+
+            let tracks = opened_tracks(gem, opened);
+            if tracks.is_empty() {
+                gem.ui.toasts.info("None of the opened files could be played.");
+                return;
+            }
+
+            info!("Playing {} opened track(s).", tracks.len());
+            replace_queue(&mut gem.player, &tracks, 0);
+
+            maybe_play_next(ctx, gem);
+        }
+        Err(TryRecvError::Empty) => {} // Still reading the files.
+        Err(TryRecvError::Disconnected) => {
+            error!("File opener channel disconnected unexpectedly.");
+            gem.file_opener = None;
+        }
+    }
+}
+
+fn poll_playlist_entry_locator(gem: &mut GemPlayer) {
+    let Some(locator) = &gem.playlist_entry_locator else {
+        return;
+    };
+
+    match locator.receiver.try_recv() {
+        Ok(maybe_file) => {
+            let Some(locator) = gem.playlist_entry_locator.take() else {
+                return;
+            };
+
+            let Some(file_path) = maybe_file else {
+                info!("No file selected");
+                return;
+            };
+
+            info!("Located missing playlist entry {:?} at {:?}", locator.missing_path, file_path);
+
+            // The playlist may have been deleted or reloaded while the dialog was open.
+            let Some(playlist) = gem.playlists.iter_mut().find(|p| p.file_path == locator.playlist_key) else {
+                let message = "The playlist is no longer in the library, so the entry wasn't relinked.";
+                warn!("{}", message);
+                gem.ui.toasts.warning(message);
+                return;
+            };
+
+            if !playlist.missing_entries.iter().any(|m| m.entry.path == locator.missing_path) {
+                let message = "The entry is no longer missing from the playlist, so it wasn't relinked.";
+                warn!("{}", message);
+                gem.ui.toasts.warning(message);
+                return;
+            }
+
+            let result = load_track(&file_path).and_then(|track| relink_missing_entry(playlist, &locator.missing_path, track));
+
+            if let Err(e) = result {
+                error!("{:#}", e);
+                gem.ui.toasts.error(format!("Failed to relink playlist entry: {:#}", e));
+                return;
+            }
+
+            gem.ui.playlists.cache_dirty = true;
+            gem.ui.toasts.success("Relinked playlist entry.");
+        }
+        Err(TryRecvError::Empty) => {} // The dialog is still open.
+        Err(TryRecvError::Disconnected) => {
+            error!("Playlist entry locator channel disconnected unexpectedly.");
+            gem.playlist_entry_locator = None;
+        }
+    }
+}
+
+fn poll_playlist_exporter(gem: &mut GemPlayer) {
+    let Some(exporter) = &gem.playlist_exporter else {
+        return;
+    };
+
+    match exporter.receiver.try_recv() {
+        Ok(maybe_path) => {
+            let Some(exporter) = gem.playlist_exporter.take() else {
+                return;
+            };
+
+            let Some(mut export_path) = maybe_path else {
+                info!("No export path selected");
+                return;
+            };
+
+            // Without a known extension there is no format to go by, so m3u is used.
+            if PlaylistFormat::from_path(&export_path).is_none() {
+                export_path.set_extension(PlaylistFormat::M3u.extension());
+            }
+
+            // The playlist may have been deleted or reloaded while the dialog was open.
+            let Some(playlist) = gem.playlists.iter().find(|p| p.file_path == exporter.playlist_key) else {
+                let message = "The playlist is no longer in the library, so it wasn't exported.";
+                warn!("{}", message);
+                gem.ui.toasts.warning(message);
+                return;
+            };
+
+            if let Err(e) = export(playlist, &export_path, &exporter.options) {
+                error!("Failed to export playlist: {:#}", e);
+                gem.ui.toasts.error(format!("Failed to export playlist: {:#}", e));
+                return;
+            }
+
+            let message = format!("Exported playlist '{}' to {}.", playlist.name, export_path.display());
+            info!("{}", message);
+            gem.ui.toasts.success(message);
+        }
+        Err(TryRecvError::Empty) => {} // The dialog is still open.
+        Err(TryRecvError::Disconnected) => {
+            error!("Playlist exporter channel disconnected unexpectedly.");
+            gem.playlist_exporter = None;
+        }
+    }
+}
+
+fn poll_library_exporter(gem: &mut GemPlayer) {
+    let Some(exporter) = &mut gem.library_exporter else {
+        return;
+    };
+
+    loop {
+        match exporter.receiver.try_recv() {
+            Ok(LibraryExportMessage::Progress { done, total }) => exporter.progress = Some((done, total)),
+            Ok(LibraryExportMessage::Finished(result)) => {
+                gem.library_exporter = None;
+
+                match result {
+                    Ok(path) => {
+                        let message = format!("Exported the library to {}.", path.display());
+                        info!("{}", message);
+                        gem.ui.toasts.success(message);
+                    }
+                    Err(e) => {
+                        error!("Failed to export the library: {:#}", e);
+                        gem.ui.toasts.error(format!("Failed to export the library: {:#}", e));
+                    }
+                }
+                return;
+            }
+            Ok(LibraryExportMessage::Cancelled) => {
+                info!("No export path selected");
+                gem.library_exporter = None;
+                return;
+            }
+            Err(TryRecvError::Empty) => return, // Still exporting.
+            Err(TryRecvError::Disconnected) => {
+                error!("Library exporter channel disconnected unexpectedly.");
+                gem.library_exporter = None;
+                return;
+            }
+        }
+    }
+}
+
+fn poll_loudness_scanner(gem: &mut GemPlayer) {
+    let Some(scanner) = &mut gem.loudness_scanner else {
+        return;
+    };
+
+    loop {
+        match scanner.receiver.try_recv() {
+            Ok(message) => {
+                scanner.progress.0 += 1;
+
+                match message {
+                    LoudnessScanMessage::Measured { path, modified, loudness } => gem.player.loudness.insert(path, modified, loudness),
+                    LoudnessScanMessage::Failed { path, modified } => gem.player.loudness.insert_failure(path, modified),
+                }
+            }
+            Err(TryRecvError::Empty) => return, // Still measuring.
+            Err(TryRecvError::Disconnected) => {
+                // The scanner thread exits once every track is measured.
+                info!("Finished measuring loudness.");
+                gem.loudness_scanner = None;
+                return;
+            }
+        }
+    }
+}
+
+fn poll_duration_scanner(gem: &mut GemPlayer) {
+    let Some(scanner) = &mut gem.duration_scanner else {
+        return;
+    };
+
+    loop {
+        match scanner.receiver.try_recv() {
+            Ok(message) => {
+                scanner.progress.0 += 1;
+
+                // Tracks that can't be measured keep the duration from their header, but are marked as measured so that
+                // they aren't tried again on every reload.
+                match message {
+                    DurationScanMessage::Measured { path, duration } => scanner.measured.insert(path, duration),
+                    DurationScanMessage::Failed { path, header_duration } => scanner.measured.insert(path, header_duration),
+                };
+            }
+            Err(TryRecvError::Empty) => break, // Still measuring.
+            Err(TryRecvError::Disconnected) => {
+                // The scanner thread exits once every track is measured.
+                info!("Finished measuring durations.");
+                stop_duration_scan(gem);
+                return;
+            }
+        }
+    }
+
+    if scanner.last_applied.elapsed() >= APPLY_INTERVAL {
+        apply_measured_durations(gem);
+    }
+}
+
+fn set_artwork(ctx: &Context, gem: &mut GemPlayer, track_keys: &[PathBuf], image_path: &Path) {
+    let image_data = match read(image_path) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Failed to read image '{}': {}", image_path.display(), e);
+            gem.ui.toasts.error("Failed to read the image.");
+            return;
+        }
+    };
+
+    embed_artwork_into_tracks(ctx, gem, track_keys, &image_data);
+}
+
+pub fn embed_artwork_into_tracks(ctx: &Context, gem: &mut GemPlayer, track_keys: &[PathBuf], image_data: &[u8]) {
+    // Cue tracks share an audio file, so each file only needs to be written once.
+    let mut written_files = HashSet::new();
+    let mut failures = 0;
+
+    for track_key in track_keys {
+        let Some(track) = gem.library.iter().find(|t| &t.path == track_key) else {
+            warn!("Track {} is not in the library.", track_key.display());
+            continue;
+        };
+
+        if !written_files.insert(track.audio_path().to_path_buf()) {
+            continue;
+        }
+
+        if let Err(e) = embed_artwork(track, image_data) {
+            error!("{:#}", e);
+            failures += 1;
+        }
+    }
+
+    if failures > 0 {
+        gem.ui.toasts.error(format!("Failed to set the artwork of {} file(s).", failures));
+    } else {
+        gem.ui.toasts.success("Artwork updated.");
+    }
+
+    // The albums view and the control panel both show artwork that may have just changed.
+    gem.ui.albums.artwork.clear();
+    ctx.forget_all_images();
+
+    if let Some(track) = &gem.player.playing
+        && written_files.contains(track.audio_path())
+        && let Err(e) = cache_track_artwork(track)
+    {
+        error!("Failed to cache artwork: {e}");
+    }
+}
+
 fn poll_file_drops(ctx: &Context, gem: &mut GemPlayer) {
     let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
 
@@ -444,6 +1767,8 @@ fn poll_file_drops(ctx: &Context, gem: &mut GemPlayer) {
         return;
     };
 
+    let mut referenced_files_changed = false;
+
     for file in dropped_files {
         let Some(path) = file.path.as_ref() else {
             error!("Dropped file '{}' has no path.", file.name);
@@ -462,6 +1787,24 @@ fn poll_file_drops(ctx: &Context, gem: &mut GemPlayer) {
             continue;
         }
 
+        if gem.reference_dropped_files {
+            let already_in_library = path.starts_with(library_path) || gem.referenced_files.contains(path);
+            if already_in_library {
+                gem.ui
+                    .toasts
+                    .info(format!("'{}' is already in the Library.", file_name.to_string_lossy()));
+                continue;
+            }
+
+            gem.referenced_files.push(path.clone());
+            referenced_files_changed = true;
+
+            gem.ui
+                .toasts
+                .success(format!("Added '{}' to Library.", file_name.to_string_lossy()));
+            continue;
+        }
+
         let destination = library_path.join(file_name);
 
         if let Err(e) = copy(path, &destination) {
@@ -474,16 +1817,49 @@ fn poll_file_drops(ctx: &Context, gem: &mut GemPlayer) {
             .toasts
             .success(format!("Added '{}' to Library.", file_name.to_string_lossy()));
     }
+
+    if referenced_files_changed {
+        let command = LibraryWatcherCommand::SetReferencedFiles(gem.referenced_files.clone());
+        if let Err(e) = gem.library_watcher.command_sender.send(command) {
+            error!("Failed to update referenced files: {e}");
+        }
+    }
 }
 
 // Reset / reconcile the relevant ui state so that we don't become out of sync.
 // For example, have selected a playlist that has since been deleted.
 fn on_library_reloaded(gem: &mut GemPlayer, new_library: Vec<Track>, new_playlists: Vec<Playlist>) {
+    let changed = changed_tracks(&gem.library, &new_library);
+    let library_changed = !changed.is_empty();
+    let changed_albums: HashSet<AlbumKey> = changed
+        .iter()
+        .filter_map(|t| Some((t.album.clone()?, t.album_artist.clone())))
+        .collect();
+
+    let selected_playlist_changed = match &gem.ui.playlists.selected_playlist_key {
+        Some(key) => {
+            let find = |playlists: &[Playlist]| playlists.iter().find(|p| &p.file_path == key).map(|p| p.tracks.as_slice());
+            playlist_tracks_changed(find(&gem.playlists), find(&new_playlists))
+        }
+        None => gem.ui.playlists.selected_auto_playlist.is_some() && library_changed,
+    };
+
     gem.library = new_library;
     gem.playlists = new_playlists;
 
-    gem.ui.library.cache_dirty = true;
-    gem.ui.playlists.cache_dirty = true;
+    // Only the views showing something that changed are rebuilt, so that the others keep their rows as they are.
+    if library_changed {
+        gem.ui.library.cache_dirty = true;
+        gem.ui.library.restore_scroll = true;
+        gem.ui.albums.cache_dirty = true;
+        for album in &changed_albums {
+            gem.ui.albums.artwork.remove(album);
+        }
+    }
+    if selected_playlist_changed {
+        gem.ui.playlists.cache_dirty = true;
+        gem.ui.playlists.restore_scroll = true;
+    }
 
     // Reconcile the selected tracks in the library view.
     gem.ui
@@ -493,7 +1869,7 @@ fn on_library_reloaded(gem: &mut GemPlayer, new_library: Vec<Track>, new_playlis
 
     // Reconcile the playlist selection + playlist-selected tracks in the playlist view.
     if let Some(selected_playlist_key) = &gem.ui.playlists.selected_playlist_key {
-        let maybe_playlist = gem.playlists.iter().find(|p| &p.m3u_path == selected_playlist_key);
+        let maybe_playlist = gem.playlists.iter().find(|p| &p.file_path == selected_playlist_key);
         if let Some(playlist) = maybe_playlist {
             // Playlist still exists -> reconcile selected tracks
             gem.ui
@@ -507,18 +1883,85 @@ fn on_library_reloaded(gem: &mut GemPlayer, new_library: Vec<Track>, new_playlis
             gem.ui.playlists.rename_buffer = None;
             gem.ui.playlists.delete_modal_open = false;
         }
+    } else if gem.ui.playlists.selected_auto_playlist.is_some() {
+        // Auto playlists are drawn from the library, so their tracks are reconciled against it.
+        gem.ui
+            .playlists
+            .selected_tracks
+            .retain(|track_id| gem.library.iter().any(|t| &t.path == track_id));
     } else {
         gem.ui.playlists.selected_tracks.clear();
     }
+
+    if let Some(saved) = gem.saved_queue.take() {
+        restore_saved_queue(gem, saved);
+    }
+
+    gem.player.loudness.retain_tracks(&gem.library);
+    if gem.player.replay_gain_mode == ReplayGainMode::Loudness {
+        start_loudness_scan(gem);
+    }
+
+    if gem.accurate_durations {
+        start_duration_scan(gem);
+    }
+}
+
+// Whether the tracks of a playlist were reordered, added, removed, or written to since the last load.
+fn playlist_tracks_changed(old: Option<&[Track]>, new: Option<&[Track]>) -> bool {
+    let (Some(old), Some(new)) = (old, new) else {
+        return true;
+    };
+
+    old.len() != new.len()
+        || old
+            .iter()
+            .zip(new)
+            .any(|(a, b)| a.path != b.path || a.date_modified != b.date_modified)
+}
+
+// Tracks that are no longer in the library are dropped.
+fn restore_saved_queue(gem: &mut GemPlayer, saved: SavedQueue) {
+    let find_tracks = |paths: &[PathBuf]| -> Vec<Track> {
+        paths
+            .iter()
+            .filter_map(|path| gem.library.iter().find(|t| &t.path == path).cloned())
+            .collect()
+    };
+
+    let queue = find_tracks(&saved.queue);
+    let history = find_tracks(&saved.history);
+
+    let dropped_count = saved.queue.len() + saved.history.len() - queue.len() - history.len();
+    if dropped_count > 0 {
+        warn!("{} track(s) from the saved queue are no longer in the library.", dropped_count);
+    }
+
+    info!("Restored {} queued track(s) from the last session.", queue.len());
+
+    // Anything queued before the library loaded stays at the front.
+    gem.player.queue.extend(queue);
+    gem.player.history.splice(0..0, history);
+}
+
+fn update_playing_bookmark(gem: &mut GemPlayer) {
+    if let Some(track) = &gem.player.playing
+        && let Some(position) = get_position(&gem.player)
+    {
+        update_bookmark(&mut gem.bookmarks, track, position);
+    }
 }
 
 fn check_for_next_track(ctx: &Context, gem: &mut GemPlayer) {
+    update_crossfade(&mut gem.player);
+    update_pause_fade(&mut gem.player);
+
     let Some(backend) = &gem.player.backend else {
         return;
     };
 
     let track_is_playing = !backend.player.empty();
-    if track_is_playing {
+    if track_is_playing && !crossfade_is_due(&gem.player) {
         return;
     }
 
@@ -527,7 +1970,31 @@ fn check_for_next_track(ctx: &Context, gem: &mut GemPlayer) {
 
 fn maybe_play_next(ctx: &Context, gem: &mut GemPlayer) {
     match play_next(&mut gem.player) {
-        Ok(()) => on_track_change(ctx, gem),
+        Ok(skipped) => {
+            if skipped > 0 {
+                let message = match skipped {
+                    1 => "Skipped a track that could not be played.".to_owned(),
+                    n => format!("Skipped {} tracks that could not be played.", n),
+                };
+                gem.ui.toasts.warning(message);
+            }
+
+            if let Some(track) = &gem.player.playing {
+                record_play(&mut gem.play_counts, track);
+                gem.ui.playlists.cache_dirty = true; // The most played tracks may have changed.
+            }
+
+            on_track_change(ctx, gem);
+
+            // There is no need for a notification when the player is already in front of the user.
+            let is_focused = ctx.input(|i| i.viewport().focused).unwrap_or(true);
+            if gem.track_notifications
+                && !is_focused
+                && let Some(track) = &gem.player.playing
+            {
+                show_track_notification(track);
+            }
+        }
         Err(e) => {
             error!("{}", e);
             gem.ui.toasts.error("Error playing the next track");
@@ -536,11 +2003,12 @@ fn maybe_play_next(ctx: &Context, gem: &mut GemPlayer) {
 }
 
 // If we are near the beginning of the track, we go to the previously played track.
-// Otherwise, we seek to the beginning.
+// Otherwise, we seek to the beginning. A threshold of zero always goes to the previous track.
 pub fn maybe_play_previous(ctx: &Context, gem: &mut GemPlayer) {
-    let rewind_threshold = 5.0;
+    let rewind_threshold = gem.player.rewind_threshold;
 
-    let under_threshold = get_position(&gem.player).is_some_and(|position| position.as_secs_f32() < rewind_threshold);
+    let under_threshold =
+        rewind_threshold == 0.0 || get_position(&gem.player).is_some_and(|position| position.as_secs_f32() < rewind_threshold);
 
     let previous_track_exists = !gem.player.history.is_empty();
 
@@ -557,7 +2025,37 @@ pub fn maybe_play_previous(ctx: &Context, gem: &mut GemPlayer) {
         if let Err(e) = backend.player.try_seek(Duration::ZERO) {
             error!("Error rewinding track: {:?}", e);
         }
-        backend.player.play();
+
+        if let Err(e) = play(&mut gem.player) {
+            error!("{}", e);
+        }
+
+        // Rewinding isn't a track change, so the OS widgets (e.g. Now Playing on macOS) need the new position separately.
+        if let OSMediaControlsState::Initialized(osmc) = &mut gem.os_media_controls
+            && let Err(e) = update_playback(&mut osmc.controls, &gem.player)
+        {
+            error!("Failed to set OS media playback state: {e}");
+        }
+    }
+}
+
+// Long tracks continue where they were left off. The bookmark is recorded again from the next logic update on.
+fn maybe_resume_from_bookmark(gem: &mut GemPlayer) {
+    let Some(position) = gem.player.playing.as_ref().and_then(|t| bookmark_position(&gem.bookmarks, t)) else {
+        return;
+    };
+
+    // Resuming from the last session already restored the position.
+    let current = get_position(&gem.player).unwrap_or_default();
+    if current.abs_diff(position) < Duration::from_secs(1) {
+        return;
+    }
+
+    match seek(&mut gem.player, position) {
+        Ok(()) => {
+            gem.ui.toasts.info(format!("Resumed at {}", format_duration_to_mmss(position)));
+        }
+        Err(e) => error!("Failed to resume from bookmark: {}", e),
     }
 }
 
@@ -574,7 +2072,15 @@ fn on_track_change(ctx: &Context, gem: &mut GemPlayer) {
         error!("Failed to clear artwork cache: {e}");
     }
 
+    gem.lyrics = gem.player.playing.as_ref().and_then(load_lyrics);
+    gem.ui.lyrics_line = None;
+
+    gem.chapters = gem.player.playing.as_ref().map(load_chapters).unwrap_or_default();
+
+    maybe_resume_from_bookmark(gem);
+
     gem.ui.marquee.reset();
+    gem.ui.queue.selected_indices.clear(); // The queue has likely shifted.
 
     if let OSMediaControlsState::Initialized(osmc) = &mut gem.os_media_controls {
         if let Err(e) = update_metadata(&mut osmc.controls, &gem.player) {
@@ -600,6 +2106,31 @@ fn apply_theme(ui: &mut Ui, preference: ThemePreference) {
     ui.set_visuals(visuals);
 }
 
+// Only touches egui's zoom when the setting changes, so that zooming with egui's shortcuts (Ctrl+/Ctrl-) isn't undone.
+// Those zooms are written back to the setting instead. Changes to the setting wait for the pointer to be released, so
+// that the zoom slider doesn't move out from under it while being dragged.
+fn apply_zoom(ui: &Ui, zoom: &mut f32, applied_zoom: &mut Option<f32>) {
+    let ctx = ui.ctx();
+    let current_zoom = ctx.zoom_factor();
+
+    if let Some(applied) = *applied_zoom
+        && current_zoom != applied
+    {
+        *zoom = current_zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        if *zoom != current_zoom {
+            ctx.set_zoom_factor(*zoom);
+        }
+        *applied_zoom = Some(*zoom);
+        return;
+    }
+
+    let pointer_is_down = ui.input(|i| i.pointer.any_down());
+    if *applied_zoom != Some(*zoom) && !pointer_is_down {
+        ctx.set_zoom_factor(*zoom);
+        *applied_zoom = Some(*zoom);
+    }
+}
+
 fn load_font_family(family_names: &[&str]) -> Option<Vec<u8>> {
     let system_source = SystemSource::new();
 
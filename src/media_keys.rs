@@ -0,0 +1,60 @@
+use anyhow::Result;
+use fully_pub::fully_pub;
+use global_hotkey::{
+    GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState,
+    hotkey::{Code, HotKey},
+};
+use log::{debug, error};
+
+use crate::{GemPlayer, commands::GemCommand};
+
+// Media keys registered with the OS, so that they reach us even when the window isn't focused.
+#[fully_pub]
+struct MediaKeys {
+    _manager: GlobalHotKeyManager,    // Never read, but the keys are unregistered once it is dropped.
+    bindings: Vec<(u32, GemCommand)>, // Hotkey ids and the command each one triggers.
+}
+
+pub fn setup_media_keys() -> Result<MediaKeys> {
+    let manager = GlobalHotKeyManager::new()?;
+
+    let keys = [
+        (Code::MediaPlayPause, GemCommand::TogglePlayback),
+        (Code::MediaTrackNext, GemCommand::NextTrack),
+        (Code::MediaTrackPrevious, GemCommand::PreviousTrack),
+        (Code::MediaStop, GemCommand::Stop),
+    ];
+
+    let mut bindings = Vec::new();
+    for (code, command) in keys {
+        let hotkey = HotKey::new(None, code);
+
+        // Another application may already own a key. The others are still worth having.
+        match manager.register(hotkey) {
+            Ok(()) => bindings.push((hotkey.id(), command)),
+            Err(e) => error!("Failed to register the {:?} media key: {}", code, e),
+        }
+    }
+
+    Ok(MediaKeys {
+        _manager: manager,
+        bindings,
+    })
+}
+
+pub fn poll_media_keys(gem: &mut GemPlayer) {
+    let Some(media_keys) = &gem.media_keys else {
+        return;
+    };
+
+    while let Ok(event) = GlobalHotKeyEvent::receiver().try_recv() {
+        if event.state != HotKeyState::Pressed {
+            continue;
+        }
+
+        if let Some((_, command)) = media_keys.bindings.iter().find(|(id, _)| *id == event.id) {
+            debug!("Media key pressed: {}", command);
+            gem.commands.push(command.clone());
+        }
+    }
+}
@@ -58,7 +58,7 @@ pub fn update_playback(controls: &mut MediaControls, player: &Player) -> Result<
 
     let progress = get_position(player).map(MediaPosition);
 
-    let is_paused = backend.is_some_and(|b| b.player.is_paused());
+    let is_paused = backend.is_some_and(|b| b.is_paused());
 
     let playback = if player.playing.is_some() {
         if is_paused {
@@ -75,6 +75,16 @@ pub fn update_playback(controls: &mut MediaControls, player: &Player) -> Result<
     Ok(())
 }
 
+// Only MPRIS exposes the volume, so media widgets on Linux can show and change it.
+#[cfg(target_os = "linux")]
+pub fn update_volume(controls: &mut MediaControls, player: &Player) -> Result<()> {
+    let volume = player.backend.as_ref().map_or(0.0, |b| b.volume());
+
+    controls.set_volume(volume as f64)?;
+
+    Ok(())
+}
+
 pub fn setup_os_media_controls(window_handle: WindowHandle<'_>) -> Result<OSMediaControls> {
     let hwnd = match window_handle.as_raw() {
         RawWindowHandle::Win32(h) => Some(h.hwnd.get() as *mut c_void),
@@ -1,60 +1,301 @@
 use crate::{
-    track::Track,
-    visualizer::{VisualizerCommand, VisualizerSource, VisualizerState},
+    equalizer::{Equalizer, EqualizerSource},
+    loudness::{DEFAULT_TARGET_LOUDNESS, LoudnessCache},
+    resampler::ResamplerSource,
+    track::{ReplayGain, Track},
+    visualizer::{VisualizerCommand, VisualizerSource, VisualizerState, send_visualizer_command},
 };
 use anyhow::{Context, Result, bail};
 use fully_pub::fully_pub;
-use log::error;
+use log::{error, warn};
 use rand::seq::SliceRandom;
-use rodio::{Decoder, Device, DeviceSinkBuilder, MixerDeviceSink, Source};
-use std::{fs::File, time::Duration};
+use rodio::{
+    ChannelCount, Decoder, Device, DeviceSinkBuilder, MixerDeviceSink, Sample, SampleRate, Source,
+    cpal::{BufferSize, StreamError, traits::DeviceTrait},
+    source::SeekError,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use strum_macros::EnumIter;
 
 #[fully_pub]
 struct Player {
     history: Vec<Track>, // In chronological order. The most recently played track is at the end.
     playing: Option<Track>,
-    queue: Vec<Track>, // In the order the tracks will be played.
+    applied_gain: Option<f32>, // In dB. The normalization the playing track was opened with, None if none was applied.
+    queue: Vec<Track>,         // In the order the tracks will be played.
 
-    repeat: bool,
+    repeat: RepeatMode,
     shuffle: Option<Vec<Track>>, // Used to restore the queue after shuffling. The tracks are what was in front of the cursor.
-    paused_before_scrubbing: Option<bool>, // None if not scrubbing, Some(true) if paused, Some(false) if playing.
+    shuffle_mode: ShuffleMode,
+    rewind_threshold: f32, // In seconds. Pressing previous past this point restarts the track instead.
+    seek_step: u64,        // In seconds. How far the seek shortcuts jump.
+    crossfade: f32,        // In seconds. Zero disables crossfading.
+    pause_fade: f32,       // In seconds. Zero disables fading out on pause and stop, and fading in on resume.
+    replay_gain_mode: ReplayGainMode,
+    loudness: LoudnessCache,
+    target_loudness: f32,                             // In LUFS. Used by ReplayGainMode::Loudness.
+    paused_before_scrubbing: Option<bool>,            // None if not scrubbing, Some(true) if paused, Some(false) if playing.
+    low_latency: bool,                                // Requests a small output buffer. See build_audio_backend_from_device().
+    output_settings: HashMap<String, OutputSettings>, // By device name.
+    unplayable: HashSet<PathBuf>,                     // Tracks that failed to load, e.g. corrupt or missing files.
 
     backend: Option<AudioBackend>,
     muted: bool,
     volume_before_mute: Option<f32>,
 
     visualizer: VisualizerState,
+    equalizer: Equalizer,
+}
+
+pub const DEFAULT_REWIND_THRESHOLD: f32 = 5.0;
+pub const MAX_REWIND_THRESHOLD: f32 = 15.0;
+pub const SEEK_STEPS: [u64; 3] = [5, 10, 30];
+pub const DEFAULT_SEEK_STEP: u64 = 10;
+pub const MAX_CROSSFADE: f32 = 10.0;
+pub const DEFAULT_PAUSE_FADE: f32 = 0.15;
+pub const MAX_PAUSE_FADE: f32 = 1.0;
+pub const LOW_LATENCY_BUFFER_SIZE: u32 = 512; // In frames per callback.
+const COMMON_SAMPLE_RATES: [u32; 6] = [44_100, 48_000, 88_200, 96_000, 176_400, 192_000];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepeatMode {
+    Off,
+    All, // When the queue runs out, everything that was played is queued again.
+    One, // The current track replays.
+}
+
+impl RepeatMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            RepeatMode::Off => "Off",
+            RepeatMode::All => "All",
+            RepeatMode::One => "One",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum ShuffleMode {
+    Random,
+    Spread, // Avoids playing tracks by the same artist or from the same album back-to-back.
+}
+
+impl ShuffleMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            ShuffleMode::Random => "Random",
+            ShuffleMode::Spread => "Spread out artists and albums",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum ReplayGainMode {
+    Off,
+    Track,    // Each track is normalized on its own.
+    Album,    // Tracks keep their loudness relative to the rest of their album.
+    Loudness, // Each track is measured and brought to the target loudness, whether or not it has ReplayGain tags.
+}
+
+impl ReplayGainMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            ReplayGainMode::Off => "Off",
+            ReplayGainMode::Track => "Track",
+            ReplayGainMode::Album => "Album",
+            ReplayGainMode::Loudness => "Measured Loudness",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter, Serialize, Deserialize)]
+pub enum ResamplerQuality {
+    #[default]
+    Fast, // rodio's linear interpolation.
+    High, // A windowed sinc filter. See ResamplerSource.
+}
+
+impl ResamplerQuality {
+    pub fn label(self) -> &'static str {
+        match self {
+            ResamplerQuality::Fast => "Fast",
+            ResamplerQuality::High => "High quality",
+        }
+    }
+}
+
+// How the output stream is opened on a device, and how tracks are converted to its sample rate.
+#[fully_pub]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct OutputSettings {
+    sample_rate: Option<u32>, // None uses the device's default rate.
+    resampler: ResamplerQuality,
+}
+
+impl Player {
+    /// Creates an idle player with an empty queue. Without a backend, the player can still manage
+    /// its queue and history but cannot play anything.
+    pub fn new(backend: Option<AudioBackend>) -> Self {
+        Self {
+            history: Vec::new(),
+            playing: None,
+            applied_gain: None,
+            queue: Vec::new(),
+
+            repeat: RepeatMode::Off,
+            shuffle: None,
+            shuffle_mode: ShuffleMode::Random,
+            rewind_threshold: DEFAULT_REWIND_THRESHOLD,
+            seek_step: DEFAULT_SEEK_STEP,
+            crossfade: 0.0,
+            pause_fade: DEFAULT_PAUSE_FADE,
+            replay_gain_mode: ReplayGainMode::Off,
+            loudness: LoudnessCache::new(),
+            target_loudness: DEFAULT_TARGET_LOUDNESS,
+            paused_before_scrubbing: None,
+            low_latency: false,
+            output_settings: HashMap::new(),
+            unplayable: HashSet::new(),
+
+            backend,
+            muted: false,
+            volume_before_mute: None,
+
+            visualizer: VisualizerState::new(),
+            equalizer: Equalizer::new(),
+        }
+    }
 }
 
 #[fully_pub]
 struct AudioBackend {
     device: Device,
-    stream: MixerDeviceSink, // Holds the MixerDeviceSink to keep it alive
-    player: rodio::Player,   // Controls playback (play, pause, stop, etc.)
+    stream: MixerDeviceSink,       // Holds the MixerDeviceSink to keep it alive
+    player: rodio::Player,         // Controls playback (play, pause, stop, etc.)
+    fading_out: Option<FadingOut>, // The previous track while crossfading into the current one.
+    low_latency: bool,             // Whether the stream was opened with a small buffer. The device may refuse one.
+    device_name: String,
+    supported_sample_rates: Vec<u32>,    // The common rates that the device can be opened at.
+    fade: Arc<FadeControl>,              // Shared with the sources of the tracks. See FadeSource.
+    pending_pause: Option<PendingPause>, // Some while fading out before pausing or stopping.
+}
+
+impl AudioBackend {
+    // Fading out before a pause already counts as paused, so that the controls respond right away.
+    pub fn is_paused(&self) -> bool {
+        self.player.is_paused() || self.pending_pause.is_some()
+    }
+
+    // The volume as shown on the slider, from 0 to 1. The gain of the output follows a cubic curve, which is much
+    // closer to how loudness is heard than a linear one.
+    pub fn volume(&self) -> f32 {
+        self.player.volume().cbrt()
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.player.set_volume(volume.clamp(0.0, 1.0).powi(3));
+    }
 }
 
-pub fn play_next(player: &mut Player) -> Result<()> {
-    if player.repeat
+#[fully_pub]
+struct PendingPause {
+    started: Instant,
+    duration: Duration,
+    stop: bool, // Whether to stop rather than pause once faded out.
+}
+
+// Lets the UI thread fade the audio in and out. The gain is ramped per frame by FadeSource, since stepping the volume
+// from the UI thread would be audible.
+pub struct FadeControl {
+    audible: AtomicBool,
+    duration_millis: AtomicU32,
+}
+
+#[fully_pub]
+struct FadingOut {
+    player: rodio::Player,
+    started: Instant,
+    duration: Duration,
+}
+
+// Tracks that fail to load are marked as unplayable and skipped. Returns how many were skipped.
+pub fn play_next(player: &mut Player) -> Result<usize> {
+    // Only crossfade if there is something audible to fade out of.
+    let should_crossfade =
+        player.crossfade > 0.0 && player.playing.is_some() && player.backend.as_ref().is_some_and(|b| !b.player.empty() && !b.is_paused());
+
+    let mut skipped = 0;
+
+    if player.repeat == RepeatMode::One
         && let Some(playing) = player.playing.clone()
     {
-        play_track(player, playing)?;
-        return Ok(());
+        if try_play_track(player, playing, should_crossfade)? {
+            return Ok(skipped);
+        }
+        skipped += 1; // Move on to the queue instead.
+    }
+
+    if player.queue.is_empty() && player.repeat == RepeatMode::All {
+        requeue_history(player);
     }
 
     if player.queue.is_empty() {
         stop(player);
-        return Ok(()); // Nothing to play
+        return Ok(skipped); // Nothing to play
     }
 
     if let Some(current) = player.playing.take() {
         player.history.push(current);
     }
 
-    let next_track = player.queue.remove(0);
+    while !player.queue.is_empty() {
+        let next_track = player.queue.remove(0);
 
-    play_track(player, next_track)?;
+        if try_play_track(player, next_track, should_crossfade)? {
+            return Ok(skipped);
+        }
+        skipped += 1;
+    }
 
-    Ok(())
+    stop(player); // None of the remaining tracks could be played.
+    Ok(skipped)
+}
+
+// Returns false if the track could not be loaded, after marking it as unplayable. Errors are only returned for
+// problems with the player itself.
+fn try_play_track(player: &mut Player, track: Track, crossfade: bool) -> Result<bool> {
+    if player.backend.is_none() {
+        bail!("No audio backend available");
+    }
+
+    let path = track.path.clone();
+    let result = if crossfade {
+        crossfade_to_track(player, track)
+    } else {
+        play_track(player, track)
+    };
+
+    match result {
+        Ok(()) => {
+            player.unplayable.remove(&path); // The file may have been fixed since.
+            Ok(true)
+        }
+        Err(e) => {
+            error!("Skipping unplayable track {:?}: {:#}", path, e);
+            player.unplayable.insert(path);
+            Ok(false)
+        }
+    }
 }
 
 pub fn play_previous(player: &mut Player) -> Result<()> {
@@ -72,42 +313,219 @@ pub fn play_previous(player: &mut Player) -> Result<()> {
 }
 
 fn play_track(player: &mut Player, track: Track) -> Result<()> {
-    let Some(backend) = &player.backend else {
+    load_track(player, track)?;
+
+    if let Some(backend) = &player.backend {
+        backend.player.play();
+    }
+
+    Ok(())
+}
+
+// Makes the track current without starting playback.
+fn load_track(player: &mut Player, track: Track) -> Result<()> {
+    let source = open_track_source(player, &track)?;
+
+    let Some(backend) = &mut player.backend else {
         bail!("No audio backend available");
     };
 
     backend.player.stop(); // Stop the current track if any.
+    backend.fading_out = None;
+    backend.pending_pause = None;
+    backend.fade.audible.store(true, Ordering::Relaxed);
 
-    let file = File::open(&track.path).with_context(|| format!("Failed to open audio file at {:?}", track.path))?;
+    backend.player.pause();
+    backend.player.append(source);
 
-    let decoder = Decoder::try_from(file).with_context(|| format!("Failed to decode audio file {:?}", track.path))?;
+    player.applied_gain = applied_gain(player, &track);
+    player.playing = Some(track);
 
-    let sample_rate = decoder.sample_rate();
-    if let Err(e) = player.visualizer.command_sender.send(VisualizerCommand::SampleRate(sample_rate)) {
-        error!("Visualizer channel error: {e}. Continuing playback anyway.");
+    Ok(())
+}
+
+// Loads the track paused at the position, e.g. to continue where the last session left off.
+pub fn resume_track(player: &mut Player, track: Track, position: Duration) -> Result<()> {
+    load_track(player, track)?;
+
+    if !position.is_zero() {
+        seek(player, position)?;
     }
 
-    let visualizer_source = VisualizerSource::new(decoder, player.visualizer.command_sender.clone());
-    backend.player.append(visualizer_source);
-    backend.player.play();
+    Ok(())
+}
+
+// Starts the track on a new rodio player while the current one fades out.
+fn crossfade_to_track(player: &mut Player, track: Track) -> Result<()> {
+    let fade_duration = Duration::from_secs_f32(player.crossfade);
+    let source = open_track_source(player, &track)?.fade_in(fade_duration);
+
+    let Some(backend) = &mut player.backend else {
+        bail!("No audio backend available");
+    };
+
+    let next_player = rodio::Player::connect_new(backend.stream.mixer());
+    next_player.set_volume(backend.player.volume());
+    next_player.append(source);
+    next_player.play();
+
+    let previous_player = std::mem::replace(&mut backend.player, next_player);
+    backend.fading_out = Some(FadingOut {
+        player: previous_player,
+        started: Instant::now(),
+        duration: fade_duration,
+    });
 
+    player.applied_gain = applied_gain(player, &track);
     player.playing = Some(track);
 
     Ok(())
 }
 
-pub fn toggle(player: &mut Player) -> Result<()> {
-    player.playing.as_ref().context("Cannot toggle without a current track")?;
+// Lowers the volume of the previous track while crossfading, and drops it once the fade is over.
+// The ramp follows the current volume so that volume changes and muting apply to both tracks.
+pub fn update_crossfade(player: &mut Player) {
+    let Some(backend) = &mut player.backend else {
+        return;
+    };
 
-    let backend = player.backend.as_mut().context("The player backend is not initialized")?;
+    let Some(fading_out) = &backend.fading_out else {
+        return;
+    };
+
+    let progress = fading_out.started.elapsed().as_secs_f32() / fading_out.duration.as_secs_f32();
+    if progress >= 1.0 || fading_out.player.empty() {
+        backend.fading_out = None; // Dropping the rodio player stops it.
+        return;
+    }
+
+    fading_out.player.set_volume(backend.player.volume() * (1.0 - progress));
+}
+
+// Whether the current track is close enough to its end that the next one should start crossfading in.
+pub fn crossfade_is_due(player: &Player) -> bool {
+    if player.crossfade <= 0.0 || (player.queue.is_empty() && player.repeat == RepeatMode::Off) {
+        return false;
+    }
+
+    let Some(track) = &player.playing else {
+        return false;
+    };
+
+    let Some(backend) = &player.backend else {
+        return false;
+    };
+
+    // Wait for any ongoing fade to finish so that short tracks are not skipped through.
+    let is_fading = backend.fading_out.is_some();
+    let is_audible = !backend.player.empty() && !backend.is_paused();
+    let remaining = track.duration.saturating_sub(backend.player.get_pos());
+
+    !is_fading && is_audible && remaining.as_secs_f32() <= player.crossfade
+}
+
+// The linear volume factor for the mode, limited by the peak so that the track does not clip.
+// Falls back to the other mode's values if the preferred ones are missing.
+fn replay_gain_factor(replay_gain: &ReplayGain, mode: ReplayGainMode) -> f32 {
+    let track = (replay_gain.track_gain, replay_gain.track_peak);
+    let album = (replay_gain.album_gain, replay_gain.album_peak);
+
+    let (gain, peak) = match mode {
+        ReplayGainMode::Off => return 1.0,
+        ReplayGainMode::Track | ReplayGainMode::Loudness if track.0.is_some() => track,
+        ReplayGainMode::Track | ReplayGainMode::Loudness => album,
+        ReplayGainMode::Album if album.0.is_some() => album,
+        ReplayGainMode::Album => track,
+    };
+
+    let Some(gain) = gain else {
+        return 1.0; // The track has no ReplayGain tags.
+    };
+
+    let factor = 10.0_f32.powf(gain / 20.0);
+
+    match peak {
+        Some(peak) if peak > 0.0 => factor.min(1.0 / peak),
+        _ => factor,
+    }
+}
+
+// The linear volume factor that brings the track to the target loudness, limited by its peak. Tracks that haven't been
+// measured yet use their ReplayGain tags instead.
+fn loudness_factor(player: &Player, track: &Track) -> f32 {
+    let Some(loudness) = player.loudness.get(track) else {
+        return replay_gain_factor(&track.replay_gain, ReplayGainMode::Track);
+    };
 
-    if backend.player.is_paused() {
-        backend.player.play()
+    let factor = 10.0_f32.powf((player.target_loudness - loudness.integrated) / 20.0);
+
+    if loudness.peak > 0.0 {
+        factor.min(1.0 / loudness.peak)
     } else {
-        backend.player.pause()
+        factor
+    }
+}
+
+fn gain_factor(player: &Player, track: &Track) -> f32 {
+    match player.replay_gain_mode {
+        ReplayGainMode::Loudness => loudness_factor(player, track),
+        mode => replay_gain_factor(&track.replay_gain, mode),
     }
+}
 
-    Ok(())
+// The gain in dB that normalization applies to the track, or None if it is off or the track has nothing to go by.
+fn applied_gain(player: &Player, track: &Track) -> Option<f32> {
+    let has_tags = track.replay_gain.track_gain.is_some() || track.replay_gain.album_gain.is_some();
+    let can_normalize = match player.replay_gain_mode {
+        ReplayGainMode::Off => false,
+        ReplayGainMode::Loudness => has_tags || player.loudness.get(track).is_some(),
+        _ => has_tags,
+    };
+
+    can_normalize.then(|| 20.0 * gain_factor(player, track).log10())
+}
+
+fn open_track_source(player: &Player, track: &Track) -> Result<impl Source + Send + 'static> {
+    let audio_path = track.audio_path();
+    let file = File::open(audio_path).with_context(|| format!("Failed to open audio file at {:?}", audio_path))?;
+
+    let decoder = Decoder::try_from(file).with_context(|| format!("Failed to decode audio file {:?}", audio_path))?;
+
+    // Cue tracks only play a section of their audio file. Regular tracks play the whole file.
+    let (start, end) = match &track.cue {
+        Some(cue) => (cue.start, cue.end),
+        None => (Duration::ZERO, None),
+    };
+    let range_source = RangeSource::new(decoder, start, end).with_context(|| format!("Failed to seek audio file {:?}", audio_path))?;
+
+    let format = VisualizerCommand::Format(range_source.sample_rate(), range_source.channels());
+    send_visualizer_command(&player.visualizer, format);
+
+    let amplified_source = range_source.amplify(gain_factor(player, track));
+
+    let equalizer_source = EqualizerSource::new(amplified_source, player.equalizer.shared_gains.clone());
+
+    let visualizer_sender = player.visualizer.pipeline.as_ref().map(|p| p.command_sender.clone());
+    let visualizer_source = VisualizerSource::new(equalizer_source, visualizer_sender);
+
+    // Otherwise, rodio converts to the output rate itself.
+    let resample_to = match current_output_settings(player).resampler {
+        ResamplerQuality::Fast => None,
+        ResamplerQuality::High => player.backend.as_ref().map(|b| b.stream.config().sample_rate()),
+    };
+
+    let resampled_source = ResamplerSource::new(visualizer_source, resample_to);
+
+    let fade = player.backend.as_ref().map(|b| b.fade.clone());
+    Ok(FadeSource::new(resampled_source, fade))
+}
+
+pub fn toggle(player: &mut Player) -> Result<()> {
+    player.playing.as_ref().context("Cannot toggle without a current track")?;
+
+    let backend = player.backend.as_ref().context("The player backend is not initialized")?;
+
+    if backend.is_paused() { play(player) } else { pause(player) }
 }
 
 pub fn play(player: &mut Player) -> Result<()> {
@@ -115,6 +533,9 @@ pub fn play(player: &mut Player) -> Result<()> {
 
     let backend = player.backend.as_mut().context("The player backend is not initialized")?;
 
+    // If the track was faded out, it fades back in from where it was.
+    set_fade(backend, true, player.pause_fade);
+    backend.pending_pause = None;
     backend.player.play();
 
     Ok(())
@@ -125,19 +546,80 @@ pub fn pause(player: &mut Player) -> Result<()> {
 
     let backend = player.backend.as_mut().context("The player backend is not initialized")?;
 
-    backend.player.pause();
+    backend.fading_out = None;
+    fade_out(backend, player.pause_fade, false);
 
     Ok(())
 }
 
+// Stops right away, e.g. when the queue has run out.
 pub fn stop(player: &mut Player) {
     if let Some(b) = &mut player.backend {
         b.player.stop();
+        b.fading_out = None;
+        b.pending_pause = None;
     }
 
     player.playing = None;
 }
 
+// Like stop(), but fades out first.
+pub fn fade_out_and_stop(player: &mut Player) {
+    if let Some(b) = &mut player.backend {
+        b.fading_out = None;
+        fade_out(b, player.pause_fade, true);
+    }
+
+    player.playing = None;
+}
+
+fn fade_out(backend: &mut AudioBackend, duration: f32, stop: bool) {
+    let is_audible = !backend.player.is_paused() && !backend.player.empty();
+    if duration <= 0.0 || !is_audible {
+        finish_fade_out(backend, stop);
+        return;
+    }
+
+    set_fade(backend, false, duration);
+    backend.pending_pause = Some(PendingPause {
+        started: Instant::now(),
+        duration: Duration::from_secs_f32(duration),
+        stop,
+    });
+}
+
+fn finish_fade_out(backend: &mut AudioBackend, stop: bool) {
+    if stop {
+        backend.player.stop();
+    } else {
+        backend.player.pause();
+    }
+
+    backend.pending_pause = None;
+}
+
+fn set_fade(backend: &AudioBackend, audible: bool, duration: f32) {
+    let millis = (duration.max(0.0) * 1000.0) as u32;
+    backend.fade.duration_millis.store(millis, Ordering::Relaxed);
+    backend.fade.audible.store(audible, Ordering::Relaxed);
+}
+
+// Pauses or stops once the fade out is over. Call this every frame.
+pub fn update_pause_fade(player: &mut Player) {
+    let Some(backend) = &mut player.backend else {
+        return;
+    };
+
+    let Some(pending) = &backend.pending_pause else {
+        return;
+    };
+
+    if pending.started.elapsed() >= pending.duration {
+        let stop = pending.stop;
+        finish_fade_out(backend, stop);
+    }
+}
+
 pub fn seek(player: &mut Player, position: Duration) -> Result<()> {
     let track = player.playing.as_ref().context("Cannot seek without a currently playing track.")?;
 
@@ -149,12 +631,13 @@ pub fn seek(player: &mut Player, position: Duration) -> Result<()> {
     backend.player.try_seek(valid_position)?;
 
     // Resume playback if the player was not paused before scrubbing
-    if player.paused_before_scrubbing == Some(false) {
-        backend.player.play();
-    }
-
+    let should_resume = player.paused_before_scrubbing == Some(false);
     player.paused_before_scrubbing = None;
 
+    if should_resume {
+        play(player)?; // Also fades back in from the pause at the start of scrubbing.
+    }
+
     Ok(())
 }
 
@@ -189,6 +672,41 @@ pub fn move_to_position(player: &mut Player, from: usize, to: usize) {
     player.queue.insert(to, track);
 }
 
+// Moves the tracks so that they are inserted before `to` (an index into the queue before moving), keeping their relative order.
+pub fn move_tracks_to_position(player: &mut Player, indices: &[usize], to: usize) {
+    let mut indices: Vec<usize> = indices.iter().copied().filter(|&i| i < player.queue.len()).collect();
+    indices.sort_unstable();
+    indices.dedup();
+
+    if let [from] = indices[..] {
+        let to = if from < to { to - 1 } else { to };
+        move_to_position(player, from, to.min(player.queue.len() - 1));
+        return;
+    }
+
+    let removed_before_target = indices.iter().filter(|&&i| i < to).count();
+
+    let mut tracks: Vec<Track> = indices.iter().rev().map(|&i| player.queue.remove(i)).collect();
+    tracks.reverse();
+
+    let to = (to - removed_before_target).min(player.queue.len());
+    player.queue.splice(to..to, tracks);
+}
+
+// Moves the tracks so that the first of them ends up at `index` in the queue, keeping their relative order. Unlike
+// move_tracks_to_position(), the index is into the queue after the move, e.g. for moving a track to a numbered position.
+pub fn move_tracks_to_index(player: &mut Player, indices: &[usize], index: usize) {
+    let mut indices: Vec<usize> = indices.iter().copied().filter(|&i| i < player.queue.len()).collect();
+    indices.sort_unstable();
+    indices.dedup();
+
+    let mut tracks: Vec<Track> = indices.iter().rev().map(|&i| player.queue.remove(i)).collect();
+    tracks.reverse();
+
+    let index = index.min(player.queue.len());
+    player.queue.splice(index..index, tracks);
+}
+
 pub fn enqueue_next(player: &mut Player, track: Track) {
     player.queue.insert(0, track);
 }
@@ -197,8 +715,57 @@ pub fn enqueue(player: &mut Player, track: Track) {
     player.queue.push(track);
 }
 
-pub fn toggle_repeat(player: &mut Player) {
-    player.repeat = !player.repeat;
+pub fn cycle_repeat(player: &mut Player) {
+    player.repeat = match player.repeat {
+        RepeatMode::Off => RepeatMode::All,
+        RepeatMode::All => RepeatMode::One,
+        RepeatMode::One => RepeatMode::Off,
+    };
+}
+
+// Moves the played tracks (including the current one) back into the queue in the order they were played.
+fn requeue_history(player: &mut Player) {
+    if let Some(current) = player.playing.take() {
+        player.history.push(current);
+    }
+
+    player.queue = std::mem::take(&mut player.history);
+
+    if player.shuffle.is_some() {
+        player.shuffle = Some(player.queue.clone());
+
+        shuffle_tracks(&mut player.queue, player.shuffle_mode);
+    }
+}
+
+pub fn shuffle_tracks(tracks: &mut [Track], mode: ShuffleMode) {
+    let mut rng = rand::rng();
+    tracks.shuffle(&mut rng);
+
+    if mode == ShuffleMode::Spread {
+        spread_out(tracks);
+    }
+}
+
+// Reorders already shuffled tracks so that neighbours differ in artist and album where possible.
+// Each position takes the first of the next few tracks that does not clash with the previous track,
+// which keeps the result random while staying fast for large libraries.
+fn spread_out(tracks: &mut [Track]) {
+    let lookahead = 32;
+
+    let clashes = |a: &Track, b: &Track| {
+        let same_artist = a.artist.is_some() && a.artist == b.artist;
+        let same_album = a.album.is_some() && a.album == b.album;
+        same_artist || same_album
+    };
+
+    for i in 1..tracks.len() {
+        let window_end = (i + lookahead).min(tracks.len());
+
+        if let Some(offset) = tracks[i..window_end].iter().position(|t| !clashes(&tracks[i - 1], t)) {
+            tracks.swap(i, i + offset);
+        }
+    }
 }
 
 pub fn toggle_shuffle(player: &mut Player) {
@@ -210,25 +777,135 @@ pub fn toggle_shuffle(player: &mut Player) {
             let original_queue = player.queue.clone();
             player.shuffle = Some(original_queue);
 
-            let mut rng = rand::rng();
-            player.queue.shuffle(&mut rng);
+            shuffle_tracks(&mut player.queue, player.shuffle_mode);
         }
     }
 }
 
-pub fn build_audio_backend_from_device(device: Device) -> Result<AudioBackend> {
+// Opens an output stream on the device. In low latency mode, a small fixed buffer is requested so that pausing, seeking,
+// and volume changes are heard sooner. cpal has no exclusive (WASAPI exclusive / CoreAudio hog) mode, so other
+// applications can still share the device. If the device refuses the buffer size or sample rate, its defaults are used
+// instead.
+pub fn build_audio_backend_from_device(device: Device, low_latency: bool, sample_rate: Option<u32>) -> Result<AudioBackend> {
     let builder = DeviceSinkBuilder::from_device(device.clone())
         .context("Failed to create DeviceSinkBuilder from device")?
-        .with_error_callback(|e| {
-            error!("Stream error: {}", e);
-        });
+        .with_error_callback(log_stream_error);
 
-    let stream = builder.open_sink_or_fallback().context("Failed to open audio sink or fallback")?;
+    let requested_stream = if low_latency || sample_rate.is_some() {
+        let mut requested_builder = DeviceSinkBuilder::from_device(device.clone())
+            .context("Failed to create DeviceSinkBuilder from device")?
+            .with_error_callback(log_stream_error);
+
+        if low_latency {
+            requested_builder = requested_builder.with_buffer_size(BufferSize::Fixed(LOW_LATENCY_BUFFER_SIZE));
+        }
+
+        if let Some(rate) = sample_rate.and_then(SampleRate::new) {
+            requested_builder = requested_builder.with_sample_rate(rate);
+        }
+
+        match requested_builder.open_stream() {
+            Ok(stream) => Some(stream),
+            Err(e) => {
+                warn!(
+                    "The output device refused the requested format, falling back to its defaults: {}",
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let low_latency = low_latency && requested_stream.is_some();
+    let stream = match requested_stream {
+        Some(stream) => stream,
+        None => builder.open_sink_or_fallback().context("Failed to open audio sink or fallback")?,
+    };
 
     let player = rodio::Player::connect_new(stream.mixer());
     player.pause();
 
-    Ok(AudioBackend { device, player, stream })
+    let supported_sample_rates = match device.supported_output_configs() {
+        Ok(configs) => {
+            let configs: Vec<_> = configs.collect();
+            COMMON_SAMPLE_RATES
+                .into_iter()
+                .filter(|&rate| configs.iter().any(|c| c.min_sample_rate() <= rate && rate <= c.max_sample_rate()))
+                .collect()
+        }
+        Err(e) => {
+            warn!("Failed to get the supported output formats: {}", e);
+            Vec::new()
+        }
+    };
+
+    Ok(AudioBackend {
+        device_name: device_name(&device),
+        device,
+        player,
+        stream,
+        fading_out: None,
+        low_latency,
+        supported_sample_rates,
+        fade: Arc::new(FadeControl {
+            audible: AtomicBool::new(true),
+            duration_millis: AtomicU32::new(0),
+        }),
+        pending_pause: None,
+    })
+}
+
+pub fn device_name(device: &Device) -> String {
+    device
+        .description()
+        .map(|d| d.name().to_owned())
+        .unwrap_or_else(|_| "Unknown device".to_owned())
+}
+
+// The settings for the device that is in use.
+pub fn current_output_settings(player: &Player) -> OutputSettings {
+    player
+        .backend
+        .as_ref()
+        .and_then(|b| player.output_settings.get(&b.device_name))
+        .copied()
+        .unwrap_or_default()
+}
+
+fn log_stream_error(e: StreamError) {
+    error!("Stream error: {}", e);
+}
+
+// Opens the output stream again on the same device, e.g. after the output settings changed. The current track is
+// reloaded at its position, and keeps playing if it was.
+pub fn reopen_audio_backend(player: &mut Player) -> Result<()> {
+    let backend = player.backend.as_ref().context("The player backend is not initialized")?;
+
+    let volume = backend.player.volume();
+    let was_playing = !backend.is_paused() && !backend.player.empty();
+    let position = backend.player.get_pos();
+    let device = backend.device.clone();
+    let sample_rate = player
+        .output_settings
+        .get(&backend.device_name)
+        .and_then(|settings| settings.sample_rate);
+
+    // The old backend keeps playing if the new one can't be opened, and is only dropped once it is replaced.
+    let new_backend = build_audio_backend_from_device(device, player.low_latency, sample_rate)?;
+    new_backend.player.set_volume(volume);
+    player.backend = Some(new_backend);
+
+    if let Some(track) = player.playing.clone() {
+        resume_track(player, track, position)?;
+
+        if was_playing {
+            play(player)?;
+        }
+    }
+
+    Ok(())
 }
 
 pub fn mute_or_unmute(player: &mut Player) {
@@ -238,7 +915,7 @@ pub fn mute_or_unmute(player: &mut Player) {
 
     if player.muted {
         if let Some(backend) = &player.backend {
-            player.volume_before_mute = Some(backend.player.volume());
+            player.volume_before_mute = Some(backend.volume());
         }
         target_volume = 0.0;
     } else if let Some(v) = player.volume_before_mute {
@@ -246,14 +923,250 @@ pub fn mute_or_unmute(player: &mut Player) {
     }
 
     if let Some(backend) = &player.backend {
-        backend.player.set_volume(target_volume);
+        backend.set_volume(target_volume);
     }
 }
 
 pub fn set_volume(player: &mut Player, volume: f32) -> Result<()> {
     let backend = player.backend.as_mut().context("The player backend is not initialized")?;
 
-    backend.player.set_volume(volume.clamp(0.0, 1.0));
+    backend.set_volume(volume);
 
     Ok(())
 }
+
+// Ramps the gain of the input towards silence or full volume, as set through a FadeControl. Passes the input through
+// untouched without a control.
+pub struct FadeSource<I> {
+    input: I,
+    control: Option<Arc<FadeControl>>,
+    gain: f32,
+    channel: usize, // The channel of the next sample, since samples are interleaved.
+}
+
+impl<I> FadeSource<I>
+where
+    I: Source,
+{
+    pub fn new(input: I, control: Option<Arc<FadeControl>>) -> Self {
+        Self {
+            input,
+            control,
+            gain: 1.0,
+            channel: 0,
+        }
+    }
+}
+
+// Steps the gain one frame towards its target.
+fn next_fade_gain(gain: f32, control: &FadeControl, sample_rate: SampleRate) -> f32 {
+    let target = if control.audible.load(Ordering::Relaxed) { 1.0 } else { 0.0 };
+
+    let duration = control.duration_millis.load(Ordering::Relaxed) as f32 / 1000.0;
+    if duration <= 0.0 {
+        return target;
+    }
+
+    let step = 1.0 / (duration * sample_rate.get() as f32);
+    if target > gain {
+        (gain + step).min(target)
+    } else {
+        (gain - step).max(target)
+    }
+}
+
+impl<I> Iterator for FadeSource<I>
+where
+    I: Source,
+{
+    type Item = Sample;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.input.next()?;
+
+        // The gain only changes between frames so that the channels stay in step.
+        if self.channel == 0
+            && let Some(control) = &self.control
+        {
+            self.gain = next_fade_gain(self.gain, control, self.input.sample_rate());
+        }
+        self.channel = (self.channel + 1) % self.input.channels().get() as usize;
+
+        Some(sample * self.gain)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> Source for FadeSource<I>
+where
+    I: Source,
+{
+    #[inline]
+    fn current_span_len(&self) -> Option<usize> {
+        self.input.current_span_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> ChannelCount {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> SampleRate {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
+}
+
+// Plays the section of the input between `start` and `end`, and reports positions relative to `start`.
+pub struct RangeSource<I> {
+    input: I,
+    start: Duration,
+    end: Option<Duration>,
+    remaining_samples: Option<u64>, // None when playing to the end of the input.
+}
+
+impl<I> RangeSource<I>
+where
+    I: Source,
+{
+    pub fn new(mut input: I, start: Duration, end: Option<Duration>) -> Result<Self, SeekError> {
+        if !start.is_zero() {
+            input.try_seek(start)?;
+        }
+
+        let mut source = Self {
+            input,
+            start,
+            end,
+            remaining_samples: None,
+        };
+        source.reset_remaining_samples(start);
+
+        Ok(source)
+    }
+
+    fn reset_remaining_samples(&mut self, position: Duration) {
+        self.remaining_samples = self.end.map(|end| {
+            let samples_per_second = self.input.sample_rate().get() as f64 * self.input.channels().get() as f64;
+            (end.saturating_sub(position).as_secs_f64() * samples_per_second) as u64
+        });
+    }
+}
+
+impl<I> Iterator for RangeSource<I>
+where
+    I: Source,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(remaining) = &mut self.remaining_samples {
+            if *remaining == 0 {
+                return None;
+            }
+            *remaining -= 1;
+        }
+
+        self.input.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> Source for RangeSource<I>
+where
+    I: Source,
+{
+    #[inline]
+    fn current_span_len(&self) -> Option<usize> {
+        self.input.current_span_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> ChannelCount {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> SampleRate {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        match self.end {
+            Some(end) => Some(end.saturating_sub(self.start)),
+            None => self.input.total_duration().map(|d| d.saturating_sub(self.start)),
+        }
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let position = self.start + pos;
+        self.input.try_seek(position)?;
+        self.reset_remaining_samples(position);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player_with_queue(paths: &[&str]) -> Player {
+        let mut player = Player::new(None);
+        player.queue = paths.iter().map(|p| Track::with_path(p)).collect();
+        player
+    }
+
+    fn queue_paths(player: &Player) -> Vec<String> {
+        player.queue.iter().map(|t| t.path.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn move_track_forward_to_index() {
+        let mut player = player_with_queue(&["a", "b", "c", "d"]);
+        move_tracks_to_index(&mut player, &[0], 2);
+        assert_eq!(queue_paths(&player), ["b", "c", "a", "d"]);
+    }
+
+    #[test]
+    fn move_track_backward_to_index() {
+        let mut player = player_with_queue(&["a", "b", "c", "d"]);
+        move_tracks_to_index(&mut player, &[3], 1);
+        assert_eq!(queue_paths(&player), ["a", "d", "b", "c"]);
+    }
+
+    #[test]
+    fn move_track_to_last_index() {
+        let mut player = player_with_queue(&["a", "b", "c", "d"]);
+        move_tracks_to_index(&mut player, &[1], 3);
+        assert_eq!(queue_paths(&player), ["a", "c", "d", "b"]);
+    }
+
+    #[test]
+    fn move_tracks_to_index_keeps_their_order() {
+        let mut player = player_with_queue(&["a", "b", "c", "d", "e"]);
+        move_tracks_to_index(&mut player, &[3, 0], 1);
+        assert_eq!(queue_paths(&player), ["b", "a", "d", "c", "e"]);
+    }
+}
@@ -1,27 +1,87 @@
-use crate::{Track, track::load_from_file};
+use crate::track::{Track, load_track};
 use anyhow::{Context, Result, anyhow, bail};
 use fully_pub::fully_pub;
 use log::warn;
-use m3u::{Entry, Reader, Writer, path_entry};
+use m3u::{Entry, EntryExtReaderConstructionError, ReadEntryExtError, Reader, Url, Writer, path_entry};
 use std::{
+    collections::{BTreeMap, HashMap, HashSet},
     fs::{self, File, metadata},
+    io::Write,
     path::{Path, PathBuf},
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
 use walkdir::WalkDir;
 
 #[fully_pub]
 struct Playlist {
     name: String,
     creation_date_time: SystemTime,
-    tracks: Vec<Track>, // Duplicates of tracks are not allowed.
-    m3u_path: PathBuf,
+    tracks: Vec<Track>, // Duplicates of tracks are only allowed if allow_duplicates is set.
+    file_path: PathBuf,
+    pinned: bool,                       // Pinned playlists are shown at the top of the sidebar.
+    allow_duplicates: bool,             // Whether a track may be added more than once, e.g. for a set list.
+    missing_entries: Vec<MissingEntry>, // Entries whose files could not be loaded. They are kept so that saving doesn't drop them.
+}
+
+// An entry as read from a playlist file, before its track has been loaded.
+// Entries whose files can't be loaded are kept as is, with the file's info (if there was any) used to display them.
+#[fully_pub]
+#[derive(Clone)]
+struct PlaylistEntry {
+    path: PathBuf, // As written in the playlist file, so it may be relative to the playlist.
+    duration: Option<Duration>,
+    artist: Option<String>,
+    title: Option<String>,
+}
+
+// Missing entries are in the order they were read, and each remembers where it was among the tracks so that saving
+// writes it back in place.
+#[fully_pub]
+#[derive(Clone)]
+struct MissingEntry {
+    entry: PlaylistEntry,
+    position: usize, // The number of tracks before the entry.
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum PlaylistFormat {
+    M3u,
+    M3u8, // The same as m3u, but the file is always UTF-8. Gem Player writes both as UTF-8.
+    Pls,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u => "m3u",
+            PlaylistFormat::M3u8 => "m3u8",
+            PlaylistFormat::Pls => "pls",
+            PlaylistFormat::Xspf => "xspf",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u => "M3U",
+            PlaylistFormat::M3u8 => "M3U8",
+            PlaylistFormat::Pls => "PLS",
+            PlaylistFormat::Xspf => "XSPF",
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<PlaylistFormat> {
+        let extension = path.extension()?;
+        PlaylistFormat::iter().find(|format| extension.eq_ignore_ascii_case(format.extension()))
+    }
 }
 
 impl PartialEq for Playlist {
     #[inline]
     fn eq(&self, other: &Playlist) -> bool {
-        self.m3u_path == other.m3u_path
+        self.file_path == other.file_path
     }
 }
 
@@ -32,16 +92,16 @@ pub trait PlaylistRetrieval {
 
 impl PlaylistRetrieval for Vec<Playlist> {
     fn get_by_path(&self, path: &Path) -> &Playlist {
-        self.iter().find(|p| p.m3u_path == path).expect("Playlist not found")
+        self.iter().find(|p| p.file_path == path).expect("Playlist not found")
     }
 
     fn get_by_path_mut(&mut self, path: &Path) -> &mut Playlist {
-        self.iter_mut().find(|p| p.m3u_path == path).expect("Playlist not found")
+        self.iter_mut().find(|p| p.file_path == path).expect("Playlist not found")
     }
 }
 
 pub fn add_to_playlist(playlist: &mut Playlist, track: Track) -> Result<()> {
-    if playlist.tracks.contains(&track) {
+    if !playlist.allow_duplicates && playlist.tracks.contains(&track) {
         bail!(
             "The track '{}' is already in the playlist. Duplicates are not allowed.",
             track.path.display()
@@ -49,7 +109,11 @@ pub fn add_to_playlist(playlist: &mut Playlist, track: Track) -> Result<()> {
     }
 
     playlist.tracks.push(track);
-    save_to_m3u(playlist).context("Failed to persist playlist after adding track")?;
+
+    if let Err(e) = save_to_file(playlist) {
+        playlist.tracks.pop(); // Roll back so that memory matches what is on disk.
+        return Err(e.context("Failed to persist playlist after adding track"));
+    }
 
     Ok(())
 }
@@ -61,12 +125,110 @@ pub fn remove_from_playlist(playlist: &mut Playlist, track_key: &Path) -> Result
         .position(|t: &Track| t.path == track_key)
         .ok_or_else(|| anyhow!("Track '{}' not found in playlist", track_key.display()))?;
 
-    playlist.tracks.remove(index);
-    save_to_m3u(playlist).context("Failed to persist playlist after removing track")?;
+    let track = playlist.tracks.remove(index);
+    let previous_missing_entries = playlist.missing_entries.clone();
+    for missing in playlist.missing_entries.iter_mut().filter(|m| m.position > index) {
+        missing.position -= 1;
+    }
+
+    if let Err(e) = save_to_file(playlist) {
+        // Roll back so that memory matches what is on disk.
+        playlist.tracks.insert(index, track);
+        playlist.missing_entries = previous_missing_entries;
+        return Err(e.context("Failed to persist playlist after removing track"));
+    }
+
+    Ok(())
+}
+
+// Replaces a missing entry with a track that the user located, at the entry's position.
+pub fn relink_missing_entry(playlist: &mut Playlist, missing_path: &Path, track: Track) -> Result<()> {
+    let index = playlist
+        .missing_entries
+        .iter()
+        .position(|m| m.entry.path == missing_path)
+        .ok_or_else(|| anyhow!("'{}' is not a missing entry of the playlist", missing_path.display()))?;
+
+    if !playlist.allow_duplicates && playlist.tracks.contains(&track) {
+        bail!(
+            "The track '{}' is already in the playlist. Duplicates are not allowed.",
+            track.path.display()
+        );
+    }
+
+    let previous_missing_entries = playlist.missing_entries.clone();
+    let position = playlist.missing_entries.remove(index).position.min(playlist.tracks.len());
+    playlist.tracks.insert(position, track);
+
+    // The entries after the relinked one now have one more track before them.
+    for missing in &mut playlist.missing_entries[index..] {
+        missing.position += 1;
+    }
+
+    if let Err(e) = save_to_file(playlist) {
+        // Roll back so that memory matches what is on disk.
+        playlist.tracks.remove(position);
+        playlist.missing_entries = previous_missing_entries;
+        return Err(e.context("Failed to persist playlist after relinking entry"));
+    }
 
     Ok(())
 }
 
+// Returns the number of entries that were removed.
+pub fn remove_missing_entries(playlist: &mut Playlist) -> Result<usize> {
+    let missing_entries = std::mem::take(&mut playlist.missing_entries);
+
+    if let Err(e) = save_to_file(playlist) {
+        playlist.missing_entries = missing_entries; // Roll back so that memory matches what is on disk.
+        return Err(e.context("Failed to persist playlist after removing missing entries"));
+    }
+
+    Ok(missing_entries.len())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter)]
+pub enum PathStyle {
+    #[default]
+    Relative, // Relative to the exported playlist, which keeps working when the playlist and music are moved together.
+    Absolute,
+}
+
+impl PathStyle {
+    pub fn label(self) -> &'static str {
+        match self {
+            PathStyle::Relative => "Relative",
+            PathStyle::Absolute => "Absolute",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter)]
+pub enum PathSeparator {
+    #[default]
+    Native,
+    Windows,
+    Unix,
+}
+
+impl PathSeparator {
+    pub fn label(self) -> &'static str {
+        match self {
+            PathSeparator::Native => "This computer's",
+            PathSeparator::Windows => "Windows (\\)",
+            PathSeparator::Unix => "UNIX (/)",
+        }
+    }
+}
+
+// How paths are written when exporting a playlist for use elsewhere, e.g. on a phone or another computer.
+#[fully_pub]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct ExportOptions {
+    path_style: PathStyle,
+    separator: PathSeparator,
+}
+
 pub fn load_playlists_from_directory(directory: &Path) -> Vec<Playlist> {
     let mut playlists = Vec::new();
 
@@ -78,11 +240,11 @@ pub fn load_playlists_from_directory(directory: &Path) -> Vec<Playlist> {
     }) {
         let path = entry.path();
 
-        if !is_m3u_file(path) {
+        if !is_playlist_file(path) {
             continue;
         }
 
-        match load_from_m3u(path) {
+        match load_from_file(path) {
             Ok(playlist) => playlists.push(playlist),
             Err(e) => {
                 warn!("Failed to load playlist {:?}: {}", path, e);
@@ -90,68 +252,376 @@ pub fn load_playlists_from_directory(directory: &Path) -> Vec<Playlist> {
         }
     }
 
+    let pinned = load_playlist_set(directory, PINNED_PLAYLISTS_FILENAME);
+    let allowing_duplicates = load_playlist_set(directory, DUPLICATES_ALLOWED_FILENAME);
+    for playlist in &mut playlists {
+        playlist.pinned = pinned.contains(&playlist.file_path);
+        playlist.allow_duplicates = allowing_duplicates.contains(&playlist.file_path);
+    }
+
     playlists.sort_by_key(|p| p.creation_date_time);
     playlists
 }
 
-pub fn is_m3u_file(path: &Path) -> bool {
-    path.is_file() && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("m3u"))
+// Per-playlist settings are stored as sets of playlists in the library directory, so that they work with every format.
+// Each set is a list of playlist file paths (relative to the library directory), one per line.
+const PINNED_PLAYLISTS_FILENAME: &str = ".pinned_playlists";
+const DUPLICATES_ALLOWED_FILENAME: &str = ".playlists_allowing_duplicates";
+
+fn load_playlist_set(directory: &Path, filename: &str) -> HashSet<PathBuf> {
+    let Ok(contents) = fs::read_to_string(directory.join(filename)) else {
+        return HashSet::new(); // The set hasn't been saved yet.
+    };
+
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| directory.join(line))
+        .collect()
+}
+
+fn save_playlist_set(playlists: &[Playlist], directory: &Path, filename: &str, is_in_set: impl Fn(&Playlist) -> bool) -> Result<()> {
+    let contents: String = playlists
+        .iter()
+        .filter(|p| is_in_set(p))
+        .map(|p| {
+            let path = p.file_path.strip_prefix(directory).unwrap_or(&p.file_path);
+            format!("{}\n", path.display())
+        })
+        .collect();
+
+    let path = directory.join(filename);
+    fs::write(&path, contents).with_context(|| format!("Failed to write '{}'", path.display()))?;
+
+    Ok(())
+}
+
+// Rewrites the per-playlist settings from the playlists. Call this whenever a setting is changed, or a playlist is renamed or deleted.
+pub fn save_playlist_settings(playlists: &[Playlist], directory: &Path) -> Result<()> {
+    save_playlist_set(playlists, directory, PINNED_PLAYLISTS_FILENAME, |p| p.pinned).context("Failed to save pinned playlists")?;
+    save_playlist_set(playlists, directory, DUPLICATES_ALLOWED_FILENAME, |p| p.allow_duplicates)
+        .context("Failed to save duplicate settings")?;
+
+    Ok(())
+}
+
+// The order that playlists are displayed in: pinned first, otherwise keeping the existing order.
+pub fn display_order(playlists: &[Playlist]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..playlists.len()).collect();
+    order.sort_by_key(|&i| !playlists[i].pinned); // The sort is stable.
+    order
+}
+
+pub fn is_playlist_file(path: &Path) -> bool {
+    path.is_file() && PlaylistFormat::from_path(path).is_some()
+}
+
+// The playlist is written to a temporary file which then replaces the playlist file, so a failed save leaves the old file intact.
+pub fn save_to_file(playlist: &mut Playlist) -> Result<()> {
+    let mut temp_path = playlist.file_path.clone().into_os_string();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+
+    let format = PlaylistFormat::from_path(&playlist.file_path).unwrap_or(PlaylistFormat::M3u);
+    let result = write_playlist_file(playlist, &temp_path, format, None).and_then(|_| {
+        fs::rename(&temp_path, &playlist.file_path)
+            .with_context(|| format!("Failed to replace playlist file '{}'", playlist.file_path.display()))
+    });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+// Writes a copy of the playlist in the format given by the path's extension, e.g. for use in other players.
+pub fn export(playlist: &Playlist, path: &Path, options: &ExportOptions) -> Result<()> {
+    let Some(format) = PlaylistFormat::from_path(path) else {
+        bail!("'{}' does not have a supported playlist extension", path.display());
+    };
+
+    // Xspf locations are URIs, which always use '/'.
+    let options = match format {
+        PlaylistFormat::Xspf => ExportOptions {
+            separator: PathSeparator::Native,
+            ..*options
+        },
+        _ => *options,
+    };
+
+    write_playlist_file(playlist, path, format, Some(&options))
+}
+
+// Options is None when saving the playlist in the library.
+fn write_playlist_file(playlist: &Playlist, path: &Path, format: PlaylistFormat, options: Option<&ExportOptions>) -> Result<()> {
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let entries = playlist_entries(playlist, directory, options);
+
+    let mut file = File::create(path).with_context(|| format!("Failed to create playlist file '{}'", path.display()))?;
+
+    match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => write_m3u(&mut file, &entries)?,
+        PlaylistFormat::Pls => write_pls(&mut file, &entries)?,
+        PlaylistFormat::Xspf => write_xspf(&mut file, &playlist.name, &entries)?,
+    }
+
+    file.sync_all().context("Failed to sync playlist file")?;
+
+    Ok(())
+}
+
+// The entries to write for the playlist, with their paths written as the export options ask. Without options, paths
+// inside of the directory that the file is written to are relative and all others are absolute.
+fn playlist_entries(playlist: &Playlist, directory: &Path, options: Option<&ExportOptions>) -> Vec<PlaylistEntry> {
+    let playlist_directory = playlist.file_path.parent().unwrap_or_else(|| Path::new(""));
+
+    let track_entry = |track: &Track| PlaylistEntry {
+        path: track.path.clone(),
+        duration: Some(track.duration),
+        artist: track.artist.clone(),
+        title: track.title.clone(),
+    };
+
+    // Missing entries are written as they were read so that they can still be located later.
+    let missing_entry = |missing: &MissingEntry| PlaylistEntry {
+        path: playlist_directory.join(&missing.entry.path), // Joining an absolute path leaves it unchanged.
+        ..missing.entry.clone()
+    };
+
+    let mut entries = Vec::with_capacity(playlist.tracks.len() + playlist.missing_entries.len());
+    let mut missing_entries = playlist.missing_entries.iter().peekable();
+    for (i, track) in playlist.tracks.iter().enumerate() {
+        while let Some(missing) = missing_entries.next_if(|m| m.position <= i) {
+            entries.push(missing_entry(missing));
+        }
+        entries.push(track_entry(track));
+    }
+    entries.extend(missing_entries.map(missing_entry));
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let path = match options {
+                None => entry.path.strip_prefix(directory).unwrap_or(&entry.path).to_path_buf(),
+                Some(options) => {
+                    let path = match options.path_style {
+                        PathStyle::Relative => relative_path(&entry.path, directory).unwrap_or(entry.path.clone()),
+                        PathStyle::Absolute => entry.path.clone(),
+                    };
+
+                    with_separator(path, options.separator)
+                }
+            };
+
+            PlaylistEntry { path, ..entry }
+        })
+        .collect()
 }
 
-pub fn save_to_m3u(playlist: &mut Playlist) -> Result<()> {
-    let mut file =
-        File::create(&playlist.m3u_path).with_context(|| format!("Failed to create playlist file '{}'", playlist.m3u_path.display()))?;
+// The path from the base directory to the path, stepping out with ".." as needed. Both paths must be absolute.
+// Returns None if there is no such path, e.g. if they are on different drives on Windows.
+fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+
+    // The root (and drive on Windows) must match, otherwise there is no path between them.
+    match (path_components.peek(), base_components.peek()) {
+        (Some(a), Some(b)) if a == b => {}
+        _ => return None,
+    }
 
-    let mut writer = Writer::new(&mut file);
+    while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek()) {
+        if a != b {
+            break;
+        }
+        path_components.next();
+        base_components.next();
+    }
 
-    let directory = playlist.m3u_path.parent().unwrap_or_else(|| Path::new(""));
+    let mut relative = PathBuf::new();
+    for _ in base_components {
+        relative.push("..");
+    }
+    relative.extend(path_components);
 
-    for track in &playlist.tracks {
-        let path = track.path.strip_prefix(directory).unwrap_or(&track.path);
+    Some(relative)
+}
 
-        let entry = path_entry(path);
+fn with_separator(path: PathBuf, separator: PathSeparator) -> PathBuf {
+    match separator {
+        PathSeparator::Native => path,
+        PathSeparator::Windows => PathBuf::from(path.to_string_lossy().replace('/', "\\")),
+        PathSeparator::Unix => PathBuf::from(path.to_string_lossy().replace('\\', "/")),
+    }
+}
+
+fn write_m3u(file: &mut File, entries: &[PlaylistEntry]) -> Result<()> {
+    let mut writer = Writer::new_ext(file).context("Failed to write playlist header")?;
+
+    for entry in entries {
+        let duration_secs = entry.duration.map_or(-1.0, |d| d.as_secs() as f64); // -1 means that the length is unknown.
+
+        let entry_ext = path_entry(&entry.path).extend(duration_secs, display_name(entry));
         writer
-            .write_entry(&entry)
-            .with_context(|| format!("Failed to write track '{}' to playlist", track.path.display()))?;
+            .write_entry(&entry_ext)
+            .with_context(|| format!("Failed to write entry '{}' to playlist", entry.path.display()))?;
     }
 
+    writer.flush().context("Failed to flush playlist file")?;
+
     Ok(())
 }
 
-pub fn load_from_m3u(path: &Path) -> Result<Playlist> {
-    if !is_m3u_file(path) {
-        bail!("The file '{}' is not an M3U playlist", path.display());
+fn write_pls(file: &mut File, entries: &[PlaylistEntry]) -> Result<()> {
+    let mut contents = String::from("[playlist]\n");
+
+    for (i, entry) in entries.iter().enumerate() {
+        let number = i + 1;
+        let length = entry.duration.map_or(-1, |d| d.as_secs() as i64); // -1 means that the length is unknown.
+
+        contents.push_str(&format!("File{}={}\n", number, entry.path.display()));
+        contents.push_str(&format!("Title{}={}\n", number, display_name(entry)));
+        contents.push_str(&format!("Length{}={}\n", number, length));
     }
 
+    contents.push_str(&format!("NumberOfEntries={}\nVersion=2\n", entries.len()));
+
+    file.write_all(contents.as_bytes()).context("Failed to write playlist file")?;
+
+    Ok(())
+}
+
+fn write_xspf(file: &mut File, name: &str, entries: &[PlaylistEntry]) -> Result<()> {
+    let mut contents = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    contents.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+    contents.push_str(&format!("  <title>{}</title>\n", escape_xml(name)));
+    contents.push_str("  <trackList>\n");
+
+    for entry in entries {
+        // Relative paths are written as relative URIs and absolute paths as file URIs.
+        let location = if entry.path.is_relative() {
+            relative_uri(&entry.path)
+        } else {
+            match Url::from_file_path(&entry.path) {
+                Ok(url) => url.to_string(),
+                Err(_) => {
+                    warn!("Skipping entry with an invalid path: {}", entry.path.display());
+                    continue;
+                }
+            }
+        };
+
+        contents.push_str("    <track>\n");
+        contents.push_str(&format!("      <location>{}</location>\n", escape_xml(&location)));
+        if let Some(title) = &entry.title {
+            contents.push_str(&format!("      <title>{}</title>\n", escape_xml(title)));
+        }
+        if let Some(artist) = &entry.artist {
+            contents.push_str(&format!("      <creator>{}</creator>\n", escape_xml(artist)));
+        }
+        if let Some(duration) = entry.duration {
+            contents.push_str(&format!("      <duration>{}</duration>\n", duration.as_millis()));
+        }
+        contents.push_str("    </track>\n");
+    }
+
+    contents.push_str("  </trackList>\n</playlist>\n");
+
+    file.write_all(contents.as_bytes()).context("Failed to write playlist file")?;
+
+    Ok(())
+}
+
+fn relative_uri(path: &Path) -> String {
+    let segments: Vec<String> = path
+        .components()
+        .map(|component| {
+            let segment = component.as_os_str().to_string_lossy();
+            segment
+                .bytes()
+                .map(|byte| match byte {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+                    _ => format!("%{:02X}", byte),
+                })
+                .collect()
+        })
+        .collect();
+
+    segments.join("/")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// The "Artist - Title" name that m3u and pls files store in place of separate tags.
+fn display_name(entry: &PlaylistEntry) -> String {
+    let title = entry.title.clone().unwrap_or_else(|| {
+        // The path may use either separator after exporting, so it is split by hand.
+        let path = entry.path.to_string_lossy();
+        let file_name = path.rsplit(['/', '\\']).next().unwrap_or_default();
+        let file_stem = file_name.rsplit_once('.').map_or(file_name, |(stem, _)| stem);
+        file_stem.to_owned()
+    });
+
+    match &entry.artist {
+        Some(artist) => format!("{} - {}", artist, title),
+        None => title,
+    }
+}
+
+// The reverse of display_name.
+fn parse_display_name(name: &str) -> (Option<String>, Option<String>) {
+    let name = name.trim();
+    if name.is_empty() {
+        return (None, None);
+    }
+
+    match name.split_once(" - ") {
+        Some((artist, title)) => (Some(artist.to_owned()), Some(title.to_owned())),
+        None => (None, Some(name.to_owned())),
+    }
+}
+
+pub fn load_from_file(path: &Path) -> Result<Playlist> {
+    let Some(format) = PlaylistFormat::from_path(path).filter(|_| path.is_file()) else {
+        bail!("The file '{}' is not a supported playlist", path.display());
+    };
+
     let name = path
         .file_stem()
         .map(|stem| stem.to_string_lossy().into_owned())
         .unwrap_or_else(|| "Unnamed Playlist".to_string());
 
     let directory = path.parent().unwrap_or_else(|| Path::new(""));
-    let mut reader = Reader::open(path).with_context(|| format!("Failed to read playlist file '{}'", path.display()))?;
 
-    let mut tracks = Vec::new();
+    let entries = match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => read_m3u_entries(path)?,
+        PlaylistFormat::Pls => read_pls_entries(path)?,
+        PlaylistFormat::Xspf => read_xspf_entries(path)?,
+    };
 
-    for maybe_entry in reader.entries() {
-        let entry = match maybe_entry {
-            Ok(e) => e,
-            Err(err) => {
-                warn!("Skipping invalid M3U entry: {}", err);
-                continue;
-            }
-        };
+    let mut tracks = Vec::new();
+    let mut missing_entries = Vec::new();
 
-        match entry {
-            Entry::Path(path) => {
-                let full_path = if path.is_absolute() { path } else { directory.join(path) };
+    for entry in entries {
+        let full_path = directory.join(&entry.path); // Joining an absolute path leaves it unchanged.
 
-                match load_from_file(&full_path) {
-                    Ok(track) => tracks.push(track),
-                    Err(err) => warn!("Skipping invalid track '{}': {}", full_path.display(), err),
-                }
+        match load_track(&full_path) {
+            Ok(track) => tracks.push(track),
+            Err(err) => {
+                warn!("Failed to load playlist entry '{}': {}", full_path.display(), err);
+                missing_entries.push(MissingEntry {
+                    entry,
+                    position: tracks.len(),
+                });
             }
-            Entry::Url(url) => warn!("Skipping URL entry: {}", url), // We do not support url tracks.
         }
     }
 
@@ -163,38 +633,310 @@ pub fn load_from_m3u(path: &Path) -> Result<Playlist> {
         name,
         creation_date_time,
         tracks,
-        m3u_path: path.to_path_buf(),
+        file_path: path.to_path_buf(),
+        pinned: false,
+        allow_duplicates: false,
+        missing_entries,
     })
 }
 
-pub fn rename(playlist: &mut Playlist, new_name: String) -> Result<()> {
-    let directory = playlist
-        .m3u_path
-        .parent()
-        .ok_or_else(|| anyhow!("Playlist path has no parent directory"))?;
+// Reads the entries of both plain and extended m3u files. Entries of an extended file may still lack #EXTINF info.
+fn read_m3u_entries(path: &Path) -> Result<Vec<PlaylistEntry>> {
+    let read_error = || format!("Failed to read playlist file '{}'", path.display());
+
+    let contents = read_m3u_text(path).with_context(read_error)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut entries = Vec::new();
+
+    match Reader::new_ext(contents.as_bytes()) {
+        Ok(mut reader) => {
+            for maybe_entry in reader.entry_exts() {
+                match maybe_entry {
+                    Ok(entry_ext) => entries.push((entry_ext.entry, Some(entry_ext.extinf))),
+                    Err(ReadEntryExtError::ExtInfNotFound(entry)) => entries.push((entry, None)),
+                    Err(err) => warn!("Skipping invalid M3U entry: {}", err),
+                }
+            }
+        }
+        Err(EntryExtReaderConstructionError::HeaderNotFound) => {
+            let mut reader = Reader::new(contents.as_bytes());
+            for maybe_entry in reader.entries() {
+                match maybe_entry {
+                    Ok(entry) => entries.push((entry, None)),
+                    Err(err) => warn!("Skipping invalid M3U entry: {}", err),
+                }
+            }
+        }
+        Err(err) => return Err(anyhow!("{}", err)).with_context(read_error),
+    }
+
+    let entries = entries
+        .into_iter()
+        .filter_map(|(entry, extinf)| {
+            let entry_path = match entry {
+                Entry::Path(entry_path) => decode_entry_path(entry_path, directory),
+                Entry::Url(url) if url.scheme() == "file" => match url.to_file_path() {
+                    Ok(entry_path) => entry_path,
+                    Err(()) => {
+                        warn!("Skipping invalid file URL entry: {}", url);
+                        return None;
+                    }
+                },
+                Entry::Url(url) => {
+                    warn!("Skipping URL entry: {}", url); // We do not support url tracks.
+                    return None;
+                }
+            };
+
+            let duration = extinf
+                .as_ref()
+                .filter(|e| e.duration_secs.is_finite() && e.duration_secs >= 0.0)
+                .map(|e| Duration::from_secs_f64(e.duration_secs));
+            let (artist, title) = extinf.map(|e| parse_display_name(&e.name)).unwrap_or_default();
+
+            Some(PlaylistEntry {
+                path: entry_path,
+                duration,
+                artist,
+                title,
+            })
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+// The text of an m3u file without the byte order mark that some players write. Plain m3u files predate UTF-8, so
+// those that aren't valid UTF-8 are read as Latin-1.
+fn read_m3u_text(path: &Path) -> Result<String> {
+    let bytes = fs::read(path)?;
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(&bytes);
+
+    let text = match String::from_utf8(bytes.to_vec()) {
+        Ok(text) => text,
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    };
+
+    Ok(text)
+}
+
+// Some players percent-encode paths, e.g. "My%20Song.mp3", as if they were URLs. These are decoded, unless a file
+// with the name as written exists.
+fn decode_entry_path(entry_path: PathBuf, directory: &Path) -> PathBuf {
+    if directory.join(&entry_path).exists() {
+        return entry_path;
+    }
+
+    let Some(decoded) = entry_path.to_str().and_then(percent_decode) else {
+        return entry_path;
+    };
+
+    PathBuf::from(decoded)
+}
+
+// None if the text has no percent-encoded characters, or doesn't decode to UTF-8.
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut changed = false;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let is_escape = bytes[i] == b'%' && bytes.get(i + 1..i + 3).is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+
+        if is_escape && let Ok(byte) = u8::from_str_radix(&text[i + 1..i + 3], 16) {
+            decoded.push(byte);
+            changed = true;
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    if !changed {
+        return None;
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+// Pls files are ini files where each entry is spread across numbered keys, e.g. "File1", "Title1", and "Length1".
+fn read_pls_entries(path: &Path) -> Result<Vec<PlaylistEntry>> {
+    let contents = fs::read_to_string(path).with_context(|| format!("Failed to read playlist file '{}'", path.display()))?;
+
+    let mut files: BTreeMap<u32, String> = BTreeMap::new();
+    let mut titles: HashMap<u32, String> = HashMap::new();
+    let mut lengths: HashMap<u32, i64> = HashMap::new();
+
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue; // The [playlist] header, or a blank line.
+        };
+
+        let key = key.trim().to_lowercase();
+        let Some(number_start) = key.find(|c: char| c.is_ascii_digit()) else {
+            continue; // Keys like NumberOfEntries and Version.
+        };
+
+        let Ok(number) = key[number_start..].parse::<u32>() else {
+            continue;
+        };
+
+        let value = value.trim().to_owned();
+        match &key[..number_start] {
+            "file" => {
+                files.insert(number, value);
+            }
+            "title" => {
+                titles.insert(number, value);
+            }
+            "length" => {
+                if let Ok(length) = value.parse() {
+                    lengths.insert(number, length);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let entries = files
+        .into_iter()
+        .filter_map(|(number, file)| {
+            let entry_path = if file.contains("://") {
+                // File URIs are allowed alongside plain paths. Anything else is a stream.
+                let url = Url::parse(&file).ok().filter(|url| url.scheme() == "file");
+                let Some(entry_path) = url.and_then(|url| url.to_file_path().ok()) else {
+                    warn!("Skipping URL entry: {}", file);
+                    return None;
+                };
+                entry_path
+            } else {
+                PathBuf::from(file)
+            };
+
+            let duration = lengths
+                .get(&number)
+                .filter(|length| **length >= 0)
+                .map(|length| Duration::from_secs(*length as u64));
+            let (artist, title) = titles.get(&number).map(|name| parse_display_name(name)).unwrap_or_default();
+
+            Some(PlaylistEntry {
+                path: entry_path,
+                duration,
+                artist,
+                title,
+            })
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+fn read_xspf_entries(path: &Path) -> Result<Vec<PlaylistEntry>> {
+    let contents = fs::read_to_string(path).with_context(|| format!("Failed to read playlist file '{}'", path.display()))?;
+    let document = roxmltree::Document::parse(&contents).with_context(|| format!("Failed to parse playlist file '{}'", path.display()))?;
+
+    // Locations are URIs, which may be relative to the playlist.
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let base_url = Url::from_directory_path(directory).map_err(|_| anyhow!("Invalid playlist directory '{}'", directory.display()))?;
+
+    let entries = document
+        .descendants()
+        .filter(|node| node.has_tag_name("track"))
+        .filter_map(|track| {
+            let child_text = |name: &str| {
+                let child = track.children().find(|child| child.has_tag_name(name))?;
+                child.text().map(|text| text.trim().to_owned()).filter(|text| !text.is_empty())
+            };
+
+            let location = child_text("location")?;
+            let url = base_url.join(&location).ok().filter(|url| url.scheme() == "file");
+            let Some(entry_path) = url.and_then(|url| url.to_file_path().ok()) else {
+                warn!("Skipping URL entry: {}", location); // We do not support url tracks.
+                return None;
+            };
 
+            let duration = child_text("duration")
+                .and_then(|millis| millis.parse().ok())
+                .map(Duration::from_millis);
+
+            Some(PlaylistEntry {
+                path: entry_path,
+                duration,
+                artist: child_text("creator"),
+                title: child_text("title"),
+            })
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+// Checks whether the playlist can be renamed to new_name, returning the name as it would be saved.
+// This is cheap enough to run on every keystroke since it only compares against the loaded playlists.
+pub fn validate_name(new_name: &str, playlist: &Playlist, playlists: &[Playlist]) -> Result<String> {
     let sanitized_name = sanitize_filename::sanitize(new_name.trim());
     if sanitized_name.is_empty() {
         bail!("Playlist name cannot be empty.");
     }
 
-    let new_filename = format!("{}.m3u", sanitized_name);
+    // Names differing only in case would map to the same file on case-insensitive file systems.
+    let lowercase_name = sanitized_name.to_lowercase();
+    let name_is_taken = playlists
+        .iter()
+        .any(|p| p.file_path != playlist.file_path && p.name.to_lowercase() == lowercase_name);
+    if name_is_taken {
+        bail!("A playlist with this name already exists.");
+    }
+
+    Ok(sanitized_name)
+}
+
+// Returns the new path of the playlist.
+pub fn rename(playlist_key: &Path, new_name: &str, playlists: &mut [Playlist]) -> Result<PathBuf> {
+    let index = playlists
+        .iter()
+        .position(|p| p.file_path == playlist_key)
+        .ok_or_else(|| anyhow!("Playlist '{}' not found", playlist_key.display()))?;
+
+    let sanitized_name = validate_name(new_name, &playlists[index], playlists)?;
+
+    let playlist = &mut playlists[index];
+    let directory = playlist
+        .file_path
+        .parent()
+        .ok_or_else(|| anyhow!("Playlist path has no parent directory"))?;
+
+    let format = PlaylistFormat::from_path(&playlist.file_path).unwrap_or(PlaylistFormat::M3u);
+    let new_filename = format!("{}.{}", sanitized_name, format.extension());
     let new_path = directory.join(new_filename);
 
+    if new_path == playlist.file_path {
+        return Ok(new_path); // The name is unchanged.
+    }
+
     if new_path.exists() {
         bail!("A playlist with this name already exists");
     }
 
-    fs::rename(&playlist.m3u_path, &new_path)
-        .with_context(|| format!("Failed to rename '{}' to '{}'", playlist.m3u_path.display(), new_path.display()))?;
+    fs::rename(&playlist.file_path, &new_path)
+        .with_context(|| format!("Failed to rename '{}' to '{}'", playlist.file_path.display(), new_path.display()))?;
 
     playlist.name = sanitized_name;
-    playlist.m3u_path = new_path;
+    playlist.file_path = new_path.clone();
 
-    Ok(())
+    Ok(new_path)
 }
 
 pub fn create(name: String, directory: &Path) -> Result<Playlist> {
+    create_with_format(name, directory, PlaylistFormat::M3u) // New playlists are m3u unless they are copies.
+}
+
+fn create_with_format(name: String, directory: &Path, format: PlaylistFormat) -> Result<Playlist> {
     let sanitized_name = sanitize_filename::sanitize(name.trim());
     if sanitized_name.is_empty() {
         bail!("Playlist name cannot be empty.");
@@ -204,8 +946,7 @@ pub fn create(name: String, directory: &Path) -> Result<Playlist> {
         bail!("The specified directory does not exist: {}", directory.display());
     }
 
-    let extension = ".m3u";
-    let filename = format!("{}{}", sanitized_name, extension);
+    let filename = format!("{}.{}", sanitized_name, format.extension());
     let file_path = directory.join(&filename);
 
     if file_path.exists() {
@@ -218,25 +959,215 @@ pub fn create(name: String, directory: &Path) -> Result<Playlist> {
         name: sanitized_name,
         creation_date_time: SystemTime::now(),
         tracks: Vec::new(),
-        m3u_path: file_path,
+        file_path,
+        pinned: false,
+        allow_duplicates: false,
+        missing_entries: Vec::new(),
+    };
+
+    save_to_file(&mut playlist).context("Failed to initialize playlist file contents")?;
+
+    Ok(playlist)
+}
+
+// Copies the playlist into a new file next to it, named like "Name (copy)". The copy keeps the format and settings,
+// except that it isn't pinned.
+pub fn duplicate(playlist: &Playlist, playlists: &[Playlist]) -> Result<Playlist> {
+    let directory = playlist
+        .file_path
+        .parent()
+        .ok_or_else(|| anyhow!("Playlist path has no parent directory"))?;
+    let format = PlaylistFormat::from_path(&playlist.file_path).unwrap_or(PlaylistFormat::M3u);
+
+    let name_is_free = |name: &String| {
+        let file_path = directory.join(format!("{}.{}", name, format.extension()));
+        !file_path.exists() && !playlists.iter().any(|p| &p.name == name)
+    };
+
+    let name = (1..)
+        .map(|n| match n {
+            1 => format!("{} (copy)", playlist.name),
+            n => format!("{} (copy {})", playlist.name, n),
+        })
+        .find(name_is_free)
+        .expect("There is always a free name eventually");
+
+    let mut copy = create_with_format(name, directory, format)?;
+    copy.tracks = playlist.tracks.clone();
+    copy.missing_entries = playlist.missing_entries.clone();
+    copy.allow_duplicates = playlist.allow_duplicates;
+
+    save_to_file(&mut copy).context("Failed to save the copied tracks")?;
+
+    Ok(copy)
+}
+
+// Creates a playlist holding the tracks, e.g. one imported from another player. If the name is taken, a number is added
+// to it, like "Name (2)".
+pub fn create_with_tracks(name: &str, tracks: Vec<Track>, directory: &Path, playlists: &[Playlist]) -> Result<Playlist> {
+    let format = PlaylistFormat::M3u;
+
+    let name_is_free = |name: &String| {
+        let file_path = directory.join(format!("{}.{}", sanitize_filename::sanitize(name), format.extension()));
+        !file_path.exists() && !playlists.iter().any(|p| &p.name == name)
     };
 
-    save_to_m3u(&mut playlist).context("Failed to initialize playlist file contents")?;
+    let name = (1..)
+        .map(|n| match n {
+            1 => name.trim().to_owned(),
+            n => format!("{} ({})", name.trim(), n),
+        })
+        .find(name_is_free)
+        .expect("There is always a free name eventually");
+
+    let mut playlist = create_with_format(name, directory, format)?;
+    playlist.tracks = tracks;
+
+    save_to_file(&mut playlist).context("Failed to save the playlist's tracks")?;
 
     Ok(playlist)
 }
 
-/// Removes the playlist from the list and deletes the associated m3u file.
+/// Removes the playlist from the list and deletes the associated playlist file.
 pub fn delete(playlist_key: &Path, playlists: &mut Vec<Playlist>) -> Result<()> {
     let index = playlists
         .iter()
-        .position(|p| p.m3u_path == playlist_key)
+        .position(|p| p.file_path == playlist_key)
         .ok_or_else(|| anyhow!("Playlist '{}' not found in library", playlist_key.display()))?;
 
     let playlist = playlists.remove(index);
 
-    // Send the m3u file to the trash!
-    trash::delete(&playlist.m3u_path).with_context(|| format!("Failed to delete playlist file '{}'", playlist.m3u_path.display()))?;
+    // Send the playlist file to the trash!
+    trash::delete(&playlist.file_path).with_context(|| format!("Failed to delete playlist file '{}'", playlist.file_path.display()))?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    // Removes the temporary file when the test ends, even if the test fails.
+    struct Blocker(PathBuf);
+
+    impl Drop for Blocker {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    // A playlist file can't be written inside of a regular file, even with permissions that would allow anything else.
+    fn unwritable_playlist(name: &str, tracks: Vec<Track>) -> (Playlist, Blocker) {
+        let blocker = std::env::temp_dir().join(format!("gem-player-test-{}-{}", std::process::id(), name));
+        fs::write(&blocker, b"").expect("The temporary file must be created");
+
+        let playlist = Playlist {
+            name: name.to_owned(),
+            creation_date_time: UNIX_EPOCH,
+            tracks,
+            file_path: blocker.join("playlist.m3u"),
+            pinned: false,
+            allow_duplicates: false,
+            missing_entries: Vec::new(),
+        };
+        (playlist, Blocker(blocker))
+    }
+
+    fn track_paths(playlist: &Playlist) -> Vec<PathBuf> {
+        playlist.tracks.iter().map(|t| t.path.clone()).collect()
+    }
+
+    #[test]
+    fn failed_add_leaves_tracks_unchanged() {
+        let (mut playlist, _blocker) = unwritable_playlist("add", vec![Track::with_path("/music/a.mp3")]);
+        let before = track_paths(&playlist);
+
+        assert!(add_to_playlist(&mut playlist, Track::with_path("/music/b.mp3")).is_err());
+        assert_eq!(track_paths(&playlist), before);
+    }
+
+    fn missing_entry(path: &str, position: usize) -> MissingEntry {
+        MissingEntry {
+            entry: PlaylistEntry {
+                path: PathBuf::from(path),
+                duration: None,
+                artist: None,
+                title: None,
+            },
+            position,
+        }
+    }
+
+    fn entry_paths(playlist: &Playlist) -> Vec<PathBuf> {
+        playlist_entries(playlist, Path::new("/"), None)
+            .into_iter()
+            .map(|e| Path::new("/").join(e.path))
+            .collect()
+    }
+
+    #[test]
+    fn missing_entries_are_written_in_place() {
+        let tracks = vec![Track::with_path("/music/a.mp3"), Track::with_path("/music/c.mp3")];
+        let (mut playlist, _blocker) = unwritable_playlist("in-place", tracks);
+        playlist.missing_entries = vec![missing_entry("/music/0.mp3", 0), missing_entry("/music/b.mp3", 1)];
+
+        let expected: Vec<PathBuf> = ["/music/0.mp3", "/music/a.mp3", "/music/b.mp3", "/music/c.mp3"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(entry_paths(&playlist), expected);
+    }
+
+    #[test]
+    fn relinked_entry_keeps_its_position() {
+        let directory = std::env::temp_dir().join(format!("gem-player-test-{}-relink", std::process::id()));
+        fs::create_dir_all(&directory).expect("The temporary directory must be created");
+
+        let (mut playlist, _blocker) =
+            unwritable_playlist("relink", vec![Track::with_path("/music/a.mp3"), Track::with_path("/music/c.mp3")]);
+        playlist.file_path = directory.join("playlist.m3u");
+        playlist.missing_entries = vec![missing_entry("/music/b.mp3", 1), missing_entry("/music/d.mp3", 2)];
+
+        relink_missing_entry(&mut playlist, Path::new("/music/b.mp3"), Track::with_path("/music/b2.mp3"))
+            .expect("The playlist must be saved");
+
+        let expected: Vec<PathBuf> = ["/music/a.mp3", "/music/b2.mp3", "/music/c.mp3"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(track_paths(&playlist), expected);
+        assert_eq!(playlist.missing_entries[0].position, 3);
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn rename_rejects_invalid_names() {
+        let (first, _first_blocker) = unwritable_playlist("first", Vec::new());
+        let (second, _second_blocker) = unwritable_playlist("second", Vec::new());
+        let mut playlists = vec![first, second];
+        let key = playlists[1].file_path.clone();
+
+        assert!(rename(&key, "   ", &mut playlists).is_err());
+        assert!(rename(&key, "first", &mut playlists).is_err());
+        assert!(validate_name("FIRST", &playlists[1], &playlists).is_err());
+        assert!(validate_name("SECOND", &playlists[1], &playlists).is_ok());
+        assert_eq!(playlists[1].name, "second");
+        assert_eq!(playlists[1].file_path, key);
+    }
+
+    #[test]
+    fn failed_remove_leaves_tracks_unchanged() {
+        let tracks = vec![
+            Track::with_path("/music/a.mp3"),
+            Track::with_path("/music/b.mp3"),
+            Track::with_path("/music/c.mp3"),
+        ];
+        let (mut playlist, _blocker) = unwritable_playlist("remove", tracks);
+        let before = track_paths(&playlist);
+
+        assert!(remove_from_playlist(&mut playlist, Path::new("/music/b.mp3")).is_err());
+        assert_eq!(track_paths(&playlist), before);
+    }
+}
@@ -0,0 +1,45 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::{Receiver, channel},
+    thread,
+};
+
+use fully_pub::fully_pub;
+use lofty::file::EXTENSIONS;
+use rfd::FileDialog;
+
+// An open file dialog for replacing a playlist entry whose file could not be found.
+#[fully_pub]
+struct PlaylistEntryLocator {
+    playlist_key: PathBuf,
+    missing_path: PathBuf,
+    receiver: Receiver<Option<PathBuf>>,
+}
+
+/// Spawns an audio file picker in a background thread. The selected file will eventually be sent to the returned locator's receiver.
+pub fn spawn_playlist_entry_locator(playlist_key: PathBuf, missing_path: PathBuf) -> PlaylistEntryLocator {
+    let (sender, receiver) = channel();
+
+    // Start in the folder that the file used to be in, if it still exists.
+    let start_directory = playlist_key
+        .parent()
+        .map(|directory| directory.join(&missing_path))
+        .and_then(|path| path.parent().map(|p| p.to_path_buf()))
+        .filter(|directory| directory.is_dir());
+
+    thread::spawn(move || {
+        let mut dialog = FileDialog::new().add_filter("Audio", EXTENSIONS);
+        if let Some(directory) = start_directory {
+            dialog = dialog.set_directory(directory);
+        }
+
+        let selected_file = dialog.pick_file();
+        let _ = sender.send(selected_file);
+    });
+
+    PlaylistEntryLocator {
+        playlist_key,
+        missing_path,
+        receiver,
+    }
+}
@@ -0,0 +1,42 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::{Receiver, channel},
+    thread,
+};
+
+use fully_pub::fully_pub;
+use rfd::FileDialog;
+use strum::IntoEnumIterator;
+
+use crate::playlist::{ExportOptions, PlaylistFormat};
+
+// An open save dialog for exporting a playlist. The format is chosen by the extension of the saved file.
+#[fully_pub]
+struct PlaylistExporter {
+    playlist_key: PathBuf,
+    options: ExportOptions,
+    receiver: Receiver<Option<PathBuf>>,
+}
+
+/// Spawns a save dialog in a background thread. The chosen path will eventually be sent to the returned exporter's receiver.
+pub fn spawn_playlist_exporter(playlist_key: PathBuf, playlist_name: &str, options: ExportOptions) -> PlaylistExporter {
+    let (sender, receiver) = channel();
+
+    let file_name = format!("{}.{}", playlist_name, PlaylistFormat::M3u.extension());
+
+    thread::spawn(move || {
+        let mut dialog = FileDialog::new().set_file_name(file_name);
+        for format in PlaylistFormat::iter() {
+            dialog = dialog.add_filter(format.label(), &[format.extension()]);
+        }
+
+        let selected_path = dialog.save_file();
+        let _ = sender.send(selected_path);
+    });
+
+    PlaylistExporter {
+        playlist_key,
+        options,
+        receiver,
+    }
+}
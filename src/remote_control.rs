@@ -0,0 +1,582 @@
+use std::{
+    fs::read,
+    hash::{DefaultHasher, Hash, Hasher},
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, Sender, channel},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use egui::Context;
+use fully_pub::fully_pub;
+use log::{error, info, warn};
+use serde_json::{Value, json};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+use tungstenite::{Message, WebSocket, handshake::derive_accept_key, protocol::Role};
+
+use crate::{
+    GemPlayer,
+    artwork_cache::artwork_path,
+    commands::GemCommand,
+    file_opener::load_opened_tracks,
+    player::{enqueue, get_position},
+    track::{Track, extract_artwork, is_audio_file},
+};
+
+pub const DEFAULT_REMOTE_PORT: u16 = 7878;
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+const REMOTE_PAGE: &str = include_str!("../assets/remote.html");
+
+// An opt-in HTTP api for controlling the player from scripts and home automation tools.
+//
+// GET  /status               The playing track, position, and volume.
+// GET  /queue                The queued tracks.
+// GET  /artwork              The artwork of the playing track, or of the one at ?track=<path>.
+// POST /play, /pause, /toggle, /next, /previous, /stop
+// POST /volume               {"volume": 0.0 to 1.0}
+// POST /queue                {"paths": ["/path/to/file.mp3", ...]}
+//
+// A web remote for phones is served at /. It receives the player's state over a WebSocket at /live whenever it changes,
+// and uses the endpoints above for its controls.
+//
+// Any website open in a browser can send requests to this computer, so every request must carry the token shown in the
+// settings, either as "Authorization: Bearer <token>" or as a "token" query parameter, and requests from other sites
+// are refused.
+#[fully_pub]
+struct RemoteControl {
+    enabled: bool,
+    port: u16,
+    allow_network: bool, // Whether other devices can connect, rather than only this computer.
+    token: String,       // Made once per install and kept with the settings.
+
+    server: Option<RemoteServer>,
+    error: Option<String>, // Why the server could not be started.
+}
+
+pub struct RemoteServer {
+    server: Arc<Server>,
+    request_receiver: Receiver<PendingRequest>,
+    thread: JoinHandle<()>,
+    live_clients: Arc<Mutex<LiveClients>>,
+    address: SocketAddr, // Where other devices can reach the server, for showing in the settings.
+}
+
+// The web remotes that are connected to /live. Each has a thread writing the states it is sent to its socket.
+#[derive(Default)]
+struct LiveClients {
+    senders: Vec<Sender<String>>,
+    last_state: Option<String>, // Sent to clients as soon as they connect.
+    last_fingerprint: Option<u64>,
+}
+
+enum RemoteRequest {
+    Status,
+    Queue,
+    Command(GemCommand),
+    Enqueue(Vec<PathBuf>),
+    Track(PathBuf), // The library or queue track with this path, for reading its artwork.
+}
+
+enum RemoteReply {
+    Json(Value),
+    Track(Option<Track>),
+}
+
+// The player lives on the ui thread, so requests wait there to be answered.
+struct PendingRequest {
+    request: RemoteRequest,
+    reply_sender: Sender<RemoteReply>,
+}
+
+impl RemoteControl {
+    pub fn new(enabled: bool, port: u16, allow_network: bool, token: String) -> Self {
+        Self {
+            enabled,
+            port,
+            allow_network,
+            token,
+            server: None,
+            error: None,
+        }
+    }
+}
+
+// Stops the server if it is running and starts it again with the current settings.
+pub fn restart_remote_control(ctx: &Context, remote: &mut RemoteControl) {
+    if let Some(server) = remote.server.take() {
+        stop_server(server);
+    }
+
+    remote.error = None;
+
+    if !remote.enabled {
+        return;
+    }
+
+    match start_server(ctx.clone(), remote.port, remote.allow_network, remote.token.clone()) {
+        Ok(server) => {
+            info!("Remote control is listening on port {}.", remote.port);
+            remote.server = Some(server);
+        }
+        Err(e) => {
+            error!("{:#}", e);
+            remote.error = Some(e.to_string());
+        }
+    }
+}
+
+pub fn stop_remote_control(remote: &mut RemoteControl) {
+    if let Some(server) = remote.server.take() {
+        stop_server(server);
+    }
+}
+
+pub fn generate_remote_token() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+fn start_server(ctx: Context, port: u16, allow_network: bool, token: String) -> Result<RemoteServer> {
+    let host = if allow_network {
+        Ipv4Addr::UNSPECIFIED
+    } else {
+        Ipv4Addr::LOCALHOST
+    };
+    let server = Server::http(SocketAddr::from((host, port))).map_err(|e| anyhow!("Failed to listen on port {}: {}", port, e))?;
+    let server = Arc::new(server);
+
+    let ip = if allow_network {
+        local_network_address().unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+    } else {
+        IpAddr::V4(Ipv4Addr::LOCALHOST)
+    };
+    let address = SocketAddr::new(ip, port);
+
+    let (request_sender, request_receiver) = channel();
+    let thread_server = Arc::clone(&server);
+
+    let live_clients = Arc::new(Mutex::new(LiveClients::default()));
+    let thread_live_clients = Arc::clone(&live_clients);
+
+    let thread = thread::spawn(move || {
+        for mut request in thread_server.incoming_requests() {
+            if let Err((status, message)) = check_request(&request, port, allow_network, &token) {
+                respond(request, status, &json!({ "error": message }));
+                continue;
+            }
+
+            // The web remote doesn't need the player, so it is served from here.
+            if *request.method() == Method::Get {
+                let path = request.url().split('?').next().unwrap_or_default().to_owned();
+                match path.as_str() {
+                    "/" => {
+                        respond_with(request, 200, REMOTE_PAGE.as_bytes().to_vec(), "text/html; charset=utf-8");
+                        continue;
+                    }
+                    "/artwork" => {
+                        // The artwork is read here rather than on the ui thread, which only looks up the track.
+                        let image = match query_value(request.url(), "track").and_then(percent_decode) {
+                            Some(path) => match ask_player(&ctx, &request_sender, RemoteRequest::Track(PathBuf::from(path))) {
+                                Some(RemoteReply::Track(Some(track))) => extract_artwork(&track).map(|picture| {
+                                    let mime_type = picture.mime_type().map_or("application/octet-stream", |m| m.as_str());
+                                    (picture.data().to_vec(), mime_type.to_owned())
+                                }),
+                                _ => None,
+                            },
+                            None => artwork_path()
+                                .and_then(|path| read(path).ok())
+                                .map(|image_data| (image_data, "image/png".to_owned())),
+                        };
+
+                        match image {
+                            Some((image_data, mime_type)) => respond_with(request, 200, image_data, &mime_type),
+                            None => respond(request, 404, &json!({ "error": "The track was not found or has no artwork" })),
+                        }
+                        continue;
+                    }
+                    "/live" => {
+                        open_live_socket(request, &thread_live_clients);
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            let (status, body) = match parse_request(&mut request) {
+                Err((status, message)) => (status, json!({ "error": message })),
+                Ok(remote_request) => match ask_player(&ctx, &request_sender, remote_request) {
+                    Some(RemoteReply::Json(body)) => (200, body),
+                    _ => (503, json!({ "error": "The player did not respond" })),
+                },
+            };
+
+            respond(request, status, &body);
+        }
+    });
+
+    Ok(RemoteServer {
+        server,
+        request_receiver,
+        thread,
+        live_clients,
+        address,
+    })
+}
+
+// None if the player didn't answer in time, or the server is being stopped.
+fn ask_player(ctx: &Context, request_sender: &Sender<PendingRequest>, request: RemoteRequest) -> Option<RemoteReply> {
+    let (reply_sender, reply_receiver) = channel();
+    request_sender.send(PendingRequest { request, reply_sender }).ok()?;
+    ctx.request_repaint();
+
+    reply_receiver.recv_timeout(REPLY_TIMEOUT).ok()
+}
+
+// The address of the interface that leads to the network. Connecting a udp socket doesn't send anything, but picks the
+// interface that packets to the address would leave from.
+fn local_network_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9)).ok()?;
+
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_unspecified()).then_some(ip)
+}
+
+// The address of the web remote, including the token, while the server is running.
+pub fn remote_url(remote: &RemoteControl) -> Option<String> {
+    let server = remote.server.as_ref()?;
+    Some(format!("http://{}/?token={}", server.address, remote.token))
+}
+
+fn stop_server(server: RemoteServer) {
+    server.server.unblock();
+
+    // The socket threads stop once nothing can send to them anymore.
+    if let Ok(mut clients) = server.live_clients.lock() {
+        clients.senders.clear();
+    }
+
+    // Dropping the receiver releases a request that is still waiting for a reply.
+    drop(server.request_receiver);
+
+    if server.thread.join().is_err() {
+        warn!("The remote control thread panicked.");
+    }
+}
+
+fn parse_request(request: &mut Request) -> Result<RemoteRequest, (u16, String)> {
+    let method = request.method().clone();
+    let path = request.url().split('?').next().unwrap_or_default().to_owned();
+
+    let command = match (&method, path.as_str()) {
+        (Method::Get, "/status") => return Ok(RemoteRequest::Status),
+        (Method::Get, "/queue") => return Ok(RemoteRequest::Queue),
+        (Method::Post, "/play") => GemCommand::Play,
+        (Method::Post, "/pause") => GemCommand::Pause,
+        (Method::Post, "/toggle") => GemCommand::TogglePlayback,
+        (Method::Post, "/next") => GemCommand::NextTrack,
+        (Method::Post, "/previous") => GemCommand::PreviousTrack,
+        (Method::Post, "/stop") => GemCommand::Stop,
+        (Method::Post, "/volume") => {
+            let body = read_json(request)?;
+            let volume = body
+                .get("volume")
+                .and_then(Value::as_f64)
+                .ok_or((400, "Expected {\"volume\": 0.0 to 1.0}".to_owned()))?;
+
+            GemCommand::SetVolume((volume as f32).clamp(0.0, 1.0))
+        }
+        (Method::Post, "/queue") => {
+            let body = read_json(request)?;
+            let paths = body
+                .get("paths")
+                .and_then(Value::as_array)
+                .and_then(|paths| paths.iter().map(|p| p.as_str().map(PathBuf::from)).collect::<Option<Vec<_>>>())
+                .ok_or((400, "Expected {\"paths\": [\"/path/to/file\", ...]}".to_owned()))?;
+
+            // Only files are accepted, since loading a folder would walk it on the ui thread.
+            let paths: Vec<PathBuf> = paths.into_iter().filter(|p| is_audio_file(p) && p.is_file()).collect();
+            if paths.is_empty() {
+                return Err((400, "None of the paths is an existing audio file".to_owned()));
+            }
+
+            return Ok(RemoteRequest::Enqueue(paths));
+        }
+        (_, "/status" | "/queue" | "/play" | "/pause" | "/toggle" | "/next" | "/previous" | "/stop" | "/volume") => {
+            return Err((405, "Method not allowed".to_owned()));
+        }
+        _ => return Err((404, "Not found".to_owned())),
+    };
+
+    Ok(RemoteRequest::Command(command))
+}
+
+// Browsers can only send other content types across sites without asking first, so JSON bodies must be labelled as such.
+fn read_json(request: &mut Request) -> Result<Value, (u16, String)> {
+    let is_json = header_value(request, "Content-Type")
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+    if !is_json {
+        return Err((415, "Expected an application/json body".to_owned()));
+    }
+
+    serde_json::from_reader(request.as_reader()).map_err(|e| (400, format!("Invalid JSON: {}", e)))
+}
+
+// Refuses requests that come from other sites, or through a domain name that merely points at this computer, and
+// requests without the token.
+fn check_request(request: &Request, port: u16, allow_network: bool, token: &str) -> Result<(), (u16, String)> {
+    let host = header_value(request, "Host").unwrap_or_default();
+    if !is_expected_host(host, port, allow_network) {
+        return Err((403, "Unexpected host".to_owned()));
+    }
+
+    // Browsers send the origin with requests from scripts, which for the web remote is this server itself.
+    if let Some(origin) = header_value(request, "Origin")
+        && origin != format!("http://{}", host)
+    {
+        return Err((403, "Requests from other sites are not allowed".to_owned()));
+    }
+
+    let bearer_token = header_value(request, "Authorization").and_then(|value| value.strip_prefix("Bearer "));
+    let request_token = bearer_token.or_else(|| query_value(request.url(), "token"));
+    if !request_token.is_some_and(|t| tokens_match(t.trim(), token)) {
+        return Err((401, "Missing or wrong token".to_owned()));
+    }
+
+    Ok(())
+}
+
+// Either localhost or an ip address, with the server's port. Other devices can only use an ip address.
+fn is_expected_host(host: &str, port: u16, allow_network: bool) -> bool {
+    let Some((name, host_port)) = host.rsplit_once(':') else {
+        return false;
+    };
+
+    if host_port.parse() != Ok(port) {
+        return false;
+    }
+
+    if name.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+
+    let Ok(ip) = name.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() else {
+        return false;
+    };
+
+    allow_network || ip.is_loopback()
+}
+
+// Compares every byte, so that the time taken doesn't tell how much of a guess was right.
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+fn header_value<'a>(request: &'a Request, field: &'static str) -> Option<&'a str> {
+    request.headers().iter().find(|h| h.field.equiv(field)).map(|h| h.value.as_str())
+}
+
+fn query_value<'a>(url: &'a str, key: &str) -> Option<&'a str> {
+    let (_, query) = url.split_once('?')?;
+    query.split('&').find_map(|pair| pair.strip_prefix(key)?.strip_prefix('='))
+}
+
+// Undoes the web remote's encodeURIComponent().
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut input = value.bytes();
+
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let hex = [input.next()?, input.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+fn respond(request: Request, status: u16, body: &Value) {
+    respond_with(request, status, body.to_string().into_bytes(), "application/json");
+}
+
+fn respond_with(request: Request, status: u16, data: Vec<u8>, content_type: &str) {
+    let content_type = Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).expect("The header must be valid");
+    let response = Response::from_data(data)
+        .with_status_code(StatusCode(status))
+        .with_header(content_type);
+
+    if let Err(e) = request.respond(response) {
+        warn!("Failed to respond to a remote request: {}", e);
+    }
+}
+
+// Upgrades the request to a WebSocket that the live state is pushed over. Clients don't send anything over it.
+// Browsers open WebSockets to any site, so this relies on check_request() having refused other origins and requests
+// without the token.
+fn open_live_socket(request: Request, live_clients: &Arc<Mutex<LiveClients>>) {
+    let key = header_value(&request, "Sec-WebSocket-Key").map(str::to_owned);
+
+    let Some(key) = key else {
+        respond(request, 400, &json!({ "error": "Expected a WebSocket upgrade" }));
+        return;
+    };
+
+    let headers = [
+        ("Upgrade", "websocket".to_owned()),
+        ("Connection", "Upgrade".to_owned()),
+        ("Sec-WebSocket-Accept", derive_accept_key(key.as_bytes())),
+    ];
+
+    let mut response = Response::empty(StatusCode(101));
+    for (field, value) in headers {
+        let header = Header::from_bytes(field.as_bytes(), value.as_bytes()).expect("The header must be valid");
+        response.add_header(header);
+    }
+
+    let stream = request.upgrade("websocket", response);
+
+    let (sender, receiver) = channel::<String>();
+    if let Ok(mut clients) = live_clients.lock() {
+        if let Some(state) = &clients.last_state {
+            let _ = sender.send(state.clone());
+        }
+        clients.senders.push(sender);
+    }
+
+    thread::spawn(move || {
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+        for state in receiver {
+            if socket.send(Message::text(state)).is_err() {
+                break; // The client went away.
+            }
+        }
+
+        let _ = socket.close(None);
+    });
+}
+
+// Sends the state to the connected web remotes, but only when it has changed since it was last sent.
+fn broadcast_live_state(gem: &GemPlayer, live_clients: &Mutex<LiveClients>) {
+    let Ok(mut clients) = live_clients.lock() else {
+        return;
+    };
+
+    if clients.senders.is_empty() {
+        clients.last_state = None;
+        clients.last_fingerprint = None;
+        return;
+    }
+
+    let fingerprint = live_state_fingerprint(gem);
+    if clients.last_fingerprint == Some(fingerprint) {
+        return;
+    }
+
+    let state = live_state_json(gem).to_string();
+    clients.senders.retain(|sender| sender.send(state.clone()).is_ok());
+    clients.last_state = Some(state);
+    clients.last_fingerprint = Some(fingerprint);
+}
+
+// Covers everything in the live state, so that the state is only built when it has changed rather than every frame.
+fn live_state_fingerprint(gem: &GemPlayer) -> u64 {
+    let backend = gem.player.backend.as_ref();
+    let mut hasher = DefaultHasher::new();
+
+    gem.player.playing.as_ref().map(|t| &t.path).hash(&mut hasher);
+    backend.is_none_or(|b| b.is_paused()).hash(&mut hasher);
+    get_position(&gem.player).map(|p| p.as_secs()).hash(&mut hasher);
+    backend.map(|b| b.volume().to_bits()).hash(&mut hasher);
+
+    gem.player.queue.len().hash(&mut hasher);
+    for track in &gem.player.queue {
+        track.path.hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
+
+pub fn poll_remote_control(gem: &mut GemPlayer) {
+    let Some(server) = &gem.remote_control.server else {
+        return;
+    };
+
+    let pending: Vec<PendingRequest> = server.request_receiver.try_iter().collect();
+
+    for PendingRequest { request, reply_sender } in pending {
+        let reply = match request {
+            RemoteRequest::Status => RemoteReply::Json(status_json(gem)),
+            RemoteRequest::Queue => RemoteReply::Json(Value::Array(gem.player.queue.iter().map(track_json).collect())),
+            RemoteRequest::Command(command) => {
+                gem.commands.push(command);
+                RemoteReply::Json(json!({ "ok": true }))
+            }
+            RemoteRequest::Enqueue(paths) => {
+                let tracks = load_opened_tracks(gem, &paths);
+                let enqueued_count = tracks.len();
+
+                for track in tracks {
+                    enqueue(&mut gem.player, track);
+                }
+
+                RemoteReply::Json(json!({ "enqueued": enqueued_count }))
+            }
+            RemoteRequest::Track(path) => {
+                // Only tracks the player knows about, so that the remote can't read just any file.
+                let player = &gem.player;
+                let mut tracks = player.playing.iter().chain(player.queue.iter()).chain(gem.library.iter());
+                RemoteReply::Track(tracks.find(|t| t.path == path).cloned())
+            }
+        };
+
+        let _ = reply_sender.send(reply); // The request may have timed out.
+    }
+
+    if let Some(server) = &gem.remote_control.server {
+        broadcast_live_state(gem, &server.live_clients);
+    }
+}
+
+fn status_json(gem: &GemPlayer) -> Value {
+    let backend = gem.player.backend.as_ref();
+
+    json!({
+        "playing": gem.player.playing.as_ref().map(track_json),
+        "paused": backend.is_none_or(|b| b.is_paused()),
+        "position": get_position(&gem.player).map(|p| p.as_secs_f64()),
+        "volume": backend.map(|b| b.volume()),
+        "queued": gem.player.queue.len(),
+    })
+}
+
+// Like the status, but with the queue and with the position in whole seconds, so that it doesn't change every frame.
+fn live_state_json(gem: &GemPlayer) -> Value {
+    let backend = gem.player.backend.as_ref();
+
+    json!({
+        "playing": gem.player.playing.as_ref().map(track_json),
+        "paused": backend.is_none_or(|b| b.is_paused()),
+        "position": get_position(&gem.player).map(|p| p.as_secs()),
+        "volume": backend.map(|b| b.volume()),
+        "queue": gem.player.queue.iter().map(track_json).collect::<Vec<_>>(),
+    })
+}
+
+fn track_json(track: &Track) -> Value {
+    json!({
+        "title": track.title,
+        "artist": track.artist,
+        "album": track.album,
+        "duration": track.duration.as_secs_f64(),
+        "path": track.path.to_string_lossy(),
+    })
+}
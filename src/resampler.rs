@@ -0,0 +1,209 @@
+use rodio::{ChannelCount, Sample, SampleRate, Source, source::SeekError};
+use std::{collections::VecDeque, f64::consts::PI, time::Duration};
+
+const TAPS: usize = 16; // Zero crossings of the sinc kernel on each side. More taps give a sharper filter but cost more CPU.
+
+// Converts the input to another sample rate with a windowed sinc filter. rodio's own converter interpolates linearly
+// and drops samples when downsampling, which dulls the highs and can alias. Passes the input through untouched if no
+// target rate is given or the rates already match.
+pub struct ResamplerSource<I> {
+    input: I,
+    from: u32,
+    to: u32,
+    channels: usize,
+    cutoff: f64,       // Relative to the input Nyquist frequency. Below 1 when downsampling, to filter out aliasing.
+    half_width: usize, // In input frames, on each side of the output position.
+
+    frames: VecDeque<Sample>, // Interleaved input frames, starting at `first_frame`.
+    first_frame: u64,
+    input_frames: Option<u64>, // The number of input frames, once the input has run out.
+    position: f64,             // The output position, in input frames.
+    weights: Vec<f64>,         // The kernel for the current output position, reused between frames.
+
+    output: Vec<Sample>, // The current output frame.
+    output_index: usize,
+}
+
+impl<I> ResamplerSource<I>
+where
+    I: Source,
+{
+    pub fn new(input: I, to: Option<SampleRate>) -> Self {
+        let from = input.sample_rate().get();
+        let to = to.map_or(from, |rate| rate.get());
+        let channels = input.channels().get() as usize;
+
+        let cutoff = (to as f64 / from as f64).min(1.0);
+        let half_width = (TAPS as f64 / cutoff).ceil() as usize;
+
+        Self {
+            input,
+            from,
+            to,
+            channels,
+            cutoff,
+            half_width,
+            frames: VecDeque::new(),
+            first_frame: 0,
+            input_frames: None,
+            position: 0.0,
+            weights: Vec::new(),
+            output: Vec::new(),
+            output_index: 0,
+        }
+    }
+
+    fn is_passthrough(&self) -> bool {
+        self.from == self.to
+    }
+
+    // Reads input frames until `last_frame` is buffered or the input runs out.
+    fn fill_to(&mut self, last_frame: u64) {
+        while self.input_frames.is_none() && self.first_frame + (self.frames.len() / self.channels) as u64 <= last_frame {
+            let buffered = self.frames.len();
+            self.frames.extend(self.input.by_ref().take(self.channels));
+
+            if self.frames.len() - buffered < self.channels {
+                self.frames.truncate(buffered); // Drop an incomplete last frame.
+                self.input_frames = Some(self.first_frame + (buffered / self.channels) as u64);
+            }
+        }
+    }
+
+    // Frames outside of the input are silent.
+    fn sample(&self, frame: i64, channel: usize) -> Sample {
+        if frame < self.first_frame as i64 {
+            return 0.0;
+        }
+
+        let index = (frame as u64 - self.first_frame) as usize * self.channels + channel;
+        self.frames.get(index).copied().unwrap_or(0.0)
+    }
+
+    fn next_output_frame(&mut self) -> bool {
+        let center = self.position.floor() as i64;
+        let fraction = self.position - center as f64;
+        let half_width = self.half_width as i64;
+
+        self.fill_to((center + half_width).max(0) as u64);
+
+        if self.input_frames.is_some_and(|count| self.position >= count as f64) {
+            return false;
+        }
+
+        // Frames that are behind the kernel are no longer needed.
+        while (self.first_frame as i64) <= center - half_width && !self.frames.is_empty() {
+            self.frames.drain(..self.channels);
+            self.first_frame += 1;
+        }
+
+        let cutoff = self.cutoff;
+        self.weights.clear();
+        self.weights
+            .extend((-half_width + 1..=half_width).map(|offset| kernel((offset as f64 - fraction) * cutoff, TAPS as f64) * cutoff));
+
+        self.output.clear();
+        for channel in 0..self.channels {
+            let value: f64 = (-half_width + 1..=half_width)
+                .zip(&self.weights)
+                .map(|(offset, weight)| self.sample(center + offset, channel) as f64 * weight)
+                .sum();
+            self.output.push(value as Sample);
+        }
+        self.output_index = 0;
+
+        self.position += self.from as f64 / self.to as f64;
+
+        true
+    }
+}
+
+// A Lanczos windowed sinc.
+fn kernel(x: f64, width: f64) -> f64 {
+    if x == 0.0 {
+        return 1.0;
+    }
+
+    if x.abs() >= width {
+        return 0.0;
+    }
+
+    let sinc = |x: f64| (PI * x).sin() / (PI * x);
+    sinc(x) * sinc(x / width)
+}
+
+impl<I> Iterator for ResamplerSource<I>
+where
+    I: Source,
+{
+    type Item = Sample;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_passthrough() {
+            return self.input.next();
+        }
+
+        if self.output_index >= self.output.len() && !self.next_output_frame() {
+            return None;
+        }
+
+        let sample = self.output[self.output_index];
+        self.output_index += 1;
+
+        Some(sample)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.is_passthrough() {
+            return self.input.size_hint();
+        }
+
+        (0, None)
+    }
+}
+
+impl<I> Source for ResamplerSource<I>
+where
+    I: Source,
+{
+    #[inline]
+    fn current_span_len(&self) -> Option<usize> {
+        if self.is_passthrough() {
+            return self.input.current_span_len();
+        }
+
+        None
+    }
+
+    #[inline]
+    fn channels(&self) -> ChannelCount {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> SampleRate {
+        SampleRate::new(self.to).unwrap_or(self.input.sample_rate())
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+
+        // The buffered frames belong to the old position, so start over as if the input began here.
+        self.frames.clear();
+        self.first_frame = 0;
+        self.input_frames = None;
+        self.position = 0.0;
+        self.output.clear();
+        self.output_index = 0;
+
+        Ok(())
+    }
+}
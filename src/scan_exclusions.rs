@@ -0,0 +1,110 @@
+use std::path::Path;
+
+// Rules for leaving files out of the library scan. A rule is matched against the name of every folder and file
+// below the library directory, e.g. "Audiobooks" or ".*". Rules containing a slash are instead matched against
+// the path relative to the library directory, e.g. "Podcasts/Old*". Rules may use the wildcards '*' and '?'.
+pub fn is_excluded(path: &Path, directory: &Path, rules: &[String]) -> bool {
+    let Ok(relative_path) = path.strip_prefix(directory) else {
+        return false; // Only paths inside of the library directory can be excluded.
+    };
+
+    let components: Vec<String> = relative_path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    rules.iter().any(|rule| {
+        let rule = rule.trim().trim_matches('/');
+        if rule.is_empty() {
+            return false;
+        }
+
+        if !rule.contains('/') {
+            return components.iter().any(|component| wildcard_match(rule, component));
+        }
+
+        // A path rule also excludes everything below the folder that it matches.
+        let rule_depth = rule.split('/').count();
+        components.len() >= rule_depth && wildcard_match(rule, &components[..rule_depth].join("/"))
+    })
+}
+
+// Matches text against a pattern where '*' is any run of characters (not including '/') and '?' is any one character.
+// Matching is case insensitive, also on case sensitive file systems, so that a rule like "audiobooks" doesn't have to
+// repeat how each folder happens to be capitalized.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None; // The position after the last '*' and where it began matching.
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+            }
+            Some('?') if text[t] != '/' => {
+                p += 1;
+                t += 1;
+            }
+            Some(&c) if c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last '*' consume one more character and try again.
+                Some((star_p, star_t)) if text[star_t] != '/' => {
+                    backtrack = Some((star_p, star_t + 1));
+                    p = star_p;
+                    t = star_t + 1;
+                }
+                _ => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(rules: &[&str]) -> Vec<String> {
+        rules.iter().map(|r| r.to_string()).collect()
+    }
+
+    #[test]
+    fn wildcards_match_within_a_path_component() {
+        assert!(wildcard_match("*.cue", "Album.CUE"));
+        assert!(wildcard_match("disc ?", "Disc 2"));
+        assert!(wildcard_match("a*b*c", "axxbyybc"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("disc ?", "Disc 10"));
+        assert!(!wildcard_match("Podcasts*", "Podcasts/Old"));
+        assert!(!wildcard_match("Podcasts?Old", "Podcasts/Old"));
+    }
+
+    #[test]
+    fn name_rules_match_any_folder_or_file() {
+        let directory = Path::new("/music");
+        let rules = rules(&["Audiobooks", ".*", "  "]);
+
+        assert!(is_excluded(Path::new("/music/audiobooks/book.mp3"), directory, &rules));
+        assert!(is_excluded(Path::new("/music/Rock/.hidden.mp3"), directory, &rules));
+        assert!(!is_excluded(Path::new("/music/Rock/song.mp3"), directory, &rules));
+        assert!(!is_excluded(Path::new("/elsewhere/Audiobooks/book.mp3"), directory, &rules));
+    }
+
+    #[test]
+    fn path_rules_match_from_the_library_directory() {
+        let directory = Path::new("/music");
+        let rules = rules(&["Podcasts/Old*/"]);
+
+        assert!(is_excluded(Path::new("/music/Podcasts/Old Episodes/1.mp3"), directory, &rules));
+        assert!(!is_excluded(Path::new("/music/Podcasts/New/1.mp3"), directory, &rules));
+        assert!(!is_excluded(Path::new("/music/Archive/Podcasts/Old/1.mp3"), directory, &rules));
+    }
+}
@@ -0,0 +1,153 @@
+use std::mem::take;
+
+use fully_pub::fully_pub;
+
+use crate::track::{Track, fold_text};
+
+// A search split into field filters and free text. For example, `artist:radiohead year:2000..2010 creep` keeps
+// tracks by Radiohead from 2000 to 2010 with "creep" in any of their text fields. The album artist and composer are
+// filtered with `albumartist:` and `composer:`. Values with spaces can be quoted,
+// e.g. `album:"ok computer"`. Tokens that aren't a known filter are treated as free text.
+#[fully_pub]
+#[derive(Debug, Default, PartialEq)]
+struct SearchQuery {
+    text: String, // Folded, see fold_text(). Matched against the title, artist, album, album artist, composer, and genre.
+    title: Vec<String>,
+    artist: Vec<String>,
+    album: Vec<String>,
+    album_artist: Vec<String>,
+    composer: Vec<String>,
+    genre: Vec<String>,
+    years: Option<(Option<u16>, Option<u16>)>, // An inclusive range. Either end may be open, e.g. `year:..1990`.
+}
+
+pub fn parse_query(search: &str) -> SearchQuery {
+    let mut query = SearchQuery::default();
+    let mut free_words = Vec::new();
+
+    for token in tokenize(search) {
+        let Some((field, value)) = token.split_once(':') else {
+            free_words.push(token);
+            continue;
+        };
+
+        let value = fold_text(value.trim_matches('"'));
+        if value.is_empty() {
+            free_words.push(token);
+            continue;
+        }
+
+        match field.to_lowercase().as_str() {
+            "title" => query.title.push(value),
+            "artist" => query.artist.push(value),
+            "album" => query.album.push(value),
+            "albumartist" => query.album_artist.push(value),
+            "composer" => query.composer.push(value),
+            "genre" => query.genre.push(value),
+            "year" => match parse_year_range(&value) {
+                Some(range) => query.years = Some(range),
+                None => free_words.push(token),
+            },
+            _ => free_words.push(token),
+        }
+    }
+
+    query.text = fold_text(&free_words.join(" ").replace('"', ""));
+    query
+}
+
+// Values are never empty, so the empty search fields of missing tags don't match.
+pub fn matches_query(track: &Track, query: &SearchQuery) -> bool {
+    let fields = &track.search_fields;
+    let any_contains = |values: &[String], value: &str| values.iter().any(|v| v.contains(value));
+
+    // Artists and genres are matched one by one rather than as the joined text that is displayed.
+    let fields_match = query.title.iter().all(|v| fields.title.contains(v.as_str()))
+        && query.artist.iter().all(|v| any_contains(&fields.artists, v))
+        && query.album.iter().all(|v| fields.album.contains(v.as_str()))
+        && query.album_artist.iter().all(|v| fields.album_artist.contains(v.as_str()))
+        && query.composer.iter().all(|v| fields.composer.contains(v.as_str()))
+        && query.genre.iter().all(|v| any_contains(&fields.genres, v));
+
+    let year_matches = match query.years {
+        Some((from, to)) => track
+            .year
+            .is_some_and(|year| from.is_none_or(|from| year >= from) && to.is_none_or(|to| year <= to)),
+        None => true,
+    };
+
+    let text_matches = query.text.is_empty()
+        || [
+            &fields.title,
+            &fields.artist,
+            &fields.album,
+            &fields.album_artist,
+            &fields.composer,
+            &fields.genre,
+        ]
+        .iter()
+        .any(|field| field.contains(query.text.as_str()));
+
+    fields_match && year_matches && text_matches
+}
+
+// Whether every track matching this query also matches the previous one, e.g. when the user has typed more
+// characters. The previous results can then be narrowed down instead of searching the whole library again.
+pub fn narrows(query: &SearchQuery, previous: &SearchQuery) -> bool {
+    let contains_all = |values: &[String], previous: &[String]| previous.iter().all(|p| values.iter().any(|v| v.contains(p.as_str())));
+
+    query.text.contains(&previous.text)
+        && contains_all(&query.title, &previous.title)
+        && contains_all(&query.artist, &previous.artist)
+        && contains_all(&query.album, &previous.album)
+        && contains_all(&query.album_artist, &previous.album_artist)
+        && contains_all(&query.composer, &previous.composer)
+        && contains_all(&query.genre, &previous.genre)
+        && (previous.years.is_none() || query.years == previous.years)
+}
+
+// Splits on whitespace, except inside of double quotes.
+fn tokenize(search: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in search.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+// Parses `2000`, `2000..2010`, `2000..`, or `..2010`.
+fn parse_year_range(value: &str) -> Option<(Option<u16>, Option<u16>)> {
+    let Some((from, to)) = value.split_once("..") else {
+        let year = value.parse().ok()?;
+        return Some((Some(year), Some(year)));
+    };
+
+    let parse_end = |end: &str| if end.is_empty() { Ok(None) } else { end.parse().map(Some) };
+    let from = parse_end(from).ok()?;
+    let to = parse_end(to).ok()?;
+
+    if from.is_none() && to.is_none() {
+        return None;
+    }
+
+    Some((from, to))
+}
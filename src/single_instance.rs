@@ -0,0 +1,217 @@
+use std::{
+    ffi::OsString,
+    fs::{File, create_dir_all, read_to_string, rename},
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, channel},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow, bail};
+use directories::ProjectDirs;
+use egui::Context;
+use fully_pub::fully_pub;
+use log::{info, warn};
+
+use crate::APP_NAME;
+
+// The first instance listens on a loopback port, and writes the port along with a random token to a file in the user's
+// config directory. Instances launched after it read the file and hand their arguments over with the token, so that
+// other users and programs can neither find the instance nor pose as one.
+const INSTANCE_FILENAME: &str = "instance";
+const HANDOFF_HEADER: &str = "gem-player-handoff 2"; // Tells a hand-off apart from anything else that connects to the port.
+const HANDOFF_ACK: &str = "ok";
+const HANDOFF_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub enum InstanceClaim {
+    Primary(PrimaryInstance), // We are the only instance and will receive hand-offs.
+    HandedOff,                // Another instance is running and has taken our arguments.
+}
+
+pub struct PrimaryInstance {
+    listener: TcpListener,
+    token: String,
+}
+
+// Receives the arguments of instances that were launched while we were running.
+#[fully_pub]
+struct InstanceListener {
+    receiver: Receiver<Vec<PathBuf>>,
+}
+
+pub fn claim_instance(paths: &[PathBuf]) -> Result<InstanceClaim> {
+    let path = instance_file_path()?;
+
+    // The file is left behind if the instance didn't quit cleanly, in which case nothing answers on its port.
+    if let Some((port, token)) = read_instance_file(&path) {
+        match hand_off(port, &token, paths) {
+            Ok(()) => return Ok(InstanceClaim::HandedOff),
+            Err(e) => info!("No running instance took the arguments: {:#}", e),
+        }
+    }
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let token = format!("{:032x}", rand::random::<u128>());
+    write_instance_file(&path, listener.local_addr()?.port(), &token)?;
+
+    Ok(InstanceClaim::Primary(PrimaryInstance { listener, token }))
+}
+
+fn instance_file_path() -> Result<PathBuf> {
+    let dirs = ProjectDirs::from("", "", APP_NAME).ok_or_else(|| anyhow!("No config directory"))?;
+    Ok(dirs.config_dir().join(INSTANCE_FILENAME))
+}
+
+// The port and the token, on one line.
+fn read_instance_file(path: &Path) -> Option<(u16, String)> {
+    let contents = read_to_string(path).ok()?;
+    let (port, token) = contents.trim().split_once(' ')?;
+    Some((port.parse().ok()?, token.to_owned()))
+}
+
+// Only the user can read the file, since the token is what lets a connection hand off arguments.
+fn write_instance_file(path: &Path, port: u16, token: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+
+    let temporary_path = path.with_extension("tmp");
+    let mut options = File::options();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(&temporary_path)?;
+    writeln!(file, "{} {}", port, token)?;
+    drop(file);
+
+    rename(&temporary_path, path)?;
+    Ok(())
+}
+
+// The token and then the paths are sent one per line after the header, and the running instance acknowledges them.
+fn hand_off(port: u16, token: &str, paths: &[PathBuf]) -> Result<()> {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let mut stream = TcpStream::connect_timeout(&address, HANDOFF_TIMEOUT)?;
+    stream.set_read_timeout(Some(HANDOFF_TIMEOUT))?;
+
+    let mut message = format!("{}\n{}\n", HANDOFF_HEADER, token);
+    for path in paths {
+        message.push_str(&encode_path(path));
+        message.push('\n');
+    }
+
+    stream.write_all(message.as_bytes())?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    if reply.trim() != HANDOFF_ACK {
+        bail!("The port {} is in use by another program", port);
+    }
+
+    Ok(())
+}
+
+pub fn listen_for_instances(primary: PrimaryInstance, ctx: Context) -> InstanceListener {
+    let (sender, receiver) = channel();
+    let PrimaryInstance { listener, token } = primary;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("Failed to accept an instance connection: {}", e);
+                    continue;
+                }
+            };
+
+            match receive_hand_off(stream, &token) {
+                Ok(paths) => {
+                    info!("Received {} path(s) from another instance", paths.len());
+
+                    if sender.send(paths).is_err() {
+                        break; // The app is shutting down.
+                    }
+
+                    ctx.request_repaint();
+                }
+                Err(e) => warn!("Ignored an instance connection: {}", e),
+            }
+        }
+    });
+
+    InstanceListener { receiver }
+}
+
+fn receive_hand_off(stream: TcpStream, token: &str) -> Result<Vec<PathBuf>> {
+    stream.set_read_timeout(Some(HANDOFF_TIMEOUT))?;
+
+    let mut lines = BufReader::new(&stream).lines();
+
+    let header = lines.next().transpose()?;
+    if header.as_deref() != Some(HANDOFF_HEADER) {
+        bail!("Unexpected header {:?}", header);
+    }
+
+    let received_token = lines.next().transpose()?;
+    if received_token.as_deref() != Some(token) {
+        bail!("Wrong token");
+    }
+
+    let lines = lines.collect::<io::Result<Vec<String>>>()?;
+    let paths = lines.iter().map(|line| decode_path(line)).collect::<Result<Vec<PathBuf>>>()?;
+
+    writeln!(&stream, "{}", HANDOFF_ACK)?;
+
+    Ok(paths)
+}
+
+// Paths are sent as the hex of their raw bytes, so that paths that aren't valid UTF-8 or that contain line breaks arrive
+// intact.
+fn encode_path(path: &Path) -> String {
+    path_bytes(path).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_path(line: &str) -> Result<PathBuf> {
+    let bytes: Option<Vec<u8>> = (0..line.len())
+        .step_by(2)
+        .map(|i| line.get(i..i + 2).and_then(|hex| u8::from_str_radix(hex, 16).ok()))
+        .collect();
+
+    bytes.and_then(path_from_bytes).ok_or_else(|| anyhow!("Malformed path {:?}", line))
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+// Windows paths are sequences of 16-bit units, which may be unpaired surrogates, so those are sent instead.
+#[cfg(windows)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::windows::ffi::OsStrExt;
+    path.as_os_str().encode_wide().flat_map(u16::to_le_bytes).collect()
+}
+
+#[cfg(windows)]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    use std::os::windows::ffi::OsStringExt;
+
+    if bytes.len() % 2 != 0 {
+        return None;
+    }
+
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+    Some(PathBuf::from(OsString::from_wide(&units)))
+}
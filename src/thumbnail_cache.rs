@@ -0,0 +1,312 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, create_dir_all, metadata, read, read_dir, remove_file, write},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, Sender, channel},
+    },
+    thread,
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use egui::{Context, load::Bytes};
+use fully_pub::fully_pub;
+use image::{ImageFormat, load_from_memory};
+use log::{error, warn};
+
+use crate::{
+    artwork_cache::{album_artwork_path, get_or_init_artwork_cache},
+    track::{Track, extract_artwork},
+    ui::albums_view::AlbumKey,
+};
+
+const THUMBNAIL_SIZE: u32 = 256; // In pixels. Sharp enough for the album rows on high dpi screens.
+const MAX_MEMORY_THUMBNAILS: usize = 256;
+const MAX_DISK_THUMBNAILS: usize = 4096;
+
+// Set when thumbnails were written since the disk cache was last trimmed. See evict_old_thumbnails_if_pending().
+static EVICTION_PENDING: AtomicBool = AtomicBool::new(false);
+
+// Downscaled album artwork. The thumbnails of recently shown albums are kept in memory, and every thumbnail is kept on
+// disk between sessions, so that scrolling doesn't decode the full size embedded images again. Both evict the least
+// recently used thumbnails once they are full. Thumbnails are loaded on a worker thread, so that reading tags and
+// downscaling artwork doesn't stall scrolling.
+#[fully_pub]
+struct ThumbnailCache {
+    entries: HashMap<AlbumKey, (Option<Bytes>, u64)>, // With when it was last used. None means the album has no artwork.
+    clock: u64,
+    evicted: Vec<String>, // The uris of thumbnails that egui may still hold, see forget_evicted().
+
+    loading: HashSet<AlbumKey>, // Albums whose thumbnails are being loaded.
+    generation: u64,            // Incremented when thumbnails are discarded, so that loads started before are dropped.
+    sender: Sender<(AlbumKey, Option<Bytes>, u64)>,
+    receiver: Receiver<(AlbumKey, Option<Bytes>, u64)>,
+}
+
+impl Default for ThumbnailCache {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+
+        Self {
+            entries: HashMap::new(),
+            clock: 0,
+            evicted: Vec::new(),
+            loading: HashSet::new(),
+            generation: 0,
+            sender,
+            receiver,
+        }
+    }
+}
+
+impl ThumbnailCache {
+    // None if the album's artwork hasn't been loaded yet.
+    pub fn get(&mut self, album: &AlbumKey) -> Option<Option<&Bytes>> {
+        self.clock += 1;
+
+        let (thumbnail, last_used) = self.entries.get_mut(album)?;
+        *last_used = self.clock;
+
+        Some(thumbnail.as_ref())
+    }
+
+    pub fn has_no_artwork(&self, album: &AlbumKey) -> bool {
+        matches!(self.entries.get(album), Some((None, _)))
+    }
+
+    pub fn insert(&mut self, album: AlbumKey, thumbnail: Option<Bytes>) {
+        while self.entries.len() >= MAX_MEMORY_THUMBNAILS {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(a, _)| a.clone())
+            else {
+                break;
+            };
+            self.evict(&oldest);
+        }
+
+        self.clock += 1;
+        self.entries.insert(album, (thumbnail, self.clock));
+    }
+
+    // E.g. after the album's artwork changed. The thumbnail is loaded again when the album is next shown.
+    pub fn remove(&mut self, album: &AlbumKey) {
+        self.evict(album);
+        self.discard_loading();
+    }
+
+    // The thumbnails are loaded again from disk, which is cheap, e.g. after artwork was embedded into tracks.
+    pub fn clear(&mut self) {
+        self.evicted.extend(self.entries.keys().map(|album| thumbnail_uri(album)));
+        self.entries.clear();
+        self.discard_loading();
+    }
+
+    fn evict(&mut self, album: &AlbumKey) {
+        if self.entries.remove(album).is_some() {
+            self.evicted.push(thumbnail_uri(album));
+        }
+    }
+
+    // Loads that are already running may have read the old artwork.
+    fn discard_loading(&mut self) {
+        self.generation += 1;
+        self.loading.clear();
+    }
+
+    pub fn is_loading(&self, album: &AlbumKey) -> bool {
+        self.loading.contains(album)
+    }
+
+    // Loads the thumbnails of the albums, given with their tracks and title, on a worker thread. They are inserted by
+    // poll_loaded() as they become ready.
+    pub fn load_in_background(&mut self, mut albums: Vec<(AlbumKey, Vec<Track>, String)>, ctx: &Context) {
+        albums.retain(|(album, _, _)| self.loading.insert(album.clone()));
+        if albums.is_empty() {
+            return;
+        }
+
+        let sender = self.sender.clone();
+        let generation = self.generation;
+        let ctx = ctx.clone();
+
+        thread::spawn(move || {
+            for (album, tracks, title) in albums {
+                let thumbnail = load_album_thumbnail(&tracks, &title);
+                if sender.send((album, thumbnail, generation)).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+
+            evict_old_thumbnails_if_pending();
+        });
+    }
+
+    pub fn poll_loaded(&mut self) {
+        while let Ok((album, thumbnail, generation)) = self.receiver.try_recv() {
+            if generation != self.generation {
+                continue;
+            }
+
+            self.loading.remove(&album);
+            self.insert(album, thumbnail);
+        }
+    }
+
+    // egui keeps the bytes and textures of every image it was given, so they have to be dropped along with the thumbnail.
+    pub fn forget_evicted(&mut self, ctx: &Context) {
+        for uri in self.evicted.drain(..) {
+            ctx.forget_image(&uri);
+        }
+    }
+}
+
+// Hashed, since titles can hold characters that aren't valid in a uri and the album artist is needed to tell albums apart.
+pub fn thumbnail_uri(album: &AlbumKey) -> String {
+    let mut hasher = DefaultHasher::new();
+    album.hash(&mut hasher);
+    format!("bytes://album_artwork/{:016x}", hasher.finish())
+}
+
+// Reads the album's thumbnail from disk, or creates it from the first track with embedded artwork, or else the artwork
+// that was fetched for the album.
+fn load_album_thumbnail(tracks: &[Track], album: &str) -> Option<Bytes> {
+    let fetched_artwork = album_artwork_path(album).ok().filter(|path| path.is_file());
+    let sources: Vec<&Path> = tracks
+        .iter()
+        .map(|track| track.audio_path())
+        .chain(fetched_artwork.as_deref())
+        .collect();
+
+    // Thumbnails that already exist are much cheaper than reading the tags of the tracks before them. An empty thumbnail
+    // marks a source without artwork, so that its tags aren't read again in later sessions.
+    let thumbnails: Vec<Option<Bytes>> = sources.iter().map(|source| read_thumbnail(&thumbnail_path(source).ok()?)).collect();
+    if let Some(thumbnail) = thumbnails.iter().flatten().find(|t| !t.is_empty()) {
+        return Some(thumbnail.clone());
+    }
+
+    for (i, source) in sources.iter().enumerate() {
+        if thumbnails[i].is_some() {
+            continue;
+        }
+
+        // The sources are the tracks, followed by the fetched artwork.
+        let image_data = match tracks.get(i) {
+            Some(track) => extract_artwork(track).map(|picture| picture.into_data()),
+            None => read(source).ok(),
+        };
+
+        let Some(image_data) = image_data else {
+            if let Err(e) = mark_without_artwork(source) {
+                warn!("Failed to remember that '{}' has no artwork: {}", source.display(), e);
+            }
+            continue;
+        };
+
+        return match create_thumbnail(source, &image_data) {
+            Ok(thumbnail) => Some(Bytes::from(thumbnail)),
+            Err(e) => {
+                warn!("Failed to create a thumbnail for '{}': {}", album, e);
+                Some(Bytes::from(image_data)) // The full size artwork still shows.
+            }
+        };
+    }
+
+    None
+}
+
+// Thumbnails are named by a hash of the source file's path, size, and modification time, so that changing the artwork
+// of a file leaves its old thumbnail unused until it is evicted.
+fn thumbnail_path(source: &Path) -> Result<PathBuf> {
+    let metadata = metadata(source)?;
+
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    metadata.modified()?.hash(&mut hasher);
+
+    Ok(thumbnails_directory()?.join(format!("{:016x}.jpg", hasher.finish())))
+}
+
+// Reading a thumbnail marks it as recently used by updating its modification time.
+fn read_thumbnail(path: &Path) -> Option<Bytes> {
+    let data = read(path).ok()?;
+
+    if let Err(e) = File::options()
+        .write(true)
+        .open(path)
+        .and_then(|f| f.set_modified(SystemTime::now()))
+    {
+        warn!("Failed to mark thumbnail {:?} as used: {}", path, e);
+    }
+
+    Some(Bytes::from(data))
+}
+
+fn create_thumbnail(source: &Path, image_data: &[u8]) -> Result<Vec<u8>> {
+    let image = load_from_memory(image_data)?.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8(); // Jpegs have no alpha.
+
+    let mut thumbnail = Vec::new();
+    image.write_to(&mut Cursor::new(&mut thumbnail), ImageFormat::Jpeg)?;
+
+    write(thumbnail_path(source)?, &thumbnail)?;
+    EVICTION_PENDING.store(true, Ordering::Relaxed);
+
+    Ok(thumbnail)
+}
+
+fn mark_without_artwork(source: &Path) -> Result<()> {
+    write(thumbnail_path(source)?, b"")?;
+    EVICTION_PENDING.store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+// Trimming lists the whole thumbnail directory, so it is done once for each batch of new thumbnails.
+fn evict_old_thumbnails_if_pending() {
+    if !EVICTION_PENDING.swap(false, Ordering::Relaxed) {
+        return;
+    }
+
+    if let Err(e) = evict_old_thumbnails() {
+        error!("Failed to evict old thumbnails: {}", e);
+    }
+}
+
+fn evict_old_thumbnails() -> Result<()> {
+    let mut thumbnails: Vec<(SystemTime, PathBuf)> = read_dir(thumbnails_directory()?)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect();
+
+    if thumbnails.len() <= MAX_DISK_THUMBNAILS {
+        return Ok(());
+    }
+
+    thumbnails.sort();
+    let excess = thumbnails.len() - MAX_DISK_THUMBNAILS;
+    for (_, path) in thumbnails.into_iter().take(excess) {
+        match remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()), // Another eviction may have removed it.
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn thumbnails_directory() -> Result<PathBuf> {
+    let directory = get_or_init_artwork_cache()?.join("thumbnails");
+    create_dir_all(&directory)?;
+    Ok(directory)
+}
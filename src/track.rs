@@ -1,21 +1,40 @@
+use crate::{
+    flac_metadata::{EmbeddedCueSheet, read_embedded_cue_sheet},
+    scan_exclusions::is_excluded,
+    search_query::{matches_query, parse_query},
+};
 use anyhow::{Context, Result, anyhow, bail};
 use fully_pub::fully_pub;
 use lofty::{
+    config::WriteOptions,
     file::{AudioFile, EXTENSIONS, FileType, TaggedFileExt},
-    picture::Picture,
+    picture::{Picture, PictureType},
     read_from, read_from_path,
-    tag::ItemKey,
+    tag::{
+        Accessor, ItemKey, ItemValue, Tag, TagExt, TagItem, TagType,
+        items::popularimeter::{Popularimeter, StarRating},
+    },
 };
 use log::warn;
 use rayon::prelude::*;
 use rodio::SampleRate;
 use std::{
-    fs::{File, metadata},
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs::{File, metadata, read},
+    iter::Peekable,
     num::NonZeroU32,
     path::{Path, PathBuf},
+    str::Chars,
+    sync::{
+        RwLock,
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    },
     time::{Duration, SystemTime},
 };
 use strum_macros::EnumIter;
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
 use walkdir::WalkDir;
 
 #[derive(EnumIter, PartialEq, Clone, Copy)]
@@ -23,8 +42,15 @@ pub enum SortBy {
     Title,
     Artist,
     Album,
+    Genre,
+    Year,
+    Rating,
     Time,
     DateAdded,
+    DateModified,
+    Bitrate,
+    Channels,
+    Format,
 }
 
 impl SortBy {
@@ -33,8 +59,15 @@ impl SortBy {
             SortBy::Title => "Title",
             SortBy::Artist => "Artist",
             SortBy::Album => "Album",
+            SortBy::Genre => "Genre",
+            SortBy::Year => "Year",
+            SortBy::Rating => "Rating",
             SortBy::Time => "Time",
             SortBy::DateAdded => "Date Added",
+            SortBy::DateModified => "Date Modified",
+            SortBy::Bitrate => "Bitrate",
+            SortBy::Channels => "Channels",
+            SortBy::Format => "Format",
         }
     }
 }
@@ -50,12 +83,62 @@ pub enum SortOrder {
 struct Track {
     title: Option<String>,
     artist: Option<String>,
+    artists: Vec<String>, // The artists listed in the artist tag, split apart. See split_tag_values().
     album: Option<String>,
+    album_artist: Option<String>, // Groups the tracks of compilations, which have different artists, into one album.
+    composer: Option<String>,
+    genre: Option<String>,
+    genres: Vec<String>, // Like artists.
+    track_number: Option<u32>,
+    disc_number: Option<u32>,
+    year: Option<u16>,
+    rating: u8, // 0 to MAX_RATING stars. 0 means unrated.
     duration: Duration,
+    duration_measured: bool, // Whether the duration was measured from the audio rather than read from the header. See duration.
     path: PathBuf,
     sample_rate: Option<SampleRate>,
+    bitrate: Option<u32>, // In kbps.
+    channels: Option<u8>,
     codec: FileType,
     date_added: SystemTime,
+    date_modified: SystemTime, // When the file was last written to, e.g. by a tag editor.
+    cue: Option<CueRange>,     // Some if this is a virtual track defined by a cue sheet.
+    replay_gain: ReplayGain,
+    search_fields: SearchFields, // Kept up to date by with_search_fields().
+}
+
+// Folded copies (see fold_text()) of the text fields that are searched, made once when the track is loaded rather than
+// for every track on every keystroke. Missing fields are empty.
+#[fully_pub]
+#[derive(Clone, Default)]
+struct SearchFields {
+    title: String,
+    artist: String,
+    artists: Vec<String>,
+    album: String,
+    album_artist: String,
+    composer: String,
+    genre: String,
+    genres: Vec<String>,
+}
+
+// Loudness normalization values read from the track's tags. Gains are in decibels.
+#[fully_pub]
+#[derive(Clone, Copy, Default)]
+struct ReplayGain {
+    track_gain: Option<f32>,
+    track_peak: Option<f32>,
+    album_gain: Option<f32>,
+    album_peak: Option<f32>,
+}
+
+// A section of a larger audio file, as defined by a cue sheet.
+#[fully_pub]
+#[derive(Clone)]
+struct CueRange {
+    audio_path: PathBuf,
+    start: Duration,
+    end: Option<Duration>, // None means the end of the file.
 }
 
 impl PartialEq for Track {
@@ -65,6 +148,57 @@ impl PartialEq for Track {
     }
 }
 
+impl Track {
+    // The file that holds the audio of this track. For virtual cue tracks, `path` is only an identifier.
+    pub fn audio_path(&self) -> &Path {
+        match &self.cue {
+            Some(cue) => &cue.audio_path,
+            None => &self.path,
+        }
+    }
+
+    // A minute long track with only a path, for tests.
+    #[cfg(test)]
+    pub fn with_path(path: &str) -> Self {
+        Track {
+            title: None,
+            artist: None,
+            artists: Vec::new(),
+            album: None,
+            album_artist: None,
+            composer: None,
+            genre: None,
+            genres: Vec::new(),
+            track_number: None,
+            disc_number: None,
+            year: None,
+            rating: 0,
+            duration: Duration::from_secs(60),
+            duration_measured: false,
+            path: PathBuf::from(path),
+            sample_rate: None,
+            bitrate: None,
+            channels: None,
+            codec: FileType::Mpeg,
+            date_added: SystemTime::UNIX_EPOCH,
+            date_modified: SystemTime::UNIX_EPOCH,
+            cue: None,
+            replay_gain: ReplayGain::default(),
+            search_fields: SearchFields::default(),
+        }
+    }
+}
+
+pub const MAX_RATING: u8 = 5;
+
+// The email that ID3 POPM ratings are saved under. Players keep their own POPM frame, e.g. MusicBee uses "MusicBee".
+pub const DEFAULT_RATING_EMAIL: &str = "Windows Media Player 9 Series";
+const PERCENT_PER_STAR: u8 = 20; // Vorbis RATING fields and the mp4 rate atom go from 0 to 100.
+
+// Tracks are loaded far from the settings, e.g. by the library watcher, so the email that read_rating() looks for is
+// kept here. See set_rating_email().
+static RATING_EMAIL: RwLock<String> = RwLock::new(String::new());
+
 pub trait TrackRetrieval {
     fn get_by_path(&self, path: &Path) -> &Track;
 }
@@ -78,11 +212,21 @@ impl TrackRetrieval for Vec<Track> {
 pub fn sort(tracks: &mut [Track], sort_by: SortBy, sort_order: SortOrder) {
     tracks.sort_by(|a, b| {
         let ordering = match sort_by {
-            SortBy::Title => a.title.as_deref().unwrap_or("").cmp(b.title.as_deref().unwrap_or("")),
-            SortBy::Artist => a.artist.as_deref().unwrap_or("").cmp(b.artist.as_deref().unwrap_or("")),
-            SortBy::Album => a.album.as_deref().unwrap_or("").cmp(b.album.as_deref().unwrap_or("")),
+            SortBy::Title => natural_cmp(a.title.as_deref().unwrap_or(""), b.title.as_deref().unwrap_or("")),
+            SortBy::Artist => natural_cmp(a.artist.as_deref().unwrap_or(""), b.artist.as_deref().unwrap_or("")),
+            SortBy::Album => natural_cmp(a.album.as_deref().unwrap_or(""), b.album.as_deref().unwrap_or(""))
+                .then_with(|| a.album_artist.cmp(&b.album_artist))
+                .then(a.disc_number.cmp(&b.disc_number))
+                .then(a.track_number.cmp(&b.track_number)),
+            SortBy::Genre => natural_cmp(a.genre.as_deref().unwrap_or(""), b.genre.as_deref().unwrap_or("")),
+            SortBy::Year => a.year.cmp(&b.year),
+            SortBy::Rating => a.rating.cmp(&b.rating),
             SortBy::Time => a.duration.cmp(&b.duration),
             SortBy::DateAdded => a.date_added.cmp(&b.date_added),
+            SortBy::DateModified => a.date_modified.cmp(&b.date_modified),
+            SortBy::Bitrate => a.bitrate.cmp(&b.bitrate),
+            SortBy::Channels => a.channels.cmp(&b.channels),
+            SortBy::Format => file_type_name(a.codec).cmp(file_type_name(b.codec)),
         };
 
         match sort_order {
@@ -92,20 +236,184 @@ pub fn sort(tracks: &mut [Track], sort_by: SortBy, sort_order: SortOrder) {
     });
 }
 
-pub fn filter(tracks: &[Track], search: &str) -> Vec<Track> {
-    let search_lowercase = search.to_lowercase();
+// Compares text the way people expect a sorted list to look: ignoring case and a leading "The ", and comparing runs
+// of digits by their value so that "Track 2" comes before "Track 10".
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (a, b) = (sort_text(a), sort_text(b));
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+
+    loop {
+        let (x, y) = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(&x), Some(&y)) => (x, y),
+        };
+
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let x_number = take_number(&mut a_chars);
+            let y_number = take_number(&mut b_chars);
+
+            // Without leading zeros, the longer number is the larger one.
+            let x_digits = x_number.trim_start_matches('0');
+            let y_digits = y_number.trim_start_matches('0');
+            let ordering = x_digits.len().cmp(&y_digits.len()).then_with(|| x_digits.cmp(y_digits));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        } else {
+            a_chars.next();
+            b_chars.next();
+            if x != y {
+                return x.cmp(&y);
+            }
+        }
+    }
+}
+
+// Fills in the search fields from the track's tags. Every track is built through this.
+pub fn with_search_fields(mut track: Track) -> Track {
+    let fold = |field: &Option<String>| field.as_deref().map(fold_text).unwrap_or_default();
+
+    track.search_fields = SearchFields {
+        title: fold(&track.title),
+        artist: fold(&track.artist),
+        artists: track.artists.iter().map(|a| fold_text(a)).collect(),
+        album: fold(&track.album),
+        album_artist: fold(&track.album_artist),
+        composer: fold(&track.composer),
+        genre: fold(&track.genre),
+        genres: track.genres.iter().map(|g| fold_text(g)).collect(),
+    };
+    track
+}
+
+// Text as it is compared when searching, sorting, and finding duplicates, so that "Björk", "Bjork", and "BJÖRK" are all
+// the same. Decomposing (NFD) splits accented letters into the letter and its accent marks, which are dropped. This
+// also makes precomposed and decomposed forms of the same text equal. Lowercasing comes first, since the lowercase
+// form of a letter can bring its own accent marks (e.g. 'İ' becomes 'i' and a combining dot).
+pub fn fold_text(text: &str) -> String {
+    text.to_lowercase().nfd().filter(|c| !is_combining_mark(*c)).collect()
+}
+
+fn sort_text(text: &str) -> String {
+    let text = fold_text(text.trim());
+    match text.strip_prefix("the ") {
+        Some(rest) => rest.to_owned(),
+        None => text,
+    }
+}
+
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        number.push(c);
+    }
+    number
+}
 
-    let matches_search = |field: Option<&str>| field.is_some_and(|text| text.to_lowercase().contains(&search_lowercase));
+// Tags can hold several values, either as separate items (e.g. repeated Vorbis comments) or within one string. ID3v2.4
+// separates them with null characters, and taggers commonly use semicolons or slashes. Only slashes with spaces around
+// them count, since names such as "AC/DC" contain bare ones.
+const TAG_VALUE_SEPARATORS: [&str; 3] = ["\0", ";", " / "];
+
+// All of the values of a tag, for display.
+fn join_tag_values<'a>(values: impl Iterator<Item = &'a str>) -> Option<String> {
+    let values = split_tag_values(values);
+    if values.is_empty() { None } else { Some(values.join("; ")) }
+}
+
+fn split_tag_values<'a>(values: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut split: Vec<String> = Vec::new();
+
+    for value in values {
+        let mut parts = vec![value];
+        for separator in TAG_VALUE_SEPARATORS {
+            parts = parts.into_iter().flat_map(|part| part.split(separator)).collect();
+        }
+
+        for part in parts.into_iter().map(str::trim).filter(|part| !part.is_empty()) {
+            if !split.iter().any(|existing| existing == part) {
+                split.push(part.to_owned());
+            }
+        }
+    }
+
+    split
+}
+
+// Formats the position of a track on its album, e.g. "2-05" for the fifth track of the second disc.
+pub fn format_track_number(track: &Track) -> String {
+    match (track.disc_number, track.track_number) {
+        (Some(disc), Some(number)) => format!("{}-{:02}", disc, number),
+        (None, Some(number)) => format!("{:02}", number),
+        _ => "-".to_owned(),
+    }
+}
+
+pub fn format_channels(channels: Option<u8>) -> String {
+    match channels {
+        Some(1) => "Mono".to_owned(),
+        Some(2) => "Stereo".to_owned(),
+        Some(count) => format!("{} ch", count),
+        None => "-".to_owned(),
+    }
+}
+
+// Formats a ReplayGain gain, e.g. "-6.54 dB".
+pub fn format_gain(gain: Option<f32>) -> String {
+    match gain {
+        Some(gain) => format!("{:+.2} dB", gain),
+        None => "-".to_owned(),
+    }
+}
+
+// Orders tracks the way they appear on their albums.
+pub fn sort_by_album_order(tracks: &mut [Track]) {
+    tracks.sort_by(|a, b| {
+        a.album
+            .cmp(&b.album)
+            .then_with(|| a.album_artist.cmp(&b.album_artist))
+            .then(a.disc_number.cmp(&b.disc_number))
+            .then(a.track_number.cmp(&b.track_number))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+}
 
-    let filtered: Vec<Track> = tracks
+// The keys of the tracks on an album, in album order. Albums are told apart by their album artist, like in the albums
+// view.
+pub fn album_track_keys(tracks: &[Track], album: &str, album_artist: Option<&str>) -> Vec<PathBuf> {
+    let mut album_tracks: Vec<Track> = tracks
         .iter()
-        .filter(|track| {
-            matches_search(track.title.as_deref()) || matches_search(track.artist.as_deref()) || matches_search(track.album.as_deref())
-        })
+        .filter(|t| t.album.as_deref() == Some(album) && t.album_artist.as_deref() == album_artist)
         .cloned()
         .collect();
+    sort_by_album_order(&mut album_tracks);
+    album_tracks.into_iter().map(|t| t.path).collect()
+}
+
+// The keys of all tracks by an artist, grouped by album and in album order.
+pub fn artist_track_keys(tracks: &[Track], artist: &str) -> Vec<PathBuf> {
+    let mut artist_tracks: Vec<Track> = tracks.iter().filter(|t| t.artists.iter().any(|a| a == artist)).cloned().collect();
+    sort_by_album_order(&mut artist_tracks);
+    artist_tracks.into_iter().map(|t| t.path).collect()
+}
+
+// Keeps the tracks matching the search. See SearchQuery for the syntax.
+pub fn filter(tracks: &[Track], search: &str) -> Vec<Track> {
+    let query = parse_query(search);
+    tracks.iter().filter(|track| matches_query(track, &query)).cloned().collect()
+}
+
+// The tracks that were added, removed, or written to between two loads of the library, from whichever load has them.
+pub fn changed_tracks<'a>(old: &'a [Track], new: &'a [Track]) -> Vec<&'a Track> {
+    let old_modified: HashMap<&Path, SystemTime> = old.iter().map(|t| (t.path.as_path(), t.date_modified)).collect();
+    let new_modified: HashMap<&Path, SystemTime> = new.iter().map(|t| (t.path.as_path(), t.date_modified)).collect();
+
+    let removed = old.iter().filter(|t| !new_modified.contains_key(t.path.as_path()));
+    let added_or_modified = new.iter().filter(|t| old_modified.get(t.path.as_path()) != Some(&t.date_modified));
 
-    filtered
+    removed.chain(added_or_modified).collect()
 }
 
 pub fn load_from_file(path: &Path) -> Result<Track> {
@@ -125,10 +433,46 @@ pub fn load_from_file(path: &Path) -> Result<Track> {
         .map(|t| t.to_owned())
         .or_else(|| path.file_stem().and_then(|s| s.to_str()).map(|s| s.to_owned()));
 
-    let artist = tag.get_string(ItemKey::TrackArtist).map(|a| a.to_owned());
+    let artist = join_tag_values(tag.get_strings(ItemKey::TrackArtist));
+    let artists = split_tag_values(tag.get_strings(ItemKey::TrackArtist));
 
     let album = tag.get_string(ItemKey::AlbumTitle).map(|a| a.to_owned());
 
+    let album_artist = tag.get_string(ItemKey::AlbumArtist).map(|a| a.to_owned());
+
+    let composer = tag.get_string(ItemKey::Composer).map(|c| c.to_owned());
+
+    let genre = join_tag_values(tag.get_strings(ItemKey::Genre));
+    let genres = split_tag_values(tag.get_strings(ItemKey::Genre));
+
+    let track_number = tag.track();
+    let disc_number = tag.disk();
+    let year = tag.date().map(|date| date.year);
+
+    let rating = read_rating(tag);
+
+    // Values are usually formatted like "-6.54 dB" for gains and "0.988" for peaks.
+    let replay_gain_value = |key: ItemKey| {
+        tag.get_string(key)
+            .and_then(|value| {
+                value
+                    .trim()
+                    .trim_end_matches("dB")
+                    .trim_end_matches("db")
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+            })
+            .filter(|value| value.is_finite())
+    };
+
+    let replay_gain = ReplayGain {
+        track_gain: replay_gain_value(ItemKey::ReplayGainTrackGain),
+        track_peak: replay_gain_value(ItemKey::ReplayGainTrackPeak),
+        album_gain: replay_gain_value(ItemKey::ReplayGainAlbumGain),
+        album_peak: replay_gain_value(ItemKey::ReplayGainAlbumPeak),
+    };
+
     let properties = tagged_file.properties();
 
     let duration = properties.duration();
@@ -138,6 +482,10 @@ pub fn load_from_file(path: &Path) -> Result<Track> {
         .map(|rate| NonZeroU32::new(rate).ok_or_else(|| anyhow!("Invalid sample rate (0) in file '{}'", path.display())))
         .transpose()?;
 
+    let bitrate = properties.audio_bitrate().filter(|&kbps| kbps > 0);
+
+    let channels = properties.channels().filter(|&count| count > 0);
+
     let file_path = path.to_path_buf();
 
     let codec = tagged_file.file_type();
@@ -149,25 +497,82 @@ pub fn load_from_file(path: &Path) -> Result<Track> {
         .or_else(|_| file_metadata.modified())
         .with_context(|| format!("Failed to determine creation/modification date for '{}'", path.display()))?;
 
-    Ok(Track {
+    let date_modified = file_metadata.modified().unwrap_or(date_added);
+
+    Ok(with_search_fields(Track {
         title,
         artist,
+        artists,
         album,
+        album_artist,
+        composer,
+        genre,
+        genres,
+        track_number,
+        disc_number,
+        year,
+        rating,
         duration,
+        duration_measured: false,
         path: file_path,
         sample_rate,
+        bitrate,
+        channels,
         codec,
         date_added,
-    })
+        date_modified,
+        cue: None,
+        replay_gain,
+        search_fields: SearchFields::default(),
+    }))
+}
+
+// Loads a track from either a regular audio file or a virtual cue track path (see cue_track_path()).
+pub fn load_track(path: &Path) -> Result<Track> {
+    let Some((cue_path, number)) = parse_cue_track_path(path) else {
+        return load_from_file(path);
+    };
+
+    let tracks = if is_cue_file(&cue_path) {
+        load_cue_tracks(&cue_path)?
+    } else {
+        load_embedded_cue_tracks(&cue_path)?.unwrap_or_default()
+    };
+
+    tracks
+        .into_iter()
+        .find(|t| t.path == path)
+        .ok_or_else(|| anyhow!("Track {} not found in cue sheet '{}'", number, cue_path.display()))
 }
 
 pub fn is_audio_file(path: &Path) -> bool {
     path.extension().is_some_and(|ext| EXTENSIONS.iter().any(|e| *e == ext))
 }
 
-pub fn load_tracks_from_directory(directory: &Path) -> Vec<Track> {
-    let entries: Vec<_> = WalkDir::new(directory)
+// The tracks loaded from a library directory, alongside the modification time of each file when it was read.
+// A rescan only needs to re-read the tags of files whose modification time has changed. The snapshot is kept between
+// sessions in the library cache (see save_library_cache()), so that the first scan after startup is a rescan too.
+#[fully_pub]
+struct LibrarySnapshot {
+    directory: PathBuf,
+    entries: HashMap<PathBuf, (SystemTime, Track)>,
+}
+
+// Loads the tracks in the directory, skipping anything matched by the exclusion rules. If a snapshot of the same
+// directory is provided, unchanged files are reused from it instead of being read again. Returns the tracks and a
+// snapshot for the next rescan. The progress is called with the number of files done, the number of files, and the
+// file that was just done, from whichever thread loaded it.
+pub fn load_tracks_from_directory(
+    directory: &Path,
+    exclusions: &[String],
+    previous: Option<&LibrarySnapshot>,
+    progress: impl Fn(usize, usize, &Path) + Sync,
+) -> (Vec<Track>, LibrarySnapshot) {
+    let previous = previous.filter(|snapshot| snapshot.directory == directory);
+
+    let entries: Vec<(PathBuf, Option<SystemTime>)> = WalkDir::new(directory)
         .into_iter()
+        .filter_entry(|entry| !is_excluded(entry.path(), directory, exclusions)) // Excluded folders aren't walked at all.
         .filter_map(|e| {
             if let Err(err) = &e {
                 warn!("Failed to read directory entry: {}", err);
@@ -176,23 +581,122 @@ pub fn load_tracks_from_directory(directory: &Path) -> Vec<Track> {
         })
         .filter(|entry| {
             let path = entry.path();
-            path.is_file() && is_audio_file(path)
+            path.is_file() && (is_audio_file(path) || is_cue_file(path))
+        })
+        .map(|entry| {
+            let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+            (entry.into_path(), modified)
         })
-        .map(|entry| entry.into_path())
         .collect();
 
-    let tracks: Vec<Track> = entries
+    // Audio files described by a cue sheet are replaced by the cue sheet's virtual tracks.
+    let (cue_files, entries): (Vec<_>, Vec<_>) = entries.into_iter().partition(|(path, _)| is_cue_file(path));
+
+    let cue_tracks: Vec<Track> = cue_files
         .par_iter()
-        .filter_map(|path| match load_from_file(path) {
-            Ok(track) => Some(track),
+        .filter_map(|(path, _)| match load_cue_tracks(path) {
+            Ok(tracks) => Some(tracks),
             Err(e) => {
-                warn!("Skipping track '{}': {}", path.display(), e);
+                warn!("Skipping cue sheet '{}': {}", path.display(), e);
                 None
             }
         })
+        .flatten()
         .collect();
 
-    tracks
+    let covered_by_cue: HashSet<&Path> = cue_tracks.iter().map(|t| t.audio_path()).collect();
+    let entries: Vec<_> = entries
+        .into_iter()
+        .filter(|(path, _)| !covered_by_cue.contains(path.as_path()))
+        .collect();
+
+    let total = entries.len();
+    let done = AtomicUsize::new(0);
+
+    let loaded: Vec<(PathBuf, SystemTime, Vec<Track>)> = entries
+        .into_par_iter()
+        .filter_map(|(path, modified)| {
+            let modified = modified.unwrap_or(SystemTime::UNIX_EPOCH);
+            progress(done.fetch_add(1, AtomicOrdering::Relaxed) + 1, total, &path);
+
+            let unchanged = previous
+                .and_then(|snapshot| snapshot.entries.get(&path))
+                .filter(|(previous_modified, _)| *previous_modified == modified);
+
+            if let Some((_, track)) = unchanged {
+                return Some((path, modified, vec![track.clone()]));
+            }
+
+            match load_file_tracks(&path) {
+                Ok(tracks) => Some((path, modified, tracks)),
+                Err(e) => {
+                    warn!("Skipping track '{}': {}", path.display(), e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    let mut tracks = Vec::with_capacity(loaded.len());
+    let mut snapshot = LibrarySnapshot {
+        directory: directory.to_path_buf(),
+        entries: HashMap::with_capacity(loaded.len()),
+    };
+
+    for (path, modified, file_tracks) in loaded {
+        // Like cue sheets, files with an embedded cue sheet are read again on every rescan.
+        if let [track] = file_tracks.as_slice()
+            && track.cue.is_none()
+        {
+            snapshot.entries.insert(path, (modified, track.clone()));
+        }
+
+        tracks.extend(file_tracks);
+    }
+
+    tracks.extend(cue_tracks);
+
+    (tracks, snapshot)
+}
+
+// Loads individual files, e.g. those referenced in place from outside the library directory.
+pub fn load_tracks_from_files(paths: &[PathBuf]) -> Vec<Track> {
+    paths
+        .par_iter()
+        .filter_map(|path| match load_file_tracks(path) {
+            Ok(tracks) => Some(tracks),
+            Err(e) => {
+                warn!("Skipping referenced track '{}': {}", path.display(), e);
+                None
+            }
+        })
+        .flatten()
+        .collect()
+}
+
+// A FLAC file with an embedded cue sheet is split into the sheet's virtual tracks. Other files are a single track.
+fn load_file_tracks(path: &Path) -> Result<Vec<Track>> {
+    if is_flac_file(path) {
+        // A broken cue sheet shouldn't hide the file, which can still be played as a single track.
+        match load_embedded_cue_tracks(path) {
+            Ok(Some(tracks)) => return Ok(tracks),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to load the embedded cue sheet of '{}': {:#}", path.display(), e),
+        }
+    }
+
+    Ok(vec![load_from_file(path)?])
+}
+
+// A shareable description of the track, e.g. "Now playing: Clair de Lune — Debussy (Suite bergamasque)".
+pub fn now_playing_string(track: &Track) -> String {
+    let title = track.title.as_deref().unwrap_or("Unknown Title");
+    let artist = track.artist.as_deref().unwrap_or("Unknown Artist");
+
+    match track.album.as_deref() {
+        Some(album) => format!("Now playing: {} — {} ({})", title, artist, album),
+        None => format!("Now playing: {} — {}", title, artist),
+    }
 }
 
 pub fn calculate_total_duration(tracks: &[Track]) -> Duration {
@@ -200,16 +704,149 @@ pub fn calculate_total_duration(tracks: &[Track]) -> Duration {
 }
 
 pub fn open_file_location(track: &Track) -> Result<()> {
-    opener::reveal(&track.path).with_context(|| format!("Failed to open file location for '{}'", track.path.display()))?;
+    let path = track.audio_path();
+    opener::reveal(path).with_context(|| format!("Failed to open file location for '{}'", path.display()))?;
+
+    Ok(())
+}
+
+// Tracks loaded afterwards read their rating from the POPM frame saved under this email.
+pub fn set_rating_email(email: &str) {
+    match RATING_EMAIL.write() {
+        Ok(mut rating_email) => *rating_email = email.trim().to_owned(),
+        Err(e) => warn!("Failed to set the rating email: {}", e),
+    }
+}
+
+fn rating_email(email: &str) -> &str {
+    if email.trim().is_empty() {
+        DEFAULT_RATING_EMAIL
+    } else {
+        email.trim()
+    }
+}
+
+// ID3 tags hold POPM frames, which may come from several players, so only the frame saved under the rating email is
+// read. Other tags hold a rating from 0 to 100, as written by foobar2000 and MusicBee.
+fn read_rating(tag: &Tag) -> u8 {
+    let popularimeter_rating = || {
+        let email_setting = RATING_EMAIL.read().map(|e| e.clone()).unwrap_or_default();
+        let email = rating_email(&email_setting);
+
+        tag.ratings()
+            .find(|popularimeter| popularimeter.email.as_deref() == Some(email))
+            .map_or(0, |popularimeter| popularimeter.rating as u8)
+    };
+
+    if tag.tag_type() == TagType::Id3v2 {
+        return popularimeter_rating();
+    }
+
+    match tag
+        .get_string(ItemKey::Popularimeter)
+        .and_then(|value| value.trim().parse::<u8>().ok())
+    {
+        Some(percent) => percent.min(100).div_ceil(PERCENT_PER_STAR),
+        None => popularimeter_rating(),
+    }
+}
+
+// Writes the rating to the file's tags so that it survives rescans and is seen by other players. 0 removes the rating.
+// In ID3 tags, the rating is saved in a POPM frame under the email, which players use to find their own rating.
+pub fn write_rating(track: &Track, rating: u8, email: &str) -> Result<()> {
+    if track.cue.is_some() {
+        bail!("Ratings can't be saved for tracks from a cue sheet");
+    }
+
+    let star_rating = match rating {
+        0 => None,
+        1 => Some(StarRating::One),
+        2 => Some(StarRating::Two),
+        3 => Some(StarRating::Three),
+        4 => Some(StarRating::Four),
+        5 => Some(StarRating::Five),
+        _ => bail!("Invalid rating {}", rating),
+    };
+
+    let path = &track.path;
+    let mut tagged_file = read_from_path(path).with_context(|| format!("Failed to read audio file '{}'", path.display()))?;
+
+    // Use the same tag that load_from_file() reads from.
+    let tag = if tagged_file.primary_tag().is_some() {
+        tagged_file.primary_tag_mut()
+    } else {
+        tagged_file.first_tag_mut()
+    }
+    .ok_or_else(|| anyhow!("No tags found in file '{}'", path.display()))?;
+
+    if tag.tag_type() == TagType::Id3v2 {
+        // Only this player's frame is replaced. The frames of other players are left as they are.
+        let email = rating_email(email);
+        let own_frames: Vec<(String, u64)> = tag
+            .ratings()
+            .filter(|popularimeter| popularimeter.email.as_deref() == Some(email))
+            .map(|popularimeter| (popularimeter.to_string(), popularimeter.play_counter))
+            .collect();
+        let play_counter = own_frames.first().map_or(0, |(_, play_counter)| *play_counter);
+
+        tag.retain(|item| {
+            let is_own_frame = matches!(item.key(), ItemKey::Popularimeter)
+                && item
+                    .value()
+                    .text()
+                    .is_some_and(|text| own_frames.iter().any(|(frame, _)| frame == text));
+            !is_own_frame
+        });
+
+        if let Some(star_rating) = star_rating {
+            let popularimeter = Popularimeter::custom(email.to_owned(), star_rating, play_counter);
+            tag.push(TagItem::new(ItemKey::Popularimeter, ItemValue::Text(popularimeter.to_string())));
+        }
+    } else {
+        tag.remove_key(ItemKey::Popularimeter);
+        if rating > 0 {
+            tag.insert_text(ItemKey::Popularimeter, (rating * PERCENT_PER_STAR).to_string());
+        }
+    }
+
+    tag.save_to_path(path, WriteOptions::default())
+        .with_context(|| format!("Failed to save the rating to '{}'", path.display()))?;
 
     Ok(())
 }
 
 pub fn extract_artwork(track: &Track) -> Option<Picture> {
-    let mut file = File::open(&track.path).ok()?;
+    let mut file = File::open(track.audio_path()).ok()?;
     let tagged_file = read_from(&mut file).ok()?;
     let tag = tagged_file.primary_tag().or_else(|| tagged_file.first_tag())?;
-    tag.pictures().first().cloned()
+    tag.get_picture_type(PictureType::CoverFront)
+        .or_else(|| tag.pictures().first())
+        .cloned()
+}
+
+// Embeds the image as the front cover of the track's audio file, replacing any existing front cover.
+pub fn embed_artwork(track: &Track, image_data: &[u8]) -> Result<()> {
+    let mut picture = Picture::from_reader(&mut &image_data[..]).context("Unsupported image format")?;
+    picture.set_pic_type(PictureType::CoverFront);
+
+    let path = track.audio_path();
+    let mut tagged_file = read_from_path(path).with_context(|| format!("Failed to read audio file '{}'", path.display()))?;
+
+    // Use the same tag that extract_artwork() reads from.
+    let tag = if tagged_file.primary_tag().is_some() {
+        tagged_file.primary_tag_mut()
+    } else {
+        tagged_file.first_tag_mut()
+    }
+    .ok_or_else(|| anyhow!("No tags found in file '{}'", path.display()))?;
+
+    tag.remove_picture_type(PictureType::CoverFront);
+    tag.push_picture(picture);
+
+    tag.save_to_path(path, WriteOptions::default())
+        .with_context(|| format!("Failed to save the artwork to '{}'", path.display()))?;
+
+    Ok(())
 }
 
 pub fn file_type_name(ft: FileType) -> &'static str {
@@ -230,3 +867,351 @@ pub fn file_type_name(ft: FileType) -> &'static str {
         _ => "UNK",
     }
 }
+
+pub fn is_cue_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("cue"))
+}
+
+fn is_flac_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("flac"))
+}
+
+#[derive(Default)]
+struct CueSheet {
+    title: Option<String>,
+    performer: Option<String>,
+    tracks: Vec<CueTrack>,
+}
+
+struct CueTrack {
+    number: u32,
+    file: PathBuf,
+    title: Option<String>,
+    performer: Option<String>,
+    start: Duration, // The position of INDEX 01 in the file.
+}
+
+// Virtual cue tracks are identified by the cue sheet's path with the track number appended, e.g. "album.cue#3".
+// Tracks of a cue sheet embedded in a FLAC file use the FLAC file's path instead, e.g. "live.flac#3".
+pub fn cue_track_path(cue_path: &Path, number: u32) -> PathBuf {
+    let mut path = OsString::from(cue_path.as_os_str());
+    path.push(format!("#{}", number));
+    PathBuf::from(path)
+}
+
+pub fn parse_cue_track_path(path: &Path) -> Option<(PathBuf, u32)> {
+    let (cue_path, number) = path.to_str()?.rsplit_once('#')?;
+    let cue_path = PathBuf::from(cue_path);
+
+    if !is_cue_file(&cue_path) && !is_flac_file(&cue_path) {
+        return None;
+    }
+
+    Some((cue_path, number.parse().ok()?))
+}
+
+// Loads each track in the cue sheet as a virtual track pointing into the sheet's audio file(s).
+pub fn load_cue_tracks(cue_path: &Path) -> Result<Vec<Track>> {
+    let bytes = read(cue_path).with_context(|| format!("Failed to read cue sheet '{}'", cue_path.display()))?;
+    let directory = cue_path.parent().unwrap_or_else(|| Path::new(""));
+    let sheet = parse_cue_sheet(&String::from_utf8_lossy(&bytes), directory, None);
+
+    if sheet.tracks.is_empty() {
+        bail!("No tracks found in cue sheet '{}'", cue_path.display());
+    }
+
+    cue_sheet_tracks(&sheet, cue_path)
+}
+
+// Loads the tracks of the cue sheet embedded in the FLAC file, if it has one. See read_embedded_cue_sheet().
+pub fn load_embedded_cue_tracks(flac_path: &Path) -> Result<Option<Vec<Track>>> {
+    let embedded =
+        read_embedded_cue_sheet(flac_path).with_context(|| format!("Failed to read the metadata of '{}'", flac_path.display()))?;
+
+    let sheet = match embedded {
+        Some(EmbeddedCueSheet::Text(text)) => parse_cue_sheet(&text, Path::new(""), Some(flac_path)),
+        Some(EmbeddedCueSheet::Tracks(starts)) => CueSheet {
+            tracks: starts
+                .into_iter()
+                .map(|(number, start)| CueTrack {
+                    number,
+                    file: flac_path.to_path_buf(),
+                    title: None,
+                    performer: None,
+                    start,
+                })
+                .collect(),
+            ..CueSheet::default()
+        },
+        None => return Ok(None),
+    };
+
+    // A sheet with a single track doesn't split anything, so the file is loaded as usual.
+    if sheet.tracks.len() < 2 {
+        return Ok(None);
+    }
+
+    cue_sheet_tracks(&sheet, flac_path).map(Some)
+}
+
+// The sheet path is what the virtual track paths are based on, see cue_track_path().
+fn cue_sheet_tracks(sheet: &CueSheet, sheet_path: &Path) -> Result<Vec<Track>> {
+    let mut audio_files: HashMap<&Path, Track> = HashMap::new();
+    let mut tracks = Vec::with_capacity(sheet.tracks.len());
+
+    // Editing either the sheet or the audio file changes the virtual tracks, so they count as modified by whichever
+    // was written to last.
+    let sheet_modified = metadata(sheet_path).and_then(|m| m.modified()).ok();
+
+    for (i, cue_track) in sheet.tracks.iter().enumerate() {
+        let file_track = match audio_files.get(cue_track.file.as_path()) {
+            Some(track) => track,
+            None => {
+                let track = load_from_file(&cue_track.file)?;
+                audio_files.entry(&cue_track.file).or_insert(track)
+            }
+        };
+
+        // A track ends where the next track in the same file begins.
+        let end = sheet
+            .tracks
+            .get(i + 1)
+            .filter(|next| next.file == cue_track.file)
+            .map(|next| next.start);
+
+        let duration = end.unwrap_or(file_track.duration).saturating_sub(cue_track.start);
+
+        let performer = cue_track.performer.as_ref().or(sheet.performer.as_ref());
+        let (artist, artists) = match performer {
+            Some(performer) => (Some(performer.clone()), split_tag_values([performer.as_str()].into_iter())),
+            None => (file_track.artist.clone(), file_track.artists.clone()),
+        };
+
+        tracks.push(with_search_fields(Track {
+            title: cue_track.title.clone().or_else(|| Some(format!("Track {:02}", cue_track.number))),
+            artist,
+            artists,
+            album: sheet.title.clone().or_else(|| file_track.album.clone()),
+            album_artist: sheet.performer.clone().or_else(|| file_track.album_artist.clone()),
+            composer: file_track.composer.clone(),
+            genre: file_track.genre.clone(),
+            genres: file_track.genres.clone(),
+            track_number: Some(cue_track.number),
+            disc_number: file_track.disc_number,
+            year: file_track.year,
+            rating: file_track.rating,
+            duration,
+            duration_measured: false,
+            path: cue_track_path(sheet_path, cue_track.number),
+            sample_rate: file_track.sample_rate,
+            bitrate: file_track.bitrate,
+            channels: file_track.channels,
+            codec: file_track.codec,
+            date_added: file_track.date_added,
+            date_modified: sheet_modified.map_or(file_track.date_modified, |m| m.max(file_track.date_modified)),
+            replay_gain: file_track.replay_gain,
+            cue: Some(CueRange {
+                audio_path: cue_track.file.clone(),
+                start: cue_track.start,
+                end,
+            }),
+            search_fields: SearchFields::default(),
+        }));
+    }
+
+    Ok(tracks)
+}
+
+// An embedded sheet's FILE commands name the files it was ripped from, so its tracks all point to the embedding file.
+fn parse_cue_sheet(text: &str, directory: &Path, embedded_file: Option<&Path>) -> CueSheet {
+    let mut sheet = CueSheet::default();
+    let mut current_file: Option<PathBuf> = embedded_file.map(Path::to_path_buf);
+    let mut current_track: Option<CueTrack> = None;
+
+    for line in text.lines() {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match command.to_ascii_uppercase().as_str() {
+            "FILE" if embedded_file.is_none() => current_file = Some(directory.join(cue_file_value(rest))),
+            "TRACK" => {
+                if let Some(track) = current_track.take() {
+                    sheet.tracks.push(track);
+                }
+
+                let number = rest.split_whitespace().next().and_then(|n| n.parse().ok());
+                if let (Some(number), Some(file)) = (number, &current_file) {
+                    current_track = Some(CueTrack {
+                        number,
+                        file: file.clone(),
+                        title: None,
+                        performer: None,
+                        start: Duration::ZERO,
+                    });
+                }
+            }
+            "TITLE" => match &mut current_track {
+                Some(track) => track.title = Some(cue_value(rest)),
+                None => sheet.title = Some(cue_value(rest)),
+            },
+            "PERFORMER" => match &mut current_track {
+                Some(track) => track.performer = Some(cue_value(rest)),
+                None => sheet.performer = Some(cue_value(rest)),
+            },
+            "INDEX" => {
+                let mut parts = rest.split_whitespace();
+                let is_start_index = parts.next().is_some_and(|n| n.parse() == Ok(1));
+
+                if is_start_index
+                    && let Some(track) = &mut current_track
+                    && let Some(start) = parts.next().and_then(parse_cue_time)
+                {
+                    track.start = start;
+                }
+            }
+            _ => {} // REM, FLAGS, ISRC, etc. are not needed.
+        }
+    }
+
+    if let Some(track) = current_track {
+        sheet.tracks.push(track);
+    }
+
+    sheet
+}
+
+// Values are either quoted, or the rest of the line, since many sheets leave values with spaces unquoted.
+fn cue_value(rest: &str) -> String {
+    if let Some(quoted) = rest.strip_prefix('"') {
+        quoted.split('"').next().unwrap_or_default().to_owned()
+    } else {
+        rest.to_owned()
+    }
+}
+
+// A FILE command's name is followed by the file's type, e.g. `"My Album.flac" WAVE`, which is left out of unquoted names.
+fn cue_file_value(rest: &str) -> String {
+    const FILE_TYPES: [&str; 5] = ["WAVE", "MP3", "AIFF", "BINARY", "MOTOROLA"];
+
+    if rest.starts_with('"') {
+        return cue_value(rest);
+    }
+
+    match rest.rsplit_once(char::is_whitespace) {
+        Some((name, file_type)) if FILE_TYPES.iter().any(|t| t.eq_ignore_ascii_case(file_type)) => name.trim_end().to_owned(),
+        _ => rest.to_owned(),
+    }
+}
+
+// Cue times are formatted as mm:ss:ff where there are 75 frames per second.
+fn parse_cue_time(time: &str) -> Option<Duration> {
+    let mut parts = time.split(':').map(|p| p.parse::<u64>().ok());
+    let minutes = parts.next()??;
+    let seconds = parts.next()??;
+    let frames = parts.next()??;
+
+    let frames_per_second = 75;
+    let total_frames = (minutes * 60 + seconds) * frames_per_second + frames;
+
+    Some(Duration::from_secs_f64(total_frames as f64 / frames_per_second as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> CueSheet {
+        parse_cue_sheet(text, Path::new("/music"), None)
+    }
+
+    #[test]
+    fn cue_sheet_reads_quoted_and_unquoted_values() {
+        let sheet = parse(
+            "PERFORMER \"The Band\"\n\
+             TITLE Greatest Hits Live\n\
+             FILE \"Side A.flac\" WAVE\n\
+             TRACK 01 AUDIO\n\
+             TITLE Opening Song\n\
+             PERFORMER \"Guest Singer\"\n\
+             INDEX 01 00:00:00\n",
+        );
+
+        assert_eq!(sheet.performer.as_deref(), Some("The Band"));
+        assert_eq!(sheet.title.as_deref(), Some("Greatest Hits Live"));
+        assert_eq!(sheet.tracks.len(), 1);
+        assert_eq!(sheet.tracks[0].file, PathBuf::from("/music/Side A.flac"));
+        assert_eq!(sheet.tracks[0].title.as_deref(), Some("Opening Song"));
+        assert_eq!(sheet.tracks[0].performer.as_deref(), Some("Guest Singer"));
+    }
+
+    #[test]
+    fn cue_sheet_leaves_the_type_out_of_unquoted_file_names() {
+        let sheet = parse("FILE My Album.wav WAVE\nTRACK 1 AUDIO\nINDEX 01 00:00:00\n");
+        assert_eq!(sheet.tracks[0].file, PathBuf::from("/music/My Album.wav"));
+
+        let sheet = parse("FILE album.mp3\nTRACK 1 AUDIO\nINDEX 01 00:00:00\n");
+        assert_eq!(sheet.tracks[0].file, PathBuf::from("/music/album.mp3"));
+    }
+
+    #[test]
+    fn cue_sheet_reads_track_starts_and_files() {
+        let sheet = parse(
+            "FILE \"a.flac\" WAVE\n\
+             TRACK 01 AUDIO\n\
+             INDEX 01 00:00:00\n\
+             TRACK 02 AUDIO\n\
+             INDEX 00 03:58:00\n\
+             INDEX 01 04:00:37\n\
+             FILE \"b.flac\" WAVE\n\
+             TRACK 03 AUDIO\n\
+             INDEX 01 00:00:00\n",
+        );
+
+        let numbers: Vec<u32> = sheet.tracks.iter().map(|t| t.number).collect();
+        assert_eq!(numbers, [1, 2, 3]);
+        assert_eq!(sheet.tracks[1].start, Duration::from_secs_f64(18037.0 / 75.0)); // 04:00:37, since INDEX 00 is the pregap.
+        assert_eq!(sheet.tracks[1].file, PathBuf::from("/music/a.flac"));
+        assert_eq!(sheet.tracks[2].file, PathBuf::from("/music/b.flac"));
+    }
+
+    #[test]
+    fn embedded_cue_sheet_tracks_point_to_the_embedding_file() {
+        let flac_path = Path::new("/music/album.flac");
+        let sheet = parse_cue_sheet(
+            "FILE \"original.wav\" WAVE\nTRACK 01 AUDIO\nINDEX 01 00:00:00\n",
+            Path::new(""),
+            Some(flac_path),
+        );
+
+        assert_eq!(sheet.tracks[0].file, flac_path);
+    }
+
+    #[test]
+    fn cue_times_are_in_75ths_of_a_second() {
+        assert_eq!(parse_cue_time("01:02:00"), Some(Duration::from_secs(62)));
+        assert_eq!(parse_cue_time("00:00:75"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_cue_time("00:01"), None);
+        assert_eq!(parse_cue_time("aa:00:00"), None);
+    }
+
+    #[test]
+    fn fold_text_ignores_case_and_accents() {
+        assert_eq!(fold_text("Björk"), "bjork");
+        assert_eq!(fold_text("BJÖRK"), "bjork");
+        assert_eq!(fold_text("ÉCOLE"), fold_text("école"));
+        assert_eq!(fold_text("ÅNGSTRÖM"), "angstrom");
+    }
+
+    #[test]
+    fn fold_text_folds_uppercase_letters_with_accented_lowercase_forms() {
+        assert_eq!(fold_text("İSTANBUL"), "istanbul");
+        assert_eq!(fold_text("İstanbul"), fold_text("istanbul"));
+    }
+
+    #[test]
+    fn fold_text_treats_decomposed_and_precomposed_text_the_same() {
+        assert_eq!(fold_text("Cafe\u{301}"), fold_text("Café"));
+        assert_eq!(fold_text("CAFE\u{301}"), fold_text("café"));
+    }
+}
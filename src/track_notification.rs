@@ -0,0 +1,35 @@
+use std::thread;
+
+use log::error;
+use notify_rust::Notification;
+
+use crate::{APP_NAME, track::Track};
+
+#[cfg(not(target_os = "macos"))]
+use crate::artwork_cache::artwork_path;
+
+// Shows a desktop notification for the track that just started. Showing can block on some platforms, so it happens off
+// the ui thread.
+pub fn show_track_notification(track: &Track) {
+    let mut notification = Notification::new();
+    notification
+        .appname(APP_NAME)
+        .summary(track.title.as_deref().unwrap_or("Unknown Title"));
+
+    let details: Vec<&str> = [track.artist.as_deref(), track.album.as_deref()].into_iter().flatten().collect();
+    if !details.is_empty() {
+        notification.body(&details.join(" • "));
+    }
+
+    // The playing track's artwork was cached on the track change. macOS always shows the app icon instead.
+    #[cfg(not(target_os = "macos"))]
+    if let Some(path) = artwork_path() {
+        notification.image_path(&path.to_string_lossy());
+    }
+
+    thread::spawn(move || {
+        if let Err(e) = notification.show() {
+            error!("Failed to show track notification: {}", e);
+        }
+    });
+}
@@ -0,0 +1,468 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::Duration,
+};
+
+use egui::{
+    Align, Button, Color32, Context, Frame, Id, Image, Layout, Margin, Modal, RichText, Sense, Spinner, TextureFilter, TextureOptions, Ui,
+    Vec2, include_image, load::Bytes,
+};
+use egui_extras::TableBuilder;
+use egui_material_icons::icons::{
+    ICON_ALBUM, ICON_ARTIST, ICON_CALENDAR_MONTH, ICON_CHEVRON_RIGHT, ICON_EXPAND_MORE, ICON_HOURGLASS, ICON_IMAGE_SEARCH, ICON_PLAY_ARROW,
+    ICON_QUEUE_MUSIC, ICON_TAG,
+};
+use fully_pub::fully_pub;
+use log::error;
+
+use crate::{
+    GemPlayer,
+    artwork_cache::{artwork_uri, cache_track_artwork, save_album_artwork},
+    commands::GemCommand,
+    embed_artwork_into_tracks,
+    thumbnail_cache::{ThumbnailCache, thumbnail_uri},
+    track::{Track, calculate_total_duration, filter, fold_text, format_track_number, sort_by_album_order},
+    ui::{
+        root::{format_duration_to_mmss, table_label, unselectable_label},
+        widgets::{centered_frame::centered_frame, playing_indicator::playing_indicator},
+    },
+};
+
+// The album title and album artist that group_into_albums() groups tracks by, since the title alone isn't unique.
+pub type AlbumKey = (String, Option<String>);
+
+#[fully_pub]
+struct AlbumsViewState {
+    expanded_album: Option<AlbumKey>, // The album whose tracks are shown.
+
+    // Same as the library view, grouping (potentially thousands) of tracks every frame is avoided.
+    cached_albums: Vec<Album>,
+    cache_dirty: bool,
+
+    // Artwork is only read from disk once an album is first shown.
+    artwork: ThumbnailCache,
+
+    fetching: HashSet<AlbumKey>,          // Albums whose artwork is being looked up online.
+    fetched_artwork: Vec<FetchedArtwork>, // Artwork found online, waiting for the user to confirm it.
+}
+
+#[fully_pub]
+struct FetchedArtwork {
+    album: AlbumKey,
+    image: Bytes,
+}
+
+enum FetchedArtworkResult {
+    Embed,
+    Cache,
+    Discard,
+}
+
+#[fully_pub]
+struct Album {
+    key: AlbumKey,
+    title: String,
+    artist: Option<String>, // The album artist, or else the artist of all tracks. None if the tracks have different artists.
+    year: Option<u16>,
+    duration: Duration,
+    tracks: Vec<Track>, // In album order.
+}
+
+// The rows of the table. The tracks of the expanded album are shown below it.
+enum AlbumRow {
+    Album(usize),
+    Track(usize, usize),
+}
+
+// Groups tracks with an album tag into albums, ordered by title. Albums with the same title are told apart by their
+// album artist, so that e.g. two "Greatest Hits" stay separate while a compilation stays together.
+pub fn group_into_albums(tracks: &[Track]) -> Vec<Album> {
+    let mut tracks_by_album: HashMap<(&str, Option<&str>), Vec<Track>> = HashMap::new();
+    for track in tracks {
+        if let Some(album) = track.album.as_deref() {
+            let key = (album, track.album_artist.as_deref());
+            tracks_by_album.entry(key).or_default().push(track.clone());
+        }
+    }
+
+    let mut albums: Vec<Album> = tracks_by_album
+        .into_iter()
+        .map(|((title, album_artist), mut tracks)| {
+            sort_by_album_order(&mut tracks);
+
+            let first_artist = tracks[0].artist.clone();
+            let artist = album_artist
+                .map(str::to_owned)
+                .or_else(|| first_artist.filter(|artist| tracks.iter().all(|t| t.artist.as_ref() == Some(artist))));
+
+            Album {
+                key: (title.to_owned(), album_artist.map(str::to_owned)),
+                title: title.to_owned(),
+                artist,
+                year: tracks.iter().find_map(|t| t.year),
+                duration: calculate_total_duration(&tracks),
+                tracks,
+            }
+        })
+        .collect();
+
+    albums.sort_by_key(|a| (fold_text(&a.title), a.artist.clone()));
+
+    albums
+}
+
+pub fn albums_view(ui: &mut Ui, gem: &mut GemPlayer) {
+    gem.ui.albums.artwork.poll_loaded();
+    gem.ui.albums.artwork.forget_evicted(ui.ctx());
+
+    ui.scope(|ui| {
+        if gem.library_directory.is_none() {
+            centered_frame(ui, |ui| {
+                ui.vertical_centered(|ui| {
+                    ui.add(unselectable_label(
+                        "No library directory set. Add your music folder in the settings.",
+                    ));
+                });
+            });
+
+            return;
+        };
+
+        if gem.ui.albums.cache_dirty {
+            gem.ui.albums.cached_albums = group_into_albums(&filter(&gem.library, &gem.ui.search));
+            gem.ui.albums.cache_dirty = false;
+        }
+
+        if gem.ui.albums.cached_albums.is_empty() {
+            centered_frame(ui, |ui| {
+                ui.vertical_centered(|ui| {
+                    ui.add(unselectable_label("No albums found."));
+                });
+            });
+
+            return;
+        }
+
+        let mut rows = Vec::new();
+        for (i, album) in gem.ui.albums.cached_albums.iter().enumerate() {
+            rows.push(AlbumRow::Album(i));
+
+            if gem.ui.albums.expanded_album.as_ref() == Some(&album.key) {
+                rows.extend((0..album.tracks.len()).map(|j| AlbumRow::Track(i, j)));
+            }
+        }
+
+        let album_row_height = 56.0;
+        let track_row_height = 26.0;
+        let row_heights = rows.iter().map(|row| match row {
+            AlbumRow::Album(_) => album_row_height,
+            AlbumRow::Track(_, _) => track_row_height,
+        });
+
+        let header_labels = [ICON_ALBUM, ICON_ARTIST, ICON_CALENDAR_MONTH, ICON_TAG, ICON_HOURGLASS];
+
+        let available_width = ui.available_width();
+        let artwork_width = 64.0;
+        let year_width = 64.0;
+        let count_width = 64.0;
+        let time_width = 64.0;
+        let actions_width = 112.0;
+        let remaining_width = available_width - artwork_width - year_width - count_width - time_width - actions_width;
+        let title_width = remaining_width * (2.0 / 3.0);
+        let artist_width = remaining_width * (1.0 / 3.0);
+
+        ui.spacing_mut().item_spacing.x = 0.0; // See comment in library_view() as to why we do this.
+
+        let mut maybe_command = None;
+        let mut toggled_album = None;
+
+        let playing_color = ui.visuals().selection.bg_fill;
+        let reduce_animations = gem.ui.reduce_animations;
+
+        // Artwork for the albums drawn this frame. Read after the table since the table borrows the state.
+        let mut missing_artwork = Vec::new();
+
+        TableBuilder::new(ui)
+            .striped(true)
+            .sense(Sense::click())
+            .cell_layout(Layout::left_to_right(Align::Center))
+            .column(egui_extras::Column::exact(artwork_width))
+            .column(egui_extras::Column::exact(title_width))
+            .column(egui_extras::Column::exact(artist_width))
+            .column(egui_extras::Column::exact(year_width))
+            .column(egui_extras::Column::exact(count_width))
+            .column(egui_extras::Column::exact(time_width))
+            .column(egui_extras::Column::exact(actions_width))
+            .header(16.0, |mut header| {
+                header.col(|_| {});
+
+                for h in header_labels {
+                    header.col(|ui| {
+                        ui.add_space(4.0);
+                        ui.add(unselectable_label(RichText::new(h).strong()));
+                    });
+                }
+            })
+            .body(|body| {
+                body.heterogeneous_rows(row_heights, |mut row| match rows[row.index()] {
+                    AlbumRow::Album(i) => {
+                        let album = &gem.ui.albums.cached_albums[i];
+                        let album_is_expanded = gem.ui.albums.expanded_album.as_ref() == Some(&album.key);
+
+                        row.col(|ui| {
+                            ui.add_space(8.0);
+                            match gem.ui.albums.artwork.get(&album.key) {
+                                Some(artwork) => album_artwork(ui, thumbnail_uri(&album.key), artwork, album_row_height - 8.0),
+                                None => missing_artwork.push(i),
+                            }
+                        });
+
+                        row.col(|ui| {
+                            ui.add_space(4.0);
+                            let icon = if album_is_expanded { ICON_EXPAND_MORE } else { ICON_CHEVRON_RIGHT };
+                            ui.add(unselectable_label(icon));
+                            ui.add_space(4.0);
+                            ui.add(unselectable_label(RichText::new(&album.title).strong()));
+                        });
+
+                        row.col(|ui| {
+                            ui.add_space(4.0);
+                            ui.add(unselectable_label(album.artist.as_deref().unwrap_or("Various Artists")));
+                        });
+
+                        row.col(|ui| {
+                            ui.add_space(4.0);
+                            let year = album.year.map(|y| y.to_string()).unwrap_or_else(|| "-".to_owned());
+                            ui.add(unselectable_label(year));
+                        });
+
+                        row.col(|ui| {
+                            ui.add_space(4.0);
+                            ui.add(unselectable_label(format!("{}", album.tracks.len())));
+                        });
+
+                        row.col(|ui| {
+                            ui.add_space(4.0);
+                            ui.add(unselectable_label(format_duration_to_mmss(album.duration)));
+                        });
+
+                        let track_keys = || album.tracks.iter().map(|t| t.path.clone()).collect();
+
+                        row.col(|ui| {
+                            ui.add_space(8.0);
+
+                            if ui.add(Button::new(ICON_PLAY_ARROW)).on_hover_text("Play album").clicked() {
+                                maybe_command = Some(GemCommand::PlayTrackList {
+                                    track_keys: track_keys(),
+                                    start_at: None,
+                                });
+                            }
+
+                            ui.add_space(8.0);
+
+                            if ui.add(Button::new(ICON_QUEUE_MUSIC)).on_hover_text("Add album to queue").clicked() {
+                                maybe_command = Some(GemCommand::EnqueueTracks { track_keys: track_keys() });
+                            }
+
+                            ui.add_space(8.0);
+
+                            let album_has_no_artwork = gem.ui.albums.artwork.has_no_artwork(&album.key);
+                            if gem.ui.albums.fetching.contains(&album.key) {
+                                ui.add(Spinner::new());
+                            } else if album_has_no_artwork {
+                                let response = ui.add(Button::new(ICON_IMAGE_SEARCH)).on_hover_text("Find artwork online");
+                                if response.clicked() {
+                                    maybe_command = Some(GemCommand::FetchAlbumArtwork {
+                                        album: album.key.clone(),
+                                        artist: album.artist.clone(),
+                                    });
+                                }
+                            }
+                        });
+
+                        if row.response().clicked() {
+                            toggled_album = Some(album.key.clone());
+                        }
+                    }
+                    AlbumRow::Track(i, j) => {
+                        let album = &gem.ui.albums.cached_albums[i];
+                        let track = &album.tracks[j];
+
+                        let track_is_playing = gem.player.playing.as_ref().is_some_and(|t| t == track);
+                        let text_color = track_is_playing.then_some(playing_color);
+
+                        row.col(|_| {});
+
+                        row.col(|ui| {
+                            ui.add_space(24.0);
+                            ui.add(unselectable_label(RichText::new(format_track_number(track)).weak()));
+                            ui.add_space(8.0);
+                            ui.add(table_label(track.title.as_deref().unwrap_or("-"), text_color));
+                        });
+
+                        row.col(|ui| {
+                            ui.add_space(4.0);
+                            ui.add(table_label(track.artist.as_deref().unwrap_or("-"), text_color));
+                        });
+
+                        row.col(|_| {});
+                        row.col(|_| {});
+
+                        row.col(|ui| {
+                            ui.add_space(4.0);
+                            ui.add(table_label(format_duration_to_mmss(track.duration), text_color));
+                        });
+
+                        row.col(|ui| {
+                            if track_is_playing {
+                                ui.add_space(8.0);
+                                playing_indicator(ui, reduce_animations);
+                            }
+                        });
+
+                        if row.response().double_clicked() {
+                            maybe_command = Some(GemCommand::PlayTrackList {
+                                track_keys: album.tracks.iter().map(|t| t.path.clone()).collect(),
+                                start_at: Some(track.path.clone()),
+                            });
+                        }
+                    }
+                });
+            });
+
+        let missing_artwork = missing_artwork
+            .into_iter()
+            .map(|i| &gem.ui.albums.cached_albums[i])
+            .filter(|album| !gem.ui.albums.artwork.is_loading(&album.key))
+            .map(|album| (album.key.clone(), album.tracks.clone(), album.title.clone()))
+            .collect();
+        gem.ui.albums.artwork.load_in_background(missing_artwork, ui.ctx());
+
+        if let Some(fetched) = gem.ui.albums.fetched_artwork.first()
+            && let Some(result) = fetched_artwork_modal(ui, fetched)
+        {
+            let fetched = gem.ui.albums.fetched_artwork.remove(0);
+            apply_fetched_artwork(ui.ctx(), gem, fetched, result);
+        }
+
+        if let Some(key) = toggled_album {
+            let albums_state = &mut gem.ui.albums;
+            if albums_state.expanded_album.as_ref() == Some(&key) {
+                albums_state.expanded_album = None;
+            } else {
+                albums_state.expanded_album = Some(key);
+            }
+        }
+
+        // Queue commands AFTER rendering the table to avoid borrow checker issues that come with mutating state inside closures.
+        if let Some(command) = maybe_command {
+            gem.commands.push(command);
+        }
+    });
+}
+
+fn fetched_artwork_modal(ui: &mut Ui, fetched: &FetchedArtwork) -> Option<FetchedArtworkResult> {
+    let mut result = None;
+
+    let modal = Modal::new(Id::new("fetched_artwork_modal"))
+        .backdrop_color(Color32::TRANSPARENT)
+        .show(ui.ctx(), |ui| {
+            ui.set_width(240.0);
+            Frame::new().outer_margin(Margin::same(4)).show(ui, |ui| {
+                ui.add(unselectable_label(
+                    RichText::new(format!("Artwork for {}", fetched.album.0)).heading(),
+                ));
+                ui.add_space(8.0);
+
+                ui.vertical_centered(|ui| {
+                    album_artwork(ui, fetched_artwork_uri(&fetched.album), Some(&fetched.image), 200.0);
+                });
+
+                ui.add_space(8.0);
+                ui.add(unselectable_label(
+                    RichText::new("Embedding writes the artwork into the album's files.").small().weak(),
+                ));
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui.button("Embed in Files").clicked() {
+                        result = Some(FetchedArtworkResult::Embed);
+                    }
+
+                    if ui.button("Save to Cache").clicked() {
+                        result = Some(FetchedArtworkResult::Cache);
+                    }
+
+                    if ui.button("Discard").clicked() {
+                        result = Some(FetchedArtworkResult::Discard);
+                    }
+                });
+            });
+        });
+
+    if modal.should_close() && result.is_none() {
+        result = Some(FetchedArtworkResult::Discard);
+    }
+
+    result
+}
+
+fn apply_fetched_artwork(ctx: &Context, gem: &mut GemPlayer, fetched: FetchedArtwork, result: FetchedArtworkResult) {
+    ctx.forget_image(&fetched_artwork_uri(&fetched.album));
+
+    let (title, album_artist) = &fetched.album;
+    let is_in_album = |track: &Track| track.album.as_ref() == Some(title) && &track.album_artist == album_artist;
+
+    match result {
+        FetchedArtworkResult::Embed => {
+            let track_keys: Vec<PathBuf> = gem.library.iter().filter(|t| is_in_album(t)).map(|t| t.path.clone()).collect();
+
+            embed_artwork_into_tracks(ctx, gem, &track_keys, &fetched.image);
+        }
+        FetchedArtworkResult::Cache => {
+            if let Err(e) = save_album_artwork(title, &fetched.image) {
+                error!("Failed to save artwork for '{}': {}", title, e);
+                gem.ui.toasts.error("Failed to save the artwork.");
+                return;
+            }
+
+            gem.ui.albums.artwork.remove(&fetched.album);
+
+            // The playing track may be showing the placeholder artwork.
+            if let Some(track) = &gem.player.playing
+                && is_in_album(track)
+            {
+                if let Some(uri) = artwork_uri() {
+                    ctx.forget_image(&uri);
+                }
+
+                if let Err(e) = cache_track_artwork(track) {
+                    error!("Failed to cache artwork: {e}");
+                }
+            }
+        }
+        FetchedArtworkResult::Discard => {}
+    }
+}
+
+fn fetched_artwork_uri(album: &AlbumKey) -> String {
+    format!("{}/fetched", thumbnail_uri(album))
+}
+
+fn album_artwork(ui: &mut Ui, uri: String, artwork: Option<&Bytes>, width: f32) {
+    let image = match artwork {
+        Some(bytes) => Image::from_bytes(uri, bytes.clone()),
+        None => Image::new(include_image!("../../assets/icon.png")),
+    };
+
+    ui.add(
+        image
+            .texture_options(TextureOptions::LINEAR.with_mipmap_mode(Some(TextureFilter::Linear)))
+            .show_loading_spinner(false)
+            .fit_to_exact_size(Vec2::splat(width))
+            .maintain_aspect_ratio(false)
+            .corner_radius(2.0),
+    );
+}
@@ -0,0 +1,89 @@
+use egui::{Align, Align2, Area, Button, CursorIcon, Frame, Id, Layout, RichText, Ui, vec2};
+use egui_material_icons::icons::ICON_FULLSCREEN_EXIT;
+use fully_pub::fully_pub;
+
+use crate::{
+    AMBIENT_MODE_SHORTCUT, GemPlayer,
+    artwork_cache::artwork_uri,
+    chapters::current_chapter_index,
+    commands::GemCommand,
+    player::get_position,
+    ui::{
+        control_panel::{display_track_marquee, display_visualizer, playback_controls},
+        widgets::artwork::artwork_ui,
+    },
+};
+
+const CONTROLS_TIMEOUT: f64 = 3.0; // Seconds without mouse activity before the controls and cursor are hidden.
+
+// A fullscreen view with just the artwork, the marquee, and a big visualizer, for parties and second monitors.
+#[fully_pub]
+struct AmbientState {
+    enabled: bool,
+    last_activity: f64, // When the mouse last moved or was pressed.
+}
+
+pub fn ambient_view(ui: &mut Ui, gem: &mut GemPlayer) {
+    let (time, is_active) = ui.input(|i| (i.time, i.pointer.is_moving() || i.pointer.any_down()));
+    if is_active {
+        gem.ui.ambient.last_activity = time;
+    }
+
+    let show_controls = time - gem.ui.ambient.last_activity < CONTROLS_TIMEOUT;
+    if !show_controls {
+        ui.ctx().set_cursor_icon(CursorIcon::None);
+    }
+
+    let available = ui.available_size();
+    let artwork_width = (available.y * 0.5).min(available.x * 0.6);
+    let visualizer_size = vec2(artwork_width, available.y * 0.15);
+
+    ui.vertical_centered(|ui| {
+        ui.add_space(available.y * 0.08);
+
+        let uri = artwork_uri();
+        artwork_ui(ui, uri.as_deref(), artwork_width);
+
+        ui.add_space(24.0);
+
+        let position = get_position(&gem.player).unwrap_or_default();
+        let chapter = current_chapter_index(&gem.chapters, position).map(|i| &gem.chapters[i]);
+        ui.allocate_ui(vec2(artwork_width, 24.0), |ui| {
+            display_track_marquee(ui, gem.player.playing.as_ref(), chapter, &mut gem.ui.marquee);
+        });
+
+        ui.add_space(32.0);
+
+        ui.allocate_ui_with_layout(visualizer_size, Layout::bottom_up(Align::Center), |ui| {
+            display_visualizer(ui, gem, visualizer_size);
+        });
+    });
+
+    if !show_controls {
+        return;
+    }
+
+    Area::new(Id::new("ambient_controls"))
+        .anchor(Align2::CENTER_BOTTOM, vec2(0.0, -32.0))
+        .show(ui.ctx(), |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if let Some(command) = playback_controls(ui, &mut gem.player) {
+                        gem.commands.push(command);
+                    }
+
+                    ui.add_space(16.0);
+
+                    let shortcut_text = ui.ctx().format_shortcut(&AMBIENT_MODE_SHORTCUT);
+                    let exit_button = Button::new(RichText::new(ICON_FULLSCREEN_EXIT).size(18.0));
+                    if ui
+                        .add(exit_button)
+                        .on_hover_text(format!("Exit ambient mode ({})", shortcut_text))
+                        .clicked()
+                    {
+                        gem.commands.push(GemCommand::ToggleAmbientMode);
+                    }
+                });
+            });
+        });
+}
@@ -1,14 +1,18 @@
 use egui::{Align, Button, Direction, Frame, Layout, Margin, Popup, PopupCloseBehavior, TextEdit, Ui};
-use egui_material_icons::icons::{ICON_CLEAR, ICON_CLEAR_ALL, ICON_FILTER_LIST, ICON_SEARCH};
+use egui_material_icons::icons::{ICON_CLEAR, ICON_CLEAR_ALL, ICON_FILTER_LIST, ICON_MY_LOCATION, ICON_SEARCH, ICON_STAR_HALF};
 use log::info;
 use strum::IntoEnumIterator;
 
 use crate::{
-    GemPlayer,
+    GemPlayer, LOCATE_PLAYING_SHORTCUT,
+    commands::GemCommand,
     player::clear_the_queue,
     playlist::PlaylistRetrieval,
-    track::{SortBy, SortOrder, Track, calculate_total_duration},
-    ui::root::{View, format_duration_to_hhmmss, unselectable_label},
+    track::{MAX_RATING, SortBy, SortOrder, Track, calculate_total_duration},
+    ui::{
+        library_view::TrackDragPayload,
+        root::{View, format_duration_to_hhmmss, rating_stars, unselectable_label},
+    },
 };
 
 pub fn bottom_bar(ui: &mut Ui, gem: &mut GemPlayer) {
@@ -38,11 +42,17 @@ fn view_selector(ui: &mut Ui, current_view: View) -> Option<View> {
     let mut selected = None;
 
     for view in View::iter() {
-        if ui
+        let response = ui
             .selectable_label(current_view == view, format!("  {}  ", view.icon()))
-            .on_hover_text(format!("{:?}", view))
-            .clicked()
-        {
+            .on_hover_text(view.name());
+
+        if response.clicked() {
+            selected = Some(view);
+        }
+
+        // Dragging tracks over the playlists tab opens it so they can be dropped onto a playlist.
+        let dragging_tracks_over = response.dnd_hover_payload::<TrackDragPayload>().is_some();
+        if view == View::Playlists && current_view != view && dragging_tracks_over {
             selected = Some(view);
         }
 
@@ -55,14 +65,21 @@ fn view_selector(ui: &mut Ui, current_view: View) -> Option<View> {
 fn get_status(gem: &GemPlayer) -> Option<String> {
     match gem.ui.current_view {
         View::Library => Some(get_count_and_duration_string_from_tracks(&gem.library)),
+        View::Albums => Some(format!("{} albums", gem.ui.albums.cached_albums.len())),
         View::Queue => Some(get_count_and_duration_string_from_tracks(&gem.player.queue)),
+        View::History => Some(get_count_and_duration_string_from_tracks(&gem.player.history)),
         View::Playlists => {
+            if let Some(auto_playlist) = gem.ui.playlists.selected_auto_playlist {
+                let tracks = auto_playlist.tracks(&gem.library, &gem.play_counts);
+                return Some(get_count_and_duration_string_from_tracks(&tracks));
+            }
+
             let playlist_key = gem.ui.playlists.selected_playlist_key.as_ref()?;
             let playlist = gem.playlists.get_by_path(playlist_key);
 
             Some(get_count_and_duration_string_from_tracks(&playlist.tracks))
         }
-        View::Settings => None,
+        View::NowPlaying | View::Settings => None,
     }
 }
 
@@ -72,8 +89,9 @@ fn controls(ui: &mut Ui, gem: &mut GemPlayer) {
             let search_was_changed = search(ui, &mut gem.ui.search);
             if search_was_changed {
                 // We reset both caches since there is only one search text state variable.
-                gem.ui.library.cache_dirty = true;
+                gem.ui.library.search_dirty = true;
                 gem.ui.library.selected_tracks.clear();
+                gem.ui.albums.cache_dirty = true;
                 gem.ui.playlists.cache_dirty = true;
                 gem.ui.playlists.selected_tracks.clear();
             }
@@ -82,6 +100,25 @@ fn controls(ui: &mut Ui, gem: &mut GemPlayer) {
             if sort_was_changed {
                 gem.ui.library.cache_dirty = true;
             }
+
+            let rating_filter_was_changed = rating_filter(ui, &mut gem.ui.library.min_rating);
+            if rating_filter_was_changed {
+                gem.ui.library.cache_dirty = true;
+                gem.ui.library.selected_tracks.clear();
+            }
+
+            locate_playing_button(ui, gem);
+        }
+        View::Albums => {
+            let search_changed = search(ui, &mut gem.ui.search);
+            if search_changed {
+                // Same as above.
+                gem.ui.library.search_dirty = true;
+                gem.ui.library.selected_tracks.clear();
+                gem.ui.albums.cache_dirty = true;
+                gem.ui.playlists.cache_dirty = true;
+                gem.ui.playlists.selected_tracks.clear();
+            }
         }
         View::Queue => {
             let queue_is_not_empty = !gem.player.queue.is_empty();
@@ -95,20 +132,52 @@ fn controls(ui: &mut Ui, gem: &mut GemPlayer) {
                 clear_the_queue(&mut gem.player);
             }
         }
+        View::History => {
+            let history_is_not_empty = !gem.player.history.is_empty();
+
+            let clear_button = Button::new(ICON_CLEAR_ALL);
+            let response = ui
+                .add_enabled(history_is_not_empty, clear_button)
+                .on_hover_text("Clear")
+                .on_disabled_hover_text("History is empty");
+            if response.clicked() {
+                gem.player.history.clear();
+            }
+        }
         View::Playlists => {
             let search_changed = search(ui, &mut gem.ui.search);
             if search_changed {
                 // Same as above.
-                gem.ui.library.cache_dirty = true;
+                gem.ui.library.search_dirty = true;
                 gem.ui.library.selected_tracks.clear();
+                gem.ui.albums.cache_dirty = true;
                 gem.ui.playlists.cache_dirty = true;
                 gem.ui.playlists.selected_tracks.clear();
             }
+
+            let sort_was_changed = playlist_sort_and_order_by(ui, &mut gem.ui.playlists.sort_by, &mut gem.ui.playlists.sort_order);
+            if sort_was_changed {
+                gem.ui.playlists.cache_dirty = true;
+            }
+
+            locate_playing_button(ui, gem);
         }
         _ => {}
     }
 }
 
+fn locate_playing_button(ui: &mut Ui, gem: &mut GemPlayer) {
+    let shortcut_text = ui.ctx().format_shortcut(&LOCATE_PLAYING_SHORTCUT);
+    let response = ui
+        .add_enabled(gem.player.playing.is_some(), Button::new(ICON_MY_LOCATION))
+        .on_hover_text(format!("Locate playing track ({})", shortcut_text))
+        .on_disabled_hover_text("Nothing is playing");
+
+    if response.clicked() {
+        gem.commands.push(GemCommand::LocatePlayingTrack);
+    }
+}
+
 fn sort_and_order_by(ui: &mut Ui, sort_by: &mut SortBy, sort_order: &mut SortOrder) -> bool {
     let response = ui.button(ICON_FILTER_LIST).on_hover_text("Sort by and order");
 
@@ -131,6 +200,54 @@ fn sort_and_order_by(ui: &mut Ui, sort_by: &mut SortBy, sort_order: &mut SortOrd
     sort_by_changed || sort_order_changed
 }
 
+// Like sort_and_order_by(), but playlists can also be shown in their own order, which is the default.
+fn playlist_sort_and_order_by(ui: &mut Ui, sort_by: &mut Option<SortBy>, sort_order: &mut SortOrder) -> bool {
+    let response = ui.button(ICON_FILTER_LIST).on_hover_text("Sort by and order");
+
+    let mut sort_by_changed = false;
+    let mut sort_order_changed = false;
+
+    Popup::menu(&response)
+        .gap(4.0)
+        .close_behavior(PopupCloseBehavior::CloseOnClickOutside)
+        .show(|ui| {
+            sort_by_changed |= ui.radio_value(sort_by, None, "Manual Order").changed();
+            for sb in SortBy::iter() {
+                sort_by_changed |= ui.radio_value(sort_by, Some(sb), sb.label()).changed();
+            }
+            ui.separator();
+            ui.add_enabled_ui(sort_by.is_some(), |ui| {
+                for so in SortOrder::iter() {
+                    sort_order_changed |= ui.radio_value(sort_order, so, format!("{:?}", so)).changed();
+                }
+            });
+        });
+
+    sort_by_changed || sort_order_changed
+}
+
+fn rating_filter(ui: &mut Ui, min_rating: &mut u8) -> bool {
+    let response = ui.button(ICON_STAR_HALF).on_hover_text("Filter by rating");
+
+    let mut changed = false;
+
+    Popup::menu(&response)
+        .gap(4.0)
+        .close_behavior(PopupCloseBehavior::CloseOnClickOutside)
+        .show(|ui| {
+            for rating in (0..=MAX_RATING).rev() {
+                let label = if rating == 0 {
+                    "All Tracks".to_owned()
+                } else {
+                    format!("{} & up", rating_stars(rating))
+                };
+                changed |= ui.radio_value(min_rating, rating, label).changed();
+            }
+        });
+
+    changed
+}
+
 fn search(ui: &mut Ui, search_text: &mut String) -> bool {
     let mut changed = false;
     let clear_button_is_visible = !search_text.is_empty();
@@ -146,9 +263,12 @@ fn search(ui: &mut Ui, search_text: &mut String) -> bool {
         .prefix(ICON_SEARCH)
         .hint_text("Search ...")
         .desired_width(140.0)
-        .char_limit(20);
+        .char_limit(64); // Long enough for a few field filters, e.g. `artist:radiohead year:2000..2010`.
 
-    if ui.add(search_bar).changed() {
+    let response = ui
+        .add(search_bar)
+        .on_hover_text("Filter by field with artist:, album:, albumartist:, composer:, title:, genre:, or year:, e.g. year:1990..1999");
+    if response.changed() {
         changed = true;
     }
 
@@ -1,30 +1,45 @@
 use std::time::Duration;
 
-use egui::{Align, Button, Frame, Layout, Margin, Popup, RectAlign, RichText, Slider, Ui, Vec2};
+use egui::{
+    Align, Button, Event, Frame, Layout, Margin, MouseWheelUnit, Popup, PopupCloseBehavior, RectAlign, RichText, ScrollArea, Sense, Slider,
+    Ui, Vec2, vec2,
+};
 use egui_extras::{Size, StripBuilder};
 use egui_material_icons::icons::{
-    ICON_PAUSE, ICON_PLAY_ARROW, ICON_REPEAT, ICON_SHUFFLE, ICON_SKIP_NEXT, ICON_SKIP_PREVIOUS, ICON_VOLUME_DOWN, ICON_VOLUME_OFF,
-    ICON_VOLUME_UP,
+    ICON_CONTENT_COPY, ICON_EQUALIZER, ICON_FULLSCREEN, ICON_LYRICS, ICON_PAUSE, ICON_PLAY_ARROW, ICON_REPEAT, ICON_REPEAT_ONE,
+    ICON_SHUFFLE, ICON_SKIP_NEXT, ICON_SKIP_PREVIOUS, ICON_TOC, ICON_VOLUME_DOWN, ICON_VOLUME_OFF, ICON_VOLUME_UP,
 };
+use strum::IntoEnumIterator;
 
 use crate::{
-    GemPlayer,
+    AMBIENT_MODE_SHORTCUT, COPY_NOW_PLAYING_SHORTCUT, GemPlayer,
     artwork_cache::artwork_uri,
+    chapters::{Chapter, current_chapter_index},
     commands::GemCommand,
-    player::{Player, get_position},
+    equalizer::{self, BAND_FREQUENCIES, Equalizer, EqualizerPreset, MAX_GAIN_DB, matching_preset},
+    player::{Player, RepeatMode, current_output_settings, get_position},
     track::{Track, file_type_name},
     ui::{
-        root::{format_duration_to_mmss, unselectable_label},
+        root::{View, format_duration_to_mmss, unselectable_label},
         widgets::{
             artwork::artwork_ui,
             bar_display::BarDisplay,
+            level_meter::level_meter_ui,
             marquee::{Marquee, marquee_ui},
             metadata_chip::MetadataChip,
+            spectrogram::spectrogram_ui,
+            spectrum_display::SpectrumDisplay,
+            toggle_switch::toggle,
+            vu_needle::VuNeedle,
+            waveform_display::WaveformDisplay,
         },
     },
-    visualizer::smooth_bars,
+    visualizer::{VisualizerFrame, VisualizerStyle, smooth_bars, update_peaks},
 };
 
+const VOLUME_SCROLL_STEP: f32 = 0.05; // Per notch of the mouse wheel.
+const POINTS_PER_NOTCH: f32 = 50.0; // Touchpads scroll in points rather than in notches.
+
 pub fn control_panel(ui: &mut Ui, gem: &mut GemPlayer) {
     // Specifying the widths of the elements in the now playing component before-hand
     // allows us to center it horizontally.
@@ -64,18 +79,179 @@ fn left_controls(ui: &mut Ui, gem: &mut GemPlayer) {
 
 fn right_controls(ui: &mut Ui, gem: &mut GemPlayer) {
     ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-        display_visualizer(ui, &mut gem.player);
+        // The other styles take up the same space as the bars.
+        let visualizer_size = vec2(80.0, ui.available_height() * 0.5);
+        if gem.player.visualizer.pipeline.is_some() {
+            display_visualizer(ui, gem, visualizer_size);
 
-        ui.add_space(16.0);
+            ui.add_space(16.0);
+
+            if gem.player.visualizer.meter_enabled {
+                level_meter_ui(ui, &mut gem.ui.level_meter, 10.0, visualizer_size.y);
+
+                ui.add_space(8.0);
+            }
+        }
 
         volume_control_button(ui, gem);
+
+        ui.add_space(8.0);
+
+        equalizer_button(ui, &mut gem.player.equalizer);
+
+        ui.add_space(8.0);
+
+        copy_now_playing_button(ui, gem);
+
+        ui.add_space(8.0);
+
+        if !gem.chapters.is_empty() {
+            chapters_button(ui, gem);
+
+            ui.add_space(8.0);
+        }
+
+        let lyrics_button = Button::new(RichText::new(ICON_LYRICS).size(18.0)).selected(gem.ui.show_lyrics);
+        if ui.add(lyrics_button).on_hover_text("Lyrics").clicked() {
+            gem.ui.show_lyrics = !gem.ui.show_lyrics;
+        }
+
+        ui.add_space(8.0);
+
+        let shortcut_text = ui.ctx().format_shortcut(&AMBIENT_MODE_SHORTCUT);
+        let ambient_button = Button::new(RichText::new(ICON_FULLSCREEN).size(18.0));
+        if ui
+            .add(ambient_button)
+            .on_hover_text(format!("Ambient mode ({})", shortcut_text))
+            .clicked()
+        {
+            gem.commands.push(GemCommand::ToggleAmbientMode);
+        }
     });
 }
 
+fn copy_now_playing_button(ui: &mut Ui, gem: &mut GemPlayer) {
+    let track_is_playing = gem.player.playing.is_some();
+
+    let shortcut_text = ui.ctx().format_shortcut(&COPY_NOW_PLAYING_SHORTCUT);
+    let copy_button = Button::new(RichText::new(ICON_CONTENT_COPY).size(18.0));
+    let response = ui
+        .add_enabled(track_is_playing, copy_button)
+        .on_hover_text(format!("Copy now playing ({})", shortcut_text))
+        .on_disabled_hover_text("Nothing is playing");
+
+    if response.clicked() {
+        gem.commands.push(GemCommand::CopyNowPlaying);
+    }
+}
+
+fn chapters_button(ui: &mut Ui, gem: &mut GemPlayer) {
+    let chapters_button = Button::new(RichText::new(ICON_TOC).size(18.0));
+    let response = ui.add(chapters_button).on_hover_text("Chapters");
+
+    let position = get_position(&gem.player).unwrap_or_default();
+    let current = current_chapter_index(&gem.chapters, position);
+
+    Popup::menu(&response)
+        .gap(4.0)
+        .close_behavior(PopupCloseBehavior::CloseOnClickOutside)
+        .show(|ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .button(ICON_SKIP_PREVIOUS.rich_text())
+                    .on_hover_text("Previous chapter")
+                    .clicked()
+                {
+                    gem.commands.push(GemCommand::PreviousChapter);
+                }
+
+                let next_enabled = current.is_none_or(|i| i + 1 < gem.chapters.len());
+                let next_button = Button::new(ICON_SKIP_NEXT.rich_text());
+                let response = ui
+                    .add_enabled(next_enabled, next_button)
+                    .on_hover_text("Next chapter")
+                    .on_disabled_hover_text("This is the last chapter");
+                if response.clicked() {
+                    gem.commands.push(GemCommand::NextChapter);
+                }
+
+                ui.add(unselectable_label("Chapters"));
+            });
+
+            ui.separator();
+
+            ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                for (i, chapter) in gem.chapters.iter().enumerate() {
+                    let text = format!("{}  {}", format_duration_to_mmss(chapter.start), chapter.title);
+                    if ui.selectable_label(current == Some(i), text).clicked() {
+                        gem.commands.push(GemCommand::SeekTo(chapter.start));
+                    }
+                }
+            });
+        });
+}
+
+fn equalizer_button(ui: &mut Ui, equalizer: &mut Equalizer) {
+    let equalizer_button = Button::new(RichText::new(ICON_EQUALIZER).size(18.0)).selected(equalizer.enabled);
+    let response = ui.add(equalizer_button).on_hover_text("Equalizer");
+
+    let mut changed = false;
+
+    Popup::menu(&response)
+        .gap(4.0)
+        .close_behavior(PopupCloseBehavior::CloseOnClickOutside)
+        .show(|ui| {
+            ui.horizontal(|ui| {
+                changed |= ui.add(toggle(&mut equalizer.enabled)).changed();
+                ui.add(unselectable_label("Equalizer"));
+            });
+
+            ui.separator();
+
+            ui.add_enabled_ui(equalizer.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    let current_preset = matching_preset(&equalizer.gains);
+                    for preset in EqualizerPreset::iter() {
+                        if ui.selectable_label(current_preset == Some(preset), preset.label()).clicked() {
+                            equalizer.gains = preset.gains();
+                            changed = true;
+                        }
+                    }
+                });
+
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    for (gain, frequency) in equalizer.gains.iter_mut().zip(BAND_FREQUENCIES) {
+                        ui.vertical(|ui| {
+                            let gain_slider = Slider::new(gain, -MAX_GAIN_DB..=MAX_GAIN_DB)
+                                .vertical()
+                                .step_by(0.5)
+                                .show_value(false)
+                                .trailing_fill(true);
+                            changed |= ui.add(gain_slider).on_hover_text(format!("{:+.1} dB", gain)).changed();
+
+                            let label = if frequency >= 1000.0 {
+                                format!("{}k", frequency / 1000.0)
+                            } else {
+                                format!("{}", frequency)
+                            };
+                            ui.add(unselectable_label(RichText::new(label).small()));
+                        });
+                    }
+                });
+            });
+        });
+
+    if changed {
+        equalizer::apply(equalizer);
+    }
+}
+
 fn volume_control_button(ui: &mut Ui, gem: &mut GemPlayer) {
     let has_backend = gem.player.backend.is_some();
 
-    let mut volume = gem.player.backend.as_ref().map(|b| b.player.volume()).unwrap_or(0.0);
+    let mut volume = gem.player.backend.as_ref().map(|b| b.volume()).unwrap_or(0.0);
 
     let volume_icon = match volume {
         0.0 => ICON_VOLUME_OFF,
@@ -122,12 +298,23 @@ fn volume_control_button(ui: &mut Ui, gem: &mut GemPlayer) {
 
     gem.ui.volume_popup_is_open = button_is_hovered || popup_is_hovered;
 
+    let notches = if gem.ui.volume_popup_is_open { wheel_notches(ui) } else { 0.0 };
+    if notches != 0.0 {
+        let scrolled_volume = (volume + notches * VOLUME_SCROLL_STEP).clamp(0.0, 1.0);
+        if scrolled_volume != volume {
+            gem.player.muted = false;
+            gem.player.volume_before_mute = if scrolled_volume == 0.0 { None } else { Some(scrolled_volume) };
+
+            gem.commands.push(GemCommand::SetVolume(scrolled_volume));
+        }
+    }
+
     if response.clicked() {
         gem.commands.push(GemCommand::ToggleMute);
     }
 }
 
-fn playback_controls(ui: &mut Ui, player: &mut Player) -> Option<GemCommand> {
+pub fn playback_controls(ui: &mut Ui, player: &mut Player) -> Option<GemCommand> {
     let mut command = None;
 
     let has_backend = player.backend.is_some();
@@ -145,7 +332,7 @@ fn playback_controls(ui: &mut Ui, player: &mut Player) -> Option<GemCommand> {
         command = Some(GemCommand::PreviousTrack);
     }
 
-    let sink_is_paused = player.backend.as_ref().is_some_and(|b| b.player.is_paused());
+    let sink_is_paused = player.backend.as_ref().is_some_and(|b| b.is_paused());
     let play_pause_icon = if sink_is_paused { ICON_PLAY_ARROW } else { ICON_PAUSE };
     let tooltip = if sink_is_paused { "Play" } else { "Pause" };
     let play_pause_enabled = has_backend && track_is_playing;
@@ -196,7 +383,12 @@ fn now_playing(ui: &mut Ui, gem: &mut GemPlayer, button_size: f32, gap: f32, art
                     strip.cell(|ui| {
                         ui.centered_and_justified(|ui| {
                             let uri = artwork_uri();
-                            artwork_ui(ui, uri.as_deref(), artwork_width);
+                            let response = artwork_ui(ui, uri.as_deref(), artwork_width)
+                                .interact(Sense::click())
+                                .on_hover_text("Now playing");
+                            if response.clicked() {
+                                gem.ui.current_view = View::NowPlaying;
+                            }
                         });
                     });
                     strip.empty();
@@ -225,11 +417,16 @@ fn display_repeat_and_shuffle_buttons(ui: &mut Ui, player: &Player, button_size:
             }
         };
 
-        let color = get_button_color(ui, player.repeat);
-        let repeat_button = Button::new(ICON_REPEAT.rich_text().color(color)).min_size(Vec2::splat(button_size));
+        let repeat_icon = match player.repeat {
+            RepeatMode::One => ICON_REPEAT_ONE,
+            RepeatMode::Off | RepeatMode::All => ICON_REPEAT,
+        };
+        let color = get_button_color(ui, player.repeat != RepeatMode::Off);
+        let repeat_button = Button::new(repeat_icon.rich_text().color(color)).min_size(Vec2::splat(button_size));
 
-        if ui.add(repeat_button).on_hover_text("Repeat").clicked() {
-            command = Some(GemCommand::ToggleRepeat);
+        let repeat_hover_text = format!("Repeat: {}", player.repeat.label());
+        if ui.add(repeat_button).on_hover_text(repeat_hover_text).clicked() {
+            command = Some(GemCommand::CycleRepeat);
         }
 
         ui.add_space(vertical_pad);
@@ -266,7 +463,8 @@ fn layout_playback_slider_and_track_info(ui: &mut Ui, gem: &mut GemPlayer, slide
         });
 
         strip.cell(|ui| {
-            layout_marquee_and_playback_position_and_metadata(ui, &gem.player, position, &mut gem.ui.marquee);
+            let chapter = current_chapter_index(&gem.chapters, position).map(|i| &gem.chapters[i]);
+            layout_marquee_and_playback_position_and_metadata(ui, &gem.player, chapter, position, &mut gem.ui.marquee);
         });
     });
 }
@@ -303,7 +501,6 @@ fn playback_slider(ui: &mut Ui, player: &mut Player, position: &mut Duration, sl
                 .backend
                 .as_ref()
                 .expect("backend should exist if slider is enabled")
-                .player
                 .is_paused();
 
             player.paused_before_scrubbing = Some(is_paused);
@@ -314,12 +511,50 @@ fn playback_slider(ui: &mut Ui, player: &mut Player, position: &mut Duration, sl
         if response.drag_stopped() {
             command = Some(GemCommand::SeekTo(*position));
         }
+
+        // Scrolling up seeks forward.
+        let notches = if response.hovered() && !response.dragged() {
+            wheel_notches(ui)
+        } else {
+            0.0
+        };
+        if notches != 0.0 {
+            let offset = Duration::from_secs_f32(notches.abs() * player.seek_step as f32);
+            command = Some(if notches > 0.0 {
+                GemCommand::SeekForward(offset)
+            } else {
+                GemCommand::SeekBackward(offset)
+            });
+        }
     });
 
     command
 }
 
-fn layout_marquee_and_playback_position_and_metadata(ui: &mut Ui, player: &Player, position: Duration, marquee: &mut Marquee) {
+// How far the mouse wheel was scrolled this frame, in notches. Positive is up.
+fn wheel_notches(ui: &Ui) -> f32 {
+    ui.input(|i| {
+        i.events
+            .iter()
+            .filter_map(|event| match event {
+                Event::MouseWheel { unit, delta, .. } => Some(match unit {
+                    MouseWheelUnit::Point => delta.y / POINTS_PER_NOTCH,
+                    MouseWheelUnit::Line => delta.y,
+                    MouseWheelUnit::Page => delta.y * 3.0,
+                }),
+                _ => None,
+            })
+            .sum()
+    })
+}
+
+fn layout_marquee_and_playback_position_and_metadata(
+    ui: &mut Ui,
+    player: &Player,
+    chapter: Option<&Chapter>,
+    position: Duration,
+    marquee: &mut Marquee,
+) {
     let duration = if let Some(track) = &player.playing {
         track.duration
     } else {
@@ -332,7 +567,7 @@ fn layout_marquee_and_playback_position_and_metadata(ui: &mut Ui, player: &Playe
         .size(Size::relative(3.0 / 4.0))
         .size(Size::relative(1.0 / 4.0))
         .horizontal(|mut hstrip| {
-            hstrip.cell(|ui| display_track_marquee(ui, player.playing.as_ref(), marquee));
+            hstrip.cell(|ui| display_track_marquee(ui, player.playing.as_ref(), chapter, marquee));
             hstrip.cell(|ui| {
                 StripBuilder::new(ui).sizes(Size::relative(1.0 / 2.0), 2).vertical(|mut strip| {
                     strip.cell(|ui| {
@@ -344,7 +579,7 @@ fn layout_marquee_and_playback_position_and_metadata(ui: &mut Ui, player: &Playe
                     strip.cell(|ui| {
                         ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                             if let Some(playing) = &player.playing {
-                                display_track_metadata(ui, playing);
+                                display_track_metadata(ui, player, playing);
                             }
                         });
                     });
@@ -353,7 +588,7 @@ fn layout_marquee_and_playback_position_and_metadata(ui: &mut Ui, player: &Playe
         });
 }
 
-fn display_track_marquee(ui: &mut Ui, maybe_track: Option<&Track>, marquee: &mut Marquee) {
+pub fn display_track_marquee(ui: &mut Ui, maybe_track: Option<&Track>, chapter: Option<&Chapter>, marquee: &mut Marquee) {
     let mut title = "-";
     let mut artist = "-";
     let mut album = "-";
@@ -365,7 +600,10 @@ fn display_track_marquee(ui: &mut Ui, maybe_track: Option<&Track>, marquee: &mut
     }
 
     let padding = "        ";
-    let text = format!("{} / {} / {}{}", title, artist, album, padding);
+    let text = match chapter {
+        Some(chapter) => format!("{} / {} / {} / {}{}", chapter.title, title, artist, album, padding),
+        None => format!("{} / {} / {}{}", title, artist, album, padding),
+    };
 
     marquee_ui(ui, marquee, &text);
 }
@@ -377,7 +615,7 @@ fn display_playback_time(ui: &mut Ui, position: Duration, duration: Duration) {
     ui.add(time_label);
 }
 
-fn display_track_metadata(ui: &mut Ui, track: &Track) {
+fn display_track_metadata(ui: &mut Ui, player: &Player, track: &Track) {
     let codec_string = file_type_name(track.codec);
     ui.add(MetadataChip::new(codec_string));
 
@@ -387,18 +625,112 @@ fn display_track_metadata(ui: &mut Ui, track: &Track) {
         let sample_rate_string = format!("{:.1} kHz", sr.get() as f32 / 1000.0);
         ui.add(MetadataChip::new(&sample_rate_string));
     }
+
+    // The format the device is actually playing at, which differs from the track's if it is resampled.
+    if let Some(backend) = &player.backend {
+        let config = backend.stream.config();
+        let output_rate = config.sample_rate().get();
+
+        ui.add_space(4.0);
+
+        let output_string = format!("Out {:.1} kHz", output_rate as f32 / 1000.0);
+        let resampler = if track.sample_rate.is_some_and(|sr| sr.get() != output_rate) {
+            current_output_settings(player).resampler.label()
+        } else {
+            "None"
+        };
+        let details = format!(
+            "Output: {} channel(s), {:?} samples\nDevice: {}\nResampling: {}",
+            config.channel_count(),
+            config.sample_format(),
+            backend.device_name,
+            resampler
+        );
+        ui.add(MetadataChip::new(&output_string)).on_hover_text(details);
+    }
+
+    // Shows that normalization is changing the level of the track, and by how much.
+    if let Some(gain) = player.applied_gain {
+        ui.add_space(4.0);
+
+        let gain_string = format!("{:+.1} dB", gain);
+        let details = "The normalization gain applied to this track. Changing the mode applies from the next track.";
+        ui.add(MetadataChip::new(&gain_string)).on_hover_text(details);
+    }
 }
 
-fn display_visualizer(ui: &mut Ui, player: &mut Player) {
+pub fn display_visualizer(ui: &mut Ui, gem: &mut GemPlayer, size: Vec2) {
     let dt = ui.input(|i| i.stable_dt);
 
-    let targets = player.visualizer.bands_receiver.try_iter().last();
+    let visualizer = &mut gem.player.visualizer;
+    let spectrogram = &mut gem.ui.spectrogram;
 
-    smooth_bars(&mut player.visualizer.display_bands, targets.as_deref(), dt);
+    let Some(pipeline) = &visualizer.pipeline else {
+        return;
+    };
 
-    let display_bands = &player.visualizer.display_bands;
+    let mut targets = None;
+    let mut waveform_received = false;
+    for frame in pipeline.frames_receiver.try_iter() {
+        match frame {
+            VisualizerFrame::Bands(bands) => targets = Some(bands),
+            VisualizerFrame::Waveform(samples) => {
+                visualizer.waveform = samples;
+                waveform_received = true;
+            }
+            VisualizerFrame::Spectrum { decibels, bin_width } => spectrogram.push(&decibels, bin_width),
+            VisualizerFrame::Levels(levels) => gem.ui.level_meter.push(&levels),
+        }
+    }
 
-    let display = BarDisplay::new(display_bands, ui.available_height() * 0.5, 10.0, 4.0, ui.visuals().text_color());
+    // Like the bars, the waveform settles down once the audio stops.
+    if !waveform_received {
+        let decay = (-10.0 * dt).exp();
+        visualizer.waveform.iter_mut().for_each(|s| *s *= decay);
+    }
 
-    ui.add(display);
+    smooth_bars(
+        &mut visualizer.display_bands,
+        targets.as_deref(),
+        visualizer.attack,
+        visualizer.decay,
+        dt,
+    );
+    update_peaks(&mut visualizer.peaks, &mut visualizer.peak_holds, &visualizer.display_bands, dt);
+    let peaks = visualizer.peak_hold.then_some(visualizer.peaks.as_slice());
+
+    let (width, height) = (size.x, size.y);
+    let color = ui.visuals().text_color();
+
+    match visualizer.style {
+        VisualizerStyle::Bars => {
+            // The bars fill the width. In the control panel that's 10 point bars with 4 point gaps.
+            let count = visualizer.display_bands.len().max(1) as f32;
+            let bar_width = width / (count + (count - 1.0) * 0.4);
+            let mut display = BarDisplay::new(&visualizer.display_bands, height, bar_width, bar_width * 0.4, color);
+            if let Some(peaks) = peaks {
+                display = display.peaks(peaks);
+            }
+            ui.add(display);
+        }
+        VisualizerStyle::Waveform => {
+            ui.add(WaveformDisplay::new(&visualizer.waveform, width, height, color));
+        }
+        VisualizerStyle::Spectrum => {
+            let mut display = SpectrumDisplay::new(&visualizer.display_bands, width, height, color);
+            if let Some(peaks) = peaks {
+                display = display.peaks(peaks);
+            }
+            ui.add(display);
+        }
+        VisualizerStyle::Needle => {
+            let level = visualizer.display_bands.first().copied().unwrap_or(0.0);
+            let mut needle = VuNeedle::new(level, width, height, color);
+            if let Some(&peak) = peaks.and_then(|peaks| peaks.first()) {
+                needle = needle.peak(peak);
+            }
+            ui.add(needle);
+        }
+        VisualizerStyle::Spectrogram => spectrogram_ui(ui, spectrogram, width * 1.5, height), // History needs the room.
+    }
 }
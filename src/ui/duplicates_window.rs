@@ -0,0 +1,179 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::{Receiver, TryRecvError, channel},
+    thread,
+};
+
+use egui::{Context, Frame, Margin, RichText, ScrollArea, Spinner, Ui, Window};
+use egui_material_icons::icons::{ICON_DELETE, ICON_PLAYLIST_REMOVE};
+use fully_pub::fully_pub;
+use log::{error, info};
+
+use crate::{
+    GemPlayer,
+    commands::GemCommand,
+    duplicates::{DuplicateGroup, find_duplicates},
+    ui::root::{format_duration_to_mmss, unselectable_label},
+};
+
+#[fully_pub]
+struct DuplicatesState {
+    window_open: bool,
+    scan_receiver: Option<Receiver<Vec<DuplicateGroup>>>, // Some while a scan is running.
+    groups: Vec<DuplicateGroup>,
+}
+
+pub fn start_duplicate_scan(gem: &mut GemPlayer) {
+    let (sender, receiver) = channel();
+    let tracks = gem.library.clone();
+
+    thread::spawn(move || {
+        let groups = find_duplicates(&tracks);
+        let _ = sender.send(groups);
+    });
+
+    info!("Scanning the library for duplicates.");
+    gem.ui.duplicates.scan_receiver = Some(receiver);
+    gem.ui.duplicates.groups.clear();
+    gem.ui.duplicates.window_open = true;
+}
+
+pub fn duplicates_window(ctx: &Context, gem: &mut GemPlayer) {
+    // Polled even while the window is closed, since the settings show whether a scan is running.
+    poll_duplicate_scan(gem);
+
+    if !gem.ui.duplicates.window_open {
+        return;
+    }
+
+    let mut window_open = gem.ui.duplicates.window_open;
+    let mut maybe_command = None;
+
+    Window::new("Duplicate Tracks")
+        .open(&mut window_open)
+        .default_size([560.0, 400.0])
+        .collapsible(false)
+        .show(ctx, |ui| {
+            if gem.ui.duplicates.scan_receiver.is_some() {
+                ui.horizontal(|ui| {
+                    ui.add(Spinner::new());
+                    ui.add(unselectable_label("Looking for duplicates…"));
+                });
+                return;
+            }
+
+            if gem.ui.duplicates.groups.is_empty() {
+                ui.add(unselectable_label("No duplicates were found."));
+                return;
+            }
+
+            ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+                // A track can be in several groups, e.g. with the same tags and the same audio, so rows are told apart by
+                // their group and row index rather than by path.
+                for (group_index, group) in gem.ui.duplicates.groups.iter().enumerate() {
+                    ui.push_id(group_index, |ui| {
+                        if let Some(command) = duplicate_group(ui, group, &gem.referenced_files) {
+                            maybe_command = Some(command);
+                        }
+                    });
+                    ui.add_space(8.0);
+                }
+            });
+        });
+
+    gem.ui.duplicates.window_open = window_open;
+
+    if let Some(command) = maybe_command {
+        // Hide the affected tracks right away rather than waiting for the library to reload.
+        let affected_keys = match &command {
+            GemCommand::TrashTracks { track_keys } | GemCommand::RemoveReferencedTracks { track_keys } => track_keys.clone(),
+            _ => Vec::new(),
+        };
+        remove_from_groups(&mut gem.ui.duplicates.groups, &affected_keys);
+
+        gem.commands.push(command);
+    }
+}
+
+fn poll_duplicate_scan(gem: &mut GemPlayer) {
+    let Some(receiver) = &gem.ui.duplicates.scan_receiver else {
+        return;
+    };
+
+    match receiver.try_recv() {
+        Ok(groups) => {
+            info!("Found {} group(s) of duplicates.", groups.len());
+            gem.ui.duplicates.groups = groups;
+            gem.ui.duplicates.scan_receiver = None;
+        }
+        Err(TryRecvError::Empty) => {} // Still scanning.
+        Err(TryRecvError::Disconnected) => {
+            error!("Duplicate scan channel disconnected unexpectedly.");
+            gem.ui.duplicates.scan_receiver = None;
+        }
+    }
+}
+
+fn duplicate_group(ui: &mut Ui, group: &DuplicateGroup, referenced_files: &[PathBuf]) -> Option<GemCommand> {
+    let mut maybe_command = None;
+
+    Frame::group(ui.style()).inner_margin(Margin::same(8)).show(ui, |ui| {
+        ui.set_width(ui.available_width());
+        ui.add(unselectable_label(RichText::new(group.reason.label()).small().weak()));
+
+        for (row_index, track) in group.tracks.iter().enumerate() {
+            ui.separator();
+
+            ui.push_id(row_index, |ui| {
+                ui.horizontal(|ui| {
+                    ui.vertical(|ui| {
+                        let title = track.title.as_deref().unwrap_or("-");
+                        let artist = track.artist.as_deref().unwrap_or("-");
+                        ui.add(unselectable_label(format!(
+                            "{} — {} ({})",
+                            title,
+                            artist,
+                            format_duration_to_mmss(track.duration)
+                        )));
+                        ui.add(unselectable_label(RichText::new(track.path.display().to_string()).small().weak()));
+                    });
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let track_keys = vec![track.path.clone()];
+
+                        // Tracks from cue sheets share their audio file with the rest of the album.
+                        let trash_response = ui
+                            .add_enabled(track.cue.is_none(), egui::Button::new(ICON_DELETE))
+                            .on_hover_text("Move to trash")
+                            .on_disabled_hover_text("Tracks from a cue sheet can't be trashed individually");
+                        if trash_response.clicked() {
+                            maybe_command = Some(GemCommand::TrashTracks {
+                                track_keys: track_keys.clone(),
+                            });
+                        }
+
+                        // Files in the library directory would be picked up again on the next scan.
+                        let is_referenced = referenced_files.contains(&track.path);
+                        let remove_response = ui
+                            .add_enabled(is_referenced, egui::Button::new(ICON_PLAYLIST_REMOVE))
+                            .on_hover_text("Remove from library")
+                            .on_disabled_hover_text("Only referenced files can be removed without deleting them");
+                        if remove_response.clicked() {
+                            maybe_command = Some(GemCommand::RemoveReferencedTracks { track_keys });
+                        }
+                    });
+                });
+            });
+        }
+    });
+
+    maybe_command
+}
+
+fn remove_from_groups(groups: &mut Vec<DuplicateGroup>, track_keys: &[PathBuf]) {
+    for group in groups.iter_mut() {
+        group.tracks.retain(|t| !track_keys.contains(&t.path));
+    }
+
+    groups.retain(|g| g.tracks.len() > 1);
+}
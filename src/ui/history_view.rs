@@ -0,0 +1,145 @@
+use egui::{Align, Button, Layout, RichText, Sense, Ui};
+use egui_extras::TableBuilder;
+use egui_material_icons::icons::{ICON_ADD, ICON_ALBUM, ICON_ARTIST, ICON_HOURGLASS, ICON_MUSIC_NOTE, ICON_REPLAY, ICON_TAG};
+use log::info;
+
+use crate::{
+    GemPlayer,
+    commands::GemCommand,
+    player::{enqueue, enqueue_next},
+    ui::{
+        root::{format_duration_to_mmss, row_height, unselectable_label},
+        widgets::centered_frame::centered_frame,
+    },
+};
+
+pub fn history_view(ui: &mut Ui, gem: &mut GemPlayer) {
+    ui.scope(|ui| {
+        if gem.player.history.is_empty() {
+            centered_frame(ui, |ui| {
+                ui.vertical_centered(|ui| {
+                    ui.add(unselectable_label("Nothing has been played yet."));
+                });
+            });
+
+            return;
+        }
+
+        let header_labels = [ICON_TAG, ICON_MUSIC_NOTE, ICON_ARTIST, ICON_ALBUM, ICON_HOURGLASS];
+
+        let available_width = ui.available_width();
+        let position_width = 64.0;
+        let time_width = 64.0;
+        let actions_width = 80.0;
+        let remaining_width = available_width - position_width - time_width - actions_width;
+        let title_width = remaining_width * (2.0 / 4.0);
+        let artist_width = remaining_width * (1.0 / 4.0);
+        let album_width = remaining_width * (1.0 / 4.0);
+
+        ui.spacing_mut().item_spacing.x = 0.0; // See comment in library_view() as to why we do this.
+
+        // We only operate on the player after we are done iterating over the history.
+        let mut to_be_played_again = None;
+        let mut to_be_enqueued = None;
+
+        let history_length = gem.player.history.len();
+        let large_rows = gem.ui.large_rows;
+
+        TableBuilder::new(ui)
+            .striped(true)
+            .sense(Sense::hover())
+            .cell_layout(Layout::left_to_right(Align::Center))
+            .column(egui_extras::Column::exact(position_width))
+            .column(egui_extras::Column::exact(title_width))
+            .column(egui_extras::Column::exact(artist_width))
+            .column(egui_extras::Column::exact(album_width))
+            .column(egui_extras::Column::exact(time_width))
+            .column(egui_extras::Column::exact(actions_width))
+            .header(16.0, |mut header| {
+                for (i, h) in header_labels.iter().enumerate() {
+                    header.col(|ui| {
+                        if i == 0 {
+                            ui.add_space(16.0);
+                        }
+                        ui.add(unselectable_label(RichText::new(*h).strong()));
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(row_height(large_rows), history_length, |mut row| {
+                    // The history is stored chronologically, but the most recent track is shown first.
+                    let position = row.index();
+                    let index = history_length - 1 - position;
+                    let track = &gem.player.history[index];
+
+                    row.col(|ui| {
+                        ui.add_space(16.0);
+                        ui.add(unselectable_label(format!("{}", position + 1)));
+                    });
+
+                    row.col(|ui| {
+                        ui.add_space(4.0);
+                        ui.add(unselectable_label(track.title.as_deref().unwrap_or("-")));
+                    });
+
+                    row.col(|ui| {
+                        ui.add_space(4.0);
+                        ui.add(unselectable_label(track.artist.as_deref().unwrap_or("-")));
+                    });
+
+                    row.col(|ui| {
+                        ui.add_space(4.0);
+                        ui.add(unselectable_label(track.album.as_deref().unwrap_or("-")));
+                    });
+
+                    row.col(|ui| {
+                        ui.add_space(4.0);
+                        ui.add(unselectable_label(format_duration_to_mmss(track.duration)));
+                    });
+
+                    // See the comment in queue_view() about showing the action buttons on hover.
+                    let row_is_hovered = row.response().hovered();
+                    row.col(|ui| {
+                        let should_show_action_buttons = row_is_hovered || ui.rect_contains_pointer(ui.max_rect());
+
+                        ui.add_space(8.0);
+
+                        let play_again_button = Button::new(ICON_REPLAY);
+                        if ui
+                            .add_visible(should_show_action_buttons, play_again_button)
+                            .on_hover_text("Play again")
+                            .clicked()
+                        {
+                            to_be_played_again = Some(index);
+                        }
+
+                        ui.add_space(8.0);
+
+                        let enqueue_button = Button::new(ICON_ADD);
+                        if ui
+                            .add_visible(should_show_action_buttons, enqueue_button)
+                            .on_hover_text("Add to queue")
+                            .clicked()
+                        {
+                            to_be_enqueued = Some(index);
+                        }
+                    });
+                });
+            });
+
+        if let Some(index) = to_be_played_again {
+            let track = gem.player.history[index].clone();
+            info!("Playing again from history: {}", track.path.display());
+
+            enqueue_next(&mut gem.player, track);
+            gem.commands.push(GemCommand::NextTrack);
+        }
+
+        if let Some(index) = to_be_enqueued {
+            let track = gem.player.history[index].clone();
+            info!("Enqueuing from history: {}", track.path.display());
+
+            enqueue(&mut gem.player, track);
+        }
+    });
+}
@@ -0,0 +1,305 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, Sender, TryRecvError, channel},
+    thread,
+};
+
+use anyhow::Result;
+use egui::{Context, ProgressBar, RichText, ScrollArea, Spinner, Window};
+use fully_pub::fully_pub;
+use log::{error, info, warn};
+use rfd::FileDialog;
+
+use crate::{
+    GemPlayer,
+    itunes_library::{match_tracks, parse_itunes_library},
+    playlist::create_with_tracks,
+    track::{Track, write_rating},
+    ui::root::unselectable_label,
+};
+
+#[fully_pub]
+struct ItunesImportState {
+    window_open: bool,
+    receiver: Option<Receiver<ImportMessage>>, // Some while an import is running.
+    progress: Option<(usize, usize)>,          // Ratings written and the number to write.
+    summary: Option<ImportSummary>,
+    error: Option<String>,
+}
+
+pub enum ImportMessage {
+    Cancelled,
+    Progress { done: usize, total: usize },
+    Finished(Result<MatchedImport>),
+}
+
+// What the background thread found. Playlists and play counts are applied on the ui thread since they are app state.
+#[fully_pub]
+struct MatchedImport {
+    play_counts: Vec<(PathBuf, u32)>,
+    playlists: Vec<(String, Vec<PathBuf>)>,
+    matched: usize,
+    ratings_written: usize,
+    unmatched: Vec<String>,
+}
+
+#[fully_pub]
+struct ImportSummary {
+    matched: usize,
+    ratings_written: usize,
+    play_counts_imported: usize,
+    playlists_created: usize,
+    unmatched: Vec<String>,
+}
+
+pub fn start_itunes_import(gem: &mut GemPlayer) {
+    let (sender, receiver) = channel();
+    let library = gem.library.clone();
+    let rating_email = gem.rating_email.clone();
+
+    thread::spawn(move || {
+        let file = FileDialog::new()
+            .set_title("Import iTunes Library")
+            .add_filter("iTunes Library", &["xml"])
+            .pick_file();
+
+        let Some(file) = file else {
+            let _ = sender.send(ImportMessage::Cancelled);
+            return;
+        };
+
+        let result = import_itunes_library(&file, &library, &rating_email, &sender);
+        let _ = sender.send(ImportMessage::Finished(result));
+    });
+
+    gem.ui.itunes_import = ItunesImportState {
+        window_open: true,
+        receiver: Some(receiver),
+        progress: None,
+        summary: None,
+        error: None,
+    };
+}
+
+// Runs off the ui thread, since writing ratings touches every rated file.
+fn import_itunes_library(path: &Path, library: &[Track], rating_email: &str, sender: &Sender<ImportMessage>) -> Result<MatchedImport> {
+    info!("Importing the iTunes library at '{}'.", path.display());
+
+    let itunes = parse_itunes_library(path)?;
+    let matches = match_tracks(&itunes, library);
+
+    let mut unmatched: Vec<String> = itunes
+        .tracks
+        .iter()
+        .filter(|(id, _)| !matches.contains_key(id))
+        .map(|(_, track)| track.description())
+        .collect();
+    unmatched.sort();
+
+    let tracks_by_path: HashMap<&Path, &Track> = library.iter().map(|t| (t.path.as_path(), t)).collect();
+
+    // Only ratings that differ are written, so that re-importing doesn't rewrite the whole library.
+    let ratings_to_write: Vec<(&Track, u8)> = matches
+        .iter()
+        .filter_map(|(id, path)| {
+            let rating = itunes.tracks[id].rating;
+            let track = *tracks_by_path.get(path.as_path())?;
+            (rating != 0 && track.rating != rating && track.cue.is_none()).then_some((track, rating))
+        })
+        .collect();
+
+    let total = ratings_to_write.len();
+    let mut ratings_written = 0;
+    for (done, (track, rating)) in ratings_to_write.into_iter().enumerate() {
+        match write_rating(track, rating, rating_email) {
+            Ok(()) => ratings_written += 1,
+            Err(e) => warn!("Failed to import the rating of '{}': {}", track.path.display(), e),
+        }
+
+        let _ = sender.send(ImportMessage::Progress { done: done + 1, total });
+    }
+
+    let play_counts = matches
+        .iter()
+        .map(|(id, path)| (path.clone(), itunes.tracks[id].play_count))
+        .filter(|(_, count)| *count > 0)
+        .collect();
+
+    let playlists = itunes
+        .playlists
+        .iter()
+        .map(|playlist| {
+            let mut seen = HashSet::new();
+            let paths = playlist
+                .track_ids
+                .iter()
+                .filter_map(|id| matches.get(id))
+                .filter(|path| seen.insert(*path))
+                .cloned()
+                .collect();
+            (playlist.name.clone(), paths)
+        })
+        .collect();
+
+    Ok(MatchedImport {
+        play_counts,
+        playlists,
+        matched: matches.len(),
+        ratings_written,
+        unmatched,
+    })
+}
+
+pub fn itunes_import_window(ctx: &Context, gem: &mut GemPlayer) {
+    // Polled even with the window closed, so that an import still finishes if the window is dismissed.
+    poll_itunes_import(gem);
+
+    if !gem.ui.itunes_import.window_open {
+        return;
+    }
+
+    let mut window_open = gem.ui.itunes_import.window_open;
+    let state = &gem.ui.itunes_import;
+
+    Window::new("Import iTunes Library")
+        .open(&mut window_open)
+        .default_size([480.0, 360.0])
+        .collapsible(false)
+        .show(ctx, |ui| {
+            if let Some(error) = &state.error {
+                ui.add(unselectable_label(format!("The import failed: {}", error)));
+                return;
+            }
+
+            if state.receiver.is_some() {
+                match state.progress {
+                    Some((done, total)) => {
+                        ui.add(unselectable_label("Importing ratings…"));
+                        ui.add(ProgressBar::new(done as f32 / total.max(1) as f32).text(format!("{} / {}", done, total)));
+                    }
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.add(Spinner::new());
+                            ui.add(unselectable_label("Reading the library…"));
+                        });
+                    }
+                }
+                return;
+            }
+
+            let Some(summary) = &state.summary else {
+                return;
+            };
+
+            ui.add(unselectable_label(format!("Matched {} track(s) in the library.", summary.matched)));
+            ui.add(unselectable_label(format!("Imported {} rating(s).", summary.ratings_written)));
+            ui.add(unselectable_label(format!(
+                "Imported {} play count(s).",
+                summary.play_counts_imported
+            )));
+            ui.add(unselectable_label(format!("Created {} playlist(s).", summary.playlists_created)));
+
+            if summary.unmatched.is_empty() {
+                return;
+            }
+
+            ui.separator();
+            ui.add(unselectable_label(format!(
+                "{} track(s) could not be found in the library:",
+                summary.unmatched.len()
+            )));
+            ui.add_space(4.0);
+
+            ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+                for description in &summary.unmatched {
+                    ui.add(unselectable_label(RichText::new(description).small().weak()));
+                }
+            });
+        });
+
+    gem.ui.itunes_import.window_open = window_open;
+}
+
+fn poll_itunes_import(gem: &mut GemPlayer) {
+    let Some(receiver) = &gem.ui.itunes_import.receiver else {
+        return;
+    };
+
+    loop {
+        match receiver.try_recv() {
+            Ok(ImportMessage::Cancelled) => {
+                gem.ui.itunes_import.window_open = false;
+                gem.ui.itunes_import.receiver = None;
+                return;
+            }
+            Ok(ImportMessage::Progress { done, total }) => gem.ui.itunes_import.progress = Some((done, total)),
+            Ok(ImportMessage::Finished(result)) => {
+                gem.ui.itunes_import.receiver = None;
+                finish_import(gem, result);
+                return;
+            }
+            Err(TryRecvError::Empty) => return, // Still importing.
+            Err(TryRecvError::Disconnected) => {
+                error!("iTunes import channel disconnected unexpectedly.");
+                gem.ui.itunes_import.receiver = None;
+                return;
+            }
+        }
+    }
+}
+
+fn finish_import(gem: &mut GemPlayer, result: Result<MatchedImport>) {
+    match result {
+        Ok(import) => {
+            let summary = apply_import(gem, import);
+            info!(
+                "Imported the iTunes library: {} matched, {} unmatched.",
+                summary.matched,
+                summary.unmatched.len()
+            );
+            gem.ui.itunes_import.summary = Some(summary);
+        }
+        Err(e) => {
+            error!("Failed to import the iTunes library: {:?}", e);
+            gem.ui.itunes_import.error = Some(e.to_string());
+        }
+    }
+}
+
+fn apply_import(gem: &mut GemPlayer, import: MatchedImport) -> ImportSummary {
+    // Keep the higher count, so that importing the same library twice doesn't double the plays.
+    let play_counts_imported = import.play_counts.len();
+    for (path, count) in import.play_counts {
+        let play_count = gem.play_counts.entry(path).or_insert(0);
+        *play_count = (*play_count).max(count);
+    }
+    gem.ui.playlists.cache_dirty = true; // The most played tracks may have changed.
+
+    let mut playlists_created = 0;
+    if let Some(directory) = gem.library_directory.clone() {
+        let tracks_by_path: HashMap<PathBuf, Track> = gem.library.iter().map(|t| (t.path.clone(), t.clone())).collect();
+
+        for (name, paths) in import.playlists {
+            let tracks: Vec<Track> = paths.iter().filter_map(|path| tracks_by_path.get(path).cloned()).collect();
+
+            match create_with_tracks(&name, tracks, &directory, &gem.playlists) {
+                Ok(playlist) => {
+                    gem.playlists.push(playlist);
+                    playlists_created += 1;
+                }
+                Err(e) => error!("Failed to import the playlist '{}': {}", name, e),
+            }
+        }
+    } else {
+        warn!("Playlists were not imported since there is no library directory.");
+    }
+
+    ImportSummary {
+        matched: import.matched,
+        ratings_written: import.ratings_written,
+        play_counts_imported,
+        playlists_created,
+        unmatched: import.unmatched,
+    }
+}
@@ -1,22 +1,33 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use egui::{Align, Button, Label, Layout, Popup, RichText, ScrollArea, Sense, Ui};
+use egui::{
+    Align, Area, Button, DragAndDrop, Frame, Id, Label, Layout, Order, Popup, ProgressBar, RichText, ScrollArea, Sense, Spinner, Ui, Vec2,
+};
 use egui_extras::TableBuilder;
 use egui_material_icons::icons::{
-    ICON_ALBUM, ICON_ARTIST, ICON_FOLDER, ICON_HOURGLASS, ICON_MORE_HORIZ, ICON_MUSIC_NOTE, ICON_PLAY_ARROW, ICON_QUEUE_MUSIC,
+    ICON_ALBUM, ICON_ARTIST, ICON_FOLDER, ICON_IMAGE, ICON_MORE_HORIZ, ICON_PLAY_ARROW, ICON_QUEUE_MUSIC, ICON_SHUFFLE, ICON_WARNING,
 };
 use fully_pub::fully_pub;
 
 use crate::{
-    GemPlayer,
+    GemPlayer, SHUFFLE_LIBRARY_SHORTCUT,
     commands::GemCommand,
-    track::{SortBy, SortOrder, Track, filter, sort},
+    library_watcher::LoadProgress,
+    playlist::display_order,
+    search_query::{SearchQuery, matches_query, narrows, parse_query},
+    track::{SortBy, SortOrder, Track, TrackRetrieval, album_track_keys, artist_track_keys, filter, sort},
     ui::{
-        root::{format_duration_to_mmss, table_label, unselectable_label},
+        root::{
+            format_duration_to_mmss, locate_flash_is_on, rating_menu, row_height, selection_menu_items, table_label, unselectable_label,
+        },
+        track_columns::{ColumnLayout, add_track_columns, column_headers, column_text, update_column_layout},
+        type_to_jump::type_to_jump,
         widgets::{centered_frame::centered_frame, playing_indicator::playing_indicator},
     },
 };
 
+const MIN_FILES_FOR_ESTIMATE: usize = 100; // The time left is only shown once it can be estimated somewhat reliably.
+
 #[fully_pub]
 struct LibraryViewState {
     selected_tracks: Vec<PathBuf>,
@@ -26,8 +37,36 @@ struct LibraryViewState {
     cached_library: Vec<Track>,
     cache_dirty: bool,
 
+    // Set when only the search changed. If the new search narrows the one the cache was built with, the cache is
+    // filtered further instead of being rebuilt, which keeps typing responsive in large libraries.
+    search_dirty: bool,
+    cached_query: SearchQuery,
+
+    // The first row shown and its track. When the library is reloaded, the table scrolls so that the same track stays
+    // at the top, even if tracks were added or removed above it.
+    scroll_anchor: Option<(usize, PathBuf)>,
+    restore_scroll: bool,
+
     sort_by: SortBy,
     sort_order: SortOrder,
+
+    columns: ColumnLayout,
+
+    min_rating: u8, // Tracks rated below this are hidden.
+}
+
+// The new row of the track that was shown first, if it has moved since the table was last shown.
+pub fn moved_anchor_row(anchor: Option<&(usize, PathBuf)>, tracks: &[Track]) -> Option<usize> {
+    let (row, path) = anchor?;
+    let new_row = tracks.iter().position(|t| &t.path == path)?;
+
+    (new_row != *row).then_some(new_row)
+}
+
+// The payload carried while dragging tracks out of the library table, e.g. onto a playlist in the sidebar.
+#[fully_pub]
+struct TrackDragPayload {
+    track_keys: Vec<PathBuf>,
 }
 
 pub fn library_view(ui: &mut Ui, gem: &mut GemPlayer) {
@@ -46,16 +85,33 @@ pub fn library_view(ui: &mut Ui, gem: &mut GemPlayer) {
 
         if gem.library.is_empty() {
             centered_frame(ui, |ui| {
-                ui.vertical_centered(|ui| {
-                    ui.add(unselectable_label("The library is empty."));
+                ui.vertical_centered(|ui| match (&gem.library_progress, &gem.library_directory) {
+                    (Some(progress), Some(directory)) => library_progress_ui(ui, progress, directory),
+                    _ => {
+                        ui.add(unselectable_label("The library is empty."));
+                    }
                 });
             });
 
             return;
         }
 
+        if gem.ui.library.search_dirty && !gem.ui.library.cache_dirty {
+            let query = parse_query(&gem.ui.search);
+            if narrows(&query, &gem.ui.library.cached_query) {
+                gem.ui.library.cached_library.retain(|t| matches_query(t, &query));
+                gem.ui.library.cached_query = query;
+            } else {
+                gem.ui.library.cache_dirty = true;
+            }
+        }
+        gem.ui.library.search_dirty = false;
+
         if gem.ui.library.cache_dirty {
+            gem.ui.library.cached_query = parse_query(&gem.ui.search);
             gem.ui.library.cached_library = filter(&gem.library, &gem.ui.search);
+            let min_rating = gem.ui.library.min_rating;
+            gem.ui.library.cached_library.retain(|t| t.rating >= min_rating);
             sort(
                 &mut gem.ui.library.cached_library,
                 gem.ui.library.sort_by,
@@ -64,58 +120,116 @@ pub fn library_view(ui: &mut Ui, gem: &mut GemPlayer) {
             gem.ui.library.cache_dirty = false;
         }
 
-        let header_labels = [ICON_MUSIC_NOTE, ICON_ARTIST, ICON_ALBUM, ICON_HOURGLASS];
-
-        let time_width = 64.0;
-        let more_width = 48.0;
+        let visible_columns = gem.ui.library.columns.visible_columns();
 
+        // The default widths of the table columns divide up the available width, so if we leave the default item spacing,
+        // the width taken up by the table will be greater than the available width, causing the right side of the table
+        // to be cut off by the window.
         let available_width = ui.available_width();
-        let remaining_width = available_width - time_width - more_width;
-
-        let title_width = remaining_width * (1.0 / 2.0);
-        let artist_width = remaining_width * (1.0 / 4.0);
-        let album_width = remaining_width * (1.0 / 4.0);
-
-        // Since we are setting the widths of the table columns manually by dividing up the available width,
-        // if we leave the default item spacing, the width taken up by the table will be greater than the available width,
-        // causing the right side of the table to be cut off by the window.
         ui.spacing_mut().item_spacing.x = 0.0;
 
-        // Used to determine if selection should be extended.
-        let shift_is_pressed = ui.input(|i| i.modifiers.shift);
+        // Used to determine if selection should be extended or toggled.
+        let (shift_is_pressed, command_is_pressed) = ui.input(|i| (i.modifiers.shift, i.modifiers.command));
 
         let mut maybe_command = None;
 
         let playing_color = ui.visuals().selection.bg_fill;
+        let reduce_animations = gem.ui.reduce_animations;
+        let large_rows = gem.ui.large_rows;
+
+        let mut scroll_to_row = None;
+
+        if gem.ui.locate_playing.scroll_pending {
+            gem.ui.locate_playing.scroll_pending = false;
+
+            let playing_index = gem.player.playing.as_ref().and_then(|playing| {
+                let cached_library = &gem.ui.library.cached_library;
+                cached_library.iter().position(|t| t == playing)
+            });
+
+            if let Some(index) = playing_index {
+                scroll_to_row = Some(index);
+                gem.ui.locate_playing.flash_started = Some(ui.input(|i| i.time));
+            } else {
+                gem.ui.toasts.info("The playing track is hidden by the search.");
+            }
+        }
 
-        TableBuilder::new(ui)
+        let view_state = &mut gem.ui.library;
+        if let Some(index) = type_to_jump(
+            ui,
+            &mut gem.ui.type_to_jump,
+            &view_state.cached_library,
+            &mut view_state.selected_tracks,
+        ) {
+            scroll_to_row = Some(index);
+        }
+
+        let flash_is_on = locate_flash_is_on(ui, &mut gem.ui.locate_playing);
+
+        let mut anchor_row = None;
+        if gem.ui.library.restore_scroll {
+            gem.ui.library.restore_scroll = false;
+            anchor_row = moved_anchor_row(gem.ui.library.scroll_anchor.as_ref(), &gem.ui.library.cached_library);
+        }
+
+        let mut table = TableBuilder::new(ui);
+        if let Some(index) = scroll_to_row {
+            table = table.scroll_to_row(index, Some(Align::Center));
+        } else if let Some(index) = anchor_row {
+            table = table.scroll_to_row(index, Some(Align::Min));
+        }
+
+        let more_width = 48.0;
+        let table = add_track_columns(
+            table,
+            "library_table",
+            &gem.ui.library.columns,
+            &visible_columns,
+            available_width,
+            more_width,
+        );
+
+        let mut column_widths = Vec::new();
+        let mut column_edit = None;
+        let mut first_shown_row = None;
+
+        table
             .striped(true)
-            .sense(Sense::click())
+            .sense(Sense::click_and_drag())
             .cell_layout(Layout::left_to_right(Align::Center))
-            .column(egui_extras::Column::exact(title_width))
-            .column(egui_extras::Column::exact(artist_width))
-            .column(egui_extras::Column::exact(album_width))
-            .column(egui_extras::Column::exact(time_width))
-            .column(egui_extras::Column::exact(more_width))
             .header(16.0, |mut header| {
-                for (i, h) in header_labels.iter().enumerate() {
-                    header.col(|ui| {
-                        if i == 0 {
-                            ui.add_space(16.0);
-                        }
-                        ui.add(unselectable_label(RichText::new(*h).strong()));
-                    });
-                }
+                column_edit = column_headers(
+                    &mut header,
+                    "library_table",
+                    &gem.ui.library.columns,
+                    &visible_columns,
+                    &mut column_widths,
+                );
+
+                header.col(|ui| {
+                    let shortcut_text = ui.ctx().format_shortcut(&SHUFFLE_LIBRARY_SHORTCUT);
+                    let shuffle_button = Button::new(RichText::new(ICON_SHUFFLE).strong()).frame(false);
+                    let response = ui.add(shuffle_button).on_hover_text(format!("Shuffle library ({})", shortcut_text));
+
+                    if response.clicked() {
+                        maybe_command = Some(GemCommand::ShuffleLibrary);
+                    }
+                });
             })
             .body(|body| {
-                body.rows(26.0, gem.ui.library.cached_library.len(), |mut row| {
+                body.rows(row_height(large_rows), gem.ui.library.cached_library.len(), |mut row| {
                     let track = &gem.ui.library.cached_library[row.index()];
                     let track_key = track.path.clone();
 
+                    if first_shown_row.is_none() {
+                        first_shown_row = Some((row.index(), track_key.clone()));
+                    }
+
                     let track_is_playing = gem.player.playing.as_ref().is_some_and(|t| t == track);
 
                     let track_is_selected = gem.ui.library.selected_tracks.contains(&track_key);
-                    row.set_selected(track_is_selected);
+                    row.set_selected(track_is_selected || (track_is_playing && flash_is_on));
 
                     let text_color = if track_is_playing && !track_is_selected {
                         Some(playing_color)
@@ -123,30 +237,14 @@ pub fn library_view(ui: &mut Ui, gem: &mut GemPlayer) {
                         None
                     };
 
-                    row.col(|ui| {
-                        ui.add_space(16.0);
-                        let label = table_label(track.title.as_deref().unwrap_or("-"), text_color);
-                        ui.add(label);
-                    });
-
-                    row.col(|ui| {
-                        ui.add_space(4.0);
-                        let label = table_label(track.artist.as_deref().unwrap_or("-"), text_color);
-                        ui.add(label);
-                    });
-
-                    row.col(|ui| {
-                        ui.add_space(4.0);
-                        let label = table_label(track.album.as_deref().unwrap_or("-"), text_color);
-                        ui.add(label);
-                    });
-
-                    row.col(|ui| {
-                        ui.add_space(4.0);
-                        let duration_string = format_duration_to_mmss(track.duration);
-                        let label = table_label(duration_string, text_color);
-                        ui.add(label);
-                    });
+                    let position = row.index() + 1;
+                    for (i, column) in visible_columns.iter().enumerate() {
+                        row.col(|ui| {
+                            ui.add_space(if i == 0 { 16.0 } else { 4.0 });
+                            let label = table_label(column_text(*column, track, position, &gem.play_counts), text_color);
+                            ui.add(label);
+                        });
+                    }
 
                     let rest_of_row_is_hovered = row.response().hovered();
                     let mut more_cell_contains_pointer = false;
@@ -174,7 +272,10 @@ pub fn library_view(ui: &mut Ui, gem: &mut GemPlayer) {
                                 }
                             });
                         } else if track_is_playing {
-                            playing_indicator(ui);
+                            playing_indicator(ui, reduce_animations);
+                        } else if gem.player.unplayable.contains(&track_key) {
+                            let warning = RichText::new(ICON_WARNING).color(ui.visuals().warn_fg_color);
+                            ui.add(unselectable_label(warning)).on_hover_text("This track could not be played.");
                         }
                     });
 
@@ -188,6 +289,12 @@ pub fn library_view(ui: &mut Ui, gem: &mut GemPlayer) {
                                 selected_tracks.clear();
                                 selected_tracks.push(track_key.clone());
                             }
+                        } else if command_is_pressed {
+                            if track_is_selected {
+                                selected_tracks.retain(|key| key != &track_key);
+                            } else {
+                                selected_tracks.push(track_key.clone());
+                            }
                         } else if shift_is_pressed && !selected_tracks.is_empty() {
                             let last_selected = selected_tracks.last().unwrap();
 
@@ -207,6 +314,20 @@ pub fn library_view(ui: &mut Ui, gem: &mut GemPlayer) {
                         }
                     }
 
+                    if response.drag_started() {
+                        let selected_tracks = &mut gem.ui.library.selected_tracks;
+
+                        // Dragging an unselected track drags only that track.
+                        if !track_is_selected {
+                            selected_tracks.clear();
+                            selected_tracks.push(track_key.clone());
+                        }
+
+                        response.dnd_set_drag_payload(TrackDragPayload {
+                            track_keys: selected_tracks.clone(),
+                        });
+                    }
+
                     if response.double_clicked() {
                         let track_keys = gem.ui.library.cached_library.iter().map(|t| t.path.clone()).collect();
                         maybe_command = Some(GemCommand::PlayTrackList {
@@ -223,6 +344,9 @@ pub fn library_view(ui: &mut Ui, gem: &mut GemPlayer) {
                 });
             });
 
+        gem.ui.library.scroll_anchor = first_shown_row;
+        update_column_layout(&mut gem.ui.library.columns, &visible_columns, &column_widths, column_edit);
+
         // Queue commands AFTER rendering the table to avoid borrow checker issues that come with mutating state inside closures.
         if let Some(command) = maybe_command {
             gem.commands.push(command);
@@ -230,6 +354,28 @@ pub fn library_view(ui: &mut Ui, gem: &mut GemPlayer) {
     });
 }
 
+// Shows how many tracks are being dragged next to the pointer. This is drawn at the root
+// since the drag may continue into another view (see view_selector()).
+pub fn track_drag_preview(ui: &mut Ui) {
+    let Some(payload) = DragAndDrop::payload::<TrackDragPayload>(ui.ctx()) else {
+        return;
+    };
+
+    let Some(pointer_position) = ui.ctx().pointer_interact_pos() else {
+        return;
+    };
+
+    Area::new(Id::new("track_drag_preview"))
+        .order(Order::Tooltip)
+        .interactable(false)
+        .fixed_pos(pointer_position + Vec2::splat(12.0))
+        .show(ui.ctx(), |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                ui.add(unselectable_label(format!("{} track(s)", payload.track_keys.len())));
+            });
+        });
+}
+
 fn library_context_menu(ui: &mut Ui, gem: &GemPlayer) -> Option<GemCommand> {
     let mut maybe_command = None;
 
@@ -243,16 +389,23 @@ fn library_context_menu(ui: &mut Ui, gem: &GemPlayer) -> Option<GemCommand> {
 
     ui.separator();
 
+    if let Some(command) = selection_menu_items(ui) {
+        maybe_command = Some(command);
+    }
+
+    ui.separator();
+
     let add_to_playlists_enabled = !gem.playlists.is_empty();
     ui.add_enabled_ui(add_to_playlists_enabled, |ui| {
         ui.menu_button("Add to Playlist", |ui| {
             ui.set_min_width(menu_width);
 
             ScrollArea::vertical().max_height(164.0).show(ui, |ui| {
-                for playlist in &gem.playlists {
+                for i in display_order(&gem.playlists) {
+                    let playlist = &gem.playlists[i];
                     if ui.button(&playlist.name).clicked() {
                         maybe_command = Some(GemCommand::AddTracksToPlaylist {
-                            playlist_key: playlist.m3u_path.clone(),
+                            playlist_key: playlist.file_path.clone(),
                             track_keys: gem.ui.library.selected_tracks.clone(),
                         });
                     }
@@ -277,6 +430,55 @@ fn library_context_menu(ui: &mut Ui, gem: &GemPlayer) -> Option<GemCommand> {
 
     ui.separator();
 
+    // Album and artist actions apply to the first selected track.
+    let first_selected = gem.ui.library.selected_tracks.first().map(|key| gem.library.get_by_path(key));
+    let album = first_selected.and_then(|t| t.album.as_deref());
+    let album_artist = first_selected.and_then(|t| t.album_artist.as_deref());
+    let artist = first_selected.and_then(|t| t.artists.first()).map(String::as_str);
+
+    ui.add_enabled_ui(album.is_some(), |ui| {
+        if ui.button(("Play Album", ICON_ALBUM)).clicked()
+            && let Some(album) = album
+        {
+            maybe_command = Some(GemCommand::PlayTrackList {
+                track_keys: album_track_keys(&gem.library, album, album_artist),
+                start_at: None,
+            });
+        }
+
+        if ui.button(("Add Album to Queue", ICON_QUEUE_MUSIC)).clicked()
+            && let Some(album) = album
+        {
+            maybe_command = Some(GemCommand::EnqueueTracks {
+                track_keys: album_track_keys(&gem.library, album, album_artist),
+            });
+        }
+    });
+
+    ui.add_enabled_ui(artist.is_some(), |ui| {
+        if ui.button(("Add All by Artist to Queue", ICON_ARTIST)).clicked()
+            && let Some(artist) = artist
+        {
+            maybe_command = Some(GemCommand::EnqueueTracks {
+                track_keys: artist_track_keys(&gem.library, artist),
+            });
+        }
+    });
+
+    ui.separator();
+
+    if let Some(command) = rating_menu(ui, &gem.ui.library.selected_tracks) {
+        maybe_command = Some(command);
+    }
+
+    if ui.button(("Set Artwork…", ICON_IMAGE)).clicked() {
+        maybe_command = Some(GemCommand::PickArtwork {
+            track_keys: gem.ui.library.selected_tracks.clone(),
+        });
+    }
+
+    ui.separator();
+
     if ui.button(("Open File Location", ICON_FOLDER)).clicked()
         && let Some(track_path) = gem.ui.library.selected_tracks.first()
     {
@@ -285,3 +487,31 @@ fn library_context_menu(ui: &mut Ui, gem: &GemPlayer) -> Option<GemCommand> {
 
     maybe_command
 }
+
+// Shown in place of the library while it loads for the first time, which can take a while for large libraries.
+fn library_progress_ui(ui: &mut Ui, progress: &LoadProgress, directory: &Path) {
+    if progress.total == 0 {
+        ui.add(Spinner::new());
+        ui.add(unselectable_label("Looking for music…"));
+        return;
+    }
+
+    let fraction = progress.done as f32 / progress.total as f32;
+    let text = format!("Scanned {} of {} files", progress.done, progress.total);
+    ui.add(ProgressBar::new(fraction).desired_width(320.0).text(text));
+
+    ui.add_space(4.0);
+
+    let folder = progress.folder.strip_prefix(directory).unwrap_or(&progress.folder);
+    ui.add(unselectable_label(RichText::new(folder.display().to_string()).small().weak()));
+
+    if progress.done >= MIN_FILES_FOR_ESTIMATE {
+        let remaining_files = (progress.total - progress.done) as f64;
+        let remaining = progress.started.elapsed().mul_f64(remaining_files / progress.done as f64);
+        ui.add(unselectable_label(
+            RichText::new(format!("About {} left", format_duration_to_mmss(remaining)))
+                .small()
+                .weak(),
+        ));
+    }
+}
@@ -0,0 +1,68 @@
+use egui::{Align, Frame, Margin, RichText, ScrollArea, Sense, Ui};
+
+use crate::{
+    GemPlayer,
+    commands::GemCommand,
+    lyrics::{Lyrics, current_line_index},
+    player::get_position,
+    ui::{root::unselectable_label, widgets::centered_frame::centered_frame},
+};
+
+pub fn lyrics_panel(ui: &mut Ui, gem: &mut GemPlayer) {
+    let Some(lyrics) = &gem.lyrics else {
+        centered_frame(ui, |ui| {
+            ui.vertical_centered(|ui| {
+                let message = if gem.player.playing.is_some() {
+                    "No lyrics found for this track."
+                } else {
+                    "Nothing is playing."
+                };
+                ui.add(unselectable_label(message));
+            });
+        });
+
+        return;
+    };
+
+    Frame::new().inner_margin(Margin::symmetric(16, 8)).show(ui, |ui| {
+        ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| match lyrics {
+            Lyrics::Plain(text) => {
+                ui.add(unselectable_label(text.as_str()));
+            }
+            Lyrics::Synced(lines) => {
+                let position = get_position(&gem.player).unwrap_or_default();
+                let current_line = current_line_index(lines, position);
+
+                // Only follow the playing line when it changes, so that the user can still scroll around.
+                let line_changed = current_line != gem.ui.lyrics_line;
+                gem.ui.lyrics_line = current_line;
+
+                let highlight_color = ui.visuals().selection.bg_fill;
+
+                for (i, line) in lines.iter().enumerate() {
+                    let text = if line.text.is_empty() { "♪" } else { line.text.as_str() };
+
+                    let rich_text = if Some(i) == current_line {
+                        RichText::new(text).strong().color(highlight_color)
+                    } else {
+                        RichText::new(text).weak()
+                    };
+
+                    let response = ui
+                        .add(unselectable_label(rich_text).sense(Sense::click()))
+                        .on_hover_text("Jump to line");
+
+                    if response.clicked() {
+                        gem.commands.push(GemCommand::SeekTo(line.time));
+                    }
+
+                    if Some(i) == current_line && line_changed {
+                        response.scroll_to_me(Some(Align::Center));
+                    }
+
+                    ui.add_space(4.0);
+                }
+            }
+        });
+    });
+}
@@ -1,9 +1,18 @@
+pub mod albums_view;
+pub mod ambient_view;
 pub mod bottom_bar;
 pub mod control_panel;
+pub mod duplicates_window;
 pub mod file_drop_overlay;
+pub mod history_view;
+pub mod itunes_import_window;
 pub mod library_view;
+pub mod lyrics_panel;
+pub mod now_playing_view;
 pub mod playlist_view;
 pub mod queue_view;
 pub mod root;
 pub mod settings_view;
+pub mod track_columns;
+pub mod type_to_jump;
 pub mod widgets;
@@ -0,0 +1,149 @@
+use egui::{Align, Button, Frame, Layout, Margin, RichText, ScrollArea, Ui};
+use egui_material_icons::icons::ICON_FOLDER;
+
+use crate::{
+    GemPlayer,
+    artwork_cache::artwork_uri,
+    commands::GemCommand,
+    track::{Track, file_type_name, format_channels, format_gain, format_track_number},
+    ui::{
+        root::{View, format_duration_to_mmss, unselectable_label},
+        widgets::{artwork::artwork_ui, centered_frame::centered_frame},
+    },
+};
+
+const UP_NEXT_COUNT: usize = 8; // How many of the queued tracks are listed.
+
+// The playing track with its artwork at full size, everything known about it, and what plays after it.
+pub fn now_playing_view(ui: &mut Ui, gem: &mut GemPlayer) {
+    let Some(track) = gem.player.playing.clone() else {
+        centered_frame(ui, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add(unselectable_label("Nothing is playing."));
+            });
+        });
+
+        return;
+    };
+
+    Frame::new().inner_margin(Margin::same(16)).show(ui, |ui| {
+        let available = ui.available_size();
+        let artwork_width = available.y.min(available.x * 0.45).max(0.0);
+
+        ui.horizontal_top(|ui| {
+            let uri = artwork_uri();
+            artwork_ui(ui, uri.as_deref(), artwork_width);
+
+            ui.add_space(24.0);
+
+            ui.vertical(|ui| {
+                ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+                    track_details(ui, gem, &track);
+
+                    ui.add_space(24.0);
+
+                    up_next(ui, gem);
+                });
+            });
+        });
+    });
+}
+
+fn track_details(ui: &mut Ui, gem: &mut GemPlayer, track: &Track) {
+    let title = track.title.as_deref().unwrap_or("Unknown Title");
+    ui.add(unselectable_label(RichText::new(title).heading().strong()).wrap());
+
+    let artist = track.artist.as_deref().unwrap_or("Unknown Artist");
+    ui.add(unselectable_label(RichText::new(artist).size(16.0)));
+
+    if let Some(album) = &track.album {
+        let album_text = match track.year {
+            Some(year) => format!("{} ({})", album, year),
+            None => album.clone(),
+        };
+        ui.add(unselectable_label(RichText::new(album_text).weak()));
+    }
+
+    ui.add_space(16.0);
+
+    let sample_rate = track.sample_rate.map(|sr| format!("{:.1} kHz", sr.get() as f32 / 1000.0));
+    let bitrate = track.bitrate.map(|kbps| format!("{} kbps", kbps));
+    let plays = gem.play_counts.get(&track.path).copied().unwrap_or(0);
+
+    let replay_gain = &track.replay_gain;
+    let with_peak = |gain: Option<f32>, peak: Option<f32>| match peak {
+        Some(peak) if gain.is_some() => format!("{} (peak {:.3})", format_gain(gain), peak),
+        _ => format_gain(gain),
+    };
+    let loudness = gem
+        .player
+        .loudness
+        .get(track)
+        .map(|loudness| format!("{:.1} LUFS (peak {:.3})", loudness.integrated, loudness.peak));
+
+    detail_row(ui, "Codec", file_type_name(track.codec));
+    detail_row(ui, "Sample rate", sample_rate.as_deref().unwrap_or("-"));
+    detail_row(ui, "Bitrate", bitrate.as_deref().unwrap_or("-"));
+    detail_row(ui, "Channels", &format_channels(track.channels));
+    detail_row(ui, "Duration", &format_duration_to_mmss(track.duration));
+    detail_row(ui, "Track gain", &with_peak(replay_gain.track_gain, replay_gain.track_peak));
+    detail_row(ui, "Album gain", &with_peak(replay_gain.album_gain, replay_gain.album_peak));
+    detail_row(ui, "Loudness", loudness.as_deref().unwrap_or("-"));
+    detail_row(ui, "Track", &format_track_number(track));
+    detail_row(ui, "Genre", track.genre.as_deref().unwrap_or("-"));
+    detail_row(ui, "Plays", &plays.to_string());
+
+    ui.horizontal(|ui| {
+        ui.add_sized([96.0, 18.0], unselectable_label(RichText::new("Path").weak()));
+
+        // Selectable so that the path can be copied.
+        ui.label(track.path.display().to_string());
+
+        ui.add_space(8.0);
+
+        let open_button = Button::new(ICON_FOLDER).frame(false);
+        if ui.add(open_button).on_hover_text("Open file location").clicked() {
+            gem.commands.push(GemCommand::OpenTrackLocation(track.path.clone()));
+        }
+    });
+}
+
+fn detail_row(ui: &mut Ui, name: &str, value: &str) {
+    ui.horizontal(|ui| {
+        ui.add_sized([96.0, 18.0], unselectable_label(RichText::new(name).weak()));
+        ui.add(unselectable_label(value));
+    });
+}
+
+fn up_next(ui: &mut Ui, gem: &mut GemPlayer) {
+    ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+        ui.add(unselectable_label(RichText::new("Up Next").heading()));
+
+        ui.add_space(8.0);
+
+        if ui.link("Show queue").clicked() {
+            gem.ui.current_view = View::Queue;
+        }
+    });
+
+    ui.add_space(8.0);
+
+    let queue = &gem.player.queue;
+    if queue.is_empty() {
+        ui.add(unselectable_label(RichText::new("The queue is empty.").weak()));
+        return;
+    }
+
+    for (i, track) in queue.iter().take(UP_NEXT_COUNT).enumerate() {
+        let title = track.title.as_deref().unwrap_or("-");
+        let artist = track.artist.as_deref().unwrap_or("-");
+        let duration = format_duration_to_mmss(track.duration);
+
+        ui.add(unselectable_label(format!("{}. {} — {} ({})", i + 1, title, artist, duration)).truncate());
+    }
+
+    let remaining = queue.len().saturating_sub(UP_NEXT_COUNT);
+    if remaining > 0 {
+        ui.add(unselectable_label(RichText::new(format!("and {} more", remaining)).weak()));
+    }
+}
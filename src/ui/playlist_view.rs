@@ -1,20 +1,34 @@
-use egui::{Align, Button, Color32, Frame, Id, Label, Layout, Margin, Popup, RichText, Sense, Separator, TextEdit, Ui, containers};
-use egui_extras::{Size, StripBuilder, TableBuilder};
+use egui::{
+    Align, Button, Color32, DragAndDrop, Frame, Id, Label, Layout, Margin, Popup, RichText, Sense, Separator, Stroke, StrokeKind, TextEdit,
+    Ui, containers,
+};
+use egui_extras::{Size, StripBuilder, TableBuilder, TableRow};
 use egui_material_icons::icons::{
-    ICON_ADD, ICON_ALBUM, ICON_ARTIST, ICON_CANCEL, ICON_CHECK, ICON_CLOSE, ICON_DELETE, ICON_EDIT, ICON_FOLDER, ICON_HOURGLASS,
-    ICON_MORE_HORIZ, ICON_MUSIC_NOTE, ICON_PLAY_ARROW, ICON_SAVE, ICON_TAG,
+    ICON_ADD, ICON_CANCEL, ICON_CHECK, ICON_CLOSE, ICON_CONTENT_COPY, ICON_DELETE, ICON_EDIT, ICON_FAVORITE, ICON_FILE_COPY,
+    ICON_FIND_IN_PAGE, ICON_FOLDER, ICON_IMAGE, ICON_IOS_SHARE, ICON_LINK_OFF, ICON_MORE_HORIZ, ICON_PLAY_ARROW, ICON_PUSH_PIN, ICON_SAVE,
+    ICON_TRENDING_UP, ICON_WARNING,
 };
 use fully_pub::fully_pub;
 use log::{error, info};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use strum::IntoEnumIterator;
 
 use crate::{
     GemPlayer,
+    auto_playlist::AutoPlaylist,
     commands::GemCommand,
-    playlist::{PlaylistRetrieval, create, delete, rename},
-    track::{Track, filter},
+    playlist::{
+        ExportOptions, PathSeparator, PathStyle, PlaylistEntry, PlaylistRetrieval, create, delete, display_order, duplicate, rename,
+        save_playlist_settings, validate_name,
+    },
+    track::{SortBy, SortOrder, Track, filter, sort},
     ui::{
-        root::{format_duration_to_mmss, table_label, unselectable_label},
+        library_view::{TrackDragPayload, moved_anchor_row},
+        root::{
+            format_duration_to_mmss, locate_flash_is_on, rating_menu, row_height, selection_menu_items, table_label, unselectable_label,
+        },
+        track_columns::{ColumnLayout, TrackColumn, add_track_columns, column_headers, column_text, update_column_layout},
+        type_to_jump::type_to_jump,
         widgets::{centered_frame::centered_frame, playing_indicator::playing_indicator},
     },
 };
@@ -22,13 +36,25 @@ use crate::{
 #[fully_pub]
 struct PlaylistsViewState {
     selected_playlist_key: Option<PathBuf>, // None: no playlist is selected. Some: the path of the selected playlist.
+    selected_auto_playlist: Option<AutoPlaylist>, // Some if a built-in playlist is selected instead of a playlist file.
     selected_tracks: Vec<PathBuf>,
 
     cached_playlist_tracks: Vec<Track>,
     cache_dirty: bool,
 
+    // Same as the library view, the first track shown stays in view when the playlist is reloaded.
+    scroll_anchor: Option<(usize, PathBuf)>,
+    restore_scroll: bool,
+
+    sort_by: Option<SortBy>, // None: the order of the playlist itself.
+    sort_order: SortOrder,
+
     rename_buffer: Option<String>, // If Some, the playlist pointed to by selected_track's name is being edited and a buffer for the new name.
     delete_modal_open: bool,       // The menu is open for selected_playlist_path.
+    export_modal_open: bool,       // The export options are being chosen for selected_playlist_path.
+    export_options: ExportOptions,
+
+    columns: ColumnLayout,
 }
 
 pub fn playlists_view(ui: &mut Ui, gem: &mut GemPlayer) {
@@ -53,7 +79,10 @@ pub fn playlists_view(ui: &mut Ui, gem: &mut GemPlayer) {
                     if let Err(e) = delete(&playlist_key, &mut gem.playlists) {
                         error!("{}", e);
                     } else {
-                        let message = "Playlist was deleted successfully. If this was a mistake, the m3u file can be found in the trash.";
+                        persist_playlist_settings(gem);
+
+                        let message =
+                            "Playlist was deleted successfully. If this was a mistake, the playlist file can be found in the trash.";
                         info!("{}", message);
                         gem.ui.toasts.success(message);
                     }
@@ -63,6 +92,19 @@ pub fn playlists_view(ui: &mut Ui, gem: &mut GemPlayer) {
             }
         }
 
+        if gem.ui.playlists.export_modal_open
+            && let Some(confirmed) = export_playlist_modal(ui, &mut gem.ui.playlists.export_options)
+        {
+            gem.ui.playlists.export_modal_open = false;
+
+            if confirmed && let Some(playlist_key) = gem.ui.playlists.selected_playlist_key.clone() {
+                gem.commands.push(GemCommand::ExportPlaylist {
+                    playlist_key,
+                    options: gem.ui.playlists.export_options,
+                });
+            }
+        }
+
         let size = ui.available_size();
         let playlists_width = size.x * (1.0 / 4.0);
 
@@ -103,7 +145,7 @@ pub fn playlists_view(ui: &mut Ui, gem: &mut GemPlayer) {
                                                     gem.ui.toasts.error(&error_message);
                                                 }
                                                 Ok(new_playlist) => {
-                                                    info!("Created and saved {} to {:?}", &new_playlist.name, &new_playlist.m3u_path);
+                                                    info!("Created and saved {} to {:?}", &new_playlist.name, &new_playlist.file_path);
                                                     gem.playlists.push(new_playlist);
                                                 }
                                             }
@@ -113,22 +155,55 @@ pub fn playlists_view(ui: &mut Ui, gem: &mut GemPlayer) {
                             });
                         })
                         .body(|body| {
-                            body.rows(36.0, gem.playlists.len(), |mut row| {
-                                let playlist = &mut gem.playlists[row.index()];
+                            let auto_playlists: Vec<AutoPlaylist> = AutoPlaylist::iter().collect();
+                            let order = display_order(&gem.playlists);
+
+                            body.rows(36.0, auto_playlists.len() + order.len(), |mut row| {
+                                // The built-in playlists are listed above the playlist files.
+                                if let Some(&auto_playlist) = auto_playlists.get(row.index()) {
+                                    auto_playlist_row(&mut row, gem, auto_playlist);
+                                    return;
+                                }
+
+                                let playlist = &mut gem.playlists[order[row.index() - auto_playlists.len()]];
 
                                 if let Some(playlist_key) = &gem.ui.playlists.selected_playlist_key {
-                                    let playlist_is_selected = playlist.m3u_path == *playlist_key;
+                                    let playlist_is_selected = playlist.file_path == *playlist_key;
                                     row.set_selected(playlist_is_selected);
                                 }
 
+                                let playlist_key = playlist.file_path.clone();
+
                                 row.col(|ui| {
+                                    // Highlight the playlist that library tracks are being dragged over.
+                                    let is_drop_target = DragAndDrop::has_payload_of_type::<TrackDragPayload>(ui.ctx())
+                                        && ui.rect_contains_pointer(ui.max_rect());
+                                    if is_drop_target {
+                                        let stroke = Stroke::new(1.0, ui.visuals().selection.bg_fill);
+                                        ui.painter().rect_stroke(ui.max_rect(), 0.0, stroke, StrokeKind::Inside);
+                                    }
+
                                     ui.add_space(8.0);
                                     ui.add(unselectable_label(&playlist.name));
+
+                                    if playlist.pinned {
+                                        ui.add_space(4.0);
+                                        let pin = RichText::new(ICON_PUSH_PIN.codepoint).small().weak();
+                                        ui.add(unselectable_label(pin)).on_hover_text("Pinned");
+                                    }
                                 });
 
+                                if let Some(payload) = row.response().dnd_release_payload::<TrackDragPayload>() {
+                                    gem.commands.push(GemCommand::AddTracksToPlaylist {
+                                        playlist_key,
+                                        track_keys: payload.track_keys.clone(),
+                                    });
+                                }
+
                                 if row.response().clicked() {
                                     info!("Selected playlist: {}", playlist.name);
-                                    gem.ui.playlists.selected_playlist_key = Some(playlist.m3u_path.clone());
+                                    gem.ui.playlists.selected_playlist_key = Some(playlist.file_path.clone());
+                                    gem.ui.playlists.selected_auto_playlist = None;
 
                                     gem.ui.playlists.rename_buffer = None; // In case we were currently editing
                                     gem.ui.playlists.cache_dirty = true;
@@ -147,6 +222,46 @@ pub fn playlists_view(ui: &mut Ui, gem: &mut GemPlayer) {
     });
 }
 
+fn auto_playlist_row(row: &mut TableRow, gem: &mut GemPlayer, auto_playlist: AutoPlaylist) {
+    row.set_selected(gem.ui.playlists.selected_auto_playlist == Some(auto_playlist));
+
+    row.col(|ui| {
+        ui.add_space(8.0);
+        ui.add(unselectable_label(RichText::new(auto_playlist_icon(auto_playlist)).weak()));
+        ui.add_space(4.0);
+        ui.add(unselectable_label(auto_playlist.name()));
+    });
+
+    if row.response().clicked() {
+        info!("Selected auto playlist: {}", auto_playlist.name());
+        gem.ui.playlists.selected_auto_playlist = Some(auto_playlist);
+        gem.ui.playlists.selected_playlist_key = None;
+
+        gem.ui.playlists.rename_buffer = None;
+        gem.ui.playlists.cache_dirty = true;
+        gem.ui.playlists.selected_tracks.clear();
+    }
+}
+
+fn auto_playlist_icon(auto_playlist: AutoPlaylist) -> &'static str {
+    match auto_playlist {
+        AutoPlaylist::TopPlayed => ICON_TRENDING_UP.codepoint,
+        AutoPlaylist::Favorites => ICON_FAVORITE.codepoint,
+    }
+}
+
+fn persist_playlist_settings(gem: &mut GemPlayer) {
+    let Some(directory) = &gem.library_directory else {
+        return;
+    };
+
+    if let Err(e) = save_playlist_settings(&gem.playlists, directory) {
+        let message = format!("{:#}", e);
+        error!("{}", message);
+        gem.ui.toasts.error(message);
+    }
+}
+
 enum DeletePlaylistResult {
     Confirm,
     Cancel,
@@ -188,7 +303,67 @@ fn delete_playlist_modal(ui: &mut Ui) -> Option<DeletePlaylistResult> {
     result
 }
 
+// Returns Some(true) once the user continues to choosing where to export to, or Some(false) if they cancel.
+fn export_playlist_modal(ui: &mut Ui, options: &mut ExportOptions) -> Option<bool> {
+    let mut result = None;
+
+    let modal = containers::Modal::new(Id::new("export_playlist_modal"))
+        .backdrop_color(Color32::TRANSPARENT)
+        .show(ui.ctx(), |ui| {
+            ui.set_width(260.0);
+            Frame::new().outer_margin(Margin::same(4)).show(ui, |ui| {
+                ui.add(unselectable_label(RichText::new("Export Playlist").heading()));
+                ui.add(unselectable_label(
+                    RichText::new("The format is chosen by the file extension, e.g. .m3u, .m3u8, .pls, or .xspf.")
+                        .small()
+                        .weak(),
+                ));
+
+                ui.separator();
+
+                ui.add(unselectable_label("Paths"));
+                for style in PathStyle::iter() {
+                    ui.radio_value(&mut options.path_style, style, style.label());
+                }
+
+                ui.add_space(8.0);
+
+                ui.add(unselectable_label("Separators"));
+                for separator in PathSeparator::iter() {
+                    ui.radio_value(&mut options.separator, separator, separator.label());
+                }
+
+                ui.separator();
+
+                let (cancel, confirm) = containers::Sides::new().show(
+                    ui,
+                    |ui| ui.button(("\t", ICON_CLOSE, "\t")).clicked(),
+                    |ui| ui.button("Choose Location…").clicked(),
+                );
+
+                if cancel {
+                    result = Some(false);
+                }
+
+                if confirm {
+                    result = Some(true);
+                }
+            });
+        });
+
+    if modal.should_close() {
+        result = Some(false);
+    }
+
+    result
+}
+
 fn playlist(ui: &mut Ui, gem: &mut GemPlayer) {
+    if let Some(auto_playlist) = gem.ui.playlists.selected_auto_playlist {
+        auto_playlist_view(ui, gem, auto_playlist);
+        return;
+    }
+
     let Some(playlist_key) = gem.ui.playlists.selected_playlist_key.clone() else {
         return; // No playlist selected, do nothing
     };
@@ -204,12 +379,37 @@ fn playlist(ui: &mut Ui, gem: &mut GemPlayer) {
                         let mut discard_clicked = false;
                         let mut save_clicked = false;
 
+                        let playlist = gem.playlists.get_by_path(&playlist_key);
+                        let validation = validate_name(name_buffer, playlist, &gem.playlists);
+
                         containers::Sides::new().height(ui.available_height()).show(
                             ui,
                             |ui| {
                                 ui.add_space(16.0);
+
+                                if validation.is_err() {
+                                    let error_stroke = Stroke::new(1.0, ui.visuals().error_fg_color);
+                                    ui.visuals_mut().widgets.inactive.bg_stroke = error_stroke;
+                                    ui.visuals_mut().widgets.hovered.bg_stroke = error_stroke;
+                                    ui.visuals_mut().selection.stroke = error_stroke;
+                                }
+
                                 let name_edit = TextEdit::singleline(name_buffer).char_limit(50);
                                 ui.add(name_edit);
+
+                                ui.add_space(8.0);
+
+                                match &validation {
+                                    Err(e) => {
+                                        let message = RichText::new(e.to_string()).small().color(ui.visuals().error_fg_color);
+                                        ui.add(unselectable_label(message));
+                                    }
+                                    Ok(sanitized_name) if sanitized_name != name_buffer.trim() => {
+                                        let message = RichText::new(format!("Will be saved as '{}'.", sanitized_name)).small().weak();
+                                        ui.add(unselectable_label(message));
+                                    }
+                                    Ok(_) => {}
+                                }
                             },
                             |ui| {
                                 ui.add_space(16.0);
@@ -219,24 +419,34 @@ fn playlist(ui: &mut Ui, gem: &mut GemPlayer) {
 
                                 ui.add_space(8.0);
 
-                                save_clicked = ui.add(Button::new(ICON_SAVE)).on_hover_text("Save").clicked();
+                                save_clicked = ui
+                                    .add_enabled(validation.is_ok(), Button::new(ICON_SAVE))
+                                    .on_hover_text("Save")
+                                    .on_disabled_hover_text("Invalid playlist name")
+                                    .clicked();
                             },
                         );
 
                         if save_clicked {
                             let name_buffer_clone = name_buffer.to_owned();
 
-                            let playlist = &mut gem.playlists.get_by_path_mut(&playlist_key);
-                            let result = rename(playlist, name_buffer_clone);
+                            let result = rename(&playlist_key, &name_buffer_clone, &mut gem.playlists);
                             match result {
                                 Err(e) => {
                                     let message = format!("Error renaming playlist: {}", e);
                                     error!("{}", message);
                                     gem.ui.toasts.error(message);
                                 }
-                                Ok(_) => {
+                                Ok(new_path) => {
+                                    let playlist = gem.playlists.get_by_path(&new_path);
+                                    let has_settings = playlist.pinned || playlist.allow_duplicates;
+
                                     // Update the selected playlist with the new path so that we remain selected.
-                                    gem.ui.playlists.selected_playlist_key = Some(playlist.m3u_path.clone());
+                                    gem.ui.playlists.selected_playlist_key = Some(new_path);
+
+                                    if has_settings {
+                                        persist_playlist_settings(gem);
+                                    }
                                 }
                             }
 
@@ -252,6 +462,10 @@ fn playlist(ui: &mut Ui, gem: &mut GemPlayer) {
                         let mut play_clicked = false;
                         let mut delete_clicked = false;
                         let mut edit_clicked = false;
+                        let mut pin_clicked = false;
+                        let mut export_clicked = false;
+                        let mut duplicates_clicked = false;
+                        let mut duplicate_clicked = false;
 
                         containers::Sides::new().height(ui.available_height()).show(
                             ui,
@@ -280,8 +494,36 @@ fn playlist(ui: &mut Ui, gem: &mut GemPlayer) {
 
                                 ui.add_space(8.0);
 
+                                let export_button = Button::new(ICON_IOS_SHARE);
+                                export_clicked = ui.add(export_button).on_hover_text("Export").clicked();
+
+                                ui.add_space(8.0);
+
+                                let duplicate_button = Button::new(ICON_FILE_COPY);
+                                duplicate_clicked = ui.add(duplicate_button).on_hover_text("Duplicate").clicked();
+
+                                ui.add_space(8.0);
+
                                 let edit_name_button = Button::new(ICON_EDIT);
                                 edit_clicked = ui.add(edit_name_button).on_hover_text("Edit name").clicked();
+
+                                ui.add_space(8.0);
+
+                                let is_pinned = gem.playlists.get_by_path(&playlist_key).pinned;
+                                let pin_button = Button::new(ICON_PUSH_PIN).selected(is_pinned);
+                                let pin_tooltip = if is_pinned { "Unpin" } else { "Pin to top" };
+                                pin_clicked = ui.add(pin_button).on_hover_text(pin_tooltip).clicked();
+
+                                ui.add_space(8.0);
+
+                                let allows_duplicates = gem.playlists.get_by_path(&playlist_key).allow_duplicates;
+                                let duplicates_button = Button::new(ICON_CONTENT_COPY).selected(allows_duplicates);
+                                let duplicates_tooltip = if allows_duplicates {
+                                    "Duplicates allowed"
+                                } else {
+                                    "Allow duplicates"
+                                };
+                                duplicates_clicked = ui.add(duplicates_button).on_hover_text(duplicates_tooltip).clicked();
                             },
                         );
 
@@ -307,6 +549,60 @@ fn playlist(ui: &mut Ui, gem: &mut GemPlayer) {
                             gem.ui.playlists.delete_modal_open = true;
                         }
 
+                        if pin_clicked {
+                            let playlist = gem.playlists.get_by_path_mut(&playlist_key);
+                            playlist.pinned = !playlist.pinned;
+                            info!(
+                                "{} playlist: {}",
+                                if playlist.pinned { "Pinned" } else { "Unpinned" },
+                                playlist.name
+                            );
+
+                            persist_playlist_settings(gem);
+                        }
+
+                        if duplicates_clicked {
+                            let playlist = gem.playlists.get_by_path_mut(&playlist_key);
+                            playlist.allow_duplicates = !playlist.allow_duplicates;
+                            info!(
+                                "{} duplicates in playlist: {}",
+                                if playlist.allow_duplicates { "Allowed" } else { "Disallowed" },
+                                playlist.name
+                            );
+
+                            persist_playlist_settings(gem);
+                        }
+
+                        if duplicate_clicked {
+                            let playlist = gem.playlists.get_by_path(&playlist_key);
+                            match duplicate(playlist, &gem.playlists) {
+                                Err(e) => {
+                                    let message = format!("Failed to duplicate playlist: {:#}", e);
+                                    error!("{}", message);
+                                    gem.ui.toasts.error(message);
+                                }
+                                Ok(copy) => {
+                                    info!("Duplicated {} to {:?}", playlist.name, copy.file_path);
+
+                                    gem.ui.playlists.selected_playlist_key = Some(copy.file_path.clone());
+                                    gem.ui.playlists.selected_tracks.clear();
+                                    gem.ui.playlists.cache_dirty = true;
+
+                                    let allows_duplicates = copy.allow_duplicates;
+                                    gem.playlists.push(copy);
+
+                                    if allows_duplicates {
+                                        persist_playlist_settings(gem);
+                                    }
+                                }
+                            }
+                        }
+
+                        if export_clicked {
+                            info!("Opening export playlist modal");
+                            gem.ui.playlists.export_modal_open = true;
+                        }
+
                         if edit_clicked {
                             let playlist = &mut gem.playlists.get_by_path(&playlist_key);
                             info!("Editing playlist name: {}", playlist.name);
@@ -320,9 +616,73 @@ fn playlist(ui: &mut Ui, gem: &mut GemPlayer) {
         });
 }
 
+// Auto playlists can be played and enqueued, but their tracks can't be edited by hand.
+fn auto_playlist_view(ui: &mut Ui, gem: &mut GemPlayer, auto_playlist: AutoPlaylist) {
+    StripBuilder::new(ui)
+        .size(Size::exact(64.0))
+        .size(Size::remainder())
+        .vertical(|mut strip| {
+            strip.cell(|ui| {
+                Frame::new().fill(ui.visuals().faint_bg_color).show(ui, |ui| {
+                    let strip_contains_pointer = ui.rect_contains_pointer(ui.max_rect());
+                    let mut play_clicked = false;
+                    let mut enqueue_clicked = false;
+
+                    containers::Sides::new().height(ui.available_height()).show(
+                        ui,
+                        |ui| {
+                            ui.add_space(16.0);
+                            ui.add(unselectable_label(RichText::new(auto_playlist.name()).heading().strong()));
+
+                            if strip_contains_pointer {
+                                ui.add_space(16.0);
+
+                                let play = Button::new(ICON_PLAY_ARROW);
+                                play_clicked = ui.add(play).clicked();
+                            }
+                        },
+                        |ui| {
+                            if !strip_contains_pointer {
+                                return;
+                            }
+
+                            ui.add_space(16.0);
+
+                            let enqueue_button = Button::new(ICON_ADD);
+                            enqueue_clicked = ui.add(enqueue_button).on_hover_text("Add to Queue").clicked();
+                        },
+                    );
+
+                    if play_clicked || enqueue_clicked {
+                        let track_keys = auto_playlist
+                            .tracks(&gem.library, &gem.play_counts)
+                            .into_iter()
+                            .map(|t| t.path)
+                            .collect();
+
+                        let command = if play_clicked {
+                            GemCommand::PlayTrackList {
+                                track_keys,
+                                start_at: None,
+                            }
+                        } else {
+                            GemCommand::EnqueueTracks { track_keys }
+                        };
+                        gem.commands.push(command);
+                    }
+                });
+            });
+
+            strip.cell(|ui| playlist_tracks(ui, gem));
+        });
+}
+
 fn playlist_tracks(ui: &mut Ui, gem: &mut GemPlayer) {
     ui.scope(|ui| {
-        let Some(playlist_key) = gem.ui.playlists.selected_playlist_key.clone() else {
+        let playlist_key = gem.ui.playlists.selected_playlist_key.clone();
+        let auto_playlist = gem.ui.playlists.selected_auto_playlist;
+
+        if playlist_key.is_none() && auto_playlist.is_none() {
             centered_frame(ui, |ui| {
                 ui.vertical_centered(|ui| {
                     ui.add(unselectable_label("No playlist selected"));
@@ -330,75 +690,167 @@ fn playlist_tracks(ui: &mut Ui, gem: &mut GemPlayer) {
             });
 
             return;
+        }
+
+        if gem.ui.playlists.cache_dirty {
+            // Auto playlists have no playlist file, so their tracks are gathered from the library.
+            gem.ui.playlists.cached_playlist_tracks = match (&playlist_key, auto_playlist) {
+                (_, Some(auto_playlist)) => filter(&auto_playlist.tracks(&gem.library, &gem.play_counts), &gem.ui.search),
+                (Some(playlist_key), None) => filter(&gem.playlists.get_by_path(playlist_key).tracks, &gem.ui.search),
+                (None, None) => Vec::new(),
+            };
+            if let Some(sort_by) = gem.ui.playlists.sort_by {
+                sort(&mut gem.ui.playlists.cached_playlist_tracks, sort_by, gem.ui.playlists.sort_order);
+            }
+            gem.ui.playlists.cache_dirty = false;
+        }
+
+        let (playlist_is_empty, empty_message) = match (&playlist_key, auto_playlist) {
+            (_, Some(auto_playlist)) => (
+                gem.ui.playlists.cached_playlist_tracks.is_empty() && gem.ui.search.is_empty(),
+                auto_playlist.empty_message(),
+            ),
+            (Some(playlist_key), None) => {
+                let playlist = gem.playlists.get_by_path(playlist_key);
+                (
+                    playlist.tracks.is_empty() && playlist.missing_entries.is_empty(),
+                    "The playlist is empty.",
+                )
+            }
+            (None, None) => (true, ""),
         };
 
-        let playlist_length = gem.playlists.get_by_path(&playlist_key).tracks.len();
-        if playlist_length == 0 {
+        if playlist_is_empty {
             centered_frame(ui, |ui| {
                 ui.vertical_centered(|ui| {
-                    ui.add(unselectable_label("The playlist is empty."));
+                    ui.add(unselectable_label(empty_message));
                 });
             });
 
             return;
         }
 
-        if gem.ui.playlists.cache_dirty {
-            gem.ui.playlists.cached_playlist_tracks = filter(&gem.playlists.get_by_path(&playlist_key).tracks, &gem.ui.search);
-            gem.ui.library.cache_dirty = false;
-        }
-
-        let header_labels = [ICON_TAG, ICON_MUSIC_NOTE, ICON_ARTIST, ICON_ALBUM, ICON_HOURGLASS];
+        // Missing entries are listed after the tracks. They have no tags to search, so they are hidden while searching.
+        let missing_entries = match &playlist_key {
+            Some(playlist_key) if auto_playlist.is_none() && gem.ui.search.is_empty() => {
+                gem.playlists.get_by_path(playlist_key).missing_entries.clone()
+            }
+            _ => Vec::new(),
+        };
 
+        let visible_columns = gem.ui.playlists.columns.visible_columns();
         let available_width = ui.available_width();
-        let position_width = 64.0;
-        let time_width = 64.0;
-        let more_width = 48.0;
-        let remaining_width = available_width - position_width - time_width - more_width;
-        let title_width = remaining_width * (2.0 / 4.0);
-        let artist_width = remaining_width * (1.0 / 4.0);
-        let album_width = remaining_width * (1.0 / 4.0);
 
         ui.spacing_mut().item_spacing.x = 0.0; // See comment in library_view() as to why we do this.
 
-        // Used to determine if selection should be extended.
-        let shift_is_pressed = ui.input(|i| i.modifiers.shift);
+        // Used to determine if selection should be extended or toggled.
+        let (shift_is_pressed, command_is_pressed) = ui.input(|i| (i.modifiers.shift, i.modifiers.command));
 
         let mut maybe_command = None;
 
         let playing_color = ui.visuals().selection.bg_fill;
+        let reduce_animations = gem.ui.reduce_animations;
+        let large_rows = gem.ui.large_rows;
 
-        TableBuilder::new(ui)
+        let mut scroll_to_row = None;
+
+        if gem.ui.locate_playing.scroll_pending {
+            gem.ui.locate_playing.scroll_pending = false;
+
+            let playing_index = gem.player.playing.as_ref().and_then(|playing| {
+                let cached_tracks = &gem.ui.playlists.cached_playlist_tracks;
+                cached_tracks.iter().position(|t| t == playing)
+            });
+
+            if let Some(index) = playing_index {
+                scroll_to_row = Some(index);
+                gem.ui.locate_playing.flash_started = Some(ui.input(|i| i.time));
+            } else {
+                gem.ui.toasts.info("The playing track isn't in this playlist.");
+            }
+        }
+
+        let view_state = &mut gem.ui.playlists;
+        if let Some(index) = type_to_jump(
+            ui,
+            &mut gem.ui.type_to_jump,
+            &view_state.cached_playlist_tracks,
+            &mut view_state.selected_tracks,
+        ) {
+            scroll_to_row = Some(index);
+        }
+
+        let flash_is_on = locate_flash_is_on(ui, &mut gem.ui.locate_playing);
+
+        let mut anchor_row = None;
+        if gem.ui.playlists.restore_scroll {
+            gem.ui.playlists.restore_scroll = false;
+            anchor_row = moved_anchor_row(gem.ui.playlists.scroll_anchor.as_ref(), &gem.ui.playlists.cached_playlist_tracks);
+        }
+
+        let mut table = TableBuilder::new(ui);
+        if let Some(index) = scroll_to_row {
+            table = table.scroll_to_row(index, Some(Align::Center));
+        } else if let Some(index) = anchor_row {
+            table = table.scroll_to_row(index, Some(Align::Min));
+        }
+
+        let more_width = 48.0;
+        let table = add_track_columns(
+            table,
+            "playlist_table",
+            &gem.ui.playlists.columns,
+            &visible_columns,
+            available_width,
+            more_width,
+        );
+
+        let mut column_widths = Vec::new();
+        let mut column_edit = None;
+        let mut first_shown_row = None;
+
+        table
             .striped(true)
             .sense(Sense::click())
             .cell_layout(Layout::left_to_right(Align::Center))
-            .column(egui_extras::Column::exact(position_width))
-            .column(egui_extras::Column::exact(title_width))
-            .column(egui_extras::Column::exact(artist_width))
-            .column(egui_extras::Column::exact(album_width))
-            .column(egui_extras::Column::exact(time_width))
-            .column(egui_extras::Column::exact(more_width))
             .header(16.0, |mut header| {
-                for (i, h) in header_labels.iter().enumerate() {
-                    header.col(|ui| {
-                        if i == 0 {
-                            ui.add_space(16.0);
-                        }
-                        ui.add(unselectable_label(RichText::new(*h).strong()));
-                    });
-                }
+                column_edit = column_headers(
+                    &mut header,
+                    "playlist_table",
+                    &gem.ui.playlists.columns,
+                    &visible_columns,
+                    &mut column_widths,
+                );
             })
             .body(|body| {
-                body.rows(26.0, gem.ui.playlists.cached_playlist_tracks.len(), |mut row| {
+                let track_count = gem.ui.playlists.cached_playlist_tracks.len();
+                body.rows(row_height(large_rows), track_count + missing_entries.len(), |mut row| {
                     let index = row.index();
 
+                    if index >= track_count {
+                        // Numbered by where the entry is in the playlist file, even though it is listed after the tracks.
+                        let missing_index = index - track_count;
+                        let missing = &missing_entries[missing_index];
+                        let position = missing.position + missing_index;
+                        if let Some(playlist_key) = &playlist_key
+                            && let Some(command) = missing_entry_row(&mut row, &visible_columns, position, &missing.entry, playlist_key)
+                        {
+                            maybe_command = Some(command);
+                        }
+                        return;
+                    }
+
                     let track = &gem.ui.playlists.cached_playlist_tracks[index];
                     let track_key = track.path.clone();
 
+                    if first_shown_row.is_none() {
+                        first_shown_row = Some((index, track_key.clone()));
+                    }
+
                     let track_is_playing = gem.player.playing.as_ref().is_some_and(|t| t == track);
 
                     let track_is_selected = gem.ui.playlists.selected_tracks.contains(&track.path);
-                    row.set_selected(track_is_selected);
+                    row.set_selected(track_is_selected || (track_is_playing && flash_is_on));
 
                     let text_color = if track_is_playing && !track_is_selected {
                         Some(playing_color)
@@ -406,36 +858,13 @@ fn playlist_tracks(ui: &mut Ui, gem: &mut GemPlayer) {
                         None
                     };
 
-                    row.col(|ui| {
-                        ui.add_space(16.0);
-                        let label = table_label((index + 1).to_string(), text_color);
-                        ui.add(label);
-                    });
-
-                    row.col(|ui| {
-                        ui.add_space(4.0);
-                        let label = table_label(track.title.as_deref().unwrap_or("-"), text_color);
-                        ui.add(label);
-                    });
-
-                    row.col(|ui| {
-                        ui.add_space(4.0);
-                        let label = table_label(track.artist.as_deref().unwrap_or("-"), text_color);
-                        ui.add(label);
-                    });
-
-                    row.col(|ui| {
-                        ui.add_space(4.0);
-                        let label = table_label(track.album.as_deref().unwrap_or("-"), text_color);
-                        ui.add(label);
-                    });
-
-                    row.col(|ui| {
-                        ui.add_space(4.0);
-                        let duration_string = format_duration_to_mmss(track.duration);
-                        let label = table_label(duration_string, text_color);
-                        ui.add(label);
-                    });
+                    for (i, column) in visible_columns.iter().enumerate() {
+                        row.col(|ui| {
+                            ui.add_space(if i == 0 { 16.0 } else { 4.0 });
+                            let label = table_label(column_text(*column, track, index + 1, &gem.play_counts), text_color);
+                            ui.add(label);
+                        });
+                    }
 
                     let rest_of_row_is_hovered = row.response().hovered();
                     let mut more_cell_contains_pointer = false;
@@ -463,7 +892,10 @@ fn playlist_tracks(ui: &mut Ui, gem: &mut GemPlayer) {
                                 }
                             });
                         } else if track_is_playing {
-                            playing_indicator(ui);
+                            playing_indicator(ui, reduce_animations);
+                        } else if gem.player.unplayable.contains(&track_key) {
+                            let warning = RichText::new(ICON_WARNING).color(ui.visuals().warn_fg_color);
+                            ui.add(unselectable_label(warning)).on_hover_text("This track could not be played.");
                         }
                     });
 
@@ -477,6 +909,12 @@ fn playlist_tracks(ui: &mut Ui, gem: &mut GemPlayer) {
                                 selected_tracks.clear();
                                 selected_tracks.push(track_key.clone());
                             }
+                        } else if command_is_pressed {
+                            if track_is_selected {
+                                selected_tracks.retain(|key| key != &track_key);
+                            } else {
+                                selected_tracks.push(track_key.clone());
+                            }
                         } else if shift_is_pressed && !selected_tracks.is_empty() {
                             let last_selected_track = selected_tracks.last().unwrap();
                             let last_index = gem
@@ -516,20 +954,105 @@ fn playlist_tracks(ui: &mut Ui, gem: &mut GemPlayer) {
                 });
             });
 
+        gem.ui.playlists.scroll_anchor = first_shown_row;
+        update_column_layout(&mut gem.ui.playlists.columns, &visible_columns, &column_widths, column_edit);
+
         if let Some(command) = maybe_command {
             gem.commands.push(command);
         }
     });
 }
 
-fn playlist_context_menu(ui: &mut Ui, gem: &GemPlayer) -> Option<GemCommand> {
-    let playlist_key = gem
-        .ui
-        .playlists
-        .selected_playlist_key
-        .as_ref()
-        .expect("The selected playlist should be set before the playlist context menu is opened.");
+// A greyed out row for an entry whose file could not be loaded.
+fn missing_entry_row(
+    row: &mut TableRow,
+    columns: &[TrackColumn],
+    index: usize,
+    missing_entry: &PlaylistEntry,
+    playlist_key: &Path,
+) -> Option<GemCommand> {
+    let mut maybe_command = None;
+    let missing_path = &missing_entry.path;
+
+    // Without info from the playlist file, the file name is all that is known about the entry.
+    let file_name = missing_path.file_name().unwrap_or(missing_path.as_os_str()).to_string_lossy();
+    let title = missing_entry.title.clone().unwrap_or_else(|| file_name.into_owned());
+    let artist = missing_entry.artist.as_deref();
+
+    for (i, column) in columns.iter().enumerate() {
+        row.col(|ui| {
+            ui.add_space(if i == 0 { 16.0 } else { 4.0 });
+
+            let text = match column {
+                TrackColumn::Position => {
+                    let warning = RichText::new(ICON_WARNING).color(ui.visuals().warn_fg_color);
+                    ui.add(unselectable_label(warning))
+                        .on_hover_text(format!("The file '{}' could not be found.", missing_path.display()));
+                    ui.add_space(4.0);
+                    (index + 1).to_string()
+                }
+                TrackColumn::Title => title.clone(),
+                TrackColumn::Artist => artist.unwrap_or("-").to_owned(),
+                TrackColumn::Album => "File not found".to_owned(),
+                TrackColumn::Duration => missing_entry.duration.map_or("-".to_owned(), format_duration_to_mmss),
+                TrackColumn::Path => missing_path.display().to_string(),
+                _ => "-".to_owned(),
+            };
+
+            let response = ui.add(unselectable_label(RichText::new(text).weak()));
+            if *column == TrackColumn::Title {
+                response.on_hover_text(missing_path.display().to_string());
+            }
+        });
+    }
+
+    let rest_of_row_is_hovered = row.response().hovered();
+    row.col(|ui| {
+        ui.add_space(8.0);
+
+        let more_cell_contains_pointer = ui.rect_contains_pointer(ui.max_rect());
+        if rest_of_row_is_hovered || more_cell_contains_pointer {
+            let response = ui.add(Button::new(ICON_MORE_HORIZ)).on_hover_text("More");
+            Popup::menu(&response).show(|ui| {
+                if let Some(command) = missing_entry_context_menu(ui, missing_path, playlist_key) {
+                    maybe_command = Some(command);
+                }
+            });
+        }
+    });
+
+    Popup::context_menu(&row.response()).show(|ui| {
+        if let Some(command) = missing_entry_context_menu(ui, missing_path, playlist_key) {
+            maybe_command = Some(command);
+        }
+    });
 
+    maybe_command
+}
+
+fn missing_entry_context_menu(ui: &mut Ui, missing_path: &Path, playlist_key: &Path) -> Option<GemCommand> {
+    let modal_width = 220.0;
+    ui.set_width(modal_width);
+
+    let mut command = None;
+
+    if ui.button(("Locate File…", ICON_FIND_IN_PAGE)).clicked() {
+        command = Some(GemCommand::LocateMissingPlaylistEntry {
+            playlist_key: playlist_key.to_path_buf(),
+            missing_path: missing_path.to_path_buf(),
+        });
+    }
+
+    if ui.button(("Remove Missing Entries", ICON_LINK_OFF)).clicked() {
+        command = Some(GemCommand::RemoveMissingPlaylistEntries {
+            playlist_key: playlist_key.to_path_buf(),
+        });
+    }
+
+    command
+}
+
+fn playlist_context_menu(ui: &mut Ui, gem: &GemPlayer) -> Option<GemCommand> {
     let track_keys = &gem.ui.playlists.selected_tracks;
 
     let modal_width = 220.0;
@@ -539,17 +1062,22 @@ fn playlist_context_menu(ui: &mut Ui, gem: &GemPlayer) -> Option<GemCommand> {
 
     ui.separator();
 
-    let mut command = None;
-
-    if ui.button(("Remove from Playlist", ICON_DELETE)).clicked() {
-        command = Some(GemCommand::RemoveTracksFromPlaylist {
-            playlist_key: playlist_key.clone(),
-            track_keys: track_keys.clone(),
-        });
-    }
+    let mut command = selection_menu_items(ui);
 
     ui.separator();
 
+    // Auto playlists have no playlist file to remove tracks from.
+    if let Some(playlist_key) = &gem.ui.playlists.selected_playlist_key {
+        if ui.button(("Remove from Playlist", ICON_DELETE)).clicked() {
+            command = Some(GemCommand::RemoveTracksFromPlaylist {
+                playlist_key: playlist_key.clone(),
+                track_keys: track_keys.clone(),
+            });
+        }
+
+        ui.separator();
+    }
+
     if ui.button(("Play Next", ICON_PLAY_ARROW)).clicked() {
         command = Some(GemCommand::EnqueueTracksNext {
             track_keys: track_keys.clone(),
@@ -564,6 +1092,18 @@ fn playlist_context_menu(ui: &mut Ui, gem: &GemPlayer) -> Option<GemCommand> {
 
     ui.separator();
 
+    if let Some(rating_command) = rating_menu(ui, track_keys) {
+        command = Some(rating_command);
+    }
+
+    if ui.button(("Set Artwork…", ICON_IMAGE)).clicked() {
+        command = Some(GemCommand::PickArtwork {
+            track_keys: track_keys.clone(),
+        });
+    }
+
+    ui.separator();
+
     if ui.button(("Open File Location", ICON_FOLDER)).clicked() {
         if let Some(first) = track_keys.first() {
             command = Some(GemCommand::OpenTrackLocation(first.clone()));
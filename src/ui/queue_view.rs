@@ -1,18 +1,36 @@
-use egui::{Align, Button, Layout, RichText, Sense, Ui};
+use egui::{Align, Button, CursorIcon, DragAndDrop, DragValue, Label, Layout, Popup, RichText, Sense, Stroke, Ui, containers};
 use egui_extras::TableBuilder;
-use egui_material_icons::icons::{ICON_ALBUM, ICON_ARROW_UPWARD, ICON_ARTIST, ICON_CLOSE, ICON_HOURGLASS, ICON_MUSIC_NOTE, ICON_TAG};
+use egui_material_icons::icons::{ICON_ARROW_DOWNWARD, ICON_ARROW_UPWARD, ICON_CLOSE, ICON_FORMAT_LIST_NUMBERED, ICON_PLAYLIST_ADD};
+use fully_pub::fully_pub;
+use log::{error, info};
 
 use crate::{
-    player::{Player, move_to_position, remove_from_queue},
+    GemPlayer,
+    player::{RepeatMode, move_tracks_to_index, move_tracks_to_position, remove_from_queue},
+    playlist::{create, save_to_file},
+    track::Track,
     ui::{
-        root::{format_duration_to_mmss, unselectable_label},
-        widgets::centered_frame::centered_frame,
+        root::{row_height, unselectable_label},
+        track_columns::{ColumnLayout, TrackColumn, add_track_columns, column_headers, column_text, update_column_layout},
+        widgets::{centered_frame::centered_frame, metadata_chip::MetadataChip},
     },
 };
 
-pub fn queue_view(ui: &mut Ui, player: &mut Player) {
+#[fully_pub]
+struct QueueViewState {
+    selected_indices: Vec<usize>, // Indices rather than paths since the queue may contain the same track more than once.
+    target_position: usize,       // The input of the "Move to Position" action. Starts at 1.
+    columns: ColumnLayout,
+}
+
+// The index of the queue row being dragged to a new position.
+struct QueueDragPayload {
+    index: usize,
+}
+
+pub fn queue_view(ui: &mut Ui, gem: &mut GemPlayer) {
     ui.scope(|ui| {
-        if player.queue.is_empty() {
+        if gem.player.queue.is_empty() {
             centered_frame(ui, |ui| {
                 ui.vertical_centered(|ui| {
                     ui.add(unselectable_label("The queue is empty."));
@@ -22,73 +40,113 @@ pub fn queue_view(ui: &mut Ui, player: &mut Player) {
             return;
         }
 
-        let header_labels = [ICON_TAG, ICON_MUSIC_NOTE, ICON_ARTIST, ICON_ALBUM, ICON_HOURGLASS];
+        let mut save_clicked = false;
+        containers::Sides::new().height(36.0).show(
+            ui,
+            |ui| {
+                ui.add_space(16.0);
+                ui.add(unselectable_label(RichText::new("Queue").heading().strong()));
+            },
+            |ui| {
+                ui.add_space(16.0);
+
+                let save_enabled = gem.library_directory.is_some();
+                save_clicked = ui
+                    .add_enabled(save_enabled, Button::new((ICON_PLAYLIST_ADD, " Save queue as playlist…")))
+                    .on_disabled_hover_text("Set a library directory in the settings first")
+                    .clicked();
+            },
+        );
 
+        if save_clicked {
+            save_queue_as_playlist(gem);
+        }
+
+        let visible_columns = gem.ui.queue.columns.visible_columns();
         let available_width = ui.available_width();
-        let position_width = 64.0;
-        let time_width = 64.0;
-        let actions_width = 80.0;
-        let remaining_width = available_width - position_width - time_width - actions_width;
-        let title_width = remaining_width * (2.0 / 4.0);
-        let artist_width = remaining_width * (1.0 / 4.0);
-        let album_width = remaining_width * (1.0 / 4.0);
 
         ui.spacing_mut().item_spacing.x = 0.0; // See comment in library_view() as to why we do this.
 
+        // The queue may have changed since the last frame, e.g. when the next track started.
+        let queue_length = gem.player.queue.len();
+        let large_rows = gem.ui.large_rows;
+        gem.ui.queue.selected_indices.retain(|&i| i < queue_length);
+
+        // Used to determine if selection should be extended or toggled.
+        let (shift_is_pressed, command_is_pressed) = ui.input(|i| (i.modifiers.shift, i.modifiers.command));
+
         // We only operate on the queue after we are done iterating over it.
-        let mut to_be_removed = None;
-        let mut to_be_moved_to_front = None;
+        let mut action = None;
 
-        TableBuilder::new(ui)
+        if DragAndDrop::has_payload_of_type::<QueueDragPayload>(ui.ctx()) {
+            ui.ctx().set_cursor_icon(CursorIcon::Grabbing);
+        }
+
+        let drop_line_stroke = Stroke::new(2.0, ui.visuals().selection.bg_fill);
+
+        let actions_width = 112.0;
+        let table = add_track_columns(
+            TableBuilder::new(ui),
+            "queue_table",
+            &gem.ui.queue.columns,
+            &visible_columns,
+            available_width,
+            actions_width,
+        );
+
+        let mut column_widths = Vec::new();
+        let mut column_edit = None;
+
+        table
             .striped(true)
-            .sense(Sense::hover())
+            .sense(Sense::click_and_drag())
             .cell_layout(Layout::left_to_right(Align::Center))
-            .column(egui_extras::Column::exact(position_width))
-            .column(egui_extras::Column::exact(title_width))
-            .column(egui_extras::Column::exact(artist_width))
-            .column(egui_extras::Column::exact(album_width))
-            .column(egui_extras::Column::exact(time_width))
-            .column(egui_extras::Column::exact(actions_width))
             .header(16.0, |mut header| {
-                for (i, h) in header_labels.iter().enumerate() {
-                    header.col(|ui| {
-                        if i == 0 {
-                            ui.add_space(16.0);
-                        }
-                        ui.add(unselectable_label(RichText::new(*h).strong()));
-                    });
-                }
+                column_edit = column_headers(
+                    &mut header,
+                    "queue_table",
+                    &gem.ui.queue.columns,
+                    &visible_columns,
+                    &mut column_widths,
+                );
             })
             .body(|body| {
-                body.rows(26.0, player.queue.len(), |mut row| {
+                body.rows(row_height(large_rows), queue_length, |mut row| {
                     let index = row.index();
-                    let track = &player.queue[index];
+                    let track = &gem.player.queue[index];
 
-                    row.col(|ui| {
-                        ui.add_space(16.0);
-                        ui.add(unselectable_label(format!("{}", index + 1)));
-                    });
+                    let row_is_selected = gem.ui.queue.selected_indices.contains(&index);
+                    row.set_selected(row_is_selected);
 
-                    row.col(|ui| {
-                        ui.add_space(4.0);
-                        ui.add(unselectable_label(track.title.as_deref().unwrap_or("-")));
-                    });
+                    // The front of the queue is what plays next, so we make it stand out. With repeat one, the current track replays instead.
+                    let is_up_next = index == 0 && gem.player.repeat != RepeatMode::One;
+                    let row_text = |text: &str| {
+                        let rich = RichText::new(text);
+                        if is_up_next { rich.strong() } else { rich }
+                    };
 
-                    row.col(|ui| {
-                        ui.add_space(4.0);
-                        ui.add(unselectable_label(track.artist.as_deref().unwrap_or("-")));
-                    });
+                    for (i, column) in visible_columns.iter().enumerate() {
+                        row.col(|ui| {
+                            ui.add_space(if i == 0 { 16.0 } else { 4.0 });
+                            if is_up_next && *column == TrackColumn::Title {
+                                ui.add(MetadataChip::new("Up next"));
+                                ui.add_space(4.0);
+                            }
+                            ui.add(unselectable_label(row_text(&column_text(
+                                *column,
+                                track,
+                                index + 1,
+                                &gem.play_counts,
+                            ))));
+                        });
+                    }
 
-                    row.col(|ui| {
-                        ui.add_space(4.0);
-                        ui.add(unselectable_label(track.album.as_deref().unwrap_or("-")));
-                    });
-
-                    row.col(|ui| {
-                        ui.add_space(4.0);
-                        let duration_string = format_duration_to_mmss(track.duration);
-                        ui.add(unselectable_label(duration_string));
-                    });
+                    // Row actions apply to the whole selection if the row is part of it.
+                    let targets = if row_is_selected {
+                        gem.ui.queue.selected_indices.clone()
+                    } else {
+                        vec![index]
+                    };
 
                     // We only display the actions column buttons if the row is hovered. There is a chicken and egg problem here.
                     // We need to know if the row is hovered before we display the actions column buttons. So, we check if
@@ -101,25 +159,203 @@ pub fn queue_view(ui: &mut Ui, player: &mut Player) {
 
                         ui.add_space(8.0);
 
-                        if ui.add_visible(should_show_action_buttons, Button::new(ICON_ARROW_UPWARD)).clicked() {
-                            to_be_moved_to_front = Some(index);
+                        let front_button = Button::new(ICON_ARROW_UPWARD);
+                        if ui
+                            .add_visible(should_show_action_buttons, front_button)
+                            .on_hover_text("Play after current")
+                            .clicked()
+                        {
+                            action = Some(QueueAction::MoveTo(targets.clone(), 0));
                         }
 
                         ui.add_space(8.0);
 
-                        if ui.add_visible(should_show_action_buttons, Button::new(ICON_CLOSE)).clicked() {
-                            to_be_removed = Some(index);
+                        let end_button = Button::new(ICON_ARROW_DOWNWARD);
+                        if ui
+                            .add_visible(should_show_action_buttons, end_button)
+                            .on_hover_text("Move to end")
+                            .clicked()
+                        {
+                            action = Some(QueueAction::MoveTo(targets.clone(), queue_length));
+                        }
+
+                        ui.add_space(8.0);
+
+                        let remove_button = Button::new(ICON_CLOSE);
+                        if ui
+                            .add_visible(should_show_action_buttons, remove_button)
+                            .on_hover_text("Remove")
+                            .clicked()
+                        {
+                            action = Some(QueueAction::Remove(targets.clone()));
                         }
                     });
+
+                    let response = row.response();
+
+                    if response.clicked() || response.secondary_clicked() {
+                        let selected_indices = &mut gem.ui.queue.selected_indices;
+
+                        if response.secondary_clicked() {
+                            if !row_is_selected {
+                                selected_indices.clear();
+                                selected_indices.push(index);
+                            }
+                        } else if command_is_pressed {
+                            if row_is_selected {
+                                selected_indices.retain(|&i| i != index);
+                            } else {
+                                selected_indices.push(index);
+                            }
+                        } else if shift_is_pressed && let Some(&last_index) = selected_indices.last() {
+                            let start = last_index.min(index);
+                            let end = last_index.max(index);
+                            for i in start..=end {
+                                if !selected_indices.contains(&i) {
+                                    selected_indices.push(i);
+                                }
+                            }
+                        } else {
+                            selected_indices.clear();
+                            selected_indices.push(index);
+                        }
+                    }
+
+                    Popup::context_menu(&response).show(|ui| {
+                        if let Some(a) = queue_context_menu(ui, &mut gem.ui.queue, queue_length) {
+                            action = Some(a);
+                        }
+                    });
+
+                    if response.drag_started() {
+                        response.dnd_set_drag_payload(QueueDragPayload { index });
+                    }
+
+                    // Dropping on the top half of a row places the track above it, otherwise below it.
+                    if let Some(pointer) = response.dnd_hover_payload::<QueueDragPayload>().and_then(|_| response.hover_pos()) {
+                        let rect = response.rect;
+                        let y = if pointer.y < rect.center().y { rect.top() } else { rect.bottom() };
+                        let painter = response.ctx.layer_painter(response.layer_id);
+                        painter.hline(rect.x_range(), y, drop_line_stroke);
+                    }
+
+                    if let Some(payload) = response.dnd_release_payload::<QueueDragPayload>()
+                        && let Some(pointer) = response.hover_pos()
+                    {
+                        let destination = if pointer.y < response.rect.center().y { index } else { index + 1 };
+                        action = Some(QueueAction::MoveTo(vec![payload.index], destination));
+                    }
                 });
             });
 
-        if let Some(index) = to_be_removed {
-            remove_from_queue(player, index);
+        update_column_layout(&mut gem.ui.queue.columns, &visible_columns, &column_widths, column_edit);
+
+        if let Some(action) = action {
+            let player = &mut gem.player;
+
+            match action {
+                QueueAction::MoveTo(indices, position) => move_tracks_to_position(player, &indices, position),
+                QueueAction::MoveToIndex(indices, index) => move_tracks_to_index(player, &indices, index),
+                QueueAction::Remove(mut indices) => {
+                    // Remove from the back so that the remaining indices stay valid.
+                    indices.sort_unstable();
+                    indices.dedup();
+                    for index in indices.into_iter().rev() {
+                        remove_from_queue(player, index);
+                    }
+                }
+            }
+
+            gem.ui.queue.selected_indices.clear(); // The indices no longer point to the same tracks.
+        }
+    });
+}
+
+enum QueueAction {
+    MoveTo(Vec<usize>, usize),      // The position is where the tracks go in the queue as it is now.
+    MoveToIndex(Vec<usize>, usize), // The position is where the first of the tracks ends up once they have moved.
+    Remove(Vec<usize>),
+}
+
+fn queue_context_menu(ui: &mut Ui, state: &mut QueueViewState, queue_length: usize) -> Option<QueueAction> {
+    let indices = &state.selected_indices;
+
+    let modal_width = 220.0;
+    ui.set_width(modal_width);
+
+    ui.add_enabled(false, Label::new(format!("{} track(s) selected", indices.len())));
+
+    ui.separator();
+
+    let mut action = None;
+
+    if ui.button(("Play After Current", ICON_ARROW_UPWARD)).clicked() {
+        action = Some(QueueAction::MoveTo(indices.clone(), 0));
+    }
+
+    if ui.button(("Move to End", ICON_ARROW_DOWNWARD)).clicked() {
+        action = Some(QueueAction::MoveTo(indices.clone(), queue_length));
+    }
+
+    ui.horizontal(|ui| {
+        let move_clicked = ui.button(("Move to Position", ICON_FORMAT_LIST_NUMBERED)).clicked();
+
+        let position_input = DragValue::new(&mut state.target_position).range(1..=queue_length);
+        ui.add(position_input);
+
+        if move_clicked {
+            // Positions are shown starting at 1.
+            action = Some(QueueAction::MoveToIndex(indices.clone(), state.target_position.saturating_sub(1)));
+            ui.close();
         }
+    });
+
+    ui.separator();
+
+    if ui.button(("Remove from Queue", ICON_CLOSE)).clicked() {
+        action = Some(QueueAction::Remove(indices.clone()));
+    }
 
-        if let Some(index) = to_be_moved_to_front {
-            move_to_position(player, index, 0);
+    action
+}
+
+fn save_queue_as_playlist(gem: &mut GemPlayer) {
+    let Some(directory) = &gem.library_directory else {
+        return;
+    };
+
+    // Use the first free name so that repeated saves do not collide.
+    let name = (1..)
+        .map(|n| format!("Queue {}", n))
+        .find(|name| !gem.playlists.iter().any(|p| &p.name == name))
+        .expect("There is always a free name");
+
+    // Playlists cannot contain duplicates, so only the first occurrence of each track is kept.
+    let mut tracks: Vec<Track> = Vec::new();
+    for track in &gem.player.queue {
+        if !tracks.contains(track) {
+            tracks.push(track.clone());
         }
+    }
+
+    let result = create(name, directory).and_then(|mut playlist| {
+        playlist.tracks = tracks;
+        save_to_file(&mut playlist)?;
+        Ok(playlist)
     });
+
+    match result {
+        Ok(playlist) => {
+            let message = format!("Saved the queue as '{}'.", playlist.name);
+            info!("{}", message);
+            gem.ui.toasts.success(message);
+
+            gem.playlists.push(playlist);
+        }
+        Err(e) => {
+            let message = format!("Failed to save the queue as a playlist: {}", e);
+            error!("{}", message);
+            gem.ui.toasts.error(message);
+        }
+    }
 }
@@ -1,31 +1,48 @@
-use std::time::Duration;
+use std::{collections::HashSet, mem::take, path::PathBuf, time::Duration};
 
-use egui::{Align, CentralPanel, Color32, Frame, Label, Layout, RichText, Separator, ThemePreference, Ui, WidgetText};
+use egui::{Align, Button, CentralPanel, Color32, Frame, Label, Layout, RichText, Separator, ThemePreference, Ui, WidgetText};
 use egui_extras::{Size, StripBuilder};
-use egui_material_icons::icons::{ICON_LIBRARY_MUSIC, ICON_QUEUE_MUSIC, ICON_SETTINGS, ICON_STAR};
+use egui_material_icons::icons::{
+    ICON_ALBUM, ICON_HISTORY, ICON_LIBRARY_MUSIC, ICON_PLAY_CIRCLE, ICON_QUEUE_MUSIC, ICON_SETTINGS, ICON_STAR, ICON_STAR_BORDER,
+    ICON_STAR_HALF,
+};
 use egui_notify::Toasts;
 use fully_pub::fully_pub;
 use strum_macros::EnumIter;
 
 use crate::{
-    GemPlayer,
+    GemPlayer, SELECT_ALL_SHORTCUT,
+    commands::GemCommand,
+    player::{Player, get_position},
+    track::{MAX_RATING, Track},
     ui::{
+        albums_view::{AlbumsViewState, albums_view},
+        ambient_view::{AmbientState, ambient_view},
         bottom_bar::bottom_bar,
         control_panel::control_panel,
+        duplicates_window::{DuplicatesState, duplicates_window},
         file_drop_overlay::file_drop_overlay,
-        library_view::{LibraryViewState, library_view},
+        history_view::history_view,
+        itunes_import_window::{ItunesImportState, itunes_import_window},
+        library_view::{LibraryViewState, library_view, track_drag_preview},
+        lyrics_panel::lyrics_panel,
+        now_playing_view::now_playing_view,
         playlist_view::{PlaylistsViewState, playlists_view},
-        queue_view::queue_view,
+        queue_view::{QueueViewState, queue_view},
         settings_view::settings_view,
-        widgets::marquee::Marquee,
+        type_to_jump::TypeToJumpState,
+        widgets::{level_meter::LevelMeter, marquee::Marquee, spectrogram::Spectrogram},
     },
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, EnumIter, Copy)]
 pub enum View {
     Library,
+    Albums,
     Playlists,
+    NowPlaying,
     Queue,
+    History,
     Settings,
 }
 
@@ -33,27 +50,95 @@ impl View {
     pub fn icon(&self) -> &'static str {
         match self {
             View::Library => ICON_LIBRARY_MUSIC.codepoint,
+            View::Albums => ICON_ALBUM.codepoint,
             View::Queue => ICON_QUEUE_MUSIC.codepoint,
+            View::History => ICON_HISTORY.codepoint,
             View::Playlists => ICON_STAR.codepoint,
+            View::NowPlaying => ICON_PLAY_CIRCLE.codepoint,
             View::Settings => ICON_SETTINGS.codepoint,
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            View::Library => "Library",
+            View::Albums => "Albums",
+            View::Playlists => "Playlists",
+            View::NowPlaying => "Now Playing",
+            View::Queue => "Queue",
+            View::History => "History",
+            View::Settings => "Settings",
+        }
+    }
+}
+
+pub const MIN_ZOOM: f32 = 0.8;
+pub const MAX_ZOOM: f32 = 1.5;
+
+const ROW_HEIGHT: f32 = 26.0;
+const LARGE_ROW_HEIGHT: f32 = 34.0;
+
+pub fn row_height(large_rows: bool) -> f32 {
+    if large_rows { LARGE_ROW_HEIGHT } else { ROW_HEIGHT }
 }
 
 #[fully_pub]
 pub struct UIState {
     current_view: View,
     theme_preference: ThemePreference,
+    reduce_animations: bool,
+    zoom: f32,                 // Applied to the whole window. Between MIN_ZOOM and MAX_ZOOM.
+    applied_zoom: Option<f32>, // What egui's zoom was last set to. None until the zoom is first applied.
+    large_rows: bool,          // Taller rows in the track tables.
+    show_title_bar_info: bool,
+    show_lyrics: bool,
+    lyrics_line: Option<usize>, // The synced lyrics line that was last highlighted.
     marquee: Marquee,
+    spectrogram: Spectrogram,
+    level_meter: LevelMeter,
     search: String,
     volume_popup_is_open: bool,
+    exclusion_buffer: String, // The scan exclusion rule being typed in the settings.
 
     library: LibraryViewState,
+    albums: AlbumsViewState,
     playlists: PlaylistsViewState,
+    queue: QueueViewState,
+    locate_playing: LocatePlayingState,
+    ambient: AmbientState,
+    type_to_jump: TypeToJumpState,
+    duplicates: DuplicatesState,
+    itunes_import: ItunesImportState,
 
     toasts: Toasts,
 }
 
+// Set by GemCommand::LocatePlayingTrack. The table showing the playing track scrolls to it once and then flashes its row.
+#[fully_pub]
+struct LocatePlayingState {
+    scroll_pending: bool,
+    flash_started: Option<f64>,
+}
+
+const LOCATE_FLASH_DURATION: f64 = 1.2;
+const LOCATE_FLASH_INTERVAL: f64 = 0.2;
+
+// Whether the located row should currently be drawn highlighted.
+pub fn locate_flash_is_on(ui: &Ui, state: &mut LocatePlayingState) -> bool {
+    let Some(started) = state.flash_started else {
+        return false;
+    };
+
+    let elapsed = ui.input(|i| i.time) - started;
+    if elapsed >= LOCATE_FLASH_DURATION {
+        state.flash_started = None;
+        return false;
+    }
+
+    ui.ctx().request_repaint();
+    ((elapsed / LOCATE_FLASH_INTERVAL) as u32).is_multiple_of(2)
+}
+
 pub fn gem_player_ui(ui: &mut Ui, gem: &mut GemPlayer) {
     CentralPanel::default()
         .frame(Frame::NONE.fill(ui.style().visuals.window_fill()))
@@ -65,6 +150,11 @@ pub fn gem_player_ui(ui: &mut Ui, gem: &mut GemPlayer) {
                 return;
             }
 
+            if gem.ui.ambient.enabled {
+                ambient_view(ui, gem);
+                return;
+            }
+
             let titlebar_ui_height = 32.0;
             let control_ui_height = 80.0;
             let navigation_ui_height = 32.0;
@@ -79,7 +169,12 @@ pub fn gem_player_ui(ui: &mut Ui, gem: &mut GemPlayer) {
                 .size(Size::exact(separator_space))
                 .size(Size::exact(navigation_ui_height))
                 .vertical(|mut strip| {
-                    strip.cell(title_bar);
+                    let title = if gem.ui.show_title_bar_info {
+                        title_bar_info(&gem.player)
+                    } else {
+                        None
+                    };
+                    strip.cell(|ui| title_bar(ui, title));
 
                     strip.cell(|ui| {
                         ui.add(Separator::default().spacing(separator_space));
@@ -91,11 +186,26 @@ pub fn gem_player_ui(ui: &mut Ui, gem: &mut GemPlayer) {
                         ui.add(Separator::default().spacing(separator_space));
                     });
 
-                    strip.cell(|ui| match gem.ui.current_view {
-                        View::Library => library_view(ui, gem),
-                        View::Queue => queue_view(ui, &mut gem.player),
-                        View::Playlists => playlists_view(ui, gem),
-                        View::Settings => settings_view(ui, gem),
+                    strip.cell(|ui| {
+                        if !gem.ui.show_lyrics {
+                            current_view(ui, gem);
+                            return;
+                        }
+
+                        let lyrics_panel_width = 320.0;
+                        StripBuilder::new(ui)
+                            .size(Size::remainder())
+                            .size(Size::exact(separator_space))
+                            .size(Size::exact(lyrics_panel_width))
+                            .horizontal(|mut strip| {
+                                strip.cell(|ui| current_view(ui, gem));
+
+                                strip.cell(|ui| {
+                                    ui.add(Separator::default().vertical().spacing(separator_space));
+                                });
+
+                                strip.cell(|ui| lyrics_panel(ui, gem));
+                            });
                     });
 
                     strip.cell(|ui| {
@@ -104,14 +214,43 @@ pub fn gem_player_ui(ui: &mut Ui, gem: &mut GemPlayer) {
 
                     strip.cell(|ui| bottom_bar(ui, gem));
                 });
+
+            track_drag_preview(ui);
         });
+
+    duplicates_window(ui.ctx(), gem);
+    itunes_import_window(ui.ctx(), gem);
+}
+
+fn current_view(ui: &mut Ui, gem: &mut GemPlayer) {
+    match gem.ui.current_view {
+        View::Library => library_view(ui, gem),
+        View::Albums => albums_view(ui, gem),
+        View::Queue => queue_view(ui, gem),
+        View::History => history_view(ui, gem),
+        View::Playlists => playlists_view(ui, gem),
+        View::NowPlaying => now_playing_view(ui, gem),
+        View::Settings => settings_view(ui, gem),
+    }
+}
+
+// The playing track's title followed by the time left until the queue runs out.
+fn title_bar_info(player: &Player) -> Option<String> {
+    let track = player.playing.as_ref()?;
+
+    let position = get_position(player).unwrap_or_default();
+    let queued: Duration = player.queue.iter().map(|t| t.duration).sum();
+    let remaining = track.duration.saturating_sub(position) + queued;
+
+    let title = track.title.as_deref().unwrap_or("Unknown Title");
+    Some(format!("{} • {} remaining", title, format_duration_to_hhmmss(remaining)))
 }
 
-fn title_bar(ui: &mut Ui) {
+fn title_bar(ui: &mut Ui, title: Option<String>) {
     #[cfg(target_os = "macos")]
     let layout = Layout::left_to_right(Align::Center);
 
-    #[cfg(target_os = "windows")]
+    #[cfg(not(target_os = "macos"))]
     let layout = Layout::right_to_left(Align::Center);
 
     ui.with_layout(layout, |ui| {
@@ -123,6 +262,11 @@ fn title_bar(ui: &mut Ui) {
         let beta = RichText::new("BETA").italics().color(ui.visuals().weak_text_color());
         ui.add(unselectable_label(beta));
 
+        if let Some(title) = title {
+            ui.add_space(16.0);
+            ui.add(unselectable_label(title).truncate());
+        }
+
         #[cfg(debug_assertions)]
         {
             ui.add_space(16.0);
@@ -137,6 +281,65 @@ pub fn unselectable_label(text: impl Into<WidgetText>) -> Label {
     Label::new(text).selectable(false)
 }
 
+// A rating drawn as filled and empty stars, e.g. 3 is ★★★☆☆.
+pub fn rating_stars(rating: u8) -> String {
+    let filled = ICON_STAR.codepoint.repeat(rating as usize);
+    let empty = ICON_STAR_BORDER.codepoint.repeat(MAX_RATING.saturating_sub(rating) as usize);
+    format!("{}{}", filled, empty)
+}
+
+// Lets the user rate the given tracks.
+pub fn rating_menu(ui: &mut Ui, track_keys: &[PathBuf]) -> Option<GemCommand> {
+    let mut maybe_command = None;
+
+    ui.menu_button(("Rating", ICON_STAR_HALF), |ui| {
+        for rating in (0..=MAX_RATING).rev() {
+            let label = if rating == 0 {
+                "No Rating".to_owned()
+            } else {
+                rating_stars(rating)
+            };
+            if ui.button(label).clicked() {
+                maybe_command = Some(GemCommand::SetRating {
+                    track_keys: track_keys.to_vec(),
+                    rating,
+                });
+            }
+        }
+    });
+
+    maybe_command
+}
+
+// Select All and Invert Selection for the track tables. The commands apply to the table in the current view.
+pub fn selection_menu_items(ui: &mut Ui) -> Option<GemCommand> {
+    let mut maybe_command = None;
+
+    let shortcut_text = ui.ctx().format_shortcut(&SELECT_ALL_SHORTCUT);
+    if ui.add(Button::new("Select All").shortcut_text(shortcut_text)).clicked() {
+        maybe_command = Some(GemCommand::SelectAllTracks);
+    }
+
+    if ui.button("Invert Selection").clicked() {
+        maybe_command = Some(GemCommand::InvertTrackSelection);
+    }
+
+    maybe_command
+}
+
+pub fn select_all_tracks(selected_tracks: &mut Vec<PathBuf>, tracks: &[Track]) {
+    *selected_tracks = tracks.iter().map(|t| t.path.clone()).collect();
+}
+
+pub fn invert_track_selection(selected_tracks: &mut Vec<PathBuf>, tracks: &[Track]) {
+    let previously_selected: HashSet<PathBuf> = take(selected_tracks).into_iter().collect();
+    *selected_tracks = tracks
+        .iter()
+        .filter(|t| !previously_selected.contains(&t.path))
+        .map(|t| t.path.clone())
+        .collect();
+}
+
 pub fn table_label(text: impl Into<String>, color: Option<Color32>) -> Label {
     let mut rich = RichText::new(text.into());
     if let Some(c) = color {
@@ -1,13 +1,35 @@
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
-use egui::{Frame, RichText, ScrollArea, Separator, ThemePreference, Ui, epaint::MarginF32};
-use egui_material_icons::icons::ICON_FOLDER_OPEN;
-use log::info;
+use egui::{
+    Button, ComboBox, DragValue, Frame, Key, ProgressBar, RichText, ScrollArea, Separator, Slider, TextEdit, ThemePreference, Ui,
+    epaint::MarginF32,
+};
+use egui_material_icons::icons::{ICON_ADD, ICON_CLOSE, ICON_FOLDER_OPEN};
+use log::{error, info};
+use strum::IntoEnumIterator;
 
 use crate::{
-    APP_NAME, GemPlayer,
+    APP_NAME, GemPlayer, MAX_BOOKMARK_THRESHOLD, MAX_CROSSFADE, MAX_PAUSE_FADE, MAX_REWIND_THRESHOLD, ReplayGainMode,
+    SEEK_BACKWARD_SHORTCUT, SEEK_FORWARD_SHORTCUT, SEEK_STEPS, ShuffleMode,
+    duration_scanner::{start_duration_scan, stop_duration_scan},
+    library_exporter::spawn_library_exporter,
     library_folder_picker::spawn_library_folder_picker,
-    ui::{root::unselectable_label, widgets::toggle_switch::toggle},
+    library_watcher::LibraryWatcherCommand,
+    loudness::{MAX_TARGET_LOUDNESS, MIN_TARGET_LOUDNESS},
+    loudness_scanner::start_loudness_scan,
+    player::{ResamplerQuality, current_output_settings, reopen_audio_backend},
+    remote_control::{remote_url, restart_remote_control},
+    track::{DEFAULT_RATING_EMAIL, set_rating_email},
+    ui::{
+        duplicates_window::start_duplicate_scan,
+        itunes_import_window::start_itunes_import,
+        root::{MAX_ZOOM, MIN_ZOOM, unselectable_label},
+        widgets::{
+            spectrogram::{self, ColorMap},
+            toggle_switch::toggle,
+        },
+    },
+    visualizer::{SMOOTHING_RANGE, VisualizerStyle, set_meter_enabled, set_visualizer_enabled, set_visualizer_style},
 };
 
 pub fn settings_view(ui: &mut Ui, gem: &mut GemPlayer) {
@@ -19,7 +41,9 @@ pub fn settings_view(ui: &mut Ui, gem: &mut GemPlayer) {
 
                 ui.add(unselectable_label(RichText::new("Music Library Path").heading()));
                 ui.add_space(8.0);
-                ui.add(unselectable_label("Playlists are also stored here as m3u files."));
+                ui.add(unselectable_label(
+                    "Playlists are also stored here as m3u, m3u8, pls, or xspf files.",
+                ));
                 ui.horizontal(|ui| {
                     let (display_path, full_path) = match gem.library_directory.as_ref() {
                         Some(p) => (elide_path(p, 80), p.to_string_lossy().to_string()),
@@ -36,6 +60,176 @@ pub fn settings_view(ui: &mut Ui, gem: &mut GemPlayer) {
                     }
                 });
 
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    if ui.add(toggle(&mut gem.reference_dropped_files)).changed() {
+                        info!(
+                            "Referencing dropped files is now {}.",
+                            if gem.reference_dropped_files { "enabled" } else { "disabled" }
+                        );
+                    }
+                    ui.add(unselectable_label(
+                        "Reference dropped files in place instead of copying them into the library.",
+                    ));
+                });
+
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    ui.add(unselectable_label("Use mp3 ratings of"));
+                    let response = ui
+                        .add(
+                            TextEdit::singleline(&mut gem.rating_email)
+                                .hint_text(DEFAULT_RATING_EMAIL)
+                                .desired_width(200.0),
+                        )
+                        .on_hover_text(
+                            "The player whose rating is read from and written to ID3 tags, e.g. \"MusicBee\". \
+                             Other formats use the 0 to 100 RATING field that most players share.",
+                        );
+
+                    // Tracks that are already loaded keep their rating until they are read again.
+                    if response.changed() {
+                        set_rating_email(&gem.rating_email);
+                    }
+                });
+
+                ui.add_space(8.0);
+
+                let is_scanning = gem.ui.duplicates.scan_receiver.is_some();
+                let find_duplicates_button = ui
+                    .add_enabled(!is_scanning, Button::new("Find Duplicates…"))
+                    .on_hover_text("Look for tracks with identical files, or the same title, artist, and length");
+                if find_duplicates_button.clicked() {
+                    start_duplicate_scan(gem);
+                }
+
+                ui.add_space(8.0);
+
+                let is_importing = gem.ui.itunes_import.receiver.is_some();
+                let import_enabled = !is_importing && gem.library_directory.is_some();
+                let import_button = ui
+                    .add_enabled(import_enabled, Button::new("Import iTunes Library…"))
+                    .on_hover_text("Import playlists, ratings, and play counts from an iTunes or Apple Music Library.xml")
+                    .on_disabled_hover_text(if is_importing {
+                        "An import is already running"
+                    } else {
+                        "Choose a library folder first"
+                    });
+                if import_button.clicked() {
+                    start_itunes_import(gem);
+                }
+
+                ui.add_space(8.0);
+
+                match &gem.library_exporter {
+                    Some(exporter) => match exporter.progress {
+                        Some((done, total)) => {
+                            let fraction = done as f32 / total.max(1) as f32;
+                            ui.add(ProgressBar::new(fraction).text(format!("Exporting… {} / {}", done, total)));
+                        }
+                        None => {
+                            ui.add(unselectable_label("Choosing where to export the library…"));
+                        }
+                    },
+                    None => {
+                        let export_button = ui
+                            .add_enabled(!gem.library.is_empty(), Button::new("Export Library…"))
+                            .on_hover_text("Save the tracks with their ratings and play counts as a CSV or JSON file")
+                            .on_disabled_hover_text("The library is empty");
+                        if export_button.clicked() {
+                            gem.library_exporter = Some(spawn_library_exporter(ui.ctx(), gem.library.clone(), gem.play_counts.clone()));
+                        }
+                    }
+                }
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Excluded From Library").heading()));
+                ui.add_space(8.0);
+                ui.add(unselectable_label(
+                    "Folders and files matching these names are skipped, e.g. \"Audiobooks\" or \".*\". \
+                     Rules with a slash match paths in the library, e.g. \"Podcasts/Old*\".",
+                ));
+                ui.add_space(8.0);
+
+                let mut exclusions_changed = false;
+                let mut removed_index = None;
+
+                for (i, rule) in gem.scan_exclusions.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.button(ICON_CLOSE).on_hover_text("Remove").clicked() {
+                            removed_index = Some(i);
+                        }
+                        ui.add_space(4.0);
+                        ui.add(unselectable_label(rule));
+                    });
+                }
+
+                if let Some(i) = removed_index {
+                    let rule = gem.scan_exclusions.remove(i);
+                    info!("Removed scan exclusion: {}", rule);
+                    exclusions_changed = true;
+                }
+
+                ui.horizontal(|ui| {
+                    let response = ui.add(TextEdit::singleline(&mut gem.ui.exclusion_buffer).hint_text("Folder name or pattern"));
+                    let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+
+                    let rule = gem.ui.exclusion_buffer.trim().to_owned();
+                    let is_valid = !rule.is_empty() && !gem.scan_exclusions.contains(&rule);
+
+                    ui.add_space(4.0);
+                    let add_clicked = ui.add_enabled(is_valid, Button::new(ICON_ADD)).on_hover_text("Add").clicked();
+
+                    if is_valid && (add_clicked || submitted) {
+                        info!("Added scan exclusion: {}", rule);
+                        gem.scan_exclusions.push(rule);
+                        gem.ui.exclusion_buffer.clear();
+                        exclusions_changed = true;
+                    }
+                });
+
+                if exclusions_changed {
+                    let command = LibraryWatcherCommand::SetExclusions(gem.scan_exclusions.clone());
+                    if let Err(e) = gem.library_watcher.command_sender.send(command) {
+                        error!("Failed to update scan exclusions: {e}");
+                    }
+                }
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Accurate Durations").heading()));
+                ui.add_space(8.0);
+                ui.add(unselectable_label(
+                    "Some files, such as VBR mp3s without a header, report the wrong length. \
+                     Measure them in the background instead.",
+                ));
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    let response = ui.add(toggle(&mut gem.accurate_durations));
+                    ui.add(unselectable_label("Measure track durations"));
+
+                    if response.changed() {
+                        if gem.accurate_durations {
+                            start_duration_scan(gem);
+                        } else {
+                            stop_duration_scan(gem);
+                        }
+                    }
+                });
+
+                if let Some(scanner) = &gem.duration_scanner {
+                    ui.add_space(4.0);
+
+                    let (done, total) = scanner.progress;
+                    let fraction = done as f32 / total.max(1) as f32;
+                    ui.add(ProgressBar::new(fraction).text(format!("Measuring… {} / {}", done, total)));
+                    ui.ctx().request_repaint_after(Duration::from_millis(250));
+                }
+
                 ui.add(Separator::default().spacing(divider_spacing));
 
                 ui.add(unselectable_label(RichText::new("Theme").heading()));
@@ -43,6 +237,319 @@ pub fn settings_view(ui: &mut Ui, gem: &mut GemPlayer) {
 
                 ThemePreference::radio_buttons(&mut gem.ui.theme_preference, ui);
 
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    ui.add(toggle(&mut gem.ui.reduce_animations));
+                    ui.add(unselectable_label("Reduce animations"));
+                });
+
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    ui.add(toggle(&mut gem.ui.show_title_bar_info));
+                    ui.add(unselectable_label(
+                        "Show the playing track and queue time remaining in the title bar",
+                    ));
+                });
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Display").heading()));
+                ui.add_space(8.0);
+
+                let zoom_slider = Slider::new(&mut gem.ui.zoom, MIN_ZOOM..=MAX_ZOOM)
+                    .step_by(0.05)
+                    .text("Zoom")
+                    .custom_formatter(|zoom, _| format!("{:.0}%", zoom * 100.0))
+                    .custom_parser(|text| text.trim().trim_end_matches('%').parse::<f64>().ok().map(|percent| percent / 100.0));
+                ui.add(zoom_slider);
+
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    ui.add(toggle(&mut gem.ui.large_rows));
+                    ui.add(unselectable_label("Larger rows in the track tables"));
+                });
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Previous Track Threshold").heading()));
+                ui.add_space(8.0);
+                ui.add(unselectable_label(
+                    "Pressing previous near the start of a track plays the previous one. Otherwise, the track restarts.",
+                ));
+                ui.add(unselectable_label("Set to 0 to always play the previous track."));
+                ui.add_space(8.0);
+
+                let threshold_slider = Slider::new(&mut gem.player.rewind_threshold, 0.0..=MAX_REWIND_THRESHOLD)
+                    .step_by(1.0)
+                    .suffix(" s");
+                ui.add(threshold_slider);
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Seek Step").heading()));
+                ui.add_space(8.0);
+                let seek_backward = ui.ctx().format_shortcut(&SEEK_BACKWARD_SHORTCUT);
+                let seek_forward = ui.ctx().format_shortcut(&SEEK_FORWARD_SHORTCUT);
+                ui.add(unselectable_label(format!(
+                    "How far {} and {} seek backward and forward in the playing track.",
+                    seek_backward, seek_forward
+                )));
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    for step in SEEK_STEPS {
+                        ui.radio_value(&mut gem.player.seek_step, step, format!("{} s", step));
+                    }
+                });
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Resume Playback").heading()));
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    ui.add(toggle(&mut gem.resume_on_startup));
+                    ui.add(unselectable_label("Continue where you left off when Gem Player starts"));
+                });
+
+                ui.add_space(8.0);
+                ui.add(unselectable_label(
+                    "Long tracks such as audiobooks, mixes, and podcasts continue from where they were last stopped.",
+                ));
+                ui.add(unselectable_label("Set to 0 to always play tracks from the start."));
+                ui.add_space(8.0);
+
+                let bookmark_slider = Slider::new(&mut gem.bookmarks.threshold, 0.0..=MAX_BOOKMARK_THRESHOLD)
+                    .step_by(5.0)
+                    .text("Minimum length")
+                    .suffix(" min");
+                ui.add(bookmark_slider);
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Shuffle").heading()));
+                ui.add_space(8.0);
+
+                for mode in ShuffleMode::iter() {
+                    ui.radio_value(&mut gem.player.shuffle_mode, mode, mode.label());
+                }
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Volume Normalization").heading()));
+                ui.add_space(8.0);
+                ui.add(unselectable_label(
+                    "Play tracks at a consistent loudness using their ReplayGain tags.",
+                ));
+                ui.add(unselectable_label(
+                    "Measured Loudness analyzes each track (EBU R128), so it also works for tracks without tags.",
+                ));
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    for mode in ReplayGainMode::iter() {
+                        let response = ui.radio_value(&mut gem.player.replay_gain_mode, mode, mode.label());
+                        if response.changed() && mode == ReplayGainMode::Loudness {
+                            start_loudness_scan(gem);
+                        }
+                    }
+                });
+
+                if gem.player.replay_gain_mode == ReplayGainMode::Loudness {
+                    ui.add_space(8.0);
+
+                    let target_slider = Slider::new(&mut gem.player.target_loudness, MIN_TARGET_LOUDNESS..=MAX_TARGET_LOUDNESS)
+                        .step_by(1.0)
+                        .text("Target loudness")
+                        .suffix(" LUFS");
+                    ui.add(target_slider);
+
+                    ui.add_space(4.0);
+
+                    match &gem.loudness_scanner {
+                        Some(scanner) => {
+                            let (done, total) = scanner.progress;
+                            let fraction = done as f32 / total.max(1) as f32;
+                            ui.add(ProgressBar::new(fraction).text(format!("Measuring… {} / {}", done, total)));
+                            ui.ctx().request_repaint_after(Duration::from_millis(250));
+                        }
+                        None => {
+                            let measured = gem.library.iter().filter(|t| gem.player.loudness.get(t).is_some()).count();
+                            let text = format!("{} of {} tracks measured.", measured, gem.library.len());
+                            ui.add(unselectable_label(RichText::new(text).small().weak()));
+                        }
+                    }
+                }
+
+                ui.add(unselectable_label(
+                    RichText::new("Changes apply from the next track.").small().weak(),
+                ));
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Crossfade").heading()));
+                ui.add_space(8.0);
+                ui.add(unselectable_label("Fade out the end of each track while the next one fades in."));
+                ui.add(unselectable_label("Set to 0 to disable crossfading."));
+                ui.add_space(8.0);
+
+                let crossfade_slider = Slider::new(&mut gem.player.crossfade, 0.0..=MAX_CROSSFADE)
+                    .step_by(1.0)
+                    .suffix(" s");
+                ui.add(crossfade_slider);
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Fade on Pause").heading()));
+                ui.add_space(8.0);
+                ui.add(unselectable_label(
+                    "Fade out when pausing or stopping, and fade back in when resuming.",
+                ));
+                ui.add(unselectable_label("Set to 0 to pause and resume instantly."));
+                ui.add_space(8.0);
+
+                let pause_fade_slider = Slider::new(&mut gem.player.pause_fade, 0.0..=MAX_PAUSE_FADE)
+                    .step_by(0.05)
+                    .suffix(" s");
+                ui.add(pause_fade_slider);
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Low Latency Output").heading()));
+                ui.add_space(8.0);
+                ui.add(unselectable_label(
+                    "Use a small output buffer so that pausing, seeking, and volume changes are heard sooner.",
+                ));
+                ui.add(unselectable_label(
+                    "Some devices don't support this and may crackle or refuse. Other apps can still play sound.",
+                ));
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    let response = ui.add(toggle(&mut gem.player.low_latency));
+                    ui.add(unselectable_label("Low latency mode"));
+
+                    if response.changed() {
+                        match reopen_audio_backend(&mut gem.player) {
+                            Ok(()) => {
+                                let refused = gem.player.low_latency && gem.player.backend.as_ref().is_some_and(|b| !b.low_latency);
+                                if refused {
+                                    gem.ui
+                                        .toasts
+                                        .warning("The output device refused low latency mode. Using the default buffer size.");
+                                }
+                            }
+                            Err(e) => {
+                                error!("Failed to reopen the output device: {:#}", e);
+                                gem.ui.toasts.error("Failed to reopen the output device.");
+                            }
+                        }
+                    }
+                });
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                output_format_settings(ui, gem);
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Visualizer").heading()));
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    let mut enabled = gem.player.visualizer.pipeline.is_some();
+                    if ui.add(toggle(&mut enabled)).changed() {
+                        set_visualizer_enabled(&mut gem.player.visualizer, enabled);
+                    }
+                    ui.add(unselectable_label("Show the visualizer"));
+                });
+                ui.add(unselectable_label(
+                    RichText::new("Turning it off saves battery. Turning it back on takes effect from the next track.").weak(),
+                ));
+
+                if gem.player.visualizer.pipeline.is_some() {
+                    ui.add_space(8.0);
+
+                    let mut style = gem.player.visualizer.style;
+                    ui.horizontal(|ui| {
+                        for option in VisualizerStyle::iter() {
+                            ui.radio_value(&mut style, option, option.label());
+                        }
+                    });
+                    if style != gem.player.visualizer.style {
+                        set_visualizer_style(&mut gem.player.visualizer, style);
+                    }
+
+                    ui.add_space(8.0);
+
+                    ui.horizontal(|ui| {
+                        let mut meter_enabled = gem.player.visualizer.meter_enabled;
+                        if ui.add(toggle(&mut meter_enabled)).changed() {
+                            set_meter_enabled(&mut gem.player.visualizer, meter_enabled);
+                        }
+                        ui.add(unselectable_label(
+                            "Show a level meter with a clip indicator next to the volume control",
+                        ));
+                    });
+
+                    if style.band_count() > 0 {
+                        ui.horizontal(|ui| {
+                            ui.add(toggle(&mut gem.player.visualizer.peak_hold));
+                            ui.add(unselectable_label("Peak hold markers"));
+                        });
+
+                        let (min, max) = SMOOTHING_RANGE;
+                        let attack_slider = Slider::new(&mut gem.player.visualizer.attack, min..=max)
+                            .step_by(1.0)
+                            .text("Attack");
+                        ui.add(attack_slider)
+                            .on_hover_text("How quickly the visualizer rises to louder levels.");
+
+                        let decay_slider = Slider::new(&mut gem.player.visualizer.decay, min..=max).step_by(1.0).text("Decay");
+                        ui.add(decay_slider)
+                            .on_hover_text("How quickly the visualizer falls back to quieter levels.");
+                    }
+
+                    if style == VisualizerStyle::Spectrogram {
+                        let mut changed = false;
+
+                        ui.add_space(8.0);
+
+                        let floor_slider = Slider::new(
+                            &mut gem.ui.spectrogram.floor_db,
+                            spectrogram::MIN_DB..=gem.ui.spectrogram.ceiling_db - 10.0,
+                        )
+                        .step_by(1.0)
+                        .text("Floor")
+                        .suffix(" dB");
+                        changed |= ui.add(floor_slider).changed();
+
+                        let ceiling_slider = Slider::new(
+                            &mut gem.ui.spectrogram.ceiling_db,
+                            gem.ui.spectrogram.floor_db + 10.0..=spectrogram::MAX_DB,
+                        )
+                        .step_by(1.0)
+                        .text("Ceiling")
+                        .suffix(" dB");
+                        changed |= ui.add(ceiling_slider).changed();
+
+                        ui.horizontal(|ui| {
+                            for color_map in ColorMap::iter() {
+                                changed |= ui
+                                    .radio_value(&mut gem.ui.spectrogram.color_map, color_map, color_map.label())
+                                    .changed();
+                            }
+                        });
+
+                        if changed {
+                            gem.ui.spectrogram.invalidate();
+                        }
+                    }
+                }
+
                 ui.add(Separator::default().spacing(divider_spacing));
 
                 ui.add(unselectable_label(RichText::new("Sleep Mode Blocker").heading()));
@@ -67,6 +574,97 @@ pub fn settings_view(ui: &mut Ui, gem: &mut GemPlayer) {
 
                 ui.add(Separator::default().spacing(divider_spacing));
 
+                ui.add(unselectable_label(RichText::new("Pause When Unfocused").heading()));
+                ui.add_space(8.0);
+                ui.add(unselectable_label("Pauses playback when the window loses focus."));
+                ui.add_space(8.0);
+
+                if ui.add(toggle(&mut gem.focus_pause.pause_on_focus_loss)).changed() {
+                    info!(
+                        "Pause on focus loss is now {}.",
+                        if gem.focus_pause.pause_on_focus_loss {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        }
+                    );
+                }
+
+                ui.add_space(8.0);
+
+                ui.add_enabled_ui(gem.focus_pause.pause_on_focus_loss, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(toggle(&mut gem.focus_pause.resume_on_focus_gain));
+                        ui.add(unselectable_label("Resume playback when the window regains focus."));
+                    });
+                });
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Notifications").heading()));
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    ui.add(toggle(&mut gem.track_notifications));
+                    ui.add(unselectable_label(
+                        "Show a notification when the track changes while the window is unfocused",
+                    ));
+                });
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
+                ui.add(unselectable_label(RichText::new("Remote Control").heading()));
+                ui.add_space(8.0);
+                ui.add(unselectable_label(
+                    "Control playback over HTTP from scripts and home automation tools.",
+                ));
+                ui.add(unselectable_label(
+                    "A web remote is served too, e.g. for a phone on the same network.",
+                ));
+                ui.add_space(8.0);
+
+                let mut settings_changed = false;
+
+                ui.horizontal(|ui| {
+                    settings_changed |= ui.add(toggle(&mut gem.remote_control.enabled)).changed();
+                    ui.add(unselectable_label("Enable the remote control server"));
+                });
+
+                ui.add_space(8.0);
+
+                ui.add_enabled_ui(gem.remote_control.enabled, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(unselectable_label("Port"));
+                        ui.add_space(8.0);
+
+                        // Only restart once the port is done being edited, not on every step of a drag.
+                        let port_input = DragValue::new(&mut gem.remote_control.port).range(1024..=65535);
+                        let response = ui.add(port_input);
+                        settings_changed |= response.lost_focus() || response.drag_stopped();
+                    });
+
+                    ui.add_space(8.0);
+
+                    ui.horizontal(|ui| {
+                        settings_changed |= ui.add(toggle(&mut gem.remote_control.allow_network)).changed();
+                        ui.add(unselectable_label("Allow other devices on the network"));
+                    });
+                });
+
+                if settings_changed {
+                    restart_remote_control(ui.ctx(), &mut gem.remote_control);
+                }
+
+                if let Some(error) = &gem.remote_control.error {
+                    let message = RichText::new(error).small().color(ui.visuals().error_fg_color);
+                    ui.add(unselectable_label(message));
+                } else if let Some(url) = remote_url(&gem.remote_control) {
+                    let message = RichText::new(format!("Listening. The web remote is at {}.", url)).small().weak();
+                    ui.add(unselectable_label(message));
+                }
+
+                ui.add(Separator::default().spacing(divider_spacing));
+
                 ui.add(unselectable_label(RichText::new(format!("About {}", APP_NAME)).heading()));
                 ui.add_space(8.0);
 
@@ -125,6 +723,80 @@ pub fn settings_view(ui: &mut Ui, gem: &mut GemPlayer) {
 
 /// Elide a path string to something like `/Users/user1/…/Music`
 /// Keeps both start and end parts if the path is too long.
+// The sample rate and resampler for the output device in use. These are remembered separately for each device.
+fn output_format_settings(ui: &mut Ui, gem: &mut GemPlayer) {
+    ui.add(unselectable_label(RichText::new("Output Format").heading()));
+    ui.add_space(8.0);
+
+    let Some(backend) = &gem.player.backend else {
+        ui.add(unselectable_label("No output device is available."));
+        return;
+    };
+
+    let device_name = backend.device_name.clone();
+    let supported_sample_rates = backend.supported_sample_rates.clone();
+    let mut settings = current_output_settings(&gem.player);
+    let previous_settings = settings;
+
+    ui.add(unselectable_label(format!("Settings for {}.", device_name)));
+    ui.add_space(8.0);
+
+    let format_rate = |rate: Option<u32>| match rate {
+        Some(rate) => format!("{:.1} kHz", rate as f32 / 1000.0),
+        None => "Device default".to_owned(),
+    };
+
+    ComboBox::from_label("Sample rate")
+        .selected_text(format_rate(settings.sample_rate))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut settings.sample_rate, None, format_rate(None));
+            for rate in supported_sample_rates {
+                ui.selectable_value(&mut settings.sample_rate, Some(rate), format_rate(Some(rate)));
+            }
+        });
+
+    ui.add_space(8.0);
+    ui.add(unselectable_label(
+        "Resampling, when a track's sample rate differs from the output's:",
+    ));
+    ui.horizontal(|ui| {
+        for quality in ResamplerQuality::iter() {
+            ui.radio_value(&mut settings.resampler, quality, quality.label());
+        }
+    });
+    ui.add(unselectable_label(
+        RichText::new("High quality uses more CPU. Changes apply from the next track.")
+            .small()
+            .weak(),
+    ));
+
+    if settings == previous_settings {
+        return;
+    }
+
+    gem.player.output_settings.insert(device_name, settings);
+
+    if settings.sample_rate == previous_settings.sample_rate {
+        return;
+    }
+
+    if let Err(e) = reopen_audio_backend(&mut gem.player) {
+        error!("Failed to reopen the output device: {:#}", e);
+        gem.ui.toasts.error("Failed to reopen the output device.");
+        return;
+    }
+
+    let output_rate = gem.player.backend.as_ref().map(|b| b.stream.config().sample_rate().get());
+    if let Some(requested) = settings.sample_rate
+        && output_rate != Some(requested)
+    {
+        gem.ui.toasts.warning(format!(
+            "The output device refused {}. Using its default sample rate.",
+            format_rate(Some(requested))
+        ));
+    }
+}
+
 pub fn elide_path(path: &Path, max_len: usize) -> String {
     let full = path.to_string_lossy();
     let full_len = full.len();
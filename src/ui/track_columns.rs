@@ -0,0 +1,414 @@
+use std::collections::{HashMap, HashSet};
+
+use egui::{Checkbox, Id, Label, Popup, RichText, Sense, StrokeKind, Ui};
+use egui_extras::{Column, TableBuilder, TableRow};
+use egui_material_icons::icons::{
+    ICON_ALBUM, ICON_ARTIST, ICON_AUDIO_FILE, ICON_CALENDAR_MONTH, ICON_CATEGORY, ICON_EDIT_NOTE, ICON_FOLDER, ICON_GRAPHIC_EQ,
+    ICON_GROUPS, ICON_HOURGLASS, ICON_MUSIC_NOTE, ICON_SPEAKER_GROUP, ICON_STAR, ICON_TAG, ICON_TRENDING_UP, ICON_VOLUME_UP,
+};
+use fully_pub::fully_pub;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    auto_playlist::PlayCounts,
+    track::{Track, file_type_name, format_channels, format_gain, format_track_number},
+    ui::root::{format_duration_to_mmss, rating_stars, unselectable_label},
+};
+
+const FIXED_COLUMN_WIDTH: f32 = 64.0;
+const MIN_COLUMN_WIDTH: f32 = 32.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TrackColumn {
+    Position, // The row's place in a playlist or the queue.
+    TrackNumber,
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Composer,
+    Genre,
+    Year,
+    Duration,
+    Bitrate,
+    Channels,
+    Format,
+    ReplayGain, // The track gain, or the album gain if the track has none.
+    Plays,
+    Rating,
+    Path,
+}
+
+pub const LIBRARY_COLUMNS: [TrackColumn; 16] = [
+    TrackColumn::TrackNumber,
+    TrackColumn::Title,
+    TrackColumn::Artist,
+    TrackColumn::Album,
+    TrackColumn::AlbumArtist,
+    TrackColumn::Composer,
+    TrackColumn::Genre,
+    TrackColumn::Year,
+    TrackColumn::Duration,
+    TrackColumn::Bitrate,
+    TrackColumn::Channels,
+    TrackColumn::Format,
+    TrackColumn::ReplayGain,
+    TrackColumn::Plays,
+    TrackColumn::Rating,
+    TrackColumn::Path,
+];
+
+pub const PLAYLIST_COLUMNS: [TrackColumn; 17] = [
+    TrackColumn::Position,
+    TrackColumn::TrackNumber,
+    TrackColumn::Title,
+    TrackColumn::Artist,
+    TrackColumn::Album,
+    TrackColumn::AlbumArtist,
+    TrackColumn::Composer,
+    TrackColumn::Genre,
+    TrackColumn::Year,
+    TrackColumn::Duration,
+    TrackColumn::Bitrate,
+    TrackColumn::Channels,
+    TrackColumn::Format,
+    TrackColumn::ReplayGain,
+    TrackColumn::Plays,
+    TrackColumn::Rating,
+    TrackColumn::Path,
+];
+
+pub const QUEUE_COLUMNS: [TrackColumn; 17] = PLAYLIST_COLUMNS;
+
+// Until the user picks their own, the tables only show the basics.
+pub const HIDDEN_BY_DEFAULT: [TrackColumn; 12] = [
+    TrackColumn::TrackNumber,
+    TrackColumn::AlbumArtist,
+    TrackColumn::Composer,
+    TrackColumn::Genre,
+    TrackColumn::Year,
+    TrackColumn::Bitrate,
+    TrackColumn::Channels,
+    TrackColumn::Format,
+    TrackColumn::ReplayGain,
+    TrackColumn::Plays,
+    TrackColumn::Rating,
+    TrackColumn::Path,
+];
+
+impl TrackColumn {
+    pub fn label(self) -> &'static str {
+        match self {
+            TrackColumn::Position => "Position",
+            TrackColumn::TrackNumber => "Track Number",
+            TrackColumn::Title => "Title",
+            TrackColumn::Artist => "Artist",
+            TrackColumn::Album => "Album",
+            TrackColumn::AlbumArtist => "Album Artist",
+            TrackColumn::Composer => "Composer",
+            TrackColumn::Genre => "Genre",
+            TrackColumn::Year => "Year",
+            TrackColumn::Duration => "Duration",
+            TrackColumn::Bitrate => "Bitrate",
+            TrackColumn::Channels => "Channels",
+            TrackColumn::Format => "Format",
+            TrackColumn::ReplayGain => "ReplayGain",
+            TrackColumn::Plays => "Plays",
+            TrackColumn::Rating => "Rating",
+            TrackColumn::Path => "Path",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            TrackColumn::Position | TrackColumn::TrackNumber => ICON_TAG.codepoint,
+            TrackColumn::Title => ICON_MUSIC_NOTE.codepoint,
+            TrackColumn::Artist => ICON_ARTIST.codepoint,
+            TrackColumn::Album => ICON_ALBUM.codepoint,
+            TrackColumn::AlbumArtist => ICON_GROUPS.codepoint,
+            TrackColumn::Composer => ICON_EDIT_NOTE.codepoint,
+            TrackColumn::Genre => ICON_CATEGORY.codepoint,
+            TrackColumn::Year => ICON_CALENDAR_MONTH.codepoint,
+            TrackColumn::Duration => ICON_HOURGLASS.codepoint,
+            TrackColumn::Bitrate => ICON_GRAPHIC_EQ.codepoint,
+            TrackColumn::Channels => ICON_SPEAKER_GROUP.codepoint,
+            TrackColumn::Format => ICON_AUDIO_FILE.codepoint,
+            TrackColumn::ReplayGain => ICON_VOLUME_UP.codepoint,
+            TrackColumn::Plays => ICON_TRENDING_UP.codepoint,
+            TrackColumn::Rating => ICON_STAR.codepoint,
+            TrackColumn::Path => ICON_FOLDER.codepoint,
+        }
+    }
+
+    // Text columns split the width left over by the fixed width columns, in proportion to their share.
+    fn share(self) -> Option<f32> {
+        match self {
+            TrackColumn::Title => Some(2.0),
+            TrackColumn::Artist | TrackColumn::Album | TrackColumn::Genre => Some(1.0),
+            TrackColumn::AlbumArtist | TrackColumn::Composer => Some(1.0),
+            TrackColumn::Path => Some(2.0),
+            _ => None,
+        }
+    }
+
+    fn fixed_width(self) -> f32 {
+        match self {
+            TrackColumn::Rating => 88.0, // Fits five stars.
+            TrackColumn::Bitrate => 80.0,
+            TrackColumn::Channels => 72.0,
+            TrackColumn::ReplayGain => 96.0,
+            _ => FIXED_COLUMN_WIDTH,
+        }
+    }
+}
+
+// The order, widths, and visibility of a table's columns, as arranged by the user.
+#[fully_pub]
+struct ColumnLayout {
+    columns: Vec<TrackColumn>,         // In display order, including the hidden columns.
+    widths: HashMap<TrackColumn, f32>, // Columns without a width get one from their share of the table.
+    hidden: HashSet<TrackColumn>,
+}
+
+// A column, its width, and whether it is visible. Columns are saved by their variant name rather than their label, so
+// that relabelling a column keeps the layout.
+pub type SavedColumn = (TrackColumn, Option<f32>, bool);
+
+// Columns that can't be read, e.g. ones that were removed since the layout was saved, are skipped rather than discarding
+// the whole layout.
+pub fn parse_saved_columns(json: &str) -> Option<Vec<SavedColumn>> {
+    let values: Vec<Value> = serde_json::from_str(json).ok()?;
+    Some(values.into_iter().filter_map(|value| serde_json::from_value(value).ok()).collect())
+}
+
+impl ColumnLayout {
+    pub fn new(columns: &[TrackColumn]) -> Self {
+        let hidden = columns.iter().copied().filter(|c| HIDDEN_BY_DEFAULT.contains(c)).collect();
+
+        Self {
+            columns: columns.to_vec(),
+            widths: HashMap::new(),
+            hidden,
+        }
+    }
+
+    // Restores a layout saved with to_saved(). Columns that aren't among the given columns are dropped, and columns
+    // missing from the save are added at the end, so that the layout always has exactly the given columns.
+    pub fn from_saved(saved: &[SavedColumn], columns: &[TrackColumn]) -> Self {
+        let mut layout = Self::new(columns);
+        layout.columns.clear();
+
+        for &(column, width, visible) in saved {
+            if !columns.contains(&column) || layout.columns.contains(&column) {
+                continue;
+            }
+
+            layout.columns.push(column);
+            if let Some(width) = width {
+                layout.widths.insert(column, width.max(MIN_COLUMN_WIDTH));
+            }
+
+            if visible {
+                layout.hidden.remove(&column);
+            } else {
+                layout.hidden.insert(column);
+            }
+        }
+
+        for column in columns {
+            if !layout.columns.contains(column) {
+                layout.columns.push(*column);
+            }
+        }
+
+        // A table needs at least one column.
+        if layout.visible_columns().is_empty() {
+            layout.hidden.clear();
+        }
+
+        layout
+    }
+
+    pub fn to_saved(&self) -> Vec<SavedColumn> {
+        self.columns
+            .iter()
+            .map(|c| (*c, self.widths.get(c).copied(), !self.hidden.contains(c)))
+            .collect()
+    }
+
+    pub fn visible_columns(&self) -> Vec<TrackColumn> {
+        self.columns.iter().copied().filter(|c| !self.hidden.contains(c)).collect()
+    }
+
+    // Places the moved column where the target column is, shifting the target and the columns after it over.
+    pub fn move_column(&mut self, moved: TrackColumn, target: TrackColumn) {
+        let Some(from) = self.columns.iter().position(|c| *c == moved) else {
+            return;
+        };
+        let Some(to) = self.columns.iter().position(|c| *c == target) else {
+            return;
+        };
+
+        let column = self.columns.remove(from);
+        self.columns.insert(to, column);
+    }
+}
+
+// The column header being dragged onto another header to reorder the columns.
+struct ColumnDragPayload {
+    column: TrackColumn,
+}
+
+// A change to the layout made from the column headers.
+pub enum ColumnEdit {
+    Move(TrackColumn, TrackColumn), // The moved column and the column it was dropped on.
+    SetVisible(TrackColumn, bool),
+}
+
+// Adds resizable columns for the visible columns of the layout, followed by a column for the row buttons that takes
+// the rest of the width. The table's id changes with the column order since egui_extras remembers widths by index.
+pub fn add_track_columns<'a>(
+    table: TableBuilder<'a>,
+    id: &str,
+    layout: &ColumnLayout,
+    visible: &[TrackColumn],
+    available_width: f32,
+    buttons_width: f32,
+) -> TableBuilder<'a> {
+    let fixed_width: f32 = visible
+        .iter()
+        .filter(|c| c.share().is_none())
+        .map(|c| layout.widths.get(c).copied().unwrap_or(c.fixed_width()))
+        .sum();
+    let total_share: f32 = visible.iter().filter_map(|c| c.share()).sum();
+    let remaining_width = (available_width - fixed_width - buttons_width).max(0.0);
+
+    let mut table = table.id_salt((id, visible));
+    for column in visible {
+        let default_width = match column.share() {
+            Some(share) => remaining_width * share / total_share,
+            None => column.fixed_width(),
+        };
+        let width = layout.widths.get(column).copied().unwrap_or(default_width);
+
+        table = table.column(Column::initial(width).at_least(MIN_COLUMN_WIDTH).resizable(true).clip(true));
+    }
+
+    table.column(Column::remainder().at_least(buttons_width))
+}
+
+// Adds a header cell for each visible column. Headers can be dragged onto each other to reorder the columns, and
+// right clicking them opens a menu for choosing which columns are shown.
+pub fn column_headers(
+    header: &mut TableRow,
+    id: &str,
+    layout: &ColumnLayout,
+    visible: &[TrackColumn],
+    widths: &mut Vec<f32>,
+) -> Option<ColumnEdit> {
+    let mut edit = None;
+
+    for (i, column) in visible.iter().enumerate() {
+        let (rect, _) = header.col(|ui| {
+            if i == 0 {
+                ui.add_space(16.0);
+            }
+
+            let cell_id = Id::new((id, "column_header", column));
+            let cell_response = ui.interact(ui.max_rect(), cell_id.with("cell"), Sense::click());
+
+            ui.dnd_drag_source(cell_id, ColumnDragPayload { column: *column }, |ui| {
+                ui.add(unselectable_label(RichText::new(column.icon()).strong()));
+            })
+            .response
+            .on_hover_text(column.label());
+
+            if cell_response.dnd_hover_payload::<ColumnDragPayload>().is_some() {
+                let stroke = ui.visuals().selection.stroke;
+                ui.painter().rect_stroke(ui.max_rect(), 2.0, stroke, StrokeKind::Inside);
+            }
+
+            if let Some(payload) = cell_response.dnd_release_payload::<ColumnDragPayload>()
+                && payload.column != *column
+            {
+                edit = Some(ColumnEdit::Move(payload.column, *column));
+            }
+
+            Popup::context_menu(&cell_response).show(|ui| {
+                if let Some(e) = column_chooser(ui, layout, visible) {
+                    edit = Some(e);
+                }
+            });
+        });
+
+        widths.push(rect.width());
+    }
+
+    edit
+}
+
+fn column_chooser(ui: &mut Ui, layout: &ColumnLayout, visible: &[TrackColumn]) -> Option<ColumnEdit> {
+    let mut edit = None;
+
+    ui.set_min_width(160.0);
+    ui.add_enabled(false, Label::new("Columns"));
+    ui.separator();
+
+    for column in &layout.columns {
+        let mut is_visible = visible.contains(column);
+        let is_last_visible = is_visible && visible.len() == 1;
+
+        let response = ui
+            .add_enabled(!is_last_visible, Checkbox::new(&mut is_visible, column.label()))
+            .on_disabled_hover_text("At least one column must be shown");
+        if response.changed() {
+            edit = Some(ColumnEdit::SetVisible(*column, is_visible));
+        }
+    }
+
+    edit
+}
+
+// Remembers the widths the columns were drawn at, which includes any resizing, and applies a change made from the
+// headers.
+pub fn update_column_layout(layout: &mut ColumnLayout, visible: &[TrackColumn], widths: &[f32], edit: Option<ColumnEdit>) {
+    for (column, width) in visible.iter().zip(widths) {
+        layout.widths.insert(*column, *width);
+    }
+
+    match edit {
+        Some(ColumnEdit::Move(moved, target)) => layout.move_column(moved, target),
+        Some(ColumnEdit::SetVisible(column, true)) => {
+            layout.hidden.remove(&column);
+        }
+        Some(ColumnEdit::SetVisible(column, false)) => {
+            layout.hidden.insert(column);
+        }
+        None => {}
+    }
+}
+
+// The text of a cell. The position is the row's place in a playlist or the queue, counting from 1.
+pub fn column_text(column: TrackColumn, track: &Track, position: usize, play_counts: &PlayCounts) -> String {
+    match column {
+        TrackColumn::Position => position.to_string(),
+        TrackColumn::TrackNumber => format_track_number(track),
+        TrackColumn::Title => track.title.clone().unwrap_or_else(|| "-".to_owned()),
+        TrackColumn::Artist => track.artist.clone().unwrap_or_else(|| "-".to_owned()),
+        TrackColumn::Album => track.album.clone().unwrap_or_else(|| "-".to_owned()),
+        TrackColumn::AlbumArtist => track.album_artist.clone().unwrap_or_else(|| "-".to_owned()),
+        TrackColumn::Composer => track.composer.clone().unwrap_or_else(|| "-".to_owned()),
+        TrackColumn::Genre => track.genre.clone().unwrap_or_else(|| "-".to_owned()),
+        TrackColumn::Year => track.year.map(|y| y.to_string()).unwrap_or_else(|| "-".to_owned()),
+        TrackColumn::Duration => format_duration_to_mmss(track.duration),
+        TrackColumn::Bitrate => track.bitrate.map(|kbps| format!("{} kbps", kbps)).unwrap_or_else(|| "-".to_owned()),
+        TrackColumn::Channels => format_channels(track.channels),
+        TrackColumn::Format => file_type_name(track.codec).to_owned(),
+        TrackColumn::ReplayGain => format_gain(track.replay_gain.track_gain.or(track.replay_gain.album_gain)),
+        TrackColumn::Plays => play_counts.get(&track.path).copied().unwrap_or(0).to_string(),
+        TrackColumn::Rating if track.rating == 0 => "-".to_owned(),
+        TrackColumn::Rating => rating_stars(track.rating),
+        TrackColumn::Path => track.path.display().to_string(),
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use egui::{Event, Ui};
+use fully_pub::fully_pub;
+
+use crate::track::{Track, fold_text};
+
+const RESET_TIMEOUT: f64 = 1.0; // Seconds after the last key press before typing starts a new prefix.
+
+// Typing while a track table is shown (and no text field has focus) jumps to the first track whose title starts with
+// what was typed, like in file managers.
+#[fully_pub]
+struct TypeToJumpState {
+    prefix: String, // Folded, see fold_text().
+    last_typed: f64,
+}
+
+// Selects and returns the index of the matching track, if something was typed this frame.
+pub fn type_to_jump(ui: &Ui, state: &mut TypeToJumpState, tracks: &[Track], selected_tracks: &mut Vec<PathBuf>) -> Option<usize> {
+    let text_has_focus = ui.memory(|m| m.focused().is_some());
+    if text_has_focus {
+        return None;
+    }
+
+    let (typed, time) = ui.input(|i| {
+        let typed: String = i
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        (typed, i.time)
+    });
+
+    if typed.is_empty() {
+        return None;
+    }
+
+    if time - state.last_typed > RESET_TIMEOUT {
+        state.prefix.clear();
+    }
+    state.last_typed = time;
+
+    state.prefix.push_str(&fold_text(&typed));
+    if state.prefix.trim().is_empty() {
+        state.prefix.clear(); // Titles don't start with a space.
+        return None;
+    }
+
+    let index = tracks.iter().position(|t| t.search_fields.title.starts_with(&state.prefix))?;
+
+    selected_tracks.clear();
+    selected_tracks.push(tracks[index].path.clone());
+
+    Some(index)
+}
@@ -1,6 +1,6 @@
-use egui::{Image, TextureFilter, TextureOptions, Ui, Vec2, include_image};
+use egui::{Image, Response, TextureFilter, TextureOptions, Ui, Vec2, include_image};
 
-pub fn artwork_ui(ui: &mut Ui, artwork_uri: Option<&str>, width: f32) {
+pub fn artwork_ui(ui: &mut Ui, artwork_uri: Option<&str>, width: f32) -> Response {
     let artwork = if let Some(uri) = artwork_uri {
         Image::from_uri(uri)
     } else {
@@ -14,5 +14,5 @@ pub fn artwork_ui(ui: &mut Ui, artwork_uri: Option<&str>, width: f32) {
             .fit_to_exact_size(Vec2::splat(width))
             .maintain_aspect_ratio(false)
             .corner_radius(2.0),
-    );
+    )
 }
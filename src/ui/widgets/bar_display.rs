@@ -2,6 +2,7 @@ use egui::{Color32, Rect, Response, Sense, Ui, Widget, pos2, vec2};
 
 pub struct BarDisplay<'a> {
     values: &'a [f32],
+    peaks: Option<&'a [f32]>, // Drawn as caps above the bars.
 
     desired_height: f32,
     bar_width: f32,
@@ -15,6 +16,7 @@ impl<'a> BarDisplay<'a> {
     pub fn new(values: &'a [f32], desired_height: f32, bar_width: f32, bar_gap: f32, color: Color32) -> Self {
         Self {
             values,
+            peaks: None,
             desired_height,
             bar_width,
             bar_gap,
@@ -23,6 +25,11 @@ impl<'a> BarDisplay<'a> {
             color,
         }
     }
+
+    pub fn peaks(mut self, peaks: &'a [f32]) -> Self {
+        self.peaks = Some(peaks);
+        self
+    }
 }
 
 impl Widget for BarDisplay<'_> {
@@ -43,6 +50,13 @@ impl Widget for BarDisplay<'_> {
             let bar_rect = Rect::from_min_max(pos2(x, y - height), pos2(x + self.bar_width, y));
 
             painter.rect_filled(bar_rect, self.bar_radius, self.color);
+
+            if let Some(&peak) = self.peaks.and_then(|peaks| peaks.get(i)) {
+                let cap_height = 2.0;
+                let cap_bottom = (y - peak.clamp(0.0, 1.0) * rect.height()).min(y - height - 1.0);
+                let cap_rect = Rect::from_min_max(pos2(x, cap_bottom - cap_height), pos2(x + self.bar_width, cap_bottom));
+                painter.rect_filled(cap_rect, 0.0, self.color.gamma_multiply(0.7));
+            }
         }
 
         response
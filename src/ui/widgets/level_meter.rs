@@ -0,0 +1,117 @@
+use std::mem::take;
+
+use egui::{Color32, Rect, Sense, Ui, pos2, vec2};
+
+use crate::visualizer::{ChannelLevel, smooth_bars, update_peaks};
+
+const FLOOR_DB: f32 = -60.0; // The quietest level the meter shows.
+const CLIP_HOLD_TIME: f64 = 2.0; // Seconds that the clip indicator stays lit. Clicking it clears it sooner.
+
+// Ballistics for the rms bars. The peak markers use the visualizer's peak hold.
+const ATTACK: f32 = 30.0;
+const DECAY: f32 = 8.0;
+
+// A stereo meter with the rms level of each channel as a bar, the recent sample peak as a marker, and a clip indicator.
+pub struct LevelMeter {
+    levels: [f32; 2], // Between 0 and 1, scaled from FLOOR_DB to full scale.
+    targets: Option<[f32; 2]>,
+    peaks: [f32; 2],
+    peak_targets: [f32; 2],
+    peak_holds: [f32; 2],
+    clip_pending: bool,
+    clipped_at: Option<f64>,
+}
+
+impl LevelMeter {
+    pub fn new() -> Self {
+        Self {
+            levels: [0.0; 2],
+            targets: None,
+            peaks: [0.0; 2],
+            peak_targets: [0.0; 2],
+            peak_holds: [0.0; 2],
+            clip_pending: false,
+            clipped_at: None,
+        }
+    }
+
+    // Takes the levels of the latest window (see VisualizerFrame::Levels). Mono is shown on both sides, and only the
+    // front left and right of surround audio are shown.
+    pub fn push(&mut self, channels: &[ChannelLevel]) {
+        let Some(&left) = channels.first() else {
+            return;
+        };
+        let right = channels.get(1).copied().unwrap_or(left);
+
+        self.targets = Some([scale(left.rms), scale(right.rms)]);
+        self.peak_targets = [scale(left.peak), scale(right.peak)];
+
+        if left.peak > 1.0 || right.peak > 1.0 {
+            self.clip_pending = true;
+        }
+    }
+}
+
+pub fn level_meter_ui(ui: &mut Ui, meter: &mut LevelMeter, width: f32, height: f32) {
+    let (dt, time) = ui.input(|i| (i.stable_dt, i.time));
+
+    let targets = meter.targets.take();
+    smooth_bars(&mut meter.levels, targets.as_ref().map(|t| t.as_slice()), ATTACK, DECAY, dt);
+
+    // On repaints without a new window, and once the audio stops, the peaks fall back towards the bars.
+    let peak_targets = take(&mut meter.peak_targets);
+    let peak_targets = if targets.is_some() { peak_targets } else { meter.levels };
+    update_peaks(&mut meter.peaks, &mut meter.peak_holds, &peak_targets, dt);
+
+    if meter.clip_pending {
+        meter.clip_pending = false;
+        meter.clipped_at = Some(time);
+    }
+    let is_clipping = meter.clipped_at.is_some_and(|at| time - at < CLIP_HOLD_TIME);
+
+    let (rect, response) = ui.allocate_exact_size(vec2(width, height), Sense::click());
+    let painter = ui.painter();
+
+    let indicator_size = width / 2.0;
+    let gap = 2.0;
+    let bar_width = (width - gap) / 2.0;
+    let bars_top = rect.top() + indicator_size + gap;
+    let bars_height = rect.bottom() - bars_top;
+
+    let clip_color = if is_clipping { Color32::RED } else { ui.visuals().faint_bg_color };
+    painter.circle_filled(
+        pos2(rect.center().x, rect.top() + indicator_size / 2.0),
+        indicator_size / 2.0,
+        clip_color,
+    );
+
+    let color = ui.visuals().text_color();
+    let background = ui.visuals().faint_bg_color;
+    for (i, (&level, &peak)) in meter.levels.iter().zip(&meter.peaks).enumerate() {
+        let x = rect.left() + i as f32 * (bar_width + gap);
+        let track = Rect::from_min_max(pos2(x, bars_top), pos2(x + bar_width, rect.bottom()));
+        painter.rect_filled(track, 1.0, background);
+
+        let level_top = rect.bottom() - level.clamp(0.0, 1.0) * bars_height;
+        painter.rect_filled(Rect::from_min_max(pos2(x, level_top), track.right_bottom()), 1.0, color);
+
+        if peak > 0.0 {
+            let peak_y = rect.bottom() - peak.clamp(0.0, 1.0) * bars_height;
+            let marker = Rect::from_min_max(pos2(x, peak_y - 1.0), pos2(x + bar_width, peak_y + 1.0));
+            painter.rect_filled(marker, 0.0, color.gamma_multiply(0.7));
+        }
+    }
+
+    if response.clicked() {
+        meter.clipped_at = None;
+    }
+
+    let hover_text = if is_clipping { "Clipping. Click to clear." } else { "Signal level" };
+    response.on_hover_text(hover_text);
+}
+
+// Scales a linear amplitude to between 0 and 1, from FLOOR_DB to full scale.
+fn scale(amplitude: f32) -> f32 {
+    let db = 20.0 * amplitude.max(1e-10).log10();
+    (1.0 - db / FLOOR_DB).clamp(0.0, 1.0)
+}
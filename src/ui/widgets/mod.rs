@@ -1,7 +1,12 @@
 pub mod bar_display;
 pub mod centered_frame;
+pub mod level_meter;
 pub mod marquee;
 pub mod metadata_chip;
 pub mod playing_indicator;
+pub mod spectrogram;
+pub mod spectrum_display;
 pub mod toggle_switch;
+pub mod vu_needle;
+pub mod waveform_display;
 pub mod artwork;
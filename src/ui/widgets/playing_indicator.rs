@@ -2,14 +2,21 @@ use egui::Ui;
 
 use crate::ui::widgets::bar_display::BarDisplay;
 
-pub fn playing_indicator(ui: &mut Ui) {
-    let time = ui.input(|i| i.time) as f32;
+// The bars stay still when animations are reduced or the window is unfocused, to save battery.
+pub fn playing_indicator(ui: &mut Ui, reduce_animations: bool) {
+    let is_focused = ui.input(|i| i.viewport().focused).unwrap_or(true);
 
-    let values = [
-        ((time * 6.0).sin() * 0.4 + 0.6).max(0.2),
-        ((time * 7.5).cos() * 0.4 + 0.6).max(0.2),
-        ((time * 5.3).sin() * 0.4 + 0.6).max(0.2),
-    ];
+    let values = if reduce_animations || !is_focused {
+        [0.6, 1.0, 0.4]
+    } else {
+        let time = ui.input(|i| i.time) as f32;
+
+        [
+            ((time * 6.0).sin() * 0.4 + 0.6).max(0.2),
+            ((time * 7.5).cos() * 0.4 + 0.6).max(0.2),
+            ((time * 5.3).sin() * 0.4 + 0.6).max(0.2),
+        ]
+    };
 
     ui.add(BarDisplay::new(
         &values,
@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+
+use egui::{Color32, ColorImage, Rect, Sense, TextureHandle, TextureOptions, Ui, pos2, vec2};
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+const COLUMN_COUNT: usize = 120; // How many spectra are kept. Newer ones scroll in from the right.
+const ROW_COUNT: usize = 48;
+const FREQUENCY_RANGE: (f32, f32) = (40.0, 16000.0); // In Hz. The rows are spaced evenly on a log scale.
+
+pub const DEFAULT_FLOOR_DB: f32 = -90.0;
+pub const DEFAULT_CEILING_DB: f32 = -10.0;
+pub const MIN_DB: f32 = -120.0;
+pub const MAX_DB: f32 = 0.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum ColorMap {
+    Fire,
+    Ocean,
+    Grayscale,
+}
+
+impl ColorMap {
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorMap::Fire => "Fire",
+            ColorMap::Ocean => "Ocean",
+            ColorMap::Grayscale => "Grayscale",
+        }
+    }
+
+    // The colors from the floor to the ceiling. Levels in between are interpolated.
+    fn stops(self) -> &'static [Color32] {
+        match self {
+            ColorMap::Fire => &[
+                Color32::from_rgb(0, 0, 0),
+                Color32::from_rgb(120, 0, 40),
+                Color32::from_rgb(230, 60, 0),
+                Color32::from_rgb(255, 200, 0),
+                Color32::from_rgb(255, 255, 220),
+            ],
+            ColorMap::Ocean => &[
+                Color32::from_rgb(0, 0, 20),
+                Color32::from_rgb(0, 40, 120),
+                Color32::from_rgb(0, 150, 200),
+                Color32::from_rgb(160, 240, 255),
+            ],
+            ColorMap::Grayscale => &[Color32::BLACK, Color32::WHITE],
+        }
+    }
+
+    fn color(self, level: f32) -> Color32 {
+        let stops = self.stops();
+        let position = level.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let index = (position.floor() as usize).min(stops.len() - 2);
+
+        stops[index].lerp_to_gamma(stops[index + 1], position - index as f32)
+    }
+}
+
+// A scrolling history of spectra, rendered into a texture.
+pub struct Spectrogram {
+    pub floor_db: f32,
+    pub ceiling_db: f32,
+    pub color_map: ColorMap,
+
+    columns: VecDeque<[f32; ROW_COUNT]>, // Levels in dBFS, with the highest frequency first.
+    texture: Option<TextureHandle>,
+    needs_update: bool,
+}
+
+impl Spectrogram {
+    pub fn new(floor_db: f32, ceiling_db: f32, color_map: ColorMap) -> Self {
+        Self {
+            floor_db,
+            ceiling_db,
+            color_map,
+            columns: VecDeque::with_capacity(COLUMN_COUNT),
+            texture: None,
+            needs_update: true,
+        }
+    }
+
+    // Adds a spectrum (see VisualizerFrame::Spectrum) as the newest column.
+    pub fn push(&mut self, decibels: &[f32], bin_width: f32) {
+        if decibels.is_empty() || bin_width <= 0.0 {
+            return;
+        }
+
+        let (low, high) = FREQUENCY_RANGE;
+        let edge = |row: usize| low * (high / low).powf(row as f32 / ROW_COUNT as f32);
+
+        let mut column = [self.floor_db; ROW_COUNT];
+        for (i, level) in column.iter_mut().rev().enumerate() {
+            // Each row shows the loudest bin in its frequency range.
+            let first_bin = (edge(i) / bin_width).floor() as usize;
+            let last_bin = ((edge(i + 1) / bin_width).ceil() as usize).min(decibels.len() - 1);
+
+            if let Some(loudest) = decibels
+                .get(first_bin..=last_bin)
+                .and_then(|bins| bins.iter().copied().reduce(f32::max))
+            {
+                *level = loudest;
+            }
+        }
+
+        if self.columns.len() == COLUMN_COUNT {
+            self.columns.pop_front();
+        }
+        self.columns.push_back(column);
+
+        self.needs_update = true;
+    }
+
+    // Settings changes re-render the whole history.
+    pub fn invalidate(&mut self) {
+        self.needs_update = true;
+    }
+}
+
+pub fn spectrogram_ui(ui: &mut Ui, spectrogram: &mut Spectrogram, width: f32, height: f32) {
+    let (rect, _) = ui.allocate_exact_size(vec2(width, height), Sense::hover());
+
+    if spectrogram.needs_update || spectrogram.texture.is_none() {
+        let image = render_image(spectrogram);
+        match &mut spectrogram.texture {
+            Some(texture) => texture.set(image, TextureOptions::LINEAR),
+            None => spectrogram.texture = Some(ui.ctx().load_texture("spectrogram", image, TextureOptions::LINEAR)),
+        }
+        spectrogram.needs_update = false;
+    }
+
+    if let Some(texture) = &spectrogram.texture {
+        let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+        ui.painter().image(texture.id(), rect, uv, Color32::WHITE);
+    }
+}
+
+fn render_image(spectrogram: &Spectrogram) -> ColorImage {
+    let range = (spectrogram.ceiling_db - spectrogram.floor_db).max(1.0);
+    let background = spectrogram.color_map.color(0.0);
+
+    let mut image = ColorImage::filled([COLUMN_COUNT, ROW_COUNT], background);
+
+    // Right aligned, so that the newest column is always at the right edge.
+    let offset = COLUMN_COUNT - spectrogram.columns.len();
+    for (x, column) in spectrogram.columns.iter().enumerate() {
+        for (y, level) in column.iter().enumerate() {
+            let normalized = (level - spectrogram.floor_db) / range;
+            image[(offset + x, y)] = spectrogram.color_map.color(normalized);
+        }
+    }
+
+    image
+}
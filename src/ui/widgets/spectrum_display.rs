@@ -0,0 +1,68 @@
+use egui::{Color32, Response, Sense, Shape, Stroke, Ui, Widget, pos2, vec2};
+
+// The band levels drawn as a filled curve, from the lowest frequency on the left to the highest on the right.
+pub struct SpectrumDisplay<'a> {
+    values: &'a [f32],
+    peaks: Option<&'a [f32]>, // Drawn as a thin line above the curve.
+
+    desired_size: (f32, f32),
+    color: Color32,
+}
+
+impl<'a> SpectrumDisplay<'a> {
+    pub fn new(values: &'a [f32], width: f32, height: f32, color: Color32) -> Self {
+        Self {
+            values,
+            peaks: None,
+            desired_size: (width, height),
+            color,
+        }
+    }
+
+    pub fn peaks(mut self, peaks: &'a [f32]) -> Self {
+        self.peaks = Some(peaks);
+        self
+    }
+}
+
+impl Widget for SpectrumDisplay<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (width, height) = self.desired_size;
+        let (rect, response) = ui.allocate_exact_size(vec2(width, height), Sense::hover());
+
+        if self.values.len() < 2 {
+            return response;
+        }
+
+        let last_index = (self.values.len() - 1) as f32;
+        let to_points = |values: &[f32]| -> Vec<_> {
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, &value)| {
+                    let x = rect.left() + rect.width() * (i as f32 / last_index);
+                    let y = rect.bottom() - value.clamp(0.0, 1.0) * rect.height();
+                    pos2(x, y)
+                })
+                .collect()
+        };
+        let points = to_points(self.values);
+
+        let painter = ui.painter();
+
+        // The curve isn't convex, so the area under it is filled one convex slice at a time.
+        let fill = self.color.gamma_multiply(0.4);
+        for pair in points.windows(2) {
+            let slice = vec![pair[0], pair[1], pos2(pair[1].x, rect.bottom()), pos2(pair[0].x, rect.bottom())];
+            painter.add(Shape::convex_polygon(slice, fill, Stroke::NONE));
+        }
+
+        painter.line(points, Stroke::new(1.5, self.color));
+
+        if let Some(peaks) = self.peaks.filter(|peaks| peaks.len() == self.values.len()) {
+            painter.line(to_points(peaks), Stroke::new(1.0, self.color.gamma_multiply(0.6)));
+        }
+
+        response
+    }
+}
@@ -0,0 +1,61 @@
+use std::f32::consts::PI;
+
+use egui::{Color32, Response, Sense, Stroke, Ui, Widget, pos2, vec2};
+
+// An analog style meter. The needle sweeps from the left (silence) to the right (full scale).
+pub struct VuNeedle {
+    level: f32,
+    peak: Option<f32>, // Marked with a tick on the scale.
+
+    desired_size: (f32, f32),
+    color: Color32,
+}
+
+impl VuNeedle {
+    pub fn new(level: f32, width: f32, height: f32, color: Color32) -> Self {
+        Self {
+            level,
+            peak: None,
+            desired_size: (width, height),
+            color,
+        }
+    }
+
+    pub fn peak(mut self, peak: f32) -> Self {
+        self.peak = Some(peak);
+        self
+    }
+}
+
+impl Widget for VuNeedle {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (width, height) = self.desired_size;
+        let (rect, response) = ui.allocate_exact_size(vec2(width, height), Sense::hover());
+
+        let pivot = rect.center_bottom();
+        let radius = (rect.width() / 2.0).min(rect.height());
+        let point_at = |angle: f32, distance: f32| pos2(pivot.x - angle.cos() * distance, pivot.y - angle.sin() * distance);
+
+        let painter = ui.painter();
+
+        // The scale is a shallow arc so that the meter stays short.
+        let sweep = (PI / 6.0, PI * 5.0 / 6.0);
+        let segments = 24;
+        let arc: Vec<_> = (0..=segments)
+            .map(|i| point_at(sweep.0 + (sweep.1 - sweep.0) * i as f32 / segments as f32, radius))
+            .collect();
+        painter.line(arc, Stroke::new(1.0, self.color.gamma_multiply(0.5)));
+
+        if let Some(peak) = self.peak {
+            let peak_angle = sweep.0 + (sweep.1 - sweep.0) * peak.clamp(0.0, 1.0);
+            let tick = [point_at(peak_angle, radius * 0.8), point_at(peak_angle, radius)];
+            painter.line_segment(tick, Stroke::new(2.0, self.color.gamma_multiply(0.7)));
+        }
+
+        let angle = sweep.0 + (sweep.1 - sweep.0) * self.level.clamp(0.0, 1.0);
+        painter.line_segment([pivot, point_at(angle, radius)], Stroke::new(1.5, self.color));
+        painter.circle_filled(pivot, 2.0, self.color);
+
+        response
+    }
+}
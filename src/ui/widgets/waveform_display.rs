@@ -0,0 +1,52 @@
+use egui::{Color32, Response, Sense, Stroke, Ui, Widget, pos2, vec2};
+
+pub struct WaveformDisplay<'a> {
+    samples: &'a [f32],
+
+    desired_size: (f32, f32),
+    stroke_width: f32,
+    color: Color32,
+}
+
+impl<'a> WaveformDisplay<'a> {
+    pub fn new(samples: &'a [f32], width: f32, height: f32, color: Color32) -> Self {
+        Self {
+            samples,
+            desired_size: (width, height),
+            stroke_width: 1.5,
+            color,
+        }
+    }
+}
+
+impl Widget for WaveformDisplay<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (width, height) = self.desired_size;
+        let (rect, response) = ui.allocate_exact_size(vec2(width, height), Sense::hover());
+
+        let stroke = Stroke::new(self.stroke_width, self.color);
+
+        // Without samples (e.g. when paused) the line rests in the middle.
+        if self.samples.len() < 2 {
+            ui.painter()
+                .line_segment([pos2(rect.left(), rect.center().y), pos2(rect.right(), rect.center().y)], stroke);
+            return response;
+        }
+
+        let last_index = (self.samples.len() - 1) as f32;
+        let points = self
+            .samples
+            .iter()
+            .enumerate()
+            .map(|(i, &sample)| {
+                let x = rect.left() + rect.width() * (i as f32 / last_index);
+                let y = rect.center().y - sample.clamp(-1.0, 1.0) * rect.height() / 2.0;
+                pos2(x, y)
+            })
+            .collect();
+
+        ui.painter().line(points, stroke);
+
+        response
+    }
+}
@@ -2,63 +2,222 @@ use fully_pub::fully_pub;
 use log::info;
 use rodio::{ChannelCount, Sample, SampleRate, Source, source::SeekError};
 use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use serde::{Deserialize, Serialize};
 use std::{
     f32::consts::{PI, SQRT_2},
+    mem::{replace, take},
     sync::mpsc::{Receiver, Sender, channel},
     thread,
     time::Duration,
 };
+use strum_macros::EnumIter;
 
 const FFT_SIZE: usize = 1 << 10; // 1024
+const SAMPLE_BATCH_SIZE: usize = 1 << 11; // Samples captured before they are sent to the processing thread.
 const HALF_OCTAVE_BANDWIDTH: f32 = SQRT_2;
 pub const CENTER_FREQUENCIES: [f32; 6] = [63.0, 125.0, 250.0, 500.0, 1000.0, 2000.0];
 
+const SPECTRUM_BAND_COUNT: usize = 32;
+const SPECTRUM_RANGE: (f32, f32) = (40.0, 16000.0); // In Hz. The bands are spaced evenly on a log scale.
+const WAVEFORM_POINTS: usize = 128; // The window is downsampled to this many points.
+const LEVEL_FLOOR_DB: f32 = -40.0; // The quietest level the needle shows.
+
+// How quickly the displayed bands chase the latest levels, as rates per second.
+pub const DEFAULT_ATTACK: f32 = 10.0;
+pub const DEFAULT_DECAY: f32 = 10.0;
+pub const SMOOTHING_RANGE: (f32, f32) = (1.0, 40.0);
+
+const PEAK_HOLD_TIME: f32 = 0.8; // Seconds that a peak marker stays put before it starts to fall.
+const PEAK_FALL_SPEED: f32 = 0.6; // Levels per second.
+
 #[fully_pub]
 struct VisualizerState {
+    pipeline: Option<VisualizerPipeline>, // None while the visualizer is disabled, so that it costs nothing.
+    style: VisualizerStyle,
+    display_bands: Vec<f32>, // Smoothed between frames. Only the waveform style doesn't use them.
+    waveform: Vec<f32>,
+
+    attack: f32,
+    decay: f32,
+    peak_hold: bool,
+    peaks: Vec<f32>,      // The highest recent level of each band.
+    peak_holds: Vec<f32>, // Seconds left before each peak starts to fall.
+
+    meter_enabled: bool, // Whether the processing thread also measures the channel levels.
+}
+
+#[fully_pub]
+struct VisualizerPipeline {
     command_sender: Sender<VisualizerCommand>,
-    bands_receiver: Receiver<Vec<f32>>,
-    display_bands: Vec<f32>,
+    frames_receiver: Receiver<VisualizerFrame>,
 }
 
 pub enum VisualizerCommand {
-    Sample(Sample),
-    SampleRate(SampleRate),
+    Samples(Vec<Sample>), // Interleaved, like the source they were captured from.
+    Format(SampleRate, ChannelCount),
+    SetStyle(VisualizerStyle),
+    SetMeterEnabled(bool),
     Shutdown,
 }
 
+// What the processing thread emits for each window of samples, depending on the style.
+pub enum VisualizerFrame {
+    Bands(Vec<f32>),    // Levels between 0 and 1. The needle style has a single band for the overall level.
+    Waveform(Vec<f32>), // Samples between -1 and 1.
+    Spectrum {
+        decibels: Vec<f32>, // The level of every fft bin in dBFS, from 0 Hz up to the nyquist frequency.
+        bin_width: f32,     // In Hz.
+    },
+    Levels(Vec<ChannelLevel>), // One per channel. Sent alongside the other frames while the meter is enabled.
+}
+
+// Linear amplitudes, where 1 is full scale. A peak above 1 means the channel clipped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChannelLevel {
+    pub rms: f32,
+    pub peak: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum VisualizerStyle {
+    Bars,
+    Waveform,
+    Spectrum,
+    Needle,
+    Spectrogram,
+}
+
+impl VisualizerStyle {
+    pub fn label(self) -> &'static str {
+        match self {
+            VisualizerStyle::Bars => "Bars",
+            VisualizerStyle::Waveform => "Waveform",
+            VisualizerStyle::Spectrum => "Filled spectrum",
+            VisualizerStyle::Needle => "VU needle",
+            VisualizerStyle::Spectrogram => "Spectrogram",
+        }
+    }
+
+    pub fn band_count(self) -> usize {
+        match self {
+            VisualizerStyle::Bars => CENTER_FREQUENCIES.len(),
+            VisualizerStyle::Spectrum => SPECTRUM_BAND_COUNT,
+            VisualizerStyle::Needle => 1,
+            VisualizerStyle::Waveform | VisualizerStyle::Spectrogram => 0,
+        }
+    }
+}
+
+impl VisualizerState {
+    pub fn new() -> Self {
+        let style = VisualizerStyle::Bars;
+        Self {
+            pipeline: Some(setup_visualizer_pipeline()),
+            style,
+            display_bands: vec![0.0; style.band_count()],
+            waveform: Vec::new(),
+            attack: DEFAULT_ATTACK,
+            decay: DEFAULT_DECAY,
+            peak_hold: true,
+            peaks: vec![0.0; style.band_count()],
+            peak_holds: vec![0.0; style.band_count()],
+            meter_enabled: false,
+        }
+    }
+}
+
+pub fn set_visualizer_style(visualizer: &mut VisualizerState, style: VisualizerStyle) {
+    visualizer.style = style;
+    visualizer.display_bands = vec![0.0; style.band_count()];
+    visualizer.peaks = vec![0.0; style.band_count()];
+    visualizer.peak_holds = vec![0.0; style.band_count()];
+    visualizer.waveform.clear();
+
+    send_visualizer_command(visualizer, VisualizerCommand::SetStyle(style));
+}
+
+pub fn set_meter_enabled(visualizer: &mut VisualizerState, enabled: bool) {
+    visualizer.meter_enabled = enabled;
+
+    send_visualizer_command(visualizer, VisualizerCommand::SetMeterEnabled(enabled));
+}
+
+// Disabling shuts down the processing thread. Sources that are already playing notice and stop capturing samples, and new
+// ones pass them straight through. Enabling starts a fresh thread, which gets samples from the next track that is loaded.
+pub fn set_visualizer_enabled(visualizer: &mut VisualizerState, enabled: bool) {
+    if enabled == visualizer.pipeline.is_some() {
+        return;
+    }
+
+    if enabled {
+        visualizer.pipeline = Some(setup_visualizer_pipeline());
+        send_visualizer_command(visualizer, VisualizerCommand::SetStyle(visualizer.style));
+        send_visualizer_command(visualizer, VisualizerCommand::SetMeterEnabled(visualizer.meter_enabled));
+    } else {
+        send_visualizer_command(visualizer, VisualizerCommand::Shutdown);
+        visualizer.pipeline = None;
+    }
+
+    set_visualizer_style(visualizer, visualizer.style); // Clears what was last shown.
+}
+
+pub fn send_visualizer_command(visualizer: &VisualizerState, command: VisualizerCommand) {
+    if let Some(pipeline) = &visualizer.pipeline {
+        let _ = pipeline.command_sender.send(command);
+    }
+}
+
 //  The visualizer pipeline is comprised of the following components:
 //  - A source wrapper that captures audio samples from the audio stream.
 //  - A processing thread that receives the samples, performs FFT, and performs other processing.
-//  - A smoothing function applied between frames.
+//  - A smoothing function applied between frames, and the peak markers that follow it.
 
-pub fn smooth_bars(bars: &mut [f32], targets: Option<&[f32]>, dt: f32) {
-    let smoothing = 10.0;
-    let step = smoothing * dt;
-    let alpha = 1.0 - (-step).exp();
+// Rising bands use the attack rate and falling ones the decay rate, so that the bars can jump up quickly and still fall
+// back gracefully.
+pub fn smooth_bars(bars: &mut [f32], targets: Option<&[f32]>, attack: f32, decay: f32, dt: f32) {
+    let attack_alpha = 1.0 - (-attack * dt).exp();
+    let decay_step = decay * dt;
+    let decay_alpha = 1.0 - (-decay_step).exp();
 
     for (i, bar) in bars.iter_mut().enumerate() {
         let raw_target = targets.and_then(|t| t.get(i)).copied().unwrap_or(0.0);
 
-        // clamp downward movement
-        let target = if raw_target < *bar {
-            (*bar - step).max(raw_target)
+        if raw_target < *bar {
+            // clamp downward movement
+            let target = (*bar - decay_step).max(raw_target);
+            *bar += (target - *bar) * decay_alpha;
         } else {
-            raw_target
-        };
+            *bar += (raw_target - *bar) * attack_alpha;
+        }
+    }
+}
 
-        // smoothing
-        *bar += (target - *bar) * alpha;
+// Peaks jump up with the bars, hold for a moment, then fall at a constant speed until they meet the bars again.
+pub fn update_peaks(peaks: &mut [f32], holds: &mut [f32], bars: &[f32], dt: f32) {
+    for ((peak, hold), &bar) in peaks.iter_mut().zip(holds.iter_mut()).zip(bars) {
+        if bar >= *peak {
+            *peak = bar;
+            *hold = PEAK_HOLD_TIME;
+        } else if *hold > 0.0 {
+            *hold -= dt;
+        } else {
+            *peak = (*peak - PEAK_FALL_SPEED * dt).max(bar);
+        }
     }
 }
 
-pub fn setup_visualizer_pipeline() -> (Sender<VisualizerCommand>, Receiver<Vec<f32>>) {
+fn setup_visualizer_pipeline() -> VisualizerPipeline {
     let (command_sender, commands_receiver) = channel::<VisualizerCommand>();
-    let (bands_sender, bands_receiver) = channel::<Vec<f32>>();
+    let (frames_sender, frames_receiver) = channel::<VisualizerFrame>();
 
     let cs = command_sender.clone();
     thread::spawn(move || {
-        let mut sample_rate = None;
+        let mut format = None;
+        let mut style = VisualizerStyle::Bars;
+        let mut meter_enabled = false;
         let mut samples = Vec::with_capacity(FFT_SIZE);
+        let mut frame = Vec::new(); // One sample per channel. They are mixed down to mono.
+        let mut levels: Vec<ChannelLevel> = Vec::new(); // Sums of squares and peaks over the window, per channel.
 
         // Cache the fft planner for perfomance.
         let mut planner = FftPlanner::<f32>::new();
@@ -66,14 +225,41 @@ pub fn setup_visualizer_pipeline() -> (Sender<VisualizerCommand>, Receiver<Vec<f
 
         while let Ok(command) = commands_receiver.recv() {
             match command {
-                VisualizerCommand::Sample(sample) => {
-                    if let Some(sr) = sample_rate {
-                        samples.push(sample);
+                VisualizerCommand::Samples(batch) => {
+                    let Some((sample_rate, channels)) = format else {
+                        continue;
+                    };
+
+                    for sample in batch {
+                        frame.push(sample);
+                        if frame.len() < channels as usize {
+                            continue;
+                        }
+
+                        // The levels are measured before the channels are mixed down, so that each side of the meter is accurate.
+                        if meter_enabled {
+                            levels.resize(frame.len(), ChannelLevel::default());
+                            for (level, sample) in levels.iter_mut().zip(&frame) {
+                                level.rms += sample * sample;
+                                level.peak = level.peak.max(sample.abs());
+                            }
+                        }
+
+                        samples.push(frame.iter().sum::<f32>() / frame.len() as f32);
+                        frame.clear();
 
                         if samples.len() == FFT_SIZE {
-                            let bands = compute_bands(&samples, sr, fft.as_ref());
+                            let output = process_window(&samples, sample_rate, style, fft.as_ref());
+
+                            let mut result = frames_sender.send(output);
+
+                            if meter_enabled {
+                                for level in &mut levels {
+                                    level.rms = (level.rms / FFT_SIZE as f32).sqrt();
+                                }
+                                result = result.and(frames_sender.send(VisualizerFrame::Levels(take(&mut levels))));
+                            }
 
-                            let result = bands_sender.send(bands);
                             if result.is_err() {
                                 let _ = cs.send(VisualizerCommand::Shutdown);
                             }
@@ -82,9 +268,20 @@ pub fn setup_visualizer_pipeline() -> (Sender<VisualizerCommand>, Receiver<Vec<f
                         }
                     }
                 }
-                VisualizerCommand::SampleRate(sr) => {
-                    sample_rate = Some(sr);
+                VisualizerCommand::Format(sample_rate, channels) => {
+                    format = Some((sample_rate, channels.get()));
                     samples.clear();
+                    frame.clear();
+                    levels.clear();
+                }
+                VisualizerCommand::SetStyle(new_style) => {
+                    style = new_style;
+                    samples.clear();
+                    levels.clear();
+                }
+                VisualizerCommand::SetMeterEnabled(enabled) => {
+                    meter_enabled = enabled;
+                    levels.clear();
                 }
                 VisualizerCommand::Shutdown => {
                     info!("Received shutdown message. Shutting down the visualizer pipeline.");
@@ -94,31 +291,73 @@ pub fn setup_visualizer_pipeline() -> (Sender<VisualizerCommand>, Receiver<Vec<f
         }
     });
 
-    (command_sender, bands_receiver)
+    VisualizerPipeline {
+        command_sender,
+        frames_receiver,
+    }
 }
 
-fn compute_bands(samples: &[Sample], sample_rate: SampleRate, fft: &dyn Fft<f32>) -> Vec<f32> {
-    let n = samples.len();
+fn process_window(samples: &[Sample], sample_rate: SampleRate, style: VisualizerStyle, fft: &dyn Fft<f32>) -> VisualizerFrame {
+    match style {
+        VisualizerStyle::Bars => {
+            let ranges: Vec<(f32, f32)> = CENTER_FREQUENCIES
+                .iter()
+                .map(|&center| (center / HALF_OCTAVE_BANDWIDTH, center * HALF_OCTAVE_BANDWIDTH))
+                .collect();
+            VisualizerFrame::Bands(compute_bands(samples, sample_rate, fft, &ranges))
+        }
+        VisualizerStyle::Spectrum => VisualizerFrame::Bands(compute_bands(samples, sample_rate, fft, &spectrum_ranges())),
+        VisualizerStyle::Needle => VisualizerFrame::Bands(vec![compute_level(samples)]),
+        VisualizerStyle::Waveform => {
+            let step = samples.len() / WAVEFORM_POINTS;
+            VisualizerFrame::Waveform(samples.iter().step_by(step.max(1)).map(|s| s.clamp(-1.0, 1.0)).collect())
+        }
+        VisualizerStyle::Spectrogram => {
+            // A full scale sine wave peaks at a quarter of the window length because of the hann window.
+            let full_scale = samples.len() as f32 / 4.0;
+            let decibels = fft_magnitudes(samples, fft)
+                .iter()
+                .map(|m| 20.0 * (m / full_scale).max(1e-10).log10())
+                .collect();
+
+            VisualizerFrame::Spectrum {
+                decibels,
+                bin_width: sample_rate.get() as f32 / samples.len() as f32,
+            }
+        }
+    }
+}
 
-    let mut buffer: Vec<Complex<f32>> = samples
-        .iter()
-        .zip(hann_window(n).iter())
-        .map(|(&s, &w)| Complex { re: s * w, im: 0.0 })
-        .collect();
+fn spectrum_ranges() -> Vec<(f32, f32)> {
+    let (low, high) = SPECTRUM_RANGE;
+    let edge = |i: usize| low * (high / low).powf(i as f32 / SPECTRUM_BAND_COUNT as f32);
 
-    fft.process(&mut buffer);
+    (0..SPECTRUM_BAND_COUNT).map(|i| (edge(i), edge(i + 1))).collect()
+}
+
+// The rms level of the window, scaled so that LEVEL_FLOOR_DB and below is 0 and full scale is 1.
+fn compute_level(samples: &[Sample]) -> f32 {
+    let mean_square = samples.iter().map(|s| s * s).sum::<f32>() / samples.len().max(1) as f32;
+    let db = 10.0 * mean_square.max(1e-10).log10();
 
-    let magnitudes: Vec<f32> = buffer.iter().take(n / 2 + 1).map(|c| c.norm()).collect();
+    (1.0 - db / LEVEL_FLOOR_DB).clamp(0.0, 1.0)
+}
+
+// Sums the power in each frequency range (in Hz), then scales the bands relative to the loudest one.
+fn compute_bands(samples: &[Sample], sample_rate: SampleRate, fft: &dyn Fft<f32>, ranges: &[(f32, f32)]) -> Vec<f32> {
+    let n = samples.len();
 
-    let mut bands = vec![0.0; CENTER_FREQUENCIES.len()];
+    let magnitudes = fft_magnitudes(samples, fft);
 
-    for (b, &center) in CENTER_FREQUENCIES.iter().enumerate() {
-        let f_start = center / HALF_OCTAVE_BANDWIDTH;
-        let f_end = center * HALF_OCTAVE_BANDWIDTH;
+    let mut bands = vec![0.0; ranges.len()];
 
+    for (b, &(f_start, f_end)) in ranges.iter().enumerate() {
         let i_min = ((f_start * n as f32) / sample_rate.get() as f32).floor() as usize;
         let i_max = ((f_end * n as f32) / sample_rate.get() as f32).ceil() as usize;
         let i_max = i_max.min(magnitudes.len() - 1); // clamp to available bins
+        if i_min > i_max {
+            continue; // Above the nyquist frequency of low sample rates.
+        }
 
         for mag in &magnitudes[i_min..=i_max] {
             bands[b] += mag * mag; // accumulate power
@@ -135,6 +374,21 @@ fn compute_bands(samples: &[Sample], sample_rate: SampleRate, fft: &dyn Fft<f32>
     bands
 }
 
+// The magnitude of each frequency bin, from 0 Hz up to the nyquist frequency.
+fn fft_magnitudes(samples: &[Sample], fft: &dyn Fft<f32>) -> Vec<f32> {
+    let n = samples.len();
+
+    let mut buffer: Vec<Complex<f32>> = samples
+        .iter()
+        .zip(hann_window(n).iter())
+        .map(|(&s, &w)| Complex { re: s * w, im: 0.0 })
+        .collect();
+
+    fft.process(&mut buffer);
+
+    buffer.iter().take(n / 2 + 1).map(|c| c.norm()).collect()
+}
+
 fn log_scale(values: &mut [f32]) {
     let amplitude_to_db_factor = 20.0;
     let minimum_db_epsilon = 1e-10;
@@ -168,14 +422,38 @@ fn hann_window(n: usize) -> Vec<f32> {
         .collect()
 }
 
+// Captured samples are sent in batches rather than one message each, since the channel overhead of a message per sample
+// adds up at high sample rates.
 pub struct VisualizerSource<I> {
     input: I,
-    sender: Sender<VisualizerCommand>,
+    sender: Option<Sender<VisualizerCommand>>, // Dropped once the visualizer is disabled, after which samples pass through.
+    batch: Vec<Sample>,
 }
 
 impl<I> VisualizerSource<I> {
-    pub fn new(input: I, sender: Sender<VisualizerCommand>) -> Self {
-        Self { input, sender }
+    pub fn new(input: I, sender: Option<Sender<VisualizerCommand>>) -> Self {
+        Self {
+            input,
+            sender,
+            batch: Vec::new(),
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+
+        let batch = replace(&mut self.batch, Vec::with_capacity(SAMPLE_BATCH_SIZE));
+        let sent = self
+            .sender
+            .as_ref()
+            .is_some_and(|s| s.send(VisualizerCommand::Samples(batch)).is_ok());
+        if !sent {
+            // The processing thread has shut down.
+            self.sender = None;
+            self.batch = Vec::new();
+        }
     }
 }
 
@@ -187,8 +465,18 @@ where
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        let sample = self.input.next()?;
-        let _ = self.sender.send(VisualizerCommand::Sample(sample));
+        let Some(sample) = self.input.next() else {
+            self.flush();
+            return None;
+        };
+
+        if self.sender.is_some() {
+            self.batch.push(sample);
+            if self.batch.len() == SAMPLE_BATCH_SIZE {
+                self.flush();
+            }
+        }
+
         Some(sample)
     }
 